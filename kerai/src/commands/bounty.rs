@@ -110,6 +110,30 @@ pub fn show(
     Ok(())
 }
 
+pub fn reserve(
+    client: &mut Client,
+    bounty_id: &str,
+    wallet_id: &str,
+    ttl: i32,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.reserve_bounty($1::uuid, $2::uuid, $3)::text",
+            &[&bounty_id, &wallet_id, &ttl],
+        )
+        .map_err(|e| format!("reserve_bounty failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    let until = value["reserved_until"].as_str().unwrap_or("unknown");
+    println!("Bounty {bounty_id} reserved until {until}");
+    print_json(&value, format);
+    Ok(())
+}
+
 pub fn claim(
     client: &mut Client,
     bounty_id: &str,
@@ -9,31 +9,38 @@ pub fn run(client: &mut Client, message: Option<&str>) -> Result<(), String> {
 
     let _ = message; // Reserved for future commit message tracking
 
-    // Walk for .rs files, skipping target/ and tgt/ and .kerai/
-    let mut rs_files: Vec<String> = Vec::new();
-    walk_rs_files(&project_root, &project_root, &mut rs_files)?;
+    // Walk all files, skipping target/ and tgt/ and .kerai/; parse_any_file
+    // picks the parser by extension and skips the rest
+    let mut files: Vec<String> = Vec::new();
+    walk_files(&project_root, &project_root, &mut files)?;
 
-    if rs_files.is_empty() {
-        println!("No .rs files found.");
+    if files.is_empty() {
+        println!("No files found.");
         return Ok(());
     }
 
-    println!("Parsing {} files...", rs_files.len());
+    println!("Parsing {} files...", files.len());
 
     let mut total_nodes = 0u64;
     let mut total_edges = 0u64;
+    let mut parsed = 0usize;
 
-    for file_path in &rs_files {
+    for file_path in &files {
         let row = client
-            .query_one("SELECT kerai.parse_file($1)::text", &[file_path])
-            .map_err(|e| format!("parse_file failed for {file_path}: {e}"))?;
+            .query_one("SELECT kerai.parse_any_file($1)::text", &[file_path])
+            .map_err(|e| format!("parse_any_file failed for {file_path}: {e}"))?;
 
         let text: String = row.get(0);
         let value: serde_json::Value =
             serde_json::from_str(&text).unwrap_or(serde_json::Value::Null);
 
-        let nodes = value["nodes_inserted"].as_u64().unwrap_or(0);
-        let edges = value["edges_inserted"].as_u64().unwrap_or(0);
+        if value["status"] == "skipped" {
+            continue;
+        }
+        parsed += 1;
+
+        let nodes = value["nodes"].as_u64().unwrap_or(0);
+        let edges = value["edges"].as_u64().unwrap_or(0);
         total_nodes += nodes;
         total_edges += edges;
 
@@ -45,14 +52,11 @@ pub fn run(client: &mut Client, message: Option<&str>) -> Result<(), String> {
         println!("  {rel}: {nodes} nodes, {edges} edges");
     }
 
-    println!(
-        "Committed {} files: {total_nodes} nodes, {total_edges} edges",
-        rs_files.len()
-    );
+    println!("Committed {parsed} files: {total_nodes} nodes, {total_edges} edges");
     Ok(())
 }
 
-fn walk_rs_files(root: &Path, dir: &Path, out: &mut Vec<String>) -> Result<(), String> {
+fn walk_files(root: &Path, dir: &Path, out: &mut Vec<String>) -> Result<(), String> {
     let entries =
         std::fs::read_dir(dir).map_err(|e| format!("Cannot read {}: {e}", dir.display()))?;
 
@@ -71,8 +75,8 @@ fn walk_rs_files(root: &Path, dir: &Path, out: &mut Vec<String>) -> Result<(), S
             {
                 continue;
             }
-            walk_rs_files(root, &path, out)?;
-        } else if name_str.ends_with(".rs") {
+            walk_files(root, &path, out)?;
+        } else {
             out.push(path.to_string_lossy().to_string());
         }
     }
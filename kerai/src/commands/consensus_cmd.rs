@@ -31,3 +31,33 @@ pub fn run(
     print_json(&value, format);
     Ok(())
 }
+
+pub fn attest(
+    client: &mut Client,
+    scope: &str,
+    min_agents: i32,
+    min_weight: f64,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.auto_attest($1, $2, $3)::text",
+            &[&scope, &min_agents, &min_weight],
+        )
+        .map_err(|e| format!("auto_attest failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    let arr = value.as_array().ok_or("Expected JSON array")?;
+
+    if arr.is_empty() {
+        println!("No new consensus to attest.");
+        return Ok(());
+    }
+
+    println!("Created {} attestation(s):", arr.len());
+    print_json(&value, format);
+    Ok(())
+}
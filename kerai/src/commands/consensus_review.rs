@@ -0,0 +1,335 @@
+use std::io::IsTerminal;
+
+use postgres::Client;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::output::{print_rows, OutputFormat};
+
+/// A node the agents disagree on, with each agent's weight.
+struct Divergent {
+    node_id: String,
+    context_id: Option<String>,
+    kind: String,
+    content: String,
+    avg_weight: f64,
+    spread: f64,
+    weights: Vec<AgentWeight>,
+}
+
+struct AgentWeight {
+    agent: String,
+    weight: f64,
+    reasoning: Option<String>,
+}
+
+/// Review nodes where agents' weights diverge by at least `min_spread`,
+/// recording overrides as `agent`. Falls back to a table when stdout is not a TTY.
+pub fn review(
+    client: &mut Client,
+    agent: &str,
+    context_id: Option<&str>,
+    min_spread: f64,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let nodes = load_divergent(client, context_id, min_spread)?;
+
+    if !std::io::stdout().is_terminal() || matches!(format, OutputFormat::Json) {
+        print_divergent(&nodes, format);
+        return Ok(());
+    }
+    if nodes.is_empty() {
+        println!("No divergent perspectives to review.");
+        return Ok(());
+    }
+
+    let mut terminal = ratatui::init();
+    let result = ReviewApp::new(agent, nodes).run(&mut terminal, client);
+    ratatui::restore();
+    result
+}
+
+fn load_divergent(
+    client: &mut Client,
+    context_id: Option<&str>,
+    min_spread: f64,
+) -> Result<Vec<Divergent>, String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.consensus($1::uuid, 2, NULL)::text",
+            &[&context_id],
+        )
+        .map_err(|e| format!("consensus failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    let mut nodes = Vec::new();
+    for c in value.as_array().ok_or("Expected JSON array")? {
+        let spread = c["max_weight"].as_f64().unwrap_or(0.0) - c["min_weight"].as_f64().unwrap_or(0.0);
+        if spread < min_spread {
+            continue;
+        }
+        let node_id = c["node_id"].as_str().unwrap_or("").to_string();
+        let node_context = c["context_id"].as_str().map(String::from);
+        let mut node = Divergent {
+            node_id,
+            context_id: node_context,
+            kind: c["node_kind"].as_str().unwrap_or("").to_string(),
+            content: c["node_content"].as_str().unwrap_or("").to_string(),
+            avg_weight: c["avg_weight"].as_f64().unwrap_or(0.0),
+            spread,
+            weights: Vec::new(),
+        };
+        node.weights = load_weights(client, &node.node_id, node.context_id.as_deref())?;
+        nodes.push(node);
+    }
+
+    nodes.sort_by(|a, b| b.spread.total_cmp(&a.spread));
+    Ok(nodes)
+}
+
+fn load_weights(
+    client: &mut Client,
+    node_id: &str,
+    context_id: Option<&str>,
+) -> Result<Vec<AgentWeight>, String> {
+    let rows = client
+        .query(
+            "SELECT a.name, p.weight, p.reasoning
+             FROM kerai.perspectives p
+             JOIN kerai.agents a ON a.id = p.agent_id
+             WHERE p.node_id = $1::uuid AND p.context_id IS NOT DISTINCT FROM $2::uuid
+             ORDER BY p.weight DESC, a.name",
+            &[&node_id, &context_id],
+        )
+        .map_err(|e| format!("Failed to load perspectives: {e}"))?;
+
+    Ok(rows
+        .iter()
+        .map(|r| AgentWeight {
+            agent: r.get(0),
+            weight: r.get(1),
+            reasoning: r.get(2),
+        })
+        .collect())
+}
+
+/// Non-interactive output: one row per agent weight on each divergent node.
+fn print_divergent(nodes: &[Divergent], format: &OutputFormat) {
+    if nodes.is_empty() {
+        println!("No divergent perspectives to review.");
+        return;
+    }
+    let columns = vec![
+        "node_id".into(),
+        "kind".into(),
+        "content".into(),
+        "spread".into(),
+        "agent".into(),
+        "weight".into(),
+    ];
+    let rows: Vec<Vec<String>> = nodes
+        .iter()
+        .flat_map(|n| {
+            n.weights.iter().map(move |w| {
+                vec![
+                    n.node_id.clone(),
+                    n.kind.clone(),
+                    n.content.clone(),
+                    format!("{:.2}", n.spread),
+                    w.agent.clone(),
+                    format!("{:.2}", w.weight),
+                ]
+            })
+        })
+        .collect();
+    print_rows(&columns, &rows, format);
+}
+
+struct ReviewApp<'a> {
+    agent: &'a str,
+    nodes: Vec<Divergent>,
+    list: ListState,
+    /// Weight being typed, when editing an override.
+    input: Option<String>,
+    status: String,
+}
+
+impl<'a> ReviewApp<'a> {
+    fn new(agent: &'a str, nodes: Vec<Divergent>) -> Self {
+        ReviewApp {
+            agent,
+            nodes,
+            list: ListState::default().with_selected(Some(0)),
+            input: None,
+            status: "↑/↓ select · Enter override weight · q quit".to_string(),
+        }
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal, client: &mut Client) -> Result<(), String> {
+        loop {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .map_err(|e| format!("Failed to draw: {e}"))?;
+
+            let Event::Key(key) = event::read().map_err(|e| format!("Failed to read input: {e}"))?
+            else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if let Some(input) = self.input.as_mut() {
+                match key.code {
+                    KeyCode::Char(c) if c.is_ascii_digit() || c == '.' || c == '-' => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Esc => {
+                        self.input = None;
+                        self.status = "Override cancelled".to_string();
+                    }
+                    KeyCode::Enter => self.confirm(client),
+                    _ => {}
+                }
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.list.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.list.select_previous(),
+                KeyCode::Enter => {
+                    self.input = Some(String::new());
+                    self.status = "Weight (-1.0 to 1.0) · Enter confirm · Esc cancel".to_string();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn selected(&self) -> Option<&Divergent> {
+        self.list.selected().and_then(|i| self.nodes.get(i))
+    }
+
+    /// Record the typed weight as this agent's perspective on the selected node.
+    fn confirm(&mut self, client: &mut Client) {
+        let Some(input) = self.input.take() else {
+            return;
+        };
+        let weight = match input.parse::<f64>() {
+            Ok(w) if (-1.0..=1.0).contains(&w) => w,
+            _ => {
+                self.status = format!("Invalid weight '{input}': must be between -1.0 and 1.0");
+                return;
+            }
+        };
+        let Some(node) = self.list.selected().and_then(|i| self.nodes.get_mut(i)) else {
+            return;
+        };
+
+        let result = client.query_one(
+            "SELECT kerai.set_perspective($1, $2::uuid, $3, $4::uuid, $5)::text",
+            &[
+                &self.agent,
+                &node.node_id,
+                &weight,
+                &node.context_id,
+                &"consensus review override",
+            ],
+        );
+        self.status = match result {
+            Ok(_) => {
+                if let Ok(weights) = load_weights(client, &node.node_id, node.context_id.as_deref()) {
+                    node.weights = weights;
+                }
+                format!("Set {} to {:.2} as {}", node.content, weight, self.agent)
+            }
+            Err(e) => format!("set_perspective failed: {e}"),
+        };
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, input_area, status_area] = Layout::vertical([
+            Constraint::Min(5),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)]).areas(main);
+
+        let items: Vec<ListItem> = self
+            .nodes
+            .iter()
+            .map(|n| ListItem::new(format!("{:>5.2}  {} {}", n.spread, n.kind, n.content)))
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(" Divergent nodes (spread) "))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        if let Some(node) = self.selected() {
+            let rows: Vec<Row> = node
+                .weights
+                .iter()
+                .map(|w| {
+                    let color = if w.weight >= 0.0 { Color::Green } else { Color::Red };
+                    Row::new(vec![
+                        w.agent.clone(),
+                        format!("{:+.2}", w.weight),
+                        weight_bar(w.weight),
+                        w.reasoning.clone().unwrap_or_default(),
+                    ])
+                    .style(Style::default().fg(color))
+                })
+                .collect();
+            let title = format!(" {} {} · avg {:+.2} ", node.kind, node.content, node.avg_weight);
+            let table = Table::new(
+                rows,
+                [
+                    Constraint::Length(16),
+                    Constraint::Length(6),
+                    Constraint::Length(21),
+                    Constraint::Min(10),
+                ],
+            )
+            .header(
+                Row::new(vec!["agent", "weight", "", "reasoning"])
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            )
+            .block(Block::default().borders(Borders::ALL).title(title));
+            frame.render_widget(table, detail_area);
+        }
+
+        let prompt = match &self.input {
+            Some(input) => format!("Override as {}: {input}_", self.agent),
+            None => format!("Overrides are recorded as agent '{}'", self.agent),
+        };
+        frame.render_widget(
+            Paragraph::new(prompt).block(Block::default().borders(Borders::ALL)),
+            input_area,
+        );
+        frame.render_widget(Line::from(self.status.as_str()), status_area);
+    }
+}
+
+/// A centered bar from -1.0 (left) to 1.0 (right), 21 cells wide.
+fn weight_bar(weight: f64) -> String {
+    let offset = (weight.clamp(-1.0, 1.0) * 10.0).round() as i32;
+    (-10..=10)
+        .map(|i| match i {
+            0 => '|',
+            _ if (offset < 0 && i >= offset && i < 0) || (offset > 0 && i <= offset && i > 0) => '█',
+            _ => '·',
+        })
+        .collect()
+}
@@ -27,6 +27,7 @@ pub fn register(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn transfer(
     client: &mut Client,
     from: &str,
@@ -35,6 +36,7 @@ pub fn transfer(
     nonce: i64,
     signature: &str,
     reason: Option<&str>,
+    rejection_signature: Option<&str>,
     format: &OutputFormat,
 ) -> Result<(), String> {
     let row = client
@@ -42,7 +44,21 @@ pub fn transfer(
             "SELECT kerai.signed_transfer($1::uuid, $2::uuid, $3, $4, $5, $6)::text",
             &[&from, &to, &amount, &nonce, &signature, &reason],
         )
-        .map_err(|e| format!("signed_transfer failed: {e}"))?;
+        .map_err(|e| {
+            // The server's rejection rolls back with the transfer, so report
+            // it separately for `currency nonce-status` when the wallet signed
+            // a report
+            let rejected = e
+                .as_db_error()
+                .is_some_and(|db| db.message().contains("Invalid nonce"));
+            if let Some(report_sig) = rejection_signature.filter(|_| rejected) {
+                let _ = client.execute(
+                    "SELECT kerai.record_nonce_rejection($1::uuid, $2, $3)",
+                    &[&from, &nonce, &report_sig],
+                );
+            }
+            format!("signed_transfer failed: {e}")
+        })?;
 
     let text: String = row.get(0);
     let value: serde_json::Value =
@@ -111,6 +127,8 @@ pub fn schedule(client: &mut Client, format: &OutputFormat) -> Result<(), String
     let columns = vec![
         "work_type".into(),
         "reward".into(),
+        "effective".into(),
+        "halving".into(),
         "enabled".into(),
         "updated".into(),
     ];
@@ -124,6 +142,14 @@ pub fn schedule(client: &mut Client, format: &OutputFormat) -> Result<(), String
                     .as_i64()
                     .map(|n| n.to_string())
                     .unwrap_or_default(),
+                e["effective_reward"]
+                    .as_i64()
+                    .map(|n| n.to_string())
+                    .unwrap_or_default(),
+                e["halving_interval"]
+                    .as_i64()
+                    .map(|n| n.to_string())
+                    .unwrap_or_else(|| "-".into()),
                 e["enabled"]
                     .as_bool()
                     .map(|b| b.to_string())
@@ -142,12 +168,13 @@ pub fn set_reward(
     work_type: &str,
     reward: i64,
     enabled: Option<bool>,
+    halving_interval: Option<i64>,
     format: &OutputFormat,
 ) -> Result<(), String> {
     let row = client
         .query_one(
-            "SELECT kerai.set_reward($1, $2, $3)::text",
-            &[&work_type, &reward, &enabled],
+            "SELECT kerai.set_reward($1, $2, $3, $4)::text",
+            &[&work_type, &reward, &enabled, &halving_interval],
         )
         .map_err(|e| format!("set_reward failed: {e}"))?;
 
@@ -159,3 +186,82 @@ pub fn set_reward(
     print_json(&value, format);
     Ok(())
 }
+
+pub fn nonce_status(
+    client: &mut Client,
+    wallet_id: &str,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.wallet_nonce_status($1::uuid)::text",
+            &[&wallet_id],
+        )
+        .map_err(|e| format!("wallet_nonce_status failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    match format {
+        OutputFormat::Json => print_json(&value, format),
+        _ => {
+            let nonce = value["nonce"].as_i64().unwrap_or(0);
+            let next = value["next_nonce"].as_i64().unwrap_or(nonce + 1);
+            println!("Nonce: {nonce} (next accepted: {next})");
+            if value["stuck"].as_bool().unwrap_or(false) {
+                let rejected = value["rejected_attempts"].as_i64().unwrap_or(0);
+                println!(
+                    "Stuck: {rejected} rejected attempt(s), nonces {}",
+                    value["rejected_nonces"]
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn reset_nonce(
+    client: &mut Client,
+    wallet_id: &str,
+    nonce: i64,
+    signature: &str,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.reset_nonce($1::uuid, $2, $3)::text",
+            &[&wallet_id, &nonce, &signature],
+        )
+        .map_err(|e| format!("reset_nonce failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    println!("Nonce for {wallet_id} reset to {nonce}");
+    print_json(&value, format);
+    Ok(())
+}
+
+pub fn set_multiplier(
+    client: &mut Client,
+    scope: &str,
+    multiplier: f64,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.set_scope_reward_multiplier($1, $2)::text",
+            &[&scope, &multiplier],
+        )
+        .map_err(|e| format!("set_scope_reward_multiplier failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    println!("Reward multiplier for '{scope}': {multiplier}x");
+    print_json(&value, format);
+    Ok(())
+}
@@ -7,12 +7,15 @@ pub fn run(
     pattern: &str,
     kind: Option<&str>,
     limit: Option<i32>,
+    highlight: bool,
+    regex: bool,
     format: &OutputFormat,
 ) -> Result<(), String> {
+    let mode = if regex { "regex" } else { "like" };
     let row = client
         .query_one(
-            "SELECT kerai.find($1, $2, $3)::text",
-            &[&pattern, &kind, &limit],
+            "SELECT kerai.find($1, $2, $3, $4, $5)::text",
+            &[&pattern, &kind, &limit, &highlight, &mode],
         )
         .map_err(|e| format!("find failed: {e}"))?;
 
@@ -39,7 +42,7 @@ pub fn run(
         .map(|n| {
             vec![
                 n["kind"].as_str().unwrap_or("").to_string(),
-                n["content"].as_str().unwrap_or("").to_string(),
+                mark_highlights(n["content"].as_str().unwrap_or(""), &n["highlights"]),
                 n["path"].as_str().unwrap_or("").to_string(),
                 n["id"].as_str().unwrap_or("").to_string(),
             ]
@@ -50,3 +53,28 @@ pub fn run(
     print_rows(&columns, &rows, format);
     Ok(())
 }
+
+/// Wrap each highlighted span (character offsets) of `content` in `**`.
+/// Content is returned unchanged when there are no highlights.
+fn mark_highlights(content: &str, highlights: &serde_json::Value) -> String {
+    let Some(spans) = highlights.as_array().filter(|a| !a.is_empty()) else {
+        return content.to_string();
+    };
+    let bounds: Vec<(usize, usize)> = spans
+        .iter()
+        .filter_map(|h| Some((h["start"].as_u64()? as usize, h["end"].as_u64()? as usize)))
+        .collect();
+
+    let mut out = String::with_capacity(content.len() + bounds.len() * 4);
+    for (i, ch) in content.chars().enumerate() {
+        if bounds.iter().any(|&(start, _)| start == i) {
+            out.push_str("**");
+        }
+        out.push(ch);
+        if bounds.iter().any(|&(_, end)| end == i + 1) {
+            out.push_str("**");
+        }
+    }
+    out
+}
+
@@ -38,6 +38,61 @@ pub fn create(
     Ok(())
 }
 
+/// Print the projected Dutch price schedule without creating an auction.
+pub fn preview(
+    client: &mut Client,
+    starting_price: i64,
+    floor_price: i64,
+    price_decrement: i64,
+    decrement_interval: i64,
+    open_delay_hours: i32,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.auction_preview($1, $2, $3, $4, $5)::text",
+            &[
+                &starting_price,
+                &floor_price,
+                &price_decrement,
+                &decrement_interval,
+                &open_delay_hours,
+            ],
+        )
+        .map_err(|e| format!("auction_preview failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    if let OutputFormat::Json = format {
+        print_json(&value, format);
+        return Ok(());
+    }
+
+    let columns = vec!["t".into(), "price".into(), "action".into()];
+    let rows: Vec<Vec<String>> = value["schedule"]
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .map(|s| {
+                    vec![
+                        s["t"].as_i64().unwrap_or(0).to_string(),
+                        s["price"].as_i64().unwrap_or(0).to_string(),
+                        s["action"].as_str().unwrap_or("").to_string(),
+                    ]
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    print_rows(&columns, &rows, format);
+    if value["truncated"].as_bool().unwrap_or(false) {
+        println!("(schedule truncated before reaching the floor)");
+    }
+    Ok(())
+}
+
 pub fn bid(
     client: &mut Client,
     auction_id: &str,
@@ -102,6 +157,65 @@ pub fn open_source(
     Ok(())
 }
 
+pub fn extend_delay(
+    client: &mut Client,
+    auction_id: &str,
+    hours: i32,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.extend_open_delay($1::uuid, $2)::text",
+            &[&auction_id, &hours],
+        )
+        .map_err(|e| format!("extend_open_delay failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    let total = value["open_delay_hours"].as_i64().unwrap_or(0);
+    println!("Auction {auction_id} open-source delay is now {total}h");
+    print_json(&value, format);
+    Ok(())
+}
+
+pub fn due(client: &mut Client, format: &OutputFormat) -> Result<(), String> {
+    let row = client
+        .query_one("SELECT kerai.open_source_due()::text", &[])
+        .map_err(|e| format!("open_source_due failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    let arr = value.as_array().ok_or("Expected JSON array")?;
+    if arr.is_empty() {
+        println!("No auctions are due to open-source.");
+        return Ok(());
+    }
+
+    let columns = vec![
+        "auction_id".into(),
+        "settled_price".into(),
+        "open_delay_hours".into(),
+        "due_at".into(),
+    ];
+    let rows: Vec<Vec<String>> = arr
+        .iter()
+        .map(|a| {
+            vec![
+                a["auction_id"].as_str().unwrap_or("").to_string(),
+                a["settled_price"].to_string(),
+                a["open_delay_hours"].to_string(),
+                a["due_at"].as_str().unwrap_or("").to_string(),
+            ]
+        })
+        .collect();
+    print_rows(&columns, &rows, format);
+    Ok(())
+}
+
 pub fn browse(
     client: &mut Client,
     scope: Option<&str>,
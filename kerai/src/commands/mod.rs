@@ -7,6 +7,7 @@ pub mod init_cmd;
 pub mod stack_cmd;
 pub mod connect;
 pub mod consensus_cmd;
+pub mod consensus_review;
 pub mod currency;
 pub mod find;
 pub mod info;
@@ -42,6 +43,7 @@ pub enum Command {
     Version,
     Query {
         sql: String,
+        params: Vec<String>,
     },
     Export {
         file: Option<String>,
@@ -66,19 +68,53 @@ pub enum Command {
     PeerInfo {
         name: String,
     },
+    PeerTrust {
+        name: String,
+        level: String,
+    },
     Sync {
         peer: String,
     },
+    SyncExport {
+        path: String,
+        since: i64,
+    },
+    SyncImport {
+        path: String,
+    },
     Find {
         pattern: String,
         kind: Option<String>,
         limit: Option<i32>,
+        highlight: bool,
+        regex: bool,
     },
     Refs {
         symbol: String,
     },
+    Definition {
+        symbol: String,
+        from_file: String,
+    },
+    Implementors {
+        trait_name: String,
+    },
+    ImplMethods {
+        type_name: String,
+    },
+    Reachable {
+        node_id: String,
+        relation: String,
+        depth: i32,
+    },
+    CallGraph {
+        node_id: String,
+        depth: i32,
+    },
     Tree {
         path: Option<String>,
+        depth: Option<i32>,
+        kind: Option<String>,
     },
     ImportCsv {
         path: String,
@@ -104,17 +140,35 @@ pub enum Command {
         context_id: Option<String>,
         min_weight: Option<f64>,
     },
+    PerspectiveExport {
+        agent: String,
+        path: String,
+    },
+    PerspectiveImport {
+        path: String,
+    },
     Consensus {
         context_id: Option<String>,
         min_agents: Option<i32>,
         min_weight: Option<f64>,
     },
+    ConsensusAttest {
+        scope: String,
+        min_agents: i32,
+        min_weight: f64,
+    },
+    ConsensusReview {
+        agent: String,
+        context_id: Option<String>,
+        min_spread: f64,
+    },
     TaskCreate {
         description: String,
         success_command: String,
         scope: Option<String>,
         budget_ops: Option<i32>,
         budget_seconds: Option<i32>,
+        reward: Option<i64>,
     },
     TaskList {
         status: Option<String>,
@@ -134,6 +188,10 @@ pub enum Command {
     SwarmStop {
         task_id: String,
     },
+    SwarmEvaluate {
+        task_id: String,
+        quorum: i32,
+    },
     SwarmLeaderboard {
         task_id: String,
     },
@@ -148,6 +206,7 @@ pub enum Command {
         decrement_interval: i64,
         min_bidders: i32,
         open_delay_hours: i32,
+        preview: bool,
     },
     MarketBid {
         auction_id: String,
@@ -159,6 +218,11 @@ pub enum Command {
     MarketOpenSource {
         auction_id: String,
     },
+    MarketExtendDelay {
+        auction_id: String,
+        hours: i32,
+    },
+    MarketDue,
     MarketBrowse {
         scope: Option<String>,
         max_price: Option<i64>,
@@ -207,6 +271,11 @@ pub enum Command {
     BountyShow {
         bounty_id: String,
     },
+    BountyReserve {
+        bounty_id: String,
+        wallet_id: String,
+        ttl: i32,
+    },
     BountyClaim {
         bounty_id: String,
         wallet_id: String,
@@ -226,6 +295,7 @@ pub enum Command {
         nonce: i64,
         signature: String,
         reason: Option<String>,
+        rejection_signature: Option<String>,
     },
     CurrencySupply,
     CurrencyShare {
@@ -236,6 +306,19 @@ pub enum Command {
         work_type: String,
         reward: i64,
         enabled: Option<bool>,
+        halving_interval: Option<i64>,
+    },
+    CurrencyNonceStatus {
+        wallet_id: String,
+    },
+    CurrencyResetNonce {
+        wallet_id: String,
+        nonce: i64,
+        signature: String,
+    },
+    CurrencySetMultiplier {
+        scope: String,
+        multiplier: f64,
     },
     ModelCreate {
         agent: String,
@@ -244,6 +327,7 @@ pub enum Command {
         layers: Option<i32>,
         context_len: Option<i32>,
         scope: Option<String>,
+        seed: Option<i64>,
     },
     ModelTrain {
         agent: String,
@@ -275,6 +359,10 @@ pub enum Command {
     ModelDelete {
         agent: String,
     },
+    ModelFork {
+        base: String,
+        agent: String,
+    },
     ConfigGet {
         key: String,
     },
@@ -334,7 +422,7 @@ pub fn run(
         Command::Ping => ping::run(&mut client),
         Command::Info => info::run(&mut client, format),
         Command::Version => version::run(&mut client, format),
-        Command::Query { sql } => query::run(&mut client, &sql, format),
+        Command::Query { sql, params } => query::run(&mut client, &sql, &params, format),
         Command::Export { file } => export::run(&mut client, file.as_deref()),
         Command::Log { author, limit } => log::run(&mut client, author.as_deref(), limit, format),
         Command::Commit { message } => commit::run(&mut client, message.as_deref()),
@@ -354,14 +442,46 @@ pub fn run(
         Command::PeerList => peer::list(&mut client, format),
         Command::PeerRemove { name } => peer::remove(&mut client, &name),
         Command::PeerInfo { name } => peer::info(&mut client, &name, format),
+        Command::PeerTrust { name, level } => peer::trust(&mut client, &name, &level),
         Command::Sync { peer } => sync::run(&mut client, &peer),
+        Command::SyncExport { path, since } => sync::export(&mut client, &path, since, format),
+        Command::SyncImport { path } => sync::import(&mut client, &path, format),
         Command::Find {
             pattern,
             kind,
             limit,
-        } => find::run(&mut client, &pattern, kind.as_deref(), limit, format),
+            highlight,
+            regex,
+        } => find::run(
+            &mut client,
+            &pattern,
+            kind.as_deref(),
+            limit,
+            highlight,
+            regex,
+            format,
+        ),
         Command::Refs { symbol } => refs::run(&mut client, &symbol, format),
-        Command::Tree { path } => tree::run(&mut client, path.as_deref(), format),
+        Command::Definition { symbol, from_file } => {
+            refs::definition(&mut client, &symbol, &from_file, format)
+        }
+        Command::Implementors { trait_name } => {
+            refs::implementors(&mut client, &trait_name, format)
+        }
+        Command::ImplMethods { type_name } => {
+            refs::impl_methods(&mut client, &type_name, format)
+        }
+        Command::Reachable {
+            node_id,
+            relation,
+            depth,
+        } => refs::reachable(&mut client, &node_id, &relation, depth, format),
+        Command::CallGraph { node_id, depth } => {
+            refs::call_graph(&mut client, &node_id, depth, format)
+        }
+        Command::Tree { path, depth, kind } => {
+            tree::run(&mut client, path.as_deref(), depth, kind.as_deref(), format)
+        }
         Command::ImportCsv {
             path,
             schema,
@@ -384,6 +504,8 @@ pub fn run(
             min_weight,
             format,
         ),
+        Command::PerspectiveExport { agent, path } => perspective::export(&mut client, &agent, &path),
+        Command::PerspectiveImport { path } => perspective::import(&mut client, &path, format),
         Command::Consensus {
             context_id,
             min_agents,
@@ -395,12 +517,29 @@ pub fn run(
             min_weight,
             format,
         ),
+        Command::ConsensusAttest {
+            scope,
+            min_agents,
+            min_weight,
+        } => consensus_cmd::attest(&mut client, &scope, min_agents, min_weight, format),
+        Command::ConsensusReview {
+            agent,
+            context_id,
+            min_spread,
+        } => consensus_review::review(
+            &mut client,
+            &agent,
+            context_id.as_deref(),
+            min_spread,
+            format,
+        ),
         Command::TaskCreate {
             description,
             success_command,
             scope,
             budget_ops,
             budget_seconds,
+            reward,
         } => task::create(
             &mut client,
             &description,
@@ -408,6 +547,7 @@ pub fn run(
             scope.as_deref(),
             budget_ops,
             budget_seconds,
+            reward,
             format,
         ),
         Command::TaskList { status } => task::list(&mut client, status.as_deref(), format),
@@ -429,12 +569,32 @@ pub fn run(
             swarm::status(&mut client, task_id.as_deref(), format)
         }
         Command::SwarmStop { task_id } => swarm::stop(&mut client, &task_id),
+        Command::SwarmEvaluate { task_id, quorum } => {
+            swarm::evaluate(&mut client, &task_id, quorum, format)
+        }
         Command::SwarmLeaderboard { task_id } => {
             swarm::leaderboard(&mut client, &task_id, format)
         }
         Command::SwarmProgress { task_id } => {
             swarm::progress(&mut client, &task_id, format)
         }
+        Command::MarketCreate {
+            starting_price,
+            floor_price,
+            price_decrement,
+            decrement_interval,
+            open_delay_hours,
+            preview: true,
+            ..
+        } => market::preview(
+            &mut client,
+            starting_price,
+            floor_price,
+            price_decrement,
+            decrement_interval,
+            open_delay_hours,
+            format,
+        ),
         Command::MarketCreate {
             attestation_id,
             starting_price,
@@ -443,6 +603,7 @@ pub fn run(
             decrement_interval,
             min_bidders,
             open_delay_hours,
+            preview: false,
         } => market::create(
             &mut client,
             &attestation_id,
@@ -464,6 +625,10 @@ pub fn run(
         Command::MarketOpenSource { auction_id } => {
             market::open_source(&mut client, &auction_id)
         }
+        Command::MarketExtendDelay { auction_id, hours } => {
+            market::extend_delay(&mut client, &auction_id, hours, format)
+        }
+        Command::MarketDue => market::due(&mut client, format),
         Command::MarketBrowse {
             scope,
             max_price,
@@ -520,6 +685,11 @@ pub fn run(
             bounty::list(&mut client, status.as_deref(), scope.as_deref(), format)
         }
         Command::BountyShow { bounty_id } => bounty::show(&mut client, &bounty_id, format),
+        Command::BountyReserve {
+            bounty_id,
+            wallet_id,
+            ttl,
+        } => bounty::reserve(&mut client, &bounty_id, &wallet_id, ttl, format),
         Command::BountyClaim {
             bounty_id,
             wallet_id,
@@ -537,6 +707,7 @@ pub fn run(
             nonce,
             signature,
             reason,
+            rejection_signature,
         } => currency::transfer(
             &mut client,
             &from,
@@ -545,6 +716,7 @@ pub fn run(
             nonce,
             &signature,
             reason.as_deref(),
+            rejection_signature.as_deref(),
             format,
         ),
         Command::CurrencySupply => currency::supply(&mut client, format),
@@ -556,7 +728,26 @@ pub fn run(
             work_type,
             reward,
             enabled,
-        } => currency::set_reward(&mut client, &work_type, reward, enabled, format),
+            halving_interval,
+        } => currency::set_reward(
+            &mut client,
+            &work_type,
+            reward,
+            enabled,
+            halving_interval,
+            format,
+        ),
+        Command::CurrencyNonceStatus { wallet_id } => {
+            currency::nonce_status(&mut client, &wallet_id, format)
+        }
+        Command::CurrencyResetNonce {
+            wallet_id,
+            nonce,
+            signature,
+        } => currency::reset_nonce(&mut client, &wallet_id, nonce, &signature, format),
+        Command::CurrencySetMultiplier { scope, multiplier } => {
+            currency::set_multiplier(&mut client, &scope, multiplier, format)
+        }
         Command::ModelCreate {
             agent,
             dim,
@@ -564,6 +755,7 @@ pub fn run(
             layers,
             context_len,
             scope,
+            seed,
         } => model::create(
             &mut client,
             &agent,
@@ -572,6 +764,7 @@ pub fn run(
             layers,
             context_len,
             scope.as_deref(),
+            seed,
             format,
         ),
         Command::ModelTrain {
@@ -610,6 +803,7 @@ pub fn run(
         } => model::ensemble(&mut client, &agents, &context, top_k, format),
         Command::ModelInfo { agent } => model::info(&mut client, &agent, format),
         Command::ModelDelete { agent } => model::delete(&mut client, &agent, format),
+        Command::ModelFork { base, agent } => model::fork(&mut client, &base, &agent, format),
         Command::ConfigGet { key } => config_cmd::config_get(&mut client, &key, format),
         Command::ConfigSet { key, value } => {
             config_cmd::config_set(&mut client, &key, &value, format)
@@ -10,12 +10,13 @@ pub fn create(
     layers: Option<i32>,
     context_len: Option<i32>,
     scope: Option<&str>,
+    seed: Option<i64>,
     format: &OutputFormat,
 ) -> Result<(), String> {
     let row = client
         .query_one(
-            "SELECT kerai.create_model($1, $2, $3, $4, $5, $6)::text",
-            &[&agent, &dim, &heads, &layers, &context_len, &scope],
+            "SELECT kerai.create_model($1, $2, $3, $4, $5, $6, $7)::text",
+            &[&agent, &dim, &heads, &layers, &context_len, &scope, &seed],
         )
         .map_err(|e| format!("create_model failed: {e}"))?;
 
@@ -182,3 +183,25 @@ pub fn delete(
     print_json(&value, format);
     Ok(())
 }
+
+pub fn fork(
+    client: &mut Client,
+    base: &str,
+    agent: &str,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.fork_model($1, $2)::text",
+            &[&base, &agent],
+        )
+        .map_err(|e| format!("fork_model failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    println!("Model forked from '{}' to '{}'", base, agent);
+    print_json(&value, format);
+    Ok(())
+}
@@ -55,6 +55,7 @@ pub fn list(client: &mut Client, format: &OutputFormat) -> Result<(), String> {
         "key_fingerprint".into(),
         "endpoint".into(),
         "connection".into(),
+        "trust_level".into(),
         "last_seen".into(),
     ];
 
@@ -66,6 +67,7 @@ pub fn list(client: &mut Client, format: &OutputFormat) -> Result<(), String> {
                 p["key_fingerprint"].as_str().unwrap_or("").to_string(),
                 p["endpoint"].as_str().unwrap_or("").to_string(),
                 p["connection"].as_str().unwrap_or("").to_string(),
+                p["trust_level"].as_str().unwrap_or("").to_string(),
                 p["last_seen"].as_str().unwrap_or("").to_string(),
             ]
         })
@@ -96,6 +98,23 @@ pub fn remove(client: &mut Client, name: &str) -> Result<(), String> {
     Ok(())
 }
 
+pub fn trust(client: &mut Client, name: &str, level: &str) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.set_peer_trust($1, $2)::text",
+            &[&name, &level],
+        )
+        .map_err(|e| format!("set_peer_trust failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    let previous = value["previous"].as_str().unwrap_or("unknown");
+    println!("Peer '{name}' trust: {previous} -> {level}");
+    Ok(())
+}
+
 pub fn info(client: &mut Client, name: &str, format: &OutputFormat) -> Result<(), String> {
     // Look up fingerprint by name first
     let fp_row = client
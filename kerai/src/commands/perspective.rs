@@ -31,3 +31,44 @@ pub fn run(
     print_json(&value, format);
     Ok(())
 }
+
+/// Write an agent's signed perspective bundle to a local file.
+pub fn export(client: &mut Client, agent: &str, path: &str) -> Result<(), String> {
+    let row = client
+        .query_one("SELECT kerai.export_perspectives($1)::text", &[&agent])
+        .map_err(|e| format!("export_perspectives failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+    let pretty = serde_json::to_string_pretty(&value).map_err(|e| format!("Invalid JSON: {e}"))?;
+    std::fs::write(path, pretty).map_err(|e| format!("Failed to write {path}: {e}"))?;
+
+    let perspectives = value["perspectives"].as_array().map_or(0, Vec::len);
+    let associations = value["associations"].as_array().map_or(0, Vec::len);
+    println!("Exported {perspectives} perspective(s) and {associations} association(s) for '{agent}' to {path}");
+    Ok(())
+}
+
+/// Re-attach a bundle written by `export` to matching nodes on this instance.
+pub fn import(client: &mut Client, path: &str, format: &OutputFormat) -> Result<(), String> {
+    let bundle =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+    let row = client
+        .query_one("SELECT kerai.import_perspectives($1::text::jsonb)::text", &[&bundle])
+        .map_err(|e| format!("import_perspectives failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    let unmatched = value["unmatched"].as_array().map_or(0, Vec::len);
+    println!(
+        "Imported {} perspective(s) and {} association(s) for '{}'; {unmatched} unmatched",
+        value["perspectives"].as_u64().unwrap_or(0),
+        value["associations"].as_u64().unwrap_or(0),
+        value["agent"].as_str().unwrap_or(""),
+    );
+    print_json(&value, format);
+    Ok(())
+}
@@ -1,11 +1,15 @@
 use postgres::Client;
 
+use crate::db;
 use crate::output::{print_rows, OutputFormat};
 
-pub fn run(client: &mut Client, sql: &str, format: &OutputFormat) -> Result<(), String> {
-    let rows = client
-        .query(sql, &[])
-        .map_err(|e| format!("Query failed: {e}"))?;
+pub fn run(
+    client: &mut Client,
+    sql: &str,
+    params: &[String],
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let rows = db::query_params(client, sql, params)?;
 
     if rows.is_empty() {
         println!("(0 rows)");
@@ -27,6 +31,19 @@ pub fn run(client: &mut Client, sql: &str, format: &OutputFormat) -> Result<(),
         })
         .collect();
 
+    // A single multi-line value (reconstructed source, DOT graphs) is printed
+    // as-is so it can be piped to other tools instead of boxed in a table.
+    if matches!(format, OutputFormat::Table) && data.len() == 1 && data[0].len() == 1 {
+        let value = &data[0][0];
+        if value.contains('\n') {
+            print!("{value}");
+            if !value.ends_with('\n') {
+                println!();
+            }
+            return Ok(());
+        }
+    }
+
     print_rows(&columns, &data, format);
     Ok(())
 }
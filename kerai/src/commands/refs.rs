@@ -43,6 +43,40 @@ pub fn run(
                 }
             }
 
+            // Trait definitions with their associated items
+            if let Some(traits) = value["traits"].as_array() {
+                for t in traits {
+                    let path = t["path"].as_str().unwrap_or("");
+                    let supers: Vec<&str> = t["supertraits"]
+                        .as_array()
+                        .map(|s| s.iter().filter_map(|x| x.as_str()).collect())
+                        .unwrap_or_default();
+                    if supers.is_empty() {
+                        println!("Trait {path}:");
+                    } else {
+                        println!("Trait {path}: {}", supers.join(" + "));
+                    }
+                    let columns = vec!["kind".into(), "content".into(), "provided".into()];
+                    let rows: Vec<Vec<String>> = t["items"]
+                        .as_array()
+                        .map(|items| {
+                            items
+                                .iter()
+                                .map(|i| {
+                                    vec![
+                                        i["kind"].as_str().unwrap_or("").to_string(),
+                                        i["content"].as_str().unwrap_or("").to_string(),
+                                        i["provided"].as_bool().unwrap_or(false).to_string(),
+                                    ]
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    print_rows(&columns, &rows, format);
+                    println!();
+                }
+            }
+
             // Impl blocks
             if let Some(impls) = value["impls"].as_array() {
                 if !impls.is_empty() {
@@ -63,6 +97,26 @@ pub fn run(
                 }
             }
 
+            // Trait implementors
+            if let Some(imps) = value["implementors"].as_array() {
+                if !imps.is_empty() {
+                    println!("Implementors ({}):", imps.len());
+                    let columns = vec!["type".into(), "content".into(), "path".into()];
+                    let rows: Vec<Vec<String>> = imps
+                        .iter()
+                        .map(|i| {
+                            vec![
+                                i["type"].as_str().unwrap_or("").to_string(),
+                                i["content"].as_str().unwrap_or("").to_string(),
+                                i["path"].as_str().unwrap_or("").to_string(),
+                            ]
+                        })
+                        .collect();
+                    print_rows(&columns, &rows, format);
+                    println!();
+                }
+            }
+
             // References
             if let Some(refs) = value["references"].as_array() {
                 if !refs.is_empty() {
@@ -93,6 +147,8 @@ pub fn run(
             // Summary if all empty
             let total = value["definitions"].as_array().map_or(0, |a| a.len())
                 + value["impls"].as_array().map_or(0, |a| a.len())
+                + value["traits"].as_array().map_or(0, |a| a.len())
+                + value["implementors"].as_array().map_or(0, |a| a.len())
                 + value["references"].as_array().map_or(0, |a| a.len());
             if total == 0 {
                 println!("No references found for '{symbol}'.");
@@ -102,3 +158,259 @@ pub fn run(
 
     Ok(())
 }
+
+pub fn definition(
+    client: &mut Client,
+    symbol: &str,
+    from_file: &str,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.definition($1, $2::uuid)::text",
+            &[&symbol, &from_file],
+        )
+        .map_err(|e| format!("definition failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    match format {
+        OutputFormat::Json => {
+            print_json(&value, format);
+        }
+        _ => {
+            let candidates = value["candidates"].as_array().cloned().unwrap_or_default();
+            if candidates.is_empty() {
+                println!("No definition found for '{symbol}'.");
+                return Ok(());
+            }
+            if value["ambiguous"].as_bool().unwrap_or(false) {
+                println!("'{symbol}' is ambiguous; candidates by rank:");
+                println!();
+            }
+            let columns = vec![
+                "rank".into(),
+                "reason".into(),
+                "kind".into(),
+                "content".into(),
+                "file".into(),
+                "path".into(),
+            ];
+            let rows: Vec<Vec<String>> = candidates
+                .iter()
+                .map(|c| {
+                    vec![
+                        c["rank"].to_string(),
+                        c["reason"].as_str().unwrap_or("").to_string(),
+                        c["kind"].as_str().unwrap_or("").to_string(),
+                        c["content"].as_str().unwrap_or("").to_string(),
+                        c["file"].as_str().unwrap_or("").to_string(),
+                        c["path"].as_str().unwrap_or("").to_string(),
+                    ]
+                })
+                .collect();
+            print_rows(&columns, &rows, format);
+        }
+    }
+
+    Ok(())
+}
+
+/// Show the functions a function calls, hottest paths first.
+pub fn call_graph(
+    client: &mut Client,
+    node_id: &str,
+    depth: i32,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.call_graph($1::uuid, $2)::text",
+            &[&node_id, &depth],
+        )
+        .map_err(|e| format!("call_graph failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    match format {
+        OutputFormat::Json => {
+            print_json(&value, format);
+        }
+        _ => {
+            let calls = value["calls"].as_array().cloned().unwrap_or_default();
+            if calls.is_empty() {
+                println!("No calls found from {node_id}.");
+                return Ok(());
+            }
+            let columns = vec![
+                "weight".into(),
+                "count".into(),
+                "depth".into(),
+                "content".into(),
+                "path".into(),
+            ];
+            let rows: Vec<Vec<String>> = calls
+                .iter()
+                .map(|c| {
+                    vec![
+                        c["weight"].to_string(),
+                        c["count"].to_string(),
+                        c["depth"].to_string(),
+                        c["content"].as_str().unwrap_or("").to_string(),
+                        c["path"].as_str().unwrap_or("").to_string(),
+                    ]
+                })
+                .collect();
+            print_rows(&columns, &rows, format);
+            if value["truncated"].as_bool().unwrap_or(false) {
+                println!("(truncated: query budget exceeded)");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn implementors(
+    client: &mut Client,
+    trait_name: &str,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one("SELECT kerai.implementors($1)::text", &[&trait_name])
+        .map_err(|e| format!("implementors failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    match format {
+        OutputFormat::Json => {
+            print_json(&value, format);
+        }
+        _ => {
+            let impls = value.as_array().cloned().unwrap_or_default();
+            if impls.is_empty() {
+                println!("No implementors found for '{trait_name}'.");
+                return Ok(());
+            }
+            let columns = vec!["type".into(), "trait".into(), "path".into()];
+            let rows: Vec<Vec<String>> = impls
+                .iter()
+                .map(|i| {
+                    vec![
+                        i["type"].as_str().unwrap_or("").to_string(),
+                        i["trait"].as_str().unwrap_or("").to_string(),
+                        i["path"].as_str().unwrap_or("").to_string(),
+                    ]
+                })
+                .collect();
+            print_rows(&columns, &rows, format);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn impl_methods(
+    client: &mut Client,
+    type_name: &str,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one("SELECT kerai.impl_methods($1)::text", &[&type_name])
+        .map_err(|e| format!("impl_methods failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    match format {
+        OutputFormat::Json => {
+            print_json(&value, format);
+        }
+        _ => {
+            let methods = value.as_array().cloned().unwrap_or_default();
+            if methods.is_empty() {
+                println!("No methods found for '{type_name}'.");
+                return Ok(());
+            }
+            let columns = vec![
+                "trait".into(),
+                "visibility".into(),
+                "method".into(),
+                "signature".into(),
+            ];
+            let rows: Vec<Vec<String>> = methods
+                .iter()
+                .map(|m| {
+                    vec![
+                        m["trait_name"].as_str().unwrap_or("").to_string(),
+                        m["visibility"].as_str().unwrap_or("").to_string(),
+                        m["method"].as_str().unwrap_or("").to_string(),
+                        m["signature"].as_str().unwrap_or("").to_string(),
+                    ]
+                })
+                .collect();
+            print_rows(&columns, &rows, format);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn reachable(
+    client: &mut Client,
+    node_id: &str,
+    relation: &str,
+    depth: i32,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.reachable($1::uuid, $2, $3)::text",
+            &[&node_id, &relation, &depth],
+        )
+        .map_err(|e| format!("reachable failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    match format {
+        OutputFormat::Json => {
+            print_json(&value, format);
+        }
+        _ => {
+            let nodes = value.as_array().cloned().unwrap_or_default();
+            if nodes.is_empty() {
+                println!("Nothing reachable from {node_id} over '{relation}'.");
+                return Ok(());
+            }
+            let columns = vec![
+                "depth".into(),
+                "kind".into(),
+                "content".into(),
+                "path".into(),
+            ];
+            let rows: Vec<Vec<String>> = nodes
+                .iter()
+                .map(|n| {
+                    vec![
+                        n["depth"].to_string(),
+                        n["kind"].as_str().unwrap_or("").to_string(),
+                        n["content"].as_str().unwrap_or("").to_string(),
+                        n["path"].as_str().unwrap_or("").to_string(),
+                    ]
+                })
+                .collect();
+            print_rows(&columns, &rows, format);
+        }
+    }
+
+    Ok(())
+}
@@ -56,6 +56,7 @@ pub fn status(
         "description".into(),
         "status".into(),
         "swarm_name".into(),
+        "winner".into(),
         "total".into(),
         "passed".into(),
         "failed".into(),
@@ -69,6 +70,7 @@ pub fn status(
                 t["description"].as_str().unwrap_or("").to_string(),
                 t["status"].as_str().unwrap_or("").to_string(),
                 t["swarm_name"].as_str().unwrap_or("").to_string(),
+                t["winner"].as_str().unwrap_or("").to_string(),
                 t["total_results"].as_i64().map(|n| n.to_string()).unwrap_or("0".into()),
                 t["passed"].as_i64().map(|n| n.to_string()).unwrap_or("0".into()),
                 t["failed"].as_i64().map(|n| n.to_string()).unwrap_or("0".into()),
@@ -97,6 +99,46 @@ pub fn stop(client: &mut Client, task_id: &str) -> Result<(), String> {
     Ok(())
 }
 
+pub fn evaluate(
+    client: &mut Client,
+    task_id: &str,
+    quorum: i32,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one(
+            "SELECT kerai.evaluate_task($1::uuid, $2)::text",
+            &[&task_id, &quorum],
+        )
+        .map_err(|e| format!("evaluate_task failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    match format {
+        OutputFormat::Json => print_json(&value, format),
+        _ => {
+            if value["completed"].as_bool().unwrap_or(false) {
+                let winner = value["winner"].as_str().unwrap_or("unknown");
+                println!("Task {task_id}: completed (winner: {winner})");
+                if let Some(reward) = value.get("reward").filter(|r| !r.is_null()) {
+                    if reward["paid"].as_bool().unwrap_or(false) {
+                        println!("Paid {} nKoi to {winner}", reward["amount"]);
+                    } else {
+                        let reason = reward["reason"].as_str().unwrap_or("unknown");
+                        println!("Reward not paid: {reason}");
+                    }
+                }
+            } else {
+                let passing = value["passing_agents"].as_array().map_or(0, |a| a.len());
+                println!("Task {task_id}: not complete ({passing}/{quorum} agents passing)");
+            }
+        }
+    }
+    Ok(())
+}
+
 pub fn leaderboard(
     client: &mut Client,
     task_id: &str,
@@ -1,5 +1,7 @@
 use postgres::{Client, NoTls};
 
+use crate::output::{print_json, OutputFormat};
+
 /// Sync protocol: pull-then-push between local and peer databases.
 ///
 /// 1. Look up peer's connection string from kerai.instances
@@ -94,6 +96,44 @@ fn get_version_vector(
     Ok(map)
 }
 
+/// Export this instance's ops to an op log file for offline transport.
+pub fn export(
+    client: &mut Client,
+    path: &str,
+    since: i64,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let row = client
+        .query_one("SELECT kerai.export_oplog($1, $2)::text", &[&path, &since])
+        .map_err(|e| format!("export_oplog failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    let exported = value["exported"].as_u64().unwrap_or(0);
+    println!("Exported {exported} ops to {path}");
+    print_json(&value, format);
+    Ok(())
+}
+
+/// Apply an op log file produced by `export` on another instance.
+pub fn import(client: &mut Client, path: &str, format: &OutputFormat) -> Result<(), String> {
+    let row = client
+        .query_one("SELECT kerai.import_oplog($1)::text", &[&path])
+        .map_err(|e| format!("import_oplog failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    let applied = value["applied"].as_u64().unwrap_or(0);
+    let duplicate = value["duplicate"].as_u64().unwrap_or(0);
+    println!("Imported {path}: {applied} applied, {duplicate} already present");
+    print_json(&value, format);
+    Ok(())
+}
+
 /// Get operations from a database for a given author since a sequence number.
 fn get_ops_since(
     client: &mut Client,
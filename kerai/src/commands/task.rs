@@ -9,12 +9,13 @@ pub fn create(
     scope: Option<&str>,
     budget_ops: Option<i32>,
     budget_seconds: Option<i32>,
+    reward: Option<i64>,
     format: &OutputFormat,
 ) -> Result<(), String> {
     let row = client
         .query_one(
-            "SELECT kerai.create_task($1, $2, $3::uuid, $4, $5)::text",
-            &[&description, &success_command, &scope, &budget_ops, &budget_seconds],
+            "SELECT kerai.create_task($1, $2, $3::uuid, $4, $5, $6)::text",
+            &[&description, &success_command, &scope, &budget_ops, &budget_seconds, &reward],
         )
         .map_err(|e| format!("create_task failed: {e}"))?;
 
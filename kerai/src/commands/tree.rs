@@ -5,8 +5,14 @@ use crate::output::{print_rows, OutputFormat};
 pub fn run(
     client: &mut Client,
     path: Option<&str>,
+    depth: Option<i32>,
+    kind: Option<&str>,
     format: &OutputFormat,
 ) -> Result<(), String> {
+    if depth.is_some() || kind.is_some() {
+        return run_limited(client, path, depth, kind, format);
+    }
+
     let row = client
         .query_one("SELECT kerai.tree($1)::text", &[&path])
         .map_err(|e| format!("tree failed: {e}"))?;
@@ -44,3 +50,88 @@ pub fn run(
     print_rows(&columns, &rows, format);
     Ok(())
 }
+
+/// Nested outline via `kerai.tree_limited`, pruned by depth and filtered by kind.
+fn run_limited(
+    client: &mut Client,
+    path: Option<&str>,
+    depth: Option<i32>,
+    kind: Option<&str>,
+    format: &OutputFormat,
+) -> Result<(), String> {
+    let kinds: Option<Vec<String>> = kind.map(|k| {
+        k.split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    });
+
+    let row = client
+        .query_one(
+            "SELECT kerai.tree_limited($1, $2, $3)::text",
+            &[&path, &depth, &kinds],
+        )
+        .map_err(|e| format!("tree failed: {e}"))?;
+
+    let text: String = row.get(0);
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    let roots = value.as_array().ok_or("Expected JSON array")?;
+
+    if roots.is_empty() {
+        println!("No nodes found.");
+        return Ok(());
+    }
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        }
+        OutputFormat::Table => {
+            for node in roots {
+                print_outline(node, 0);
+            }
+        }
+        OutputFormat::Csv => {
+            let columns = vec![
+                "depth".into(),
+                "kind".into(),
+                "content".into(),
+                "path".into(),
+                "children".into(),
+            ];
+            let mut rows = Vec::new();
+            for node in roots {
+                flatten(node, 0, &mut rows);
+            }
+            print_rows(&columns, &rows, format);
+        }
+    }
+    Ok(())
+}
+
+fn print_outline(node: &serde_json::Value, indent: usize) {
+    println!(
+        "{}{} {}",
+        "  ".repeat(indent),
+        node["kind"].as_str().unwrap_or(""),
+        node["content"].as_str().unwrap_or(""),
+    );
+    for child in node["children"].as_array().into_iter().flatten() {
+        print_outline(child, indent + 1);
+    }
+}
+
+fn flatten(node: &serde_json::Value, depth: usize, rows: &mut Vec<Vec<String>>) {
+    rows.push(vec![
+        depth.to_string(),
+        node["kind"].as_str().unwrap_or("").to_string(),
+        node["content"].as_str().unwrap_or("").to_string(),
+        node["path"].as_str().unwrap_or("").to_string(),
+        node["child_count"].as_i64().unwrap_or(0).to_string(),
+    ]);
+    for child in node["children"].as_array().into_iter().flatten() {
+        flatten(child, depth + 1, rows);
+    }
+}
@@ -92,7 +92,7 @@ pub fn balance(
     let row = match wallet_id {
         Some(id) => client
             .query_one(
-                "SELECT kerai.get_wallet_balance($1::uuid)::text",
+                "SELECT kerai.get_wallet_balance(kerai.resolve_wallet($1))::text",
                 &[&id],
             )
             .map_err(|e| format!("get_wallet_balance failed: {e}"))?,
@@ -128,7 +128,7 @@ pub fn transfer(
 ) -> Result<(), String> {
     let row = client
         .query_one(
-            "SELECT kerai.transfer_koi($1::uuid, $2::uuid, $3, $4)::text",
+            "SELECT kerai.transfer_koi($1::text, $2::text, $3, $4)::text",
             &[&from, &to, &amount, &reason],
         )
         .map_err(|e| format!("transfer_koi failed: {e}"))?;
@@ -150,7 +150,7 @@ pub fn history(
 ) -> Result<(), String> {
     let row = client
         .query_one(
-            "SELECT kerai.wallet_history($1::uuid, $2)::text",
+            "SELECT kerai.wallet_history($1::text, $2)::text",
             &[&wallet_id, &limit],
         )
         .map_err(|e| format!("wallet_history failed: {e}"))?;
@@ -1,15 +1,146 @@
-use postgres::{Client, NoTls};
+use std::error::Error as _;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use postgres::error::SqlState;
+use postgres::types::{ToSql, Type};
+use postgres::{Client, Config, NoTls, Row};
 
 use crate::config::Profile;
 
-/// Connect to Postgres. `db_override` (from --db flag) takes highest priority,
-/// then the profile's connection string.
+/// Total connection attempts before giving up on a transient failure.
+const CONNECT_ATTEMPTS: u32 = 4;
+
+/// Delay before the first retry; doubles on each subsequent attempt.
+const CONNECT_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Resolve the connection string. `db_override` (from --db flag) takes highest
+/// priority, then the `KERAI_DB` environment variable, then the profile's
+/// connection string.
+pub fn connection_string(profile: &Profile, db_override: Option<&str>) -> Option<String> {
+    db_override
+        .map(str::to_string)
+        .or_else(|| std::env::var("KERAI_DB").ok().filter(|s| !s.is_empty()))
+        .or_else(|| profile.connection.clone())
+}
+
+/// Connect to Postgres, retrying with backoff on transient errors
+/// (connection refused, too many clients, server starting up).
+/// `PGCONNECT_TIMEOUT` (seconds) applies when the connection string sets no timeout.
 pub fn connect(profile: &Profile, db_override: Option<&str>) -> Result<Client, String> {
-    let conn_str = db_override
-        .or(profile.connection.as_deref())
-        .ok_or("No connection string. Use --db or set one in .kerai/config.toml")?;
+    let conn_str = connection_string(profile, db_override)
+        .ok_or("No connection string. Use --db, set KERAI_DB, or set one in .kerai/config.toml")?;
+
+    let mut config: Config = conn_str
+        .parse()
+        .map_err(|e| format!("Invalid connection string: {e}"))?;
+    if config.get_connect_timeout().is_none() {
+        if let Some(timeout) = connect_timeout_from_env() {
+            config.connect_timeout(timeout);
+        }
+    }
+
+    let mut delay = CONNECT_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match config.connect(NoTls) {
+            Ok(client) => return Ok(client),
+            Err(e) if attempt < CONNECT_ATTEMPTS && is_transient(&e) => {
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) if attempt > 1 => {
+                return Err(format!("Connection failed after {attempt} attempts: {e}"));
+            }
+            Err(e) => return Err(format!("Connection failed: {e}")),
+        }
+    }
+}
+
+/// `PGCONNECT_TIMEOUT` as libpq reads it: whole seconds, zero or unset means none.
+fn connect_timeout_from_env() -> Option<Duration> {
+    std::env::var("PGCONNECT_TIMEOUT")
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+}
+
+/// Whether a connection error is worth retrying.
+fn is_transient(e: &postgres::Error) -> bool {
+    if let Some(code) = e.code() {
+        return *code == SqlState::TOO_MANY_CONNECTIONS || *code == SqlState::CANNOT_CONNECT_NOW;
+    }
+    match e.source().and_then(|s| s.downcast_ref::<io::Error>()) {
+        Some(io_err) => matches!(
+            io_err.kind(),
+            io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::NotFound
+        ),
+        None => false,
+    }
+}
+
+/// Run a query with `$n` bind parameters given as strings. Each value is
+/// converted to the type Postgres infers for its placeholder and sent as a
+/// real parameter, never interpolated into the SQL text.
+pub fn query_params(client: &mut Client, sql: &str, params: &[String]) -> Result<Vec<Row>, String> {
+    let stmt = client
+        .prepare(sql)
+        .map_err(|e| format!("Query failed: {e}"))?;
+
+    let types = stmt.params();
+    if types.len() != params.len() {
+        return Err(format!(
+            "Query expects {} parameter(s), got {}",
+            types.len(),
+            params.len()
+        ));
+    }
+
+    let values = types
+        .iter()
+        .zip(params)
+        .enumerate()
+        .map(|(i, (ty, raw))| bind_param(ty, raw).map_err(|e| format!("${}: {e}", i + 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let refs: Vec<&(dyn ToSql + Sync)> = values.iter().map(|v| v.as_ref()).collect();
+
+    client
+        .query(&stmt, &refs)
+        .map_err(|e| format!("Query failed: {e}"))
+}
 
-    Client::connect(conn_str, NoTls).map_err(|e| format!("Connection failed: {e}"))
+/// Convert a string argument to the parameter type Postgres expects.
+fn bind_param(ty: &Type, raw: &str) -> Result<Box<dyn ToSql + Sync>, String> {
+    let bad = || format!("expected {}, got {raw:?}", ty.name());
+    Ok(match *ty {
+        Type::BOOL => Box::new(raw.parse::<bool>().map_err(|_| bad())?),
+        Type::INT2 => Box::new(raw.parse::<i16>().map_err(|_| bad())?),
+        Type::INT4 => Box::new(raw.parse::<i32>().map_err(|_| bad())?),
+        Type::INT8 => Box::new(raw.parse::<i64>().map_err(|_| bad())?),
+        Type::FLOAT4 => Box::new(raw.parse::<f32>().map_err(|_| bad())?),
+        Type::FLOAT8 => Box::new(raw.parse::<f64>().map_err(|_| bad())?),
+        Type::UUID => Box::new(raw.parse::<uuid::Uuid>().map_err(|_| bad())?),
+        Type::JSON | Type::JSONB => {
+            Box::new(serde_json::from_str::<serde_json::Value>(raw).map_err(|_| bad())?)
+        }
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => {
+            Box::new(raw.to_string())
+        }
+        _ => {
+            return Err(format!(
+                "unsupported parameter type {}; cast in SQL instead, e.g. $n::text::{}",
+                ty.name(),
+                ty.name()
+            ))
+        }
+    })
 }
 
 /// Ensure ltree and kerai extensions are loaded.
@@ -16,7 +16,7 @@ use output::OutputFormat;
 #[derive(Parser)]
 #[command(name = "kerai", version, about = "AST-based version control")]
 struct Cli {
-    /// Postgres connection string (overrides config)
+    /// Postgres connection string (overrides KERAI_DB and config)
     #[arg(long, global = true)]
     db: Option<String>,
 
@@ -171,6 +171,10 @@ enum PostgresAction {
     Query {
         /// SQL statement to execute
         sql: String,
+
+        /// Bind parameter for $1, $2, ... (repeatable, in order)
+        #[arg(long = "param")]
+        params: Vec<String>,
     },
 
     /// Export source files reconstructed from AST
@@ -200,7 +204,7 @@ enum PostgresAction {
 
     /// Search AST nodes by content pattern
     Find {
-        /// Search pattern (ILIKE syntax, e.g. %hello%)
+        /// Search pattern (ILIKE syntax, e.g. %hello%; a POSIX regex with --regex)
         pattern: String,
 
         /// Filter by node kind (e.g. fn, struct, enum)
@@ -210,6 +214,14 @@ enum PostgresAction {
         /// Maximum results (default 50)
         #[arg(long)]
         limit: Option<i32>,
+
+        /// Mark matched text in content (as **match** in table output)
+        #[arg(long)]
+        highlight: bool,
+
+        /// Treat the pattern as a case-sensitive POSIX regex
+        #[arg(long)]
+        regex: bool,
     },
 
     /// Find definitions, references, and impls for a symbol
@@ -218,10 +230,64 @@ enum PostgresAction {
         symbol: String,
     },
 
+    /// Go to the most likely definition of a symbol as seen from a file
+    Definition {
+        /// Symbol name or `::` path
+        symbol: String,
+
+        /// File node id the symbol is used in
+        #[arg(long = "from")]
+        from_file: String,
+    },
+
+    /// List the types that implement a trait
+    Implementors {
+        /// Trait name (a `::` path matches on its last segment)
+        trait_name: String,
+    },
+
+    /// List the methods of a type across all of its impl blocks
+    ImplMethods {
+        /// Type name (a `::` path matches on its last segment)
+        type_name: String,
+    },
+
+    /// List the nodes reachable from a node along one edge relation
+    Reachable {
+        /// Start node id
+        node_id: String,
+
+        /// Edge relation to follow
+        #[arg(long, default_value = "calls")]
+        relation: String,
+
+        /// Maximum number of hops to follow
+        #[arg(long, default_value = "10")]
+        depth: i32,
+    },
+
+    /// Show what a function calls, ranked by call count along each path
+    CallGraph {
+        /// Function node id
+        node_id: String,
+
+        /// Maximum number of hops to follow
+        #[arg(long, default_value = "3")]
+        depth: i32,
+    },
+
     /// Show AST tree structure
     Tree {
         /// ltree path pattern (subtree or lquery with wildcards)
         path: Option<String>,
+
+        /// Maximum depth below the starting nodes (prints a nested outline)
+        #[arg(long)]
+        depth: Option<i32>,
+
+        /// Only show these node kinds, comma-separated (e.g. fn,struct)
+        #[arg(long)]
+        kind: Option<String>,
     },
 
     /// Import CSV files into typed Postgres tables with kerai nodes
@@ -246,6 +312,22 @@ enum SyncAction {
         /// Peer name to sync with
         peer: String,
     },
+
+    /// Append this instance's signed ops to a log file on the database host
+    Export {
+        /// Path of the newline-delimited JSON log
+        path: String,
+
+        /// Only ops after this author sequence number
+        #[arg(long, default_value = "0")]
+        since: i64,
+    },
+
+    /// Apply ops from a log file on the database host
+    Import {
+        /// Path of the newline-delimited JSON log
+        path: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -263,6 +345,21 @@ enum PerspectiveAction {
         #[arg(long)]
         min_weight: Option<f64>,
     },
+
+    /// Export an agent's perspectives and associations as a signed bundle
+    Export {
+        /// Agent name
+        agent: String,
+
+        /// File to write the bundle to
+        path: String,
+    },
+
+    /// Import a perspective bundle, re-attaching it to matching nodes
+    Import {
+        /// Bundle file written by `perspective export`
+        path: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -281,6 +378,35 @@ enum ConsensusAction {
         #[arg(long)]
         min_weight: Option<f64>,
     },
+
+    /// Create signed attestations for nodes that reach consensus
+    Attest {
+        /// ltree scope to scan (e.g. pkg.module)
+        scope: String,
+
+        /// Minimum number of agreeing agents
+        #[arg(long, default_value = "2")]
+        min_agents: i32,
+
+        /// Minimum average weight
+        #[arg(long, default_value = "0.5")]
+        min_weight: f64,
+    },
+
+    /// Review nodes where agents disagree and override their weights (TUI)
+    Review {
+        /// Agent that overriding perspectives are recorded as
+        #[arg(long)]
+        agent: String,
+
+        /// Filter by context node ID
+        #[arg(long)]
+        context: Option<String>,
+
+        /// Minimum gap between the highest and lowest agent weight
+        #[arg(long, default_value = "0.5")]
+        min_spread: f64,
+    },
 }
 
 #[derive(Subcommand)]
@@ -317,6 +443,15 @@ enum PeerAction {
         /// Peer name
         name: String,
     },
+
+    /// Set how much a peer's synced ops are trusted
+    Trust {
+        /// Peer name
+        name: String,
+
+        /// Trust level: full, read_only, or untrusted
+        level: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -341,6 +476,10 @@ enum TaskAction {
         /// Time budget in seconds
         #[arg(long)]
         budget_seconds: Option<i32>,
+
+        /// Reward in nKoi paid to the winning agent on completion
+        #[arg(long)]
+        reward: Option<i64>,
     },
 
     /// List tasks
@@ -389,6 +528,16 @@ enum SwarmAction {
         task_id: String,
     },
 
+    /// Complete the task if enough agents' latest results passed
+    Evaluate {
+        /// Task ID
+        task_id: String,
+
+        /// Agents whose latest result must pass
+        #[arg(long, default_value = "1")]
+        quorum: i32,
+    },
+
     /// Show per-agent leaderboard
     Leaderboard {
         /// Task ID
@@ -432,6 +581,10 @@ enum MarketAction {
         /// Hours after settlement before open-sourcing
         #[arg(long, default_value = "24")]
         open_delay_hours: i32,
+
+        /// Print the projected price schedule instead of creating the auction
+        #[arg(long)]
+        preview: bool,
     },
 
     /// Place a bid on an auction
@@ -456,6 +609,18 @@ enum MarketAction {
         auction_id: String,
     },
 
+    /// Push back open-sourcing of an auction you are selling
+    ExtendDelay {
+        /// Auction ID
+        auction_id: String,
+
+        /// Hours to add to the open-source delay
+        hours: i32,
+    },
+
+    /// List settled auctions whose open-source delay has elapsed
+    Due,
+
     /// Browse auctions
     Browse {
         /// Filter by scope (ltree path)
@@ -517,17 +682,17 @@ enum WalletAction {
 
     /// Show wallet balance
     Balance {
-        /// Wallet ID (default: self instance wallet)
+        /// Wallet ID or @label (default: self instance wallet)
         wallet_id: Option<String>,
     },
 
     /// Transfer Koi between wallets
     Transfer {
-        /// Source wallet ID
+        /// Source wallet ID or @label
         #[arg(long)]
         from: String,
 
-        /// Destination wallet ID
+        /// Destination wallet ID or @label
         #[arg(long)]
         to: String,
 
@@ -542,7 +707,7 @@ enum WalletAction {
 
     /// Show transaction history
     History {
-        /// Wallet ID
+        /// Wallet ID or @label
         wallet_id: String,
 
         /// Maximum entries
@@ -593,6 +758,20 @@ enum BountyAction {
         bounty_id: String,
     },
 
+    /// Reserve a bounty while you work on it
+    Reserve {
+        /// Bounty ID
+        bounty_id: String,
+
+        /// Reserving wallet ID
+        #[arg(long)]
+        wallet: String,
+
+        /// Reservation lifetime in seconds
+        #[arg(long, default_value = "3600")]
+        ttl: i32,
+    },
+
     /// Claim a bounty
     Claim {
         /// Bounty ID
@@ -673,6 +852,10 @@ enum ModelAction {
         /// Scope (ltree path) to build vocabulary from
         #[arg(long)]
         scope: Option<String>,
+
+        /// Seed for reproducible weight initialization
+        #[arg(long)]
+        seed: Option<i64>,
     },
 
     /// Train a model on graph walks
@@ -764,6 +947,17 @@ enum ModelAction {
         #[arg(long)]
         agent: String,
     },
+
+    /// Copy a base agent's model to another agent for fine-tuning
+    Fork {
+        /// Agent whose model is copied
+        #[arg(long)]
+        base: String,
+
+        /// Agent receiving the copy (must not have a model yet)
+        #[arg(long)]
+        agent: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -808,6 +1002,11 @@ enum CurrencyAction {
         /// Transfer reason
         #[arg(long)]
         reason: Option<String>,
+
+        /// Signature over "nonce_rejection:{from}:{expected}:{nonce}" (hex-encoded),
+        /// used to report the nonce for `currency nonce-status` if it is rejected
+        #[arg(long)]
+        rejection_signature: Option<String>,
     },
 
     /// Show total supply info
@@ -835,6 +1034,39 @@ enum CurrencyAction {
         /// Enable or disable this reward
         #[arg(long)]
         enabled: Option<bool>,
+
+        /// Mints between reward halvings (0 disables halving)
+        #[arg(long)]
+        halving_interval: Option<i64>,
+    },
+
+    /// Show a wallet's accepted nonce and any rejected transfer attempts
+    NonceStatus {
+        /// Wallet ID
+        wallet_id: String,
+    },
+
+    /// Move a stuck wallet's nonce forward (signed with the instance key)
+    ResetNonce {
+        /// Wallet ID
+        wallet_id: String,
+
+        /// New nonce; the next accepted transfer uses this + 1
+        #[arg(long)]
+        nonce: i64,
+
+        /// Ed25519 signature over "reset_nonce:{wallet_id}:{current}:{nonce}" (hex)
+        #[arg(long)]
+        signature: String,
+    },
+
+    /// Scale rewards for work under a scope (most specific scope wins)
+    SetMultiplier {
+        /// Scope (ltree path, e.g. pkg.core)
+        scope: String,
+
+        /// Multiplier applied to the scheduled reward (e.g. 2.0)
+        multiplier: f64,
     },
 }
 
@@ -1202,10 +1434,8 @@ fn main() {
     // Handle serve subcommand separately — it creates its own tokio runtime
     if let CliCommand::Serve { addr } = &cli.command {
         let profile = config::load_config(&cli.profile);
-        let db_url = cli.db.as_deref()
-            .or(profile.connection.as_deref())
-            .unwrap_or("host=/tmp dbname=kerai")
-            .to_string();
+        let db_url = db::connection_string(&profile, cli.db.as_deref())
+            .unwrap_or_else(|| "host=/tmp dbname=kerai".to_string());
         tokio::runtime::Runtime::new()
             .expect("Failed to create tokio runtime")
             .block_on(kerai_cli::serve::run(addr, &db_url));
@@ -1219,7 +1449,7 @@ fn main() {
             PostgresAction::Ping => commands::Command::Ping,
             PostgresAction::Info => commands::Command::Info,
             PostgresAction::Version => commands::Command::Version,
-            PostgresAction::Query { sql } => commands::Command::Query { sql },
+            PostgresAction::Query { sql, params } => commands::Command::Query { sql, params },
             PostgresAction::Export { file } => commands::Command::Export { file },
             PostgresAction::Log { author, limit } => commands::Command::Log { author, limit },
             PostgresAction::Commit { message } => commands::Command::Commit { message },
@@ -1227,13 +1457,40 @@ fn main() {
                 pattern,
                 kind,
                 limit,
+                highlight,
+                regex,
             } => commands::Command::Find {
                 pattern,
                 kind,
                 limit,
+                highlight,
+                regex,
             },
             PostgresAction::Refs { symbol } => commands::Command::Refs { symbol },
-            PostgresAction::Tree { path } => commands::Command::Tree { path },
+            PostgresAction::Definition { symbol, from_file } => {
+                commands::Command::Definition { symbol, from_file }
+            }
+            PostgresAction::Implementors { trait_name } => {
+                commands::Command::Implementors { trait_name }
+            }
+            PostgresAction::ImplMethods { type_name } => {
+                commands::Command::ImplMethods { type_name }
+            }
+            PostgresAction::Reachable {
+                node_id,
+                relation,
+                depth,
+            } => commands::Command::Reachable {
+                node_id,
+                relation,
+                depth,
+            },
+            PostgresAction::CallGraph { node_id, depth } => {
+                commands::Command::CallGraph { node_id, depth }
+            }
+            PostgresAction::Tree { path, depth, kind } => {
+                commands::Command::Tree { path, depth, kind }
+            }
             PostgresAction::ImportCsv {
                 path,
                 schema,
@@ -1246,6 +1503,8 @@ fn main() {
         },
         CliCommand::Sync { action } => match action {
             SyncAction::Run { peer } => commands::Command::Sync { peer },
+            SyncAction::Export { path, since } => commands::Command::SyncExport { path, since },
+            SyncAction::Import { path } => commands::Command::SyncImport { path },
         },
         CliCommand::Perspective { action } => match action {
             PerspectiveAction::List {
@@ -1257,6 +1516,10 @@ fn main() {
                 context_id: context,
                 min_weight,
             },
+            PerspectiveAction::Export { agent, path } => {
+                commands::Command::PerspectiveExport { agent, path }
+            }
+            PerspectiveAction::Import { path } => commands::Command::PerspectiveImport { path },
         },
         CliCommand::Consensus { action } => match action {
             ConsensusAction::Status {
@@ -1268,6 +1531,24 @@ fn main() {
                 min_agents,
                 min_weight,
             },
+            ConsensusAction::Attest {
+                scope,
+                min_agents,
+                min_weight,
+            } => commands::Command::ConsensusAttest {
+                scope,
+                min_agents,
+                min_weight,
+            },
+            ConsensusAction::Review {
+                agent,
+                context,
+                min_spread,
+            } => commands::Command::ConsensusReview {
+                agent,
+                context_id: context,
+                min_spread,
+            },
         },
         CliCommand::Peer { action } => match action {
             PeerAction::Add {
@@ -1284,6 +1565,7 @@ fn main() {
             PeerAction::List => commands::Command::PeerList,
             PeerAction::Remove { name } => commands::Command::PeerRemove { name },
             PeerAction::Info { name } => commands::Command::PeerInfo { name },
+            PeerAction::Trust { name, level } => commands::Command::PeerTrust { name, level },
         },
         CliCommand::Agent { action } => match action {
             AgentAction::Add { name, kind, model } => commands::Command::AgentAdd {
@@ -1302,12 +1584,14 @@ fn main() {
                 scope,
                 budget_ops,
                 budget_seconds,
+                reward,
             } => commands::Command::TaskCreate {
                 description,
                 success_command,
                 scope,
                 budget_ops,
                 budget_seconds,
+                reward,
             },
             TaskAction::List { status } => commands::Command::TaskList { status },
             TaskAction::Show { task_id } => commands::Command::TaskShow { task_id },
@@ -1326,6 +1610,9 @@ fn main() {
             },
             SwarmAction::Status { task_id } => commands::Command::SwarmStatus { task_id },
             SwarmAction::Stop { task_id } => commands::Command::SwarmStop { task_id },
+            SwarmAction::Evaluate { task_id, quorum } => {
+                commands::Command::SwarmEvaluate { task_id, quorum }
+            }
             SwarmAction::Leaderboard { task_id } => {
                 commands::Command::SwarmLeaderboard { task_id }
             }
@@ -1372,6 +1659,15 @@ fn main() {
             },
             BountyAction::List { status, scope } => commands::Command::BountyList { status, scope },
             BountyAction::Show { bounty_id } => commands::Command::BountyShow { bounty_id },
+            BountyAction::Reserve {
+                bounty_id,
+                wallet,
+                ttl,
+            } => commands::Command::BountyReserve {
+                bounty_id,
+                wallet_id: wallet,
+                ttl,
+            },
             BountyAction::Claim { bounty_id, wallet } => commands::Command::BountyClaim {
                 bounty_id,
                 wallet_id: wallet,
@@ -1387,6 +1683,7 @@ fn main() {
                 decrement_interval,
                 min_bidders,
                 open_delay_hours,
+                preview,
             } => commands::Command::MarketCreate {
                 attestation_id,
                 starting_price,
@@ -1395,6 +1692,7 @@ fn main() {
                 decrement_interval,
                 min_bidders,
                 open_delay_hours,
+                preview,
             },
             MarketAction::Bid {
                 auction_id,
@@ -1409,6 +1707,10 @@ fn main() {
             MarketAction::OpenSource { auction_id } => {
                 commands::Command::MarketOpenSource { auction_id }
             }
+            MarketAction::ExtendDelay { auction_id, hours } => {
+                commands::Command::MarketExtendDelay { auction_id, hours }
+            }
+            MarketAction::Due => commands::Command::MarketDue,
             MarketAction::Browse {
                 scope,
                 max_price,
@@ -1435,6 +1737,7 @@ fn main() {
                 layers,
                 context_len,
                 scope,
+                seed,
             } => commands::Command::ModelCreate {
                 agent,
                 dim,
@@ -1442,6 +1745,7 @@ fn main() {
                 layers,
                 context_len,
                 scope,
+                seed,
             },
             ModelAction::Train {
                 agent,
@@ -1489,6 +1793,7 @@ fn main() {
             },
             ModelAction::Info { agent } => commands::Command::ModelInfo { agent },
             ModelAction::Delete { agent } => commands::Command::ModelDelete { agent },
+            ModelAction::Fork { base, agent } => commands::Command::ModelFork { base, agent },
         },
         CliCommand::Config { action } => match action {
             ConfigAction::Get { key } => commands::Command::ConfigGet { key },
@@ -1532,6 +1837,7 @@ fn main() {
                 nonce,
                 signature,
                 reason,
+                rejection_signature,
             } => commands::Command::CurrencyTransfer {
                 from,
                 to,
@@ -1539,6 +1845,7 @@ fn main() {
                 nonce,
                 signature,
                 reason,
+                rejection_signature,
             },
             CurrencyAction::Supply => commands::Command::CurrencySupply,
             CurrencyAction::Share { wallet_id } => {
@@ -1549,11 +1856,28 @@ fn main() {
                 work_type,
                 reward,
                 enabled,
+                halving_interval,
             } => commands::Command::CurrencySetReward {
                 work_type,
                 reward,
                 enabled,
+                halving_interval,
             },
+            CurrencyAction::NonceStatus { wallet_id } => {
+                commands::Command::CurrencyNonceStatus { wallet_id }
+            }
+            CurrencyAction::ResetNonce {
+                wallet_id,
+                nonce,
+                signature,
+            } => commands::Command::CurrencyResetNonce {
+                wallet_id,
+                nonce,
+                signature,
+            },
+            CurrencyAction::SetMultiplier { scope, multiplier } => {
+                commands::Command::CurrencySetMultiplier { scope, multiplier }
+            }
         },
         CliCommand::Serve { .. } => unreachable!("handled above"),
     };
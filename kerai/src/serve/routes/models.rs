@@ -20,6 +20,7 @@ pub struct CreateModelBody {
     pub n_layers: Option<i32>,
     pub context_len: Option<i32>,
     pub scope: Option<String>,
+    pub seed: Option<i64>,
 }
 
 /// POST /api/models — create a new model
@@ -29,13 +30,14 @@ pub async fn create_model(
 ) -> ApiResult {
     let client = pool.get().await.map_err(internal_err)?;
     let sql = format!(
-        "SELECT kerai.create_model('{}', {}, {}, {}, {}, {})::text",
+        "SELECT kerai.create_model('{}', {}, {}, {}, {}, {}, {})::text",
         body.agent.replace('\'', "''"),
         body.dim.map(|v| v.to_string()).unwrap_or("NULL".into()),
         body.n_heads.map(|v| v.to_string()).unwrap_or("NULL".into()),
         body.n_layers.map(|v| v.to_string()).unwrap_or("NULL".into()),
         body.context_len.map(|v| v.to_string()).unwrap_or("NULL".into()),
         body.scope.as_ref().map(|s| format!("'{}'", s.replace('\'', "''"))).unwrap_or("NULL".into()),
+        body.seed.map(|v| v.to_string()).unwrap_or("NULL".into()),
     );
     let row = client.query_one(&sql, &[]).await.map_err(internal_err)?;
     let text: String = row.get(0);
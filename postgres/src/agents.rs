@@ -1,6 +1,7 @@
 /// Agent management — register, list, get, remove AI agents.
 use pgrx::prelude::*;
 
+use crate::pagination;
 use crate::sql::sql_escape;
 
 /// Register or update an AI agent. Returns JSON with agent info.
@@ -78,27 +79,44 @@ fn register_agent(
     }))
 }
 
-/// List agents with optional kind filter.
+/// List agents with optional kind filter, by name.
+///
+/// With `limit` or `cursor`, returns one page `{items, total_count, next_cursor}`
+/// in creation order instead (see `pagination`).
 #[pg_extern]
-fn list_agents(kind_filter: Option<&str>) -> pgrx::JsonB {
-    let where_clause = match kind_filter {
-        Some(k) => format!("WHERE kind = '{}'", sql_escape(k)),
-        None => String::new(),
-    };
+fn list_agents(
+    kind_filter: Option<&str>,
+    limit: default!(Option<i32>, "NULL"),
+    cursor: default!(Option<&str>, "NULL"),
+) -> pgrx::JsonB {
+    let conditions: Vec<String> = kind_filter
+        .map(|k| format!("ag.kind = '{}'", sql_escape(k)))
+        .into_iter()
+        .collect();
+    let object = "jsonb_build_object(
+                'id', ag.id,
+                'name', ag.name,
+                'kind', ag.kind,
+                'model', ag.model,
+                'config', ag.config,
+                'created_at', ag.created_at
+            )";
+
+    if pagination::requested(limit, cursor) {
+        return pagination::keyset_page(
+            object,
+            "kerai.agents ag",
+            &conditions,
+            "ag",
+            limit,
+            cursor,
+        );
+    }
 
     let json = Spi::get_one::<pgrx::JsonB>(&format!(
-        "SELECT COALESCE(
-            jsonb_agg(jsonb_build_object(
-                'id', id,
-                'name', name,
-                'kind', kind,
-                'model', model,
-                'config', config,
-                'created_at', created_at
-            ) ORDER BY name),
-            '[]'::jsonb
-        ) FROM kerai.agents {}",
-        where_clause,
+        "SELECT COALESCE(jsonb_agg({object} ORDER BY ag.name), '[]'::jsonb)
+        FROM kerai.agents ag {}",
+        pagination::where_clause(&conditions),
     ))
     .unwrap()
     .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
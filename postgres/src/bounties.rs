@@ -1,10 +1,13 @@
 /// Bounties — task bounty lifecycle management.
 use pgrx::prelude::*;
 
+use crate::escrow;
+use crate::pagination;
 use crate::sql::sql_escape;
 
 /// Create a bounty. Uses the self instance wallet as poster.
-/// Validates reward > 0 and poster has sufficient balance.
+/// Validates reward > 0 and poster has sufficient balance, then holds the
+/// reward in escrow until the bounty is paid or expires.
 #[pg_extern]
 fn create_bounty(
     scope: &str,
@@ -54,9 +57,25 @@ fn create_bounty(
         None => "NULL".to_string(),
     };
 
-    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+    let bounty_id = Spi::get_one::<String>(&format!(
         "INSERT INTO kerai.bounties (poster_wallet, scope, description, success_command, reward, expires_at)
          VALUES ('{}'::uuid, '{}'::ltree, '{}', {}, {}, {})
+         RETURNING id::text",
+        sql_escape(&self_wallet),
+        sql_escape(scope),
+        sql_escape(description),
+        cmd_sql,
+        reward,
+        expires_sql,
+    ))
+    .unwrap()
+    .unwrap();
+
+    let escrow_id = escrow::hold(&self_wallet, reward, &format!("bounty {}", bounty_id));
+
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "UPDATE kerai.bounties SET escrow_id = '{}'::uuid
+         WHERE id = '{}'::uuid
          RETURNING jsonb_build_object(
              'id', id,
              'poster_wallet', poster_wallet,
@@ -66,54 +85,71 @@ fn create_bounty(
              'reward', reward,
              'status', status,
              'created_at', created_at,
-             'expires_at', expires_at
+             'expires_at', expires_at,
+             'escrow_id', escrow_id
          )",
-        sql_escape(&self_wallet),
-        sql_escape(scope),
-        sql_escape(description),
-        cmd_sql,
-        reward,
-        expires_sql,
+        sql_escape(&escrow_id),
+        sql_escape(&bounty_id),
     ))
     .unwrap()
     .unwrap();
     row
 }
 
-/// List bounties with optional status and scope filters.
+/// List bounties with optional status and scope filters, highest reward first.
+/// Reserved bounties include `reserved_by` and their `reserved_until` expiry.
+///
+/// With `limit` or `cursor`, returns one page `{items, total_count, next_cursor}`
+/// in creation order instead (see `pagination`).
 #[pg_extern]
-fn list_bounties(status_filter: Option<&str>, scope_filter: Option<&str>) -> pgrx::JsonB {
+fn list_bounties(
+    status_filter: Option<&str>,
+    scope_filter: Option<&str>,
+    limit: default!(Option<i32>, "NULL"),
+    cursor: default!(Option<&str>, "NULL"),
+) -> pgrx::JsonB {
     let mut conditions = Vec::new();
 
     if let Some(s) = status_filter {
-        conditions.push(format!("b.status = '{}'", sql_escape(s)));
+        conditions.push(format!("{} = '{}'", EFFECTIVE_STATUS, sql_escape(s)));
     }
     if let Some(scope) = scope_filter {
         conditions.push(format!("b.scope <@ '{}'::ltree", sql_escape(scope)));
     }
 
-    let where_clause = if conditions.is_empty() {
-        String::new()
-    } else {
-        format!("WHERE {}", conditions.join(" AND "))
-    };
-
-    let json = Spi::get_one::<pgrx::JsonB>(&format!(
-        "SELECT COALESCE(
-            jsonb_agg(jsonb_build_object(
+    let object = format!(
+        "jsonb_build_object(
                 'id', b.id,
                 'poster_wallet', b.poster_wallet,
                 'scope', b.scope::text,
                 'description', b.description,
                 'reward', b.reward,
-                'status', b.status,
+                'status', {status},
                 'claimed_by', b.claimed_by,
+                'reserved_by', CASE WHEN {lapsed} THEN NULL ELSE b.reserved_by END,
+                'reserved_until', CASE WHEN {lapsed} THEN NULL ELSE b.reserved_until END,
                 'created_at', b.created_at,
                 'expires_at', b.expires_at
-            ) ORDER BY b.reward DESC),
-            '[]'::jsonb
-        ) FROM kerai.bounties b {}",
-        where_clause,
+            )",
+        status = EFFECTIVE_STATUS,
+        lapsed = RESERVATION_LAPSED,
+    );
+
+    if pagination::requested(limit, cursor) {
+        return pagination::keyset_page(
+            &object,
+            "kerai.bounties b",
+            &conditions,
+            "b",
+            limit,
+            cursor,
+        );
+    }
+
+    let json = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg({object} ORDER BY b.reward DESC), '[]'::jsonb)
+        FROM kerai.bounties b {}",
+        pagination::where_clause(&conditions),
     ))
     .unwrap()
     .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
@@ -131,13 +167,18 @@ fn get_bounty(bounty_id: pgrx::Uuid) -> pgrx::JsonB {
             'description', b.description,
             'success_command', b.success_command,
             'reward', b.reward,
-            'status', b.status,
+            'status', {status},
             'claimed_by', b.claimed_by,
+            'reserved_by', CASE WHEN {lapsed} THEN NULL ELSE b.reserved_by END,
+            'reserved_until', CASE WHEN {lapsed} THEN NULL ELSE b.reserved_until END,
+            'claim_expires_at', b.claim_expires_at,
             'verified_at', b.verified_at,
             'created_at', b.created_at,
             'expires_at', b.expires_at
-        ) FROM kerai.bounties b WHERE b.id = '{}'::uuid",
-        bounty_id,
+        ) FROM kerai.bounties b WHERE b.id = '{id}'::uuid",
+        status = EFFECTIVE_STATUS,
+        lapsed = RESERVATION_LAPSED,
+        id = bounty_id,
     ))
     .unwrap_or(None);
 
@@ -147,54 +188,179 @@ fn get_bounty(bounty_id: pgrx::Uuid) -> pgrx::JsonB {
     }
 }
 
-/// Claim an open bounty. Sets status='claimed' and records claimer wallet.
+/// Reserve an open bounty for one worker while they work on it.
+///
+/// Sets status='reserved' until `ttl_seconds` from now. Only the reserving
+/// wallet can claim it meanwhile, and may call this again to extend. An
+/// expired reservation reads as 'open' and can be taken by anyone.
 #[pg_extern]
-fn claim_bounty(bounty_id: pgrx::Uuid, claimer_wallet_id: pgrx::Uuid) -> pgrx::JsonB {
-    // Verify claimer wallet exists
-    let claimer_exists = Spi::get_one::<bool>(&format!(
+fn reserve_bounty(
+    bounty_id: pgrx::Uuid,
+    wallet_id: pgrx::Uuid,
+    ttl_seconds: default!(i32, "3600"),
+) -> pgrx::JsonB {
+    if ttl_seconds <= 0 {
+        error!("Reservation TTL must be positive");
+    }
+    let wallet_exists = Spi::get_one::<bool>(&format!(
         "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id = '{}'::uuid)",
-        claimer_wallet_id,
+        wallet_id,
     ))
     .unwrap()
     .unwrap_or(false);
-    if !claimer_exists {
-        error!("Claimer wallet not found: {}", claimer_wallet_id);
+    if !wallet_exists {
+        error!("Wallet not found: {}", wallet_id);
     }
 
-    // Get current bounty status
-    let status = Spi::get_one::<String>(&format!(
-        "SELECT status FROM kerai.bounties WHERE id = '{}'::uuid",
-        bounty_id,
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "UPDATE kerai.bounties b
+         SET status = 'reserved', reserved_by = '{wallet}'::uuid,
+             reserved_until = now() + make_interval(secs => {ttl})
+         WHERE id = '{id}'::uuid AND {available}
+         RETURNING jsonb_build_object(
+             'id', id,
+             'status', status,
+             'reserved_by', reserved_by,
+             'reserved_until', reserved_until,
+             'reward', reward,
+             'scope', scope::text,
+             'description', description
+         )",
+        wallet = wallet_id,
+        ttl = ttl_seconds,
+        id = bounty_id,
+        available = available_to(wallet_id),
     ))
     .unwrap_or(None);
+    row.unwrap_or_else(|| reservation_error(bounty_id, wallet_id, "reserved"))
+}
 
-    match status.as_deref() {
-        None => error!("Bounty not found: {}", bounty_id),
-        Some("open") => {}
-        Some(s) => error!("Bounty cannot be claimed, currently '{}' (must be 'open')", s),
+/// Claim an open bounty, or one reserved by the claimer. Sets status='claimed'
+/// and records claimer wallet. The claim lapses after `claim_ttl_seconds`
+/// (default 7 days), after which `release_claim` can reopen the bounty.
+#[pg_extern]
+fn claim_bounty(
+    bounty_id: pgrx::Uuid,
+    claimer_wallet_id: pgrx::Uuid,
+    claim_ttl_seconds: default!(i32, "604800"),
+) -> pgrx::JsonB {
+    if claim_ttl_seconds <= 0 {
+        error!("Claim TTL must be positive");
+    }
+    // Verify claimer wallet exists
+    let claimer_exists = Spi::get_one::<bool>(&format!(
+        "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id = '{}'::uuid)",
+        claimer_wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if !claimer_exists {
+        error!("Claimer wallet not found: {}", claimer_wallet_id);
     }
 
     let row = Spi::get_one::<pgrx::JsonB>(&format!(
-        "UPDATE kerai.bounties
-         SET status = 'claimed', claimed_by = '{}'::uuid
-         WHERE id = '{}'::uuid
+        "UPDATE kerai.bounties b
+         SET status = 'claimed', claimed_by = '{wallet}'::uuid,
+             reserved_by = NULL, reserved_until = NULL,
+             claim_expires_at = now() + make_interval(secs => {ttl})
+         WHERE id = '{id}'::uuid AND {available}
          RETURNING jsonb_build_object(
              'id', id,
              'status', status,
              'claimed_by', claimed_by,
+             'claim_expires_at', claim_expires_at,
              'reward', reward,
              'scope', scope::text,
              'description', description
          )",
-        claimer_wallet_id,
+        wallet = claimer_wallet_id,
+        ttl = claim_ttl_seconds,
+        id = bounty_id,
+        available = available_to(claimer_wallet_id),
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| reservation_error(bounty_id, claimer_wallet_id, "claimed"));
+
+    Spi::run(&format!(
+        "INSERT INTO kerai.bounty_claims (bounty_id, claimer_wallet, expires_at)
+         SELECT id, claimed_by, claim_expires_at FROM kerai.bounties WHERE id = '{}'::uuid",
         bounty_id,
     ))
-    .unwrap()
     .unwrap();
     row
 }
 
-/// Settle a claimed bounty: transfer reward from poster to claimer.
+/// Release a lapsed claim: once the active claim is past `claim_expires_at`
+/// without settlement, the bounty returns to 'open' for anyone to claim.
+#[pg_extern]
+fn release_claim(bounty_id: pgrx::Uuid) -> pgrx::JsonB {
+    let row = Spi::get_three::<String, bool, String>(&format!(
+        "SELECT status, claim_expires_at < now(), claim_expires_at::text
+         FROM kerai.bounties WHERE id = '{}'::uuid FOR UPDATE",
+        bounty_id,
+    ));
+    let (status, lapsed, expires) = match row {
+        Ok((Some(status), lapsed, expires)) => (status, lapsed, expires),
+        _ => error!("Bounty not found: {}", bounty_id),
+    };
+    if status != "claimed" {
+        error!(
+            "Bounty must be 'claimed' to release its claim, currently '{}'",
+            status
+        );
+    }
+    if lapsed != Some(true) {
+        error!(
+            "Claim on bounty {} is still active until {}",
+            bounty_id,
+            expires.unwrap_or_default()
+        );
+    }
+
+    let released_from = Spi::get_one::<String>(&format!(
+        "UPDATE kerai.bounty_claims SET status = 'released', ended_at = now()
+         WHERE bounty_id = '{}'::uuid AND status = 'active'
+         RETURNING claimer_wallet::text",
+        bounty_id,
+    ))
+    .unwrap_or(None);
+
+    Spi::run(&format!(
+        "UPDATE kerai.bounties
+         SET status = 'open', claimed_by = NULL, claim_expires_at = NULL
+         WHERE id = '{}'::uuid",
+        bounty_id,
+    ))
+    .unwrap();
+
+    pgrx::JsonB(serde_json::json!({
+        "bounty_id": bounty_id.to_string(),
+        "status": "open",
+        "released_from": released_from,
+    }))
+}
+
+/// Claim history for a bounty, oldest first.
+#[pg_extern]
+fn list_claims(bounty_id: pgrx::Uuid) -> pgrx::JsonB {
+    Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', id,
+            'claimer_wallet', claimer_wallet,
+            'status', status,
+            'claimed_at', claimed_at,
+            'expires_at', expires_at,
+            'ended_at', ended_at
+        ) ORDER BY claimed_at, id), '[]'::jsonb)
+        FROM kerai.bounty_claims WHERE bounty_id = '{}'::uuid",
+        bounty_id,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])))
+}
+
+/// Settle a claimed bounty: release the escrowed reward to the claimer, or
+/// transfer it from the poster for bounties without escrow (e.g. replicated).
 #[pg_extern]
 fn settle_bounty(bounty_id: pgrx::Uuid) -> pgrx::JsonB {
     // Get bounty details
@@ -204,7 +370,8 @@ fn settle_bounty(bounty_id: pgrx::Uuid) -> pgrx::JsonB {
             'poster_wallet', poster_wallet,
             'claimed_by', claimed_by,
             'reward', reward,
-            'status', status
+            'status', status,
+            'escrow_id', escrow_id
         ) FROM kerai.bounties WHERE id = '{}'::uuid",
         bounty_id,
     ))
@@ -230,6 +397,36 @@ fn settle_bounty(bounty_id: pgrx::Uuid) -> pgrx::JsonB {
         .unwrap_or_else(|| error!("Bounty has no claimer"));
     let reward = obj["reward"].as_i64().unwrap();
 
+    if let Some(escrow_id) = obj["escrow_id"].as_str() {
+        escrow::release(escrow_id, claimed_by);
+    } else {
+        pay_from_poster(bounty_id, poster_wallet, claimed_by, reward);
+    }
+
+    // Update bounty status
+    Spi::run(&format!(
+        "UPDATE kerai.bounties SET status = 'paid', verified_at = now() WHERE id = '{}'::uuid",
+        bounty_id,
+    ))
+    .unwrap();
+    Spi::run(&format!(
+        "UPDATE kerai.bounty_claims SET status = 'settled', ended_at = now()
+         WHERE bounty_id = '{}'::uuid AND status = 'active'",
+        bounty_id,
+    ))
+    .unwrap();
+
+    pgrx::JsonB(serde_json::json!({
+        "bounty_id": bounty_id.to_string(),
+        "status": "paid",
+        "reward": reward,
+        "poster_wallet": poster_wallet,
+        "claimed_by": claimed_by,
+    }))
+}
+
+/// Transfer an unescrowed reward straight from the poster to the claimer.
+fn pay_from_poster(bounty_id: pgrx::Uuid, poster_wallet: &str, claimed_by: &str, reward: i64) {
     // Verify poster has sufficient balance
     let balance = Spi::get_one::<i64>(&format!(
         "SELECT COALESCE(
@@ -267,19 +464,90 @@ fn settle_bounty(bounty_id: pgrx::Uuid) -> pgrx::JsonB {
         lamport,
     ))
     .unwrap();
+}
 
-    // Update bounty status
-    Spi::run(&format!(
-        "UPDATE kerai.bounties SET status = 'paid', verified_at = now() WHERE id = '{}'::uuid",
-        bounty_id,
-    ))
+/// Close open bounties past `expires_at` as 'expired', refunding any escrowed
+/// reward to the poster. Run periodically by the bounty expiry worker.
+/// Returns the number of bounties closed.
+#[pg_extern]
+fn expire_bounties() -> i64 {
+    release_expired_reservations();
+
+    // One entry per closed bounty; null for bounties without escrow
+    let expired = Spi::get_one::<pgrx::JsonB>(
+        "WITH e AS (
+            UPDATE kerai.bounties SET status = 'expired'
+            WHERE status = 'open' AND expires_at < now()
+            RETURNING escrow_id
+        ) SELECT COALESCE(jsonb_agg(escrow_id), '[]'::jsonb) FROM e",
+    )
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| serde_json::json!([]));
+
+    let expired = expired.as_array().cloned().unwrap_or_default();
+    for escrow_id in expired.iter().filter_map(|e| e.as_str()) {
+        escrow::refund(escrow_id);
+    }
+    expired.len() as i64
+}
+
+/// Return reservations past their `reserved_until` to 'open'.
+fn release_expired_reservations() {
+    Spi::run(
+        "UPDATE kerai.bounties
+         SET status = 'open', reserved_by = NULL, reserved_until = NULL
+         WHERE status = 'reserved' AND reserved_until < now()",
+    )
     .unwrap();
+}
 
-    pgrx::JsonB(serde_json::json!({
-        "bounty_id": bounty_id.to_string(),
-        "status": "paid",
-        "reward": reward,
-        "poster_wallet": poster_wallet,
-        "claimed_by": claimed_by,
-    }))
+/// True for a reservation past `reserved_until`, which reads as 'open'.
+/// Read paths report it that way rather than writing, so they work in
+/// read-only transactions.
+const RESERVATION_LAPSED: &str = "(b.status = 'reserved' AND b.reserved_until < now())";
+
+/// A bounty's status with lapsed reservations shown as 'open'.
+const EFFECTIVE_STATUS: &str =
+    "CASE WHEN (b.status = 'reserved' AND b.reserved_until < now()) THEN 'open' ELSE b.status END";
+
+/// Condition for a bounty `b` that `wallet_id` may reserve or claim: open,
+/// reserved by that wallet, or with a lapsed reservation. Used in the
+/// UPDATE's WHERE so the check and the write are one atomic step.
+fn available_to(wallet_id: pgrx::Uuid) -> String {
+    format!(
+        "(b.status = 'open' OR (b.status = 'reserved' AND (b.reserved_by = '{}'::uuid OR {})))",
+        wallet_id, RESERVATION_LAPSED,
+    )
+}
+
+/// Explain why the bounty couldn't become `target` ('reserved' or 'claimed')
+/// for `wallet_id`: it doesn't exist, is reserved by another wallet, or is
+/// no longer open.
+fn reservation_error(bounty_id: pgrx::Uuid, wallet_id: pgrx::Uuid, target: &str) -> ! {
+    let row = Spi::get_two::<String, String>(&format!(
+        "SELECT {}, reserved_by::text FROM kerai.bounties b WHERE id = '{}'::uuid",
+        EFFECTIVE_STATUS, bounty_id,
+    ));
+    let (status, reserved_by) = match row {
+        Ok((Some(status), reserved_by)) => (status, reserved_by),
+        _ => error!("Bounty not found: {}", bounty_id),
+    };
+
+    match status.as_str() {
+        "reserved" if reserved_by.as_deref() != Some(wallet_id.to_string().as_str()) => error!(
+            "Bounty cannot be {}, reserved by another wallet ({})",
+            target,
+            reserved_by.unwrap_or_default()
+        ),
+        // It was taken and released again between the update and this read
+        "open" | "reserved" => error!(
+            "Bounty {} changed while being {}; try again",
+            bounty_id, target
+        ),
+        s => error!(
+            "Bounty cannot be {}, currently '{}' (must be 'open')",
+            target, s
+        ),
+    }
 }
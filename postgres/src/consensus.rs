@@ -59,6 +59,88 @@ fn consensus(
     json
 }
 
+/// Nodes where agents disagree most: those rated by at least `min_agents`
+/// (default 2) whose weight standard deviation is at least `min_stddev`
+/// (default 0), most contested first.
+#[pg_extern]
+fn contested_nodes(min_agents: Option<i32>, min_stddev: Option<f64>) -> pgrx::JsonB {
+    let min_a = min_agents.unwrap_or(2);
+    let min_s = min_stddev.unwrap_or(0.0);
+
+    Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(
+            jsonb_agg(jsonb_build_object(
+                'node_id', c.node_id,
+                'context_id', c.context_id,
+                'agent_count', c.agent_count,
+                'avg_weight', c.avg_weight,
+                'min_weight', c.min_weight,
+                'max_weight', c.max_weight,
+                'stddev_weight', c.stddev_weight,
+                'node_kind', n.kind,
+                'node_content', n.content
+            ) ORDER BY c.stddev_weight DESC, c.agent_count DESC),
+            '[]'::jsonb
+        ) FROM kerai.consensus_perspectives c
+        JOIN kerai.nodes n ON n.id = c.node_id
+        WHERE c.agent_count >= {} AND c.stddev_weight >= {}",
+        min_a, min_s,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])))
+}
+
+/// Cross-instance consensus on a node. Groups perspectives by the instance they
+/// originated on (self plus peers whose perspectives arrived via sync) and
+/// reports each instance's weights alongside a blend where every instance
+/// counts once. `agreement` is true when all instances lean the same way.
+#[pg_extern]
+fn global_consensus(node_id: pgrx::Uuid, min_instances: Option<i32>) -> pgrx::JsonB {
+    let min_i = min_instances.unwrap_or(2);
+
+    Spi::get_one::<pgrx::JsonB>(&format!(
+        "WITH per_instance AS (
+            SELECT COALESCE(p.instance_id,
+                            (SELECT id FROM kerai.instances WHERE is_self = true)) AS instance_id,
+                   count(DISTINCT p.agent_id) AS agent_count,
+                   count(*) AS perspective_count,
+                   avg(p.weight) AS avg_weight,
+                   min(p.weight) AS min_weight,
+                   max(p.weight) AS max_weight
+            FROM kerai.perspectives p
+            WHERE p.node_id = '{0}'::uuid
+            GROUP BY 1
+        )
+        SELECT jsonb_build_object(
+            'node_id', '{0}',
+            'instance_count', count(pi.instance_id),
+            'min_instances', {1},
+            'meets_min', count(pi.instance_id) >= {1},
+            'blended_weight', avg(pi.avg_weight),
+            'perspective_weight',
+                sum(pi.avg_weight * pi.perspective_count) / NULLIF(sum(pi.perspective_count), 0),
+            'spread', COALESCE(stddev_pop(pi.avg_weight), 0),
+            'agreement', CASE WHEN count(pi.instance_id) = 0 THEN NULL
+                              ELSE bool_and(pi.avg_weight > 0) OR bool_and(pi.avg_weight < 0) END,
+            'instances', COALESCE(jsonb_agg(jsonb_build_object(
+                'instance_id', pi.instance_id,
+                'name', i.name,
+                'is_self', i.is_self,
+                'agent_count', pi.agent_count,
+                'perspective_count', pi.perspective_count,
+                'avg_weight', pi.avg_weight,
+                'min_weight', pi.min_weight,
+                'max_weight', pi.max_weight
+            ) ORDER BY pi.avg_weight DESC), '[]'::jsonb)
+        )
+        FROM per_instance pi
+        JOIN kerai.instances i ON i.id = pi.instance_id",
+        node_id, min_i,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(serde_json::json!({})))
+}
+
 /// Compare two agents' perspectives. Returns nodes only in agent1,
 /// only in agent2, and disagreements (same node, different weights).
 #[pg_extern]
@@ -8,8 +8,20 @@ pub fn current_lamport_ts() -> i64 {
         .unwrap_or(0)
 }
 
+/// Advisory lock key serializing Lamport clock bumps ("KLMP").
+pub const LAMPORT_LOCK_KEY: i64 = 0x4b4c_4d50;
+
 /// Get the next Lamport timestamp (current + 1).
+///
+/// Takes a transaction-scoped advisory lock first, so concurrent `apply_op`
+/// calls serialize here and each sees the previous op's timestamp once it
+/// commits. The lock is released at commit/abort.
 pub fn next_lamport_ts() -> i64 {
+    Spi::run(&format!(
+        "SELECT pg_advisory_xact_lock({})",
+        LAMPORT_LOCK_KEY
+    ))
+    .expect("Failed to acquire Lamport clock lock");
     current_lamport_ts() + 1
 }
 
@@ -27,6 +39,21 @@ pub fn next_author_seq(author: &str) -> i64 {
     .unwrap()
 }
 
+/// Peek at the sequence number the next op by `author` would receive,
+/// without advancing the version vector.
+pub fn peek_author_seq(author: &str) -> i64 {
+    let escaped = author.replace('\'', "''");
+    Spi::get_one::<i64>(&format!(
+        "SELECT COALESCE(
+            (SELECT max_seq FROM kerai.version_vector WHERE author = '{}'),
+            0
+        )::bigint + 1",
+        escaped
+    ))
+    .unwrap()
+    .unwrap_or(1)
+}
+
 /// Advance the version_vector entry for a remote author to at least `seq`.
 /// Uses GREATEST semantics — never goes backwards.
 pub fn advance_author_seq(author: &str, seq: i64) {
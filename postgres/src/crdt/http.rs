@@ -0,0 +1,108 @@
+/// Minimal blocking HTTP client for pulling ops from peer endpoints.
+///
+/// Speaks HTTP/1.0 with `Connection: close` so responses arrive unchunked and
+/// end at EOF. Only plain `http://` URLs are supported.
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// Connect, read, and write timeout for a single request.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Total time allowed for a request, however the peer paces its response.
+const DEADLINE: Duration = Duration::from_secs(300);
+
+/// Largest response accepted, headers included.
+const MAX_RESPONSE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// GET `url` and return the response body. Non-2xx statuses are errors.
+pub fn get(url: &str) -> Result<String, String> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("Unsupported URL '{}': only http:// is supported", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let has_port = authority
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+    let host_port = if has_port {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+
+    let started = Instant::now();
+    let address = host_port
+        .to_socket_addrs()
+        .map_err(|e| format!("Cannot resolve {}: {}", host_port, e))?
+        .next()
+        .ok_or_else(|| format!("Cannot resolve {}", host_port))?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)
+        .map_err(|e| format!("Cannot connect to {}: {}", host_port, e))?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok();
+    stream.set_write_timeout(Some(TIMEOUT)).ok();
+
+    write!(
+        stream,
+        "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n\r\n",
+        path, authority,
+    )
+    .map_err(|e| format!("Failed to send request: {}", e))?;
+
+    // Read in chunks so a slow or oversized response can't hold the backend
+    let mut response = Vec::new();
+    let mut reader = (&stream).take(MAX_RESPONSE_BYTES + 1);
+    let mut chunk = [0u8; 64 * 1024];
+    loop {
+        pgrx::check_for_interrupts!();
+        let remaining = DEADLINE
+            .checked_sub(started.elapsed())
+            .filter(|d| !d.is_zero())
+            .ok_or_else(|| format!("{} did not respond within {}s", url, DEADLINE.as_secs()))?;
+        stream.set_read_timeout(Some(remaining.min(TIMEOUT))).ok();
+        let n = reader
+            .read(&mut chunk)
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&chunk[..n]);
+        if response.len() as u64 > MAX_RESPONSE_BYTES {
+            return Err(format!(
+                "{} returned more than {} bytes",
+                url, MAX_RESPONSE_BYTES
+            ));
+        }
+    }
+    let response =
+        String::from_utf8(response).map_err(|_| "Response is not valid UTF-8".to_string())?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| "Malformed HTTP response".to_string())?;
+    let status: u16 = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| "Malformed HTTP status line".to_string())?;
+    if !(200..300).contains(&status) {
+        return Err(format!("{} returned HTTP {}", url, status));
+    }
+    Ok(body.to_string())
+}
+
+/// Percent-encode a query string value, leaving RFC 3986 unreserved
+/// characters as they are.
+pub fn encode_query_value(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -1,14 +1,20 @@
 /// CRDT operation layer — signed operation log with Lamport clock and version vector.
-mod clock;
+pub(crate) mod clock;
+mod http;
 mod operations;
+mod replay;
 mod signer;
 
+use std::collections::HashSet;
+
 use pgrx::prelude::*;
 use serde_json::Value;
 
 use crate::identity;
 use crate::sql::sql_escape;
 
+pub(crate) use signer::{verify_op_signature, OpRecord};
+
 /// Format bytes as PostgreSQL hex bytea literal: \xABCD...
 fn bytes_to_pg_hex(bytes: &[u8]) -> String {
     let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
@@ -82,17 +88,22 @@ fn insert_operation(
     author_seq: i64,
     payload: &Value,
     signature: &[u8],
+    target_path: Option<&str>,
 ) {
     let node_sql = match node_id {
         Some(nid) => format!("'{}'::uuid", sql_escape(nid)),
         None => "NULL".to_string(),
     };
+    let path_sql = match target_path {
+        Some(path) => format!("'{}'::ltree", sql_escape(path)),
+        None => "NULL".to_string(),
+    };
     let payload_str = sql_escape(&payload.to_string());
     let sig_hex = bytes_to_pg_hex(signature);
 
     Spi::run(&format!(
-        "INSERT INTO kerai.operations (instance_id, op_type, node_id, author, lamport_ts, author_seq, payload, signature)
-         VALUES ('{}'::uuid, '{}', {}, '{}', {}, {}, '{}'::jsonb, '{}'::bytea)",
+        "INSERT INTO kerai.operations (instance_id, op_type, node_id, author, lamport_ts, author_seq, payload, signature, target_path)
+         VALUES ('{}'::uuid, '{}', {}, '{}', {}, {}, '{}'::jsonb, '{}'::bytea, {})",
         sql_escape(instance_id),
         sql_escape(op_type),
         node_sql,
@@ -101,10 +112,38 @@ fn insert_operation(
         author_seq,
         payload_str,
         sig_hex,
+        path_sql,
     ))
     .unwrap();
 }
 
+/// Current ltree path of a node, if it exists and has one.
+fn node_path(node_id: &str) -> Option<String> {
+    Spi::get_one::<String>(&format!(
+        "SELECT path::text FROM kerai.nodes WHERE id = '{}'::uuid",
+        sql_escape(node_id),
+    ))
+    .unwrap_or(None)
+}
+
+/// Path recorded as an op's `target_path`: the node's path before the op
+/// (deletes remove it), else after (inserts create it), else an insert's
+/// payload path. Ops that touch no single node get None.
+fn op_target_path(
+    op_type: &str,
+    before: Option<String>,
+    affected_id: Option<&str>,
+    payload: &Value,
+) -> Option<String> {
+    before
+        .or_else(|| affected_id.and_then(node_path))
+        .or_else(|| {
+            (op_type == "insert_node")
+                .then(|| payload["path"].as_str().map(str::to_string))
+                .flatten()
+        })
+}
+
 /// Apply a local CRDT operation. Validates, applies to materialized state,
 /// signs with the local Ed25519 key, and records in the operation log.
 ///
@@ -112,35 +151,123 @@ fn insert_operation(
 #[pg_extern]
 fn apply_op(op_type: &str, node_id: Option<pgrx::Uuid>, payload: pgrx::JsonB) -> pgrx::JsonB {
     let (instance_id, fingerprint) = get_self_identity();
+    let signing_key = identity::load_signing_key()
+        .unwrap_or_else(|| error!("No signing key found — identity not initialized"));
     let nid_str = node_id.map(|u| u.to_string());
-    let nid_ref = nid_str.as_deref();
 
+    let applied = apply_local_op(
+        op_type,
+        nid_str.as_deref(),
+        &payload.0,
+        &instance_id,
+        &fingerprint,
+        &signing_key,
+    );
+
+    pgrx::JsonB(serde_json::json!({
+        "op_type": op_type,
+        "node_id": applied.node_id,
+        "lamport_ts": applied.lamport_ts,
+        "author_seq": applied.author_seq,
+        "author": fingerprint,
+    }))
+}
+
+/// Apply several local CRDT operations in one transaction.
+///
+/// `ops` is an array of `{op_type, node_id?, payload}`, applied in order. Each
+/// op is signed and logged on its own with consecutive author_seq values, so
+/// peers replay them exactly like individual `apply_op` calls. If any op fails,
+/// the error aborts the statement and none of them take effect.
+///
+/// Returns JSON: {applied, first_seq, last_seq, author}
+#[pg_extern]
+fn apply_ops(ops: pgrx::JsonB) -> pgrx::JsonB {
+    let ops = ops
+        .0
+        .as_array()
+        .cloned()
+        .unwrap_or_else(|| error!("apply_ops expects a JSON array of operations"));
+    let (instance_id, fingerprint) = get_self_identity();
+    let signing_key = identity::load_signing_key()
+        .unwrap_or_else(|| error!("No signing key found — identity not initialized"));
+
+    let mut seqs = Vec::with_capacity(ops.len());
+    for (i, op) in ops.iter().enumerate() {
+        let op_type = op["op_type"]
+            .as_str()
+            .unwrap_or_else(|| error!("Operation {} is missing 'op_type'", i));
+        let node_id = op.get("node_id").and_then(|v| v.as_str());
+        let empty_obj = Value::Object(serde_json::Map::new());
+        let payload = op.get("payload").unwrap_or(&empty_obj);
+
+        let applied = apply_local_op(
+            op_type,
+            node_id,
+            payload,
+            &instance_id,
+            &fingerprint,
+            &signing_key,
+        );
+        seqs.push(applied.author_seq);
+    }
+
+    pgrx::JsonB(serde_json::json!({
+        "applied": seqs.len(),
+        "first_seq": seqs.first(),
+        "last_seq": seqs.last(),
+        "author": fingerprint,
+    }))
+}
+
+/// Where a locally applied op landed in the log.
+struct AppliedOp {
+    node_id: String,
+    lamport_ts: i64,
+    author_seq: i64,
+}
+
+/// Validate, apply, sign, and record one local op, then notify listeners.
+fn apply_local_op(
+    op_type: &str,
+    node_id: Option<&str>,
+    payload: &Value,
+    instance_id: &str,
+    fingerprint: &str,
+    signing_key: &ed25519_dalek::SigningKey,
+) -> AppliedOp {
     // Validate
-    operations::validate_op(op_type, nid_ref, &payload.0);
+    operations::validate_op(op_type, node_id, payload);
+    let path_before = node_id.and_then(node_path);
 
     // Apply to materialized state
-    let affected_id = operations::apply(op_type, nid_ref, &payload.0, &instance_id);
+    let affected_id = operations::apply(op_type, node_id, payload, instance_id);
+    let target_path = op_target_path(op_type, path_before, Some(&affected_id), payload);
 
     // Clock
     let lamport_ts = clock::next_lamport_ts();
-    let author_seq = clock::next_author_seq(&fingerprint);
+    let author_seq = clock::next_author_seq(fingerprint);
 
     // Sign
-    let signing_key = identity::load_signing_key()
-        .unwrap_or_else(|| error!("No signing key found — identity not initialized"));
-    let signable = signer::build_signable(op_type, Some(&affected_id), author_seq, &payload.0.to_string());
-    let signature = identity::sign_data(&signing_key, &signable);
+    let signable = signer::build_signable(
+        op_type,
+        Some(&affected_id),
+        author_seq,
+        &payload.to_string(),
+    );
+    let signature = identity::sign_data(signing_key, &signable);
 
     // Record
     insert_operation(
-        &instance_id,
+        instance_id,
         op_type,
         Some(&affected_id),
-        &fingerprint,
+        fingerprint,
         lamport_ts,
         author_seq,
-        &payload.0,
+        payload,
         &signature,
+        target_path.as_deref(),
     );
 
     // Notify connected listeners
@@ -156,20 +283,35 @@ fn apply_op(op_type: &str, node_id: Option<pgrx::Uuid>, payload: pgrx::JsonB) ->
     ))
     .ok();
 
-    pgrx::JsonB(serde_json::json!({
-        "op_type": op_type,
-        "node_id": affected_id,
-        "lamport_ts": lamport_ts,
-        "author_seq": author_seq,
-        "author": fingerprint,
-    }))
+    AppliedOp {
+        node_id: affected_id,
+        lamport_ts,
+        author_seq,
+    }
+}
+
+/// SQL condition on an instance row `i`: ops it authored may be served to
+/// peers and materialized. `read_only` peers' ops are kept only as a record.
+const FULLY_TRUSTED: &str = "(i.is_self OR i.trust_level = 'full')";
+
+/// Trust level of an instance. Self is always `full`.
+fn instance_trust_level(instance_id: &str) -> String {
+    Spi::get_one::<String>(&format!(
+        "SELECT CASE WHEN is_self THEN 'full' ELSE trust_level END \
+         FROM kerai.instances WHERE id = '{}'::uuid",
+        sql_escape(instance_id),
+    ))
+    .unwrap()
+    .unwrap_or_else(|| "full".to_string())
 }
 
 /// Apply a remote CRDT operation received from a peer.
 /// Verifies the signature, checks causality, applies to materialized state.
 ///
 /// Input JSON: {op_type, node_id?, author, author_seq, lamport_ts, payload, signature (hex), public_key (hex)}
-/// Returns JSON: {status: "applied"|"duplicate", ...}
+/// Returns JSON: {status: "applied"|"duplicate"|"recorded"|"rejected", ...}
+/// `recorded` means the op came from a read_only peer and was logged without
+/// being applied; `rejected` means the peer is untrusted.
 #[pg_extern]
 fn apply_remote_op(op_json: pgrx::JsonB) -> pgrx::JsonB {
     let obj = op_json.0.as_object()
@@ -199,14 +341,14 @@ fn apply_remote_op(op_json: pgrx::JsonB) -> pgrx::JsonB {
         .unwrap_or_else(|_| error!("Invalid hex public_key"));
 
     // Verify signature
-    if !signer::verify_op_signature(
-        &public_key,
-        op_type,
-        node_id,
+    let record = OpRecord {
+        op_type: op_type.to_string(),
+        node_id: node_id.map(str::to_string),
         author_seq,
-        &payload.to_string(),
-        &signature,
-    ) {
+        payload: payload.to_string(),
+        signature: signature.clone(),
+    };
+    if !verify_op_signature(&public_key, &record) {
         error!("Signature verification failed for remote op");
     }
 
@@ -230,9 +372,28 @@ fn apply_remote_op(op_json: pgrx::JsonB) -> pgrx::JsonB {
     // Resolve instance_id for the remote author (auto-registers unknown peers)
     let instance_id = resolve_author_instance(author, pk_hex);
 
+    // Honor the peer's trust level: untrusted ops are dropped, read_only ops
+    // are recorded for visibility but never touch materialized state
+    let trust = instance_trust_level(&instance_id);
+    if trust == "untrusted" {
+        return pgrx::JsonB(serde_json::json!({
+            "status": "rejected",
+            "reason": "untrusted peer",
+            "author": author,
+            "author_seq": author_seq,
+        }));
+    }
+
     // Validate and apply
     operations::validate_op(op_type, node_id, payload);
-    let affected_id = operations::apply(op_type, node_id, payload, &instance_id);
+    let path_before = node_id.and_then(node_path);
+    let affected_id = if trust == "read_only" {
+        node_id.unwrap_or_default().to_string()
+    } else {
+        operations::apply(op_type, node_id, payload, &instance_id)
+    };
+    let affected = (!affected_id.is_empty()).then_some(affected_id.as_str());
+    let target_path = op_target_path(op_type, path_before, affected, payload);
 
     // Advance clocks
     clock::advance_author_seq(author, author_seq);
@@ -241,14 +402,33 @@ fn apply_remote_op(op_json: pgrx::JsonB) -> pgrx::JsonB {
     insert_operation(
         &instance_id,
         op_type,
-        Some(&affected_id),
+        affected,
         author,
         lamport_ts,
         author_seq,
         payload,
         &signature,
+        target_path.as_deref(),
     );
 
+    if trust == "read_only" {
+        Spi::run(&format!(
+            "UPDATE kerai.operations SET applied = false WHERE author = '{}' AND author_seq = {}",
+            sql_escape(author),
+            author_seq,
+        ))
+        .unwrap();
+        return pgrx::JsonB(serde_json::json!({
+            "status": "recorded",
+            "reason": "read_only peer",
+            "op_type": op_type,
+            "node_id": affected,
+            "lamport_ts": lamport_ts,
+            "author_seq": author_seq,
+            "author": author,
+        }));
+    }
+
     // Notify connected listeners
     let notify_payload = serde_json::json!({
         "op_type": op_type,
@@ -272,6 +452,253 @@ fn apply_remote_op(op_json: pgrx::JsonB) -> pgrx::JsonB {
     }))
 }
 
+/// Apply the ops recorded from a peer while it was `read_only`, in the order
+/// they were logged, and mark them applied. Called when the peer is raised to
+/// `full` trust. Returns how many ops were applied.
+pub(crate) fn apply_recorded_ops(instance_id: &str) -> u64 {
+    let ops = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', id,
+            'op_type', op_type,
+            'node_id', node_id,
+            'payload', payload
+        ) ORDER BY lamport_ts, author, author_seq), '[]'::jsonb)
+        FROM kerai.operations
+        WHERE instance_id = '{}'::uuid AND NOT applied",
+        sql_escape(instance_id),
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| serde_json::json!([]));
+
+    let mut applied = 0;
+    for op in ops.as_array().into_iter().flatten() {
+        let op_type = op["op_type"].as_str().unwrap_or_default();
+        let node_id = op["node_id"].as_str();
+        let payload = &op["payload"];
+        let path_before = node_id.and_then(node_path);
+        let affected_id = operations::apply(op_type, node_id, payload, instance_id);
+        let affected = (!affected_id.is_empty()).then_some(affected_id.as_str());
+        let target_path = op_target_path(op_type, path_before, affected, payload);
+
+        let node_sql = match affected {
+            Some(nid) => format!("'{}'::uuid", sql_escape(nid)),
+            None => "NULL".to_string(),
+        };
+        let path_sql = match target_path {
+            Some(path) => format!("'{}'::ltree", sql_escape(&path)),
+            None => "NULL".to_string(),
+        };
+        Spi::run(&format!(
+            "UPDATE kerai.operations SET applied = true, node_id = {}, target_path = {}
+             WHERE id = '{}'::uuid",
+            node_sql,
+            path_sql,
+            sql_escape(op["id"].as_str().unwrap_or_default()),
+        ))
+        .unwrap();
+        applied += 1;
+    }
+    applied
+}
+
+/// Ingest a batch of peer ops in causal order.
+///
+/// `ops` is an array in the `apply_remote_op` format. An op is applied only
+/// once its author's previous op (`author_seq - 1`) has been; later ones are
+/// held in `kerai.pending_ops` and applied as soon as the gap is filled, by this
+/// call or a later one. A `state_snapshot` op is applied as soon as it arrives
+/// and moves every author it covers up to its coverage. Ops at or below the
+/// author's version vector entry are skipped as duplicates.
+///
+/// Returns JSON: {applied, buffered, skipped_duplicates, rejected}, where
+/// `buffered` counts ops from this batch still waiting on a predecessor.
+#[pg_extern]
+fn ingest_ops(ops: pgrx::JsonB) -> pgrx::JsonB {
+    let ops = ops
+        .0
+        .as_array()
+        .cloned()
+        .unwrap_or_else(|| error!("ingest_ops expects a JSON array of operations"));
+    pgrx::JsonB(ingest(ops))
+}
+
+/// The body of `ingest_ops`, shared with `sync_pull`.
+fn ingest(ops: Vec<Value>) -> Value {
+    let mut tally = IngestTally::default();
+    let mut buffered: HashSet<(String, i64)> = HashSet::new();
+    for (i, op) in ops.into_iter().enumerate() {
+        let author = op["author"]
+            .as_str()
+            .unwrap_or_else(|| error!("Operation {} is missing 'author'", i))
+            .to_string();
+        let author_seq = op["author_seq"]
+            .as_i64()
+            .unwrap_or_else(|| error!("Operation {} is missing 'author_seq'", i));
+
+        let expected = clock::peek_author_seq(&author);
+        if author_seq < expected {
+            tally.skipped_duplicates += 1;
+        } else if op["op_type"] == "state_snapshot" {
+            // A snapshot stands in for everything it covers, so it needn't wait
+            // on the author's earlier (possibly pruned) ops; afterwards the
+            // covered authors' buffered ops may be ready
+            let covered: Vec<String> = op["payload"]["covers"]
+                .as_object()
+                .map(|covers| covers.keys().cloned().collect())
+                .unwrap_or_default();
+            tally.record(&apply_remote_op(pgrx::JsonB(op)).0);
+            for covered_author in covered.iter().chain(std::iter::once(&author)) {
+                drain_pending_ops(covered_author, &mut tally, &mut buffered);
+            }
+        } else if author_seq > expected {
+            let inserted = Spi::get_one::<bool>(&format!(
+                "INSERT INTO kerai.pending_ops (author, author_seq, op)
+                 VALUES ('{}', {}, '{}'::jsonb)
+                 ON CONFLICT (author, author_seq) DO NOTHING
+                 RETURNING true",
+                sql_escape(&author),
+                author_seq,
+                sql_escape(&op.to_string()),
+            ))
+            .unwrap_or(None)
+            .unwrap_or(false);
+            if inserted {
+                buffered.insert((author, author_seq));
+            } else {
+                tally.skipped_duplicates += 1;
+            }
+        } else {
+            tally.record(&apply_remote_op(pgrx::JsonB(op)).0);
+            drain_pending_ops(&author, &mut tally, &mut buffered);
+        }
+    }
+
+    serde_json::json!({
+        "applied": tally.applied,
+        "buffered": buffered.len(),
+        "skipped_duplicates": tally.skipped_duplicates,
+        "rejected": tally.rejected,
+    })
+}
+
+/// Counts of `apply_remote_op` outcomes during `ingest_ops`.
+#[derive(Default)]
+struct IngestTally {
+    applied: usize,
+    skipped_duplicates: usize,
+    rejected: usize,
+}
+
+impl IngestTally {
+    fn record(&mut self, result: &Value) {
+        match result["status"].as_str() {
+            Some("duplicate") => self.skipped_duplicates += 1,
+            Some("rejected") => self.rejected += 1,
+            _ => self.applied += 1,
+        }
+    }
+}
+
+/// Apply `author`'s buffered ops for as long as the next expected seq is
+/// waiting, removing each from `kerai.pending_ops` (and from `buffered`).
+fn drain_pending_ops(author: &str, tally: &mut IngestTally, buffered: &mut HashSet<(String, i64)>) {
+    loop {
+        let expected = clock::peek_author_seq(author);
+        let next = Spi::get_one::<pgrx::JsonB>(&format!(
+            "DELETE FROM kerai.pending_ops WHERE author = '{}' AND author_seq = {} RETURNING op",
+            sql_escape(author),
+            expected,
+        ))
+        .unwrap_or(None);
+        let Some(op) = next else {
+            break;
+        };
+        buffered.remove(&(author.to_string(), expected));
+        tally.record(&apply_remote_op(op).0);
+        // A rejected op doesn't advance the version vector; stop rather than spin
+        if clock::peek_author_seq(author) == expected {
+            break;
+        }
+    }
+}
+
+/// Pull a peer's own ops over HTTP and ingest them.
+///
+/// GETs `{endpoint}/ops_since?author=<fingerprint>&seq=<cursor>` from the
+/// peer's registered endpoint, where the cursor is the highest seq of the
+/// peer's ops already applied here. The response is an `ops_since` array.
+/// Ops whose signature doesn't verify against their `public_key`, or whose
+/// key doesn't match their `author`, are counted as rejected and dropped; the
+/// rest go through `ingest_ops`. Only `http://` endpoints are supported.
+///
+/// Returns JSON: {fetched, applied, rejected, buffered, new_cursor}
+#[pg_extern]
+fn sync_pull(peer_name: &str) -> pgrx::JsonB {
+    let peer = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object('fingerprint', key_fingerprint, 'endpoint', endpoint)
+         FROM kerai.instances WHERE name = '{}' AND is_self = false",
+        sql_escape(peer_name),
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Peer '{}' not found", peer_name))
+    .0;
+    let fingerprint = peer["fingerprint"].as_str().unwrap_or_default();
+    let endpoint = peer["endpoint"]
+        .as_str()
+        .unwrap_or_else(|| error!("Peer '{}' has no endpoint", peer_name));
+
+    let cursor = clock::peek_author_seq(fingerprint) - 1;
+    let url = format!(
+        "{}/ops_since?author={}&seq={}",
+        endpoint.trim_end_matches('/'),
+        http::encode_query_value(fingerprint),
+        cursor,
+    );
+    let body =
+        http::get(&url).unwrap_or_else(|e| error!("Failed to pull from '{}': {}", peer_name, e));
+    let ops = match serde_json::from_str::<Value>(&body) {
+        Ok(Value::Array(ops)) => ops,
+        _ => error!("Peer '{}' did not return a JSON array of ops", peer_name),
+    };
+
+    let fetched = ops.len();
+    let (valid, invalid): (Vec<Value>, Vec<Value>) =
+        ops.into_iter().partition(op_signature_is_valid);
+    let result = ingest(valid);
+
+    Spi::run(&format!(
+        "UPDATE kerai.instances SET last_seen = now() WHERE name = '{}'",
+        sql_escape(peer_name),
+    ))
+    .unwrap();
+
+    pgrx::JsonB(serde_json::json!({
+        "fetched": fetched,
+        "applied": result["applied"],
+        "rejected": invalid.len() as u64 + result["rejected"].as_u64().unwrap_or(0),
+        "buffered": result["buffered"],
+        "new_cursor": clock::peek_author_seq(fingerprint) - 1,
+    }))
+}
+
+/// Whether a pulled op carries a signature that verifies against its
+/// `public_key`, and that key is the one its `author` fingerprint names.
+fn op_signature_is_valid(op: &Value) -> bool {
+    let (Some(record), Some(author), Some(pk_hex)) = (
+        OpRecord::from_json(op),
+        op["author"].as_str(),
+        op["public_key"].as_str(),
+    ) else {
+        return false;
+    };
+    let Ok(verifying_key) = identity::parse_public_key_hex(pk_hex) else {
+        return false;
+    };
+    identity::fingerprint(&verifying_key) == author
+        && verify_op_signature(verifying_key.as_bytes(), &record)
+}
+
 /// Get the current version vector as JSON: {"author_fingerprint": max_seq, ...}
 #[pg_extern]
 fn version_vector() -> pgrx::JsonB {
@@ -284,11 +711,57 @@ fn lamport_clock() -> i64 {
     clock::current_lamport_ts()
 }
 
+/// Get the author_seq the next local op would receive, without applying one.
+#[pg_extern]
+fn next_author_seq() -> i64 {
+    let (_, fingerprint) = get_self_identity();
+    clock::peek_author_seq(&fingerprint)
+}
+
 /// Get operations for a given author since a sequence number (exclusive).
 /// Returns a JSON array of operation objects, including the author's public_key.
+/// Ops recorded from `read_only` or `untrusted` peers are not re-served.
+///
+/// With `scope`, only ops whose `target_path` lies under that ltree path are
+/// returned; ops without a target path are left out. A scoped result skips
+/// the author_seqs of every op outside the scope, so it is a partial view for
+/// reading a subtree, not something to feed to `ingest_ops` (which would hold
+/// every op after the first gap). Resume a scoped pull from the author_seq of
+/// the last op it returned.
+///
+/// The author's own `state_snapshot` ops are returned in sequence like any
+/// other op. If the requested range reaches into ops that were pruned by
+/// `compact_operations`, the latest `state_snapshot` op is returned first,
+/// followed by the author's ops after the snapshot's coverage. In a scoped
+/// result that snapshot's nodes and edges are cut down to the scope; its
+/// signature covered the whole payload, so the cut-down copy carries
+/// `"signature": null` and the `scope` it was filtered to.
 #[pg_extern]
-fn ops_since(author: &str, since_seq: i64) -> pgrx::JsonB {
+fn ops_since(
+    author: &str,
+    since_seq: i64,
+    scope: default!(Option<&str>, "NULL"),
+) -> pgrx::JsonB {
     let escaped = sql_escape(author);
+    let scope_clause = match scope {
+        Some(s) => format!("AND o.target_path <@ '{}'::ltree", sql_escape(s)),
+        None => String::new(),
+    };
+
+    let mut from_seq = since_seq;
+    let mut snapshot: Option<Value> = None;
+    if let Some(snap) = latest_snapshot_op() {
+        let pruned = snap["payload"]["pruned_through"][author].as_i64().unwrap_or(0);
+        if since_seq < pruned {
+            let covered = snap["payload"]["covers"][author].as_i64().unwrap_or(0);
+            from_seq = since_seq.max(covered);
+            snapshot = Some(match scope {
+                Some(s) => scope_snapshot(snap, s),
+                None => snap,
+            });
+        }
+    }
+
     let json = Spi::get_one::<pgrx::JsonB>(&format!(
         "SELECT COALESCE(
             jsonb_agg(jsonb_build_object(
@@ -304,11 +777,550 @@ fn ops_since(author: &str, since_seq: i64) -> pgrx::JsonB {
             '[]'::jsonb
         ) FROM kerai.operations o
         JOIN kerai.instances i ON i.key_fingerprint = o.author
-        WHERE o.author = '{}' AND o.author_seq > {}",
-        escaped,
-        since_seq,
+        WHERE o.author = '{}' AND o.author_seq > {} AND {} {}",
+        escaped, from_seq, FULLY_TRUSTED, scope_clause,
     ))
     .unwrap()
     .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
-    json
+
+    // The author's own snapshot is already in the range after its coverage
+    let in_range =
+        |snap: &Value| snap["author"] == author && snap["author_seq"].as_i64() > Some(from_seq);
+    match snapshot.filter(|snap| !in_range(snap)) {
+        Some(snap) => {
+            let mut ops = vec![snap];
+            ops.extend(json.0.as_array().cloned().unwrap_or_default());
+            pgrx::JsonB(Value::Array(ops))
+        }
+        None => json,
+    }
+}
+
+/// Cut a `state_snapshot` op down to the nodes under `scope` and the edges
+/// between them. The result no longer matches its signature, so it is dropped.
+fn scope_snapshot(mut snap: Value, scope: &str) -> Value {
+    let prefix = format!("{}.", scope);
+    let payload = &mut snap["payload"];
+    let nodes: Vec<Value> = payload["nodes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|n| {
+            n["path"]
+                .as_str()
+                .is_some_and(|p| p == scope || p.starts_with(&prefix))
+        })
+        .cloned()
+        .collect();
+    let kept: HashSet<&str> = nodes.iter().filter_map(|n| n["id"].as_str()).collect();
+    let edges: Vec<Value> = payload["edges"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|e| {
+            e["source_id"].as_str().is_some_and(|id| kept.contains(id))
+                && e["target_id"].as_str().is_some_and(|id| kept.contains(id))
+        })
+        .cloned()
+        .collect();
+    payload["edges"] = Value::Array(edges);
+    payload["nodes"] = Value::Array(nodes);
+    snap["signature"] = Value::Null;
+    snap["scope"] = Value::String(scope.to_string());
+    snap
+}
+
+/// Root hash of an author's operation log, as hex.
+///
+/// Chains SHA-256 over the signatures of the author's ops in author_seq order
+/// (`h = sha256(h || signature)`, starting from 32 zero bytes), so two peers
+/// with the same ops from `author` get the same root and any missing or
+/// differing op changes it. `state_snapshot` ops are left out, as in
+/// `ops_since`; peers that pruned different ranges will not match.
+#[pg_extern]
+fn ops_digest(author: &str) -> String {
+    let signatures = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(
+            jsonb_agg(encode(COALESCE(signature, ''::bytea), 'hex') ORDER BY author_seq),
+            '[]'::jsonb
+        ) FROM kerai.operations
+        WHERE author = '{}' AND op_type <> 'state_snapshot'",
+        sql_escape(author),
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_default();
+
+    let chained = signatures
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .fold([0u8; 32], |root, sig| {
+            let bytes = hex::decode(sig).unwrap_or_default();
+            chain_digest(&root, &bytes)
+        });
+    hex::encode(chained)
+}
+
+/// Combined root over every author's `ops_digest`, as hex.
+///
+/// Chains `author || root` pairs in author order the same way, so it matches
+/// between two peers exactly when every per-author digest does.
+#[pg_extern]
+fn ops_digest_all() -> String {
+    let authors = Spi::get_one::<pgrx::JsonB>(
+        "SELECT COALESCE(jsonb_agg(DISTINCT author), '[]'::jsonb)
+         FROM kerai.operations WHERE op_type <> 'state_snapshot'",
+    )
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_default();
+
+    let mut authors: Vec<&str> = authors
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    authors.sort_unstable();
+
+    let chained = authors.into_iter().fold([0u8; 32], |root, author| {
+        let entry = format!("{}{}", author, ops_digest(author));
+        chain_digest(&root, entry.as_bytes())
+    });
+    hex::encode(chained)
+}
+
+/// One link of a digest chain: `sha256(root || data)`.
+fn chain_digest(root: &[u8; 32], data: &[u8]) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(root);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// Fill `target_path` on logged ops recorded before it existed: from the
+/// node's current path, or for a deleted node from its insert_node payload.
+/// Returns JSON: {updated, remaining} where `remaining` still have no path.
+#[pg_extern]
+fn backfill_operation_paths() -> pgrx::JsonB {
+    let updated = Spi::get_one::<i64>(
+        "WITH done AS (
+            UPDATE kerai.operations o
+            SET target_path = COALESCE(
+                (SELECT n.path FROM kerai.nodes n WHERE n.id = o.node_id),
+                (SELECT (i.payload->>'path')::ltree FROM kerai.operations i
+                 WHERE i.node_id = o.node_id AND i.op_type = 'insert_node'
+                   AND i.payload ? 'path'
+                 ORDER BY i.lamport_ts LIMIT 1)
+            )
+            WHERE o.target_path IS NULL AND o.node_id IS NOT NULL
+              AND (EXISTS (SELECT 1 FROM kerai.nodes n WHERE n.id = o.node_id AND n.path IS NOT NULL)
+                   OR EXISTS (SELECT 1 FROM kerai.operations i
+                              WHERE i.node_id = o.node_id AND i.op_type = 'insert_node'
+                                AND i.payload ? 'path'))
+            RETURNING 1
+        ) SELECT count(*)::bigint FROM done",
+    )
+    .unwrap()
+    .unwrap_or(0);
+
+    let remaining = Spi::get_one::<i64>(
+        "SELECT count(*)::bigint FROM kerai.operations WHERE target_path IS NULL",
+    )
+    .unwrap()
+    .unwrap_or(0);
+
+    pgrx::JsonB(serde_json::json!({
+        "updated": updated,
+        "remaining": remaining,
+    }))
+}
+
+/// The most recent `state_snapshot` op in ops_since format, if any.
+fn latest_snapshot_op() -> Option<Value> {
+    Spi::get_one::<pgrx::JsonB>(
+        "SELECT jsonb_build_object(
+            'op_type', o.op_type,
+            'node_id', o.node_id,
+            'author', o.author,
+            'author_seq', o.author_seq,
+            'lamport_ts', o.lamport_ts,
+            'payload', o.payload,
+            'signature', encode(o.signature, 'hex'),
+            'public_key', encode(i.public_key, 'hex')
+        ) FROM kerai.operations o
+        JOIN kerai.instances i ON i.key_fingerprint = o.author
+        WHERE o.op_type = 'state_snapshot'
+        ORDER BY o.lamport_ts DESC
+        LIMIT 1",
+    )
+    .unwrap_or(None)
+    .map(|j| j.0)
+}
+
+/// Compact the operation log: record a signed `state_snapshot` op holding the
+/// current materialized nodes and edges, then prune ops created before `before`.
+/// With `scope`, only ops whose `target_path` lies under that ltree path are
+/// pruned; the snapshot still holds the whole graph, since it stands in for
+/// every op up to its coverage.
+///
+/// The snapshot's `covers` is the full version vector at compaction time (the
+/// materialized state reflects every op applied so far); `pruned_through` is the
+/// highest pruned author_seq per author, accumulated across compactions. The
+/// version vector itself is untouched, so sync keeps working, and `ops_since`
+/// serves the snapshot to peers that are behind the pruned range.
+///
+/// Returns JSON: {snapshot_id, author_seq, nodes, edges, pruned, covers, pruned_through}
+#[pg_extern]
+fn compact_operations(
+    before: pgrx::datum::TimestampWithTimeZone,
+    scope: default!(Option<&str>, "NULL"),
+) -> pgrx::JsonB {
+    let before_sql = format!("'{}'::timestamptz", sql_escape(&before.to_string()));
+    let scope_clause = match scope {
+        Some(s) => format!("AND target_path <@ '{}'::ltree", sql_escape(s)),
+        None => String::new(),
+    };
+
+    // Carry forward coverage from the previous snapshot, since its pruned ops are gone
+    let mut pruned_through = latest_snapshot_op()
+        .and_then(|snap| snap["payload"]["pruned_through"].as_object().cloned())
+        .unwrap_or_default();
+    let newly_pruned = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_object_agg(author, max_seq), '{{}}'::jsonb) FROM (
+            SELECT author, MAX(author_seq) AS max_seq FROM kerai.operations
+            WHERE created_at < {} {}
+            GROUP BY author
+        ) sub",
+        before_sql, scope_clause,
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| serde_json::json!({}));
+    if let Some(obj) = newly_pruned.as_object() {
+        for (author, seq) in obj {
+            let seq = seq.as_i64().unwrap_or(0);
+            let prev = pruned_through.get(author).and_then(|v| v.as_i64()).unwrap_or(0);
+            pruned_through.insert(author.clone(), serde_json::json!(prev.max(seq)));
+        }
+    }
+
+    let nodes = Spi::get_one::<pgrx::JsonB>(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', id,
+            'instance_id', instance_id,
+            'kind', kind,
+            'language', language,
+            'content', content,
+            'parent_id', parent_id,
+            'position', position,
+            'path', path::text,
+            'metadata', metadata
+        ) ORDER BY nlevel(COALESCE(path, ''::ltree)), id), '[]'::jsonb) FROM kerai.nodes",
+    )
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| serde_json::json!([]));
+    let edges = Spi::get_one::<pgrx::JsonB>(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', id,
+            'source_id', source_id,
+            'target_id', target_id,
+            'relation', relation,
+            'metadata', metadata
+        ) ORDER BY id), '[]'::jsonb) FROM kerai.edges",
+    )
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| serde_json::json!([]));
+
+    let node_count = nodes.as_array().map(|a| a.len()).unwrap_or(0);
+    let edge_count = edges.as_array().map(|a| a.len()).unwrap_or(0);
+    let covers = clock::get_version_vector().0;
+    let snapshot_id = uuid::Uuid::new_v4().to_string();
+
+    let payload = serde_json::json!({
+        "snapshot_id": snapshot_id,
+        "before": before.to_string(),
+        "scope": scope,
+        "covers": covers.clone(),
+        "pruned_through": pruned_through.clone(),
+        "nodes": nodes,
+        "edges": edges,
+    });
+    let recorded = apply_op("state_snapshot", None, pgrx::JsonB(payload));
+    let author_seq = recorded.0["author_seq"].as_i64().unwrap_or(0);
+
+    let pruned = Spi::get_one::<i64>(&format!(
+        "WITH gone AS (
+            DELETE FROM kerai.operations
+            WHERE created_at < {} AND node_id IS DISTINCT FROM '{}'::uuid {}
+            RETURNING 1
+        ) SELECT count(*)::bigint FROM gone",
+        before_sql,
+        sql_escape(&snapshot_id),
+        scope_clause,
+    ))
+    .unwrap()
+    .unwrap_or(0);
+
+    pgrx::JsonB(serde_json::json!({
+        "snapshot_id": snapshot_id,
+        "author_seq": author_seq,
+        "nodes": node_count,
+        "edges": edge_count,
+        "pruned": pruned,
+        "covers": covers,
+        "pruned_through": pruned_through,
+    }))
+}
+
+/// Version tag written as `"v"` on every line of an exported op log.
+const OPLOG_VERSION: i64 = 1;
+
+/// Export this instance's signed operations to a newline-delimited JSON file,
+/// for offline (air-gapped) sync.
+///
+/// Each line is one op in `ops_since` form plus a format version:
+/// `{"v": 1, "op_type", "node_id", "author", "author_seq", "lamport_ts",
+/// "payload", "signature", "public_key"}`. `signature` and `public_key` are
+/// hex; the signature is Ed25519 over `op_type|node_id|author_seq|payload`
+/// (`node_id` is `null` when absent, `payload` is its compact JSON), so every
+/// line verifies on its own. Lines are in `author_seq` order.
+///
+/// The file is append-only: if it already exists, only ops newer than the
+/// highest `author_seq` it holds for this instance (and newer than
+/// `since_seq`) are appended.
+///
+/// Writes a server-side file, so the caller needs the privileges of
+/// `pg_write_server_files` (as for `COPY ... TO 'file'`).
+///
+/// Returns JSON: {path, author, exported, from_seq, through_seq}
+#[pg_extern]
+fn export_oplog(path: &str, since_seq: default!(i64, "0")) -> pgrx::JsonB {
+    use std::io::Write;
+
+    require_server_file_role("pg_write_server_files", "export_oplog");
+    let (_, fingerprint) = get_self_identity();
+
+    let mut from_seq = since_seq;
+    if let Ok(existing) = std::fs::read_to_string(path) {
+        for (i, line) in existing.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let op: Value = serde_json::from_str(line).unwrap_or_else(|e| {
+                error!("{}:{}: existing log line is not valid JSON: {}", path, i + 1, e)
+            });
+            if op["author"].as_str() == Some(fingerprint.as_str()) {
+                from_seq = from_seq.max(op["author_seq"].as_i64().unwrap_or(0));
+            }
+        }
+    }
+
+    let ops = ops_since(&fingerprint, from_seq, None).0;
+    let ops = ops.as_array().cloned().unwrap_or_default();
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap_or_else(|e| error!("Failed to open {}: {}", path, e));
+    let mut through_seq = from_seq;
+    for mut op in ops.iter().cloned() {
+        through_seq = through_seq.max(op["author_seq"].as_i64().unwrap_or(0));
+        op["v"] = serde_json::json!(OPLOG_VERSION);
+        writeln!(file, "{}", op).unwrap_or_else(|e| error!("Failed to write {}: {}", path, e));
+    }
+
+    pgrx::JsonB(serde_json::json!({
+        "path": path,
+        "author": fingerprint,
+        "exported": ops.len(),
+        "from_seq": from_seq,
+        "through_seq": through_seq,
+    }))
+}
+
+/// Import an op log written by `export_oplog` (or any producer of the same
+/// format), applying each line through `apply_remote_op`.
+///
+/// Every line's signature is checked before anything is applied, and a bad
+/// line aborts the import naming its line number. Ops already present (same
+/// author and author_seq) are counted as `duplicate`, so re-importing a log
+/// is a no-op.
+///
+/// Reads a server-side file, so the caller needs the privileges of
+/// `pg_read_server_files` (as for `COPY ... FROM 'file'`).
+///
+/// Returns JSON: {path, lines, applied, duplicate, recorded, rejected}
+#[pg_extern]
+fn import_oplog(path: &str) -> pgrx::JsonB {
+    require_server_file_role("pg_read_server_files", "import_oplog");
+    let text =
+        std::fs::read_to_string(path).unwrap_or_else(|e| error!("Failed to read {}: {}", path, e));
+
+    let mut ops = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let op: Value = serde_json::from_str(line)
+            .unwrap_or_else(|e| error!("{}:{}: invalid JSON: {}", path, i + 1, e));
+        if let Some(v) = op.get("v").and_then(Value::as_i64) {
+            if v != OPLOG_VERSION {
+                error!("{}:{}: unsupported op log version {}", path, i + 1, v);
+            }
+        }
+        if !oplog_line_verifies(&op) {
+            error!("{}:{}: signature verification failed", path, i + 1);
+        }
+        ops.push(op);
+    }
+
+    let mut counts: std::collections::BTreeMap<String, u64> =
+        ["applied", "duplicate", "recorded", "rejected"]
+            .iter()
+            .map(|s| (s.to_string(), 0))
+            .collect();
+    for mut op in ops.iter().cloned() {
+        if let Some(obj) = op.as_object_mut() {
+            obj.remove("v");
+        }
+        let result = apply_remote_op(pgrx::JsonB(op));
+        let status = result.0["status"].as_str().unwrap_or("unknown").to_string();
+        *counts.entry(status).or_default() += 1;
+    }
+
+    let mut result = serde_json::json!({"path": path, "lines": ops.len()});
+    for (status, n) in counts {
+        result[status] = serde_json::json!(n);
+    }
+    pgrx::JsonB(result)
+}
+
+/// Error unless the current user has the privileges of `role` (superusers do).
+fn require_server_file_role(role: &str, func: &str) {
+    let allowed = Spi::get_one::<bool>(&format!(
+        "SELECT pg_has_role(current_user, '{}', 'USAGE')",
+        sql_escape(role),
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if !allowed {
+        error!(
+            "permission denied for {}: requires privileges of {}",
+            func, role
+        );
+    }
+}
+
+/// Check one op log line's signature against its embedded public key.
+fn oplog_line_verifies(op: &Value) -> bool {
+    let (Some(record), Some(pk_hex)) = (OpRecord::from_json(op), op["public_key"].as_str()) else {
+        return false;
+    };
+    let Ok(public_key) = hex::decode(pk_hex) else {
+        return false;
+    };
+    verify_op_signature(&public_key, &record)
+}
+
+/// Check an op's signature, for debugging sync problems.
+///
+/// `op` is in `ops_since` form. Its `public_key` is used when present, else the
+/// public key of the instance whose fingerprint is its `author`. Returns false
+/// when a signed field is missing or no key can be found.
+#[pg_extern]
+fn verify_op(op: pgrx::JsonB) -> bool {
+    let Some(record) = OpRecord::from_json(&op.0) else {
+        return false;
+    };
+    let public_key = match op.0["public_key"].as_str() {
+        Some(pk_hex) => hex::decode(pk_hex).ok(),
+        None => op.0["author"].as_str().and_then(|author| {
+            Spi::get_one::<String>(&format!(
+                "SELECT encode(public_key, 'hex') FROM kerai.instances WHERE key_fingerprint = '{}'",
+                sql_escape(author),
+            ))
+            .unwrap_or(None)
+            .and_then(|pk_hex| hex::decode(pk_hex).ok())
+        }),
+    };
+    public_key.is_some_and(|pk| verify_op_signature(&pk, &record))
+}
+
+/// Re-derive node and edge state by replaying the op log from a checkpoint
+/// and diff it against the materialized tables.
+///
+/// The checkpoint is `author`'s op number `seq`. Every node/edge op from any
+/// author at or after it in Lamport order is replayed into an in-memory
+/// shadow, so live data is untouched unless `apply` is true; then divergent
+/// fields are rewritten from the replay, without recording new ops. Only what
+/// the replayed ops determine is compared: a node updated but not inserted in
+/// the range has just its updated fields checked. Other op types are skipped,
+/// and ops recorded from `read_only` or `untrusted` peers are never replayed.
+///
+/// Returns JSON: {checkpoint: {author, seq, lamport_ts}, replayed, skipped,
+/// nodes, edges, consistent, divergent: [{node_id | edge, field, replayed,
+/// current}], applied, repaired}
+#[pg_extern]
+fn replay_ops_from(author: &str, seq: i64, apply: default!(bool, "false")) -> pgrx::JsonB {
+    let escaped = sql_escape(author);
+    let lamport_ts = Spi::get_one::<i64>(&format!(
+        "SELECT lamport_ts FROM kerai.operations WHERE author = '{}' AND author_seq = {}",
+        escaped, seq,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| {
+        error!(
+            "No op {} by author '{}' in the log (unknown or already compacted)",
+            seq, author
+        )
+    });
+
+    let ops = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'op_type', o.op_type,
+            'node_id', o.node_id,
+            'instance_id', o.instance_id,
+            'payload', o.payload
+        ) ORDER BY o.lamport_ts, o.author, o.author_seq), '[]'::jsonb)
+        FROM kerai.operations o
+        JOIN kerai.instances i ON i.id = o.instance_id
+        WHERE (o.lamport_ts, o.author, o.author_seq) >= ({}, '{}', {}) AND {}",
+        lamport_ts, escaped, seq, FULLY_TRUSTED,
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| serde_json::json!([]));
+
+    let mut shadow = replay::Shadow::default();
+    let (mut replayed, mut skipped) = (0u64, 0u64);
+    for op in ops.as_array().into_iter().flatten() {
+        if shadow.replay(op) {
+            replayed += 1;
+        } else {
+            skipped += 1;
+        }
+    }
+
+    let divergent = shadow.diff();
+    let repaired = if apply { shadow.repair(&divergent) } else { 0 };
+    let (nodes, edges) = shadow.counts();
+
+    pgrx::JsonB(serde_json::json!({
+        "checkpoint": {"author": author, "seq": seq, "lamport_ts": lamport_ts},
+        "replayed": replayed,
+        "skipped": skipped,
+        "nodes": nodes,
+        "edges": edges,
+        "consistent": divergent.is_empty(),
+        "divergent": divergent,
+        "applied": apply,
+        "repaired": repaired,
+    }))
 }
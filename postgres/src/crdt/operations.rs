@@ -4,12 +4,14 @@ use base64::Engine as _;
 use pgrx::prelude::*;
 use serde_json::Value;
 
-use crate::sql::sql_escape;
+use crate::parser::path_builder::sanitize_label;
+use crate::sql::{sql_escape, sql_ltree, sql_text, sql_uuid};
 
 /// Valid operation types.
 const VALID_OP_TYPES: &[&str] = &[
     "insert_node",
     "update_content",
+    "rename_symbol",
     "update_metadata",
     "move_node",
     "delete_node",
@@ -32,6 +34,7 @@ const VALID_OP_TYPES: &[&str] = &[
     "update_model_weights",
     "delete_model",
     "train_step",
+    "state_snapshot",
 ];
 
 /// Validate that op_type is known and node_id requirements are met.
@@ -60,6 +63,7 @@ pub fn validate_op(op_type: &str, node_id: Option<&str>, _payload: &Value) {
         "update_model_weights",
         "delete_model",
         "train_step",
+        "state_snapshot",
     ];
     if !no_node_id_ops.contains(&op_type) && node_id.is_none() {
         error!("op_type '{}' requires a node_id", op_type);
@@ -81,6 +85,11 @@ pub fn apply(
             apply_update_content(nid, payload);
             nid.to_string()
         }
+        "rename_symbol" => {
+            let nid = node_id.unwrap();
+            apply_rename_symbol(nid, payload);
+            nid.to_string()
+        }
         "update_metadata" => {
             let nid = node_id.unwrap();
             apply_update_metadata(nid, payload);
@@ -106,7 +115,7 @@ pub fn apply(
             apply_delete_edge(nid, payload);
             nid.to_string()
         }
-        "set_perspective" => apply_set_perspective(payload),
+        "set_perspective" => apply_set_perspective(payload, instance_id),
         "delete_perspective" => apply_delete_perspective(payload),
         "set_association" => apply_set_association(payload),
         "delete_association" => apply_delete_association(payload),
@@ -123,6 +132,7 @@ pub fn apply(
         "update_model_weights" => apply_update_model_weights(payload),
         "delete_model" => apply_delete_model(payload),
         "train_step" => apply_train_step(payload),
+        "state_snapshot" => apply_state_snapshot(payload, instance_id),
         _ => error!("Unknown op_type: '{}'", op_type),
     }
 }
@@ -192,6 +202,59 @@ fn apply_update_content(node_id: &str, payload: &Value) {
     .unwrap();
 }
 
+/// Rename a node: set its content to `new_name`, replace the last segment of
+/// its path, and move descendants' paths under the new segment. The old name
+/// is appended to `metadata.rename_history` as `{from, to}`.
+fn apply_rename_symbol(node_id: &str, payload: &Value) {
+    let new_name = payload["new_name"]
+        .as_str()
+        .unwrap_or_else(|| error!("rename_symbol requires 'new_name' in payload"));
+    let id = sql_uuid(node_id);
+
+    let old_path = Spi::get_one::<String>(&format!(
+        "SELECT path::text FROM kerai.nodes WHERE id = {id}"
+    ))
+    .unwrap_or_else(|_| error!("Node not found: {}", node_id));
+    let new_path = old_path.as_deref().map(|path| {
+        let label = sanitize_label(new_name);
+        match path.rsplit_once('.') {
+            Some((parent, _)) => format!("{parent}.{label}"),
+            None => label,
+        }
+    });
+
+    // Descendants first, while the renamed node still has its old path
+    if let (Some(old_path), Some(new_path)) = (&old_path, &new_path) {
+        let (old, new) = (sql_ltree(old_path), sql_ltree(new_path));
+        Spi::run(&format!(
+            "WITH RECURSIVE descendants AS (
+                SELECT id FROM kerai.nodes WHERE parent_id = {id}
+                UNION ALL
+                SELECT n.id FROM kerai.nodes n JOIN descendants d ON n.parent_id = d.id
+            )
+            UPDATE kerai.nodes SET path = CASE
+                WHEN path = {old} THEN {new}
+                ELSE {new} || subpath(path, nlevel({old}))
+            END
+            WHERE id IN (SELECT id FROM descendants) AND path <@ {old}",
+        ))
+        .unwrap();
+    }
+
+    let path_sql = new_path.as_deref().map_or("NULL".to_string(), sql_ltree);
+    Spi::run(&format!(
+        "UPDATE kerai.nodes SET
+            content = {name},
+            path = {path_sql},
+            metadata = metadata || jsonb_build_object('rename_history',
+                COALESCE(metadata->'rename_history', '[]'::jsonb)
+                || jsonb_build_array(jsonb_build_object('from', content, 'to', {name})))
+         WHERE id = {id}",
+        name = sql_text(new_name),
+    ))
+    .unwrap();
+}
+
 /// UPDATE the metadata field of a node (JSONB merge via ||).
 fn apply_update_metadata(node_id: &str, payload: &Value) {
     let merge = payload
@@ -324,8 +387,9 @@ fn apply_delete_edge(source_id: &str, payload: &Value) {
     .unwrap();
 }
 
-/// UPSERT a perspective. Returns the perspective id.
-fn apply_set_perspective(payload: &Value) -> String {
+/// UPSERT a perspective, recording the instance it originated on.
+/// Returns the perspective id.
+fn apply_set_perspective(payload: &Value, instance_id: &str) -> String {
     let agent_id = payload["agent_id"]
         .as_str()
         .unwrap_or_else(|| error!("set_perspective requires 'agent_id' in payload"));
@@ -345,23 +409,55 @@ fn apply_set_perspective(payload: &Value) -> String {
         None => "NULL".to_string(),
     };
 
+    ensure_peer_agent(agent_id, payload, instance_id);
+
     let pid = Spi::get_one::<String>(&format!(
-        "INSERT INTO kerai.perspectives (agent_id, node_id, weight, context_id, reasoning)
-         VALUES ('{}'::uuid, '{}'::uuid, {}, {}, {})
+        "INSERT INTO kerai.perspectives (agent_id, node_id, weight, context_id, reasoning, instance_id)
+         VALUES ('{}'::uuid, '{}'::uuid, {}, {}, {}, '{}'::uuid)
          ON CONFLICT (agent_id, node_id, context_id)
-         DO UPDATE SET weight = EXCLUDED.weight, reasoning = EXCLUDED.reasoning, updated_at = now()
+         DO UPDATE SET weight = EXCLUDED.weight, reasoning = EXCLUDED.reasoning,
+                       instance_id = EXCLUDED.instance_id, updated_at = now()
          RETURNING id::text",
         sql_escape(agent_id),
         sql_escape(node_id),
         weight,
         ctx_sql,
         reasoning_sql,
+        sql_escape(instance_id),
     ))
     .unwrap()
     .unwrap();
     pid
 }
 
+/// Register a placeholder for an agent that lives on a peer, so its synced
+/// perspectives have an agent row to reference. Agents are matched by id.
+/// Named `<name>@<instance>` to stay clear of local agent names; if that name
+/// is already taken by another agent, the agent id is appended to it.
+fn ensure_peer_agent(agent_id: &str, payload: &Value, instance_id: &str) {
+    let name = payload
+        .get("agent_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or(agent_id);
+    let suffix: String = instance_id.chars().take(8).collect();
+    let base = format!("{}@{}", name, suffix);
+
+    Spi::run(&format!(
+        "INSERT INTO kerai.agents (id, name, kind, config, origin_instance_id)
+         SELECT '{0}'::uuid,
+                CASE WHEN EXISTS (SELECT 1 FROM kerai.agents WHERE name = '{1}')
+                     THEN '{1}/{0}' ELSE '{1}' END,
+                'llm',
+                jsonb_build_object('peer_instance_id', '{2}'),
+                (SELECT id FROM kerai.instances WHERE id = '{2}'::uuid AND NOT is_self)
+         WHERE NOT EXISTS (SELECT 1 FROM kerai.agents WHERE id = '{0}'::uuid)",
+        sql_escape(agent_id),
+        sql_escape(&base),
+        sql_escape(instance_id),
+    ))
+    .unwrap();
+}
+
 /// DELETE a perspective. Returns the agent_id.
 fn apply_delete_perspective(payload: &Value) -> String {
     let agent_id = payload["agent_id"]
@@ -1001,3 +1097,157 @@ fn apply_train_step(payload: &Value) -> String {
 
     run_id
 }
+
+/// Materialize a compacted state snapshot: upsert its nodes and edges (keeping
+/// their original ids so later ops still resolve), remove the covered nodes and
+/// edges it no longer holds, and advance the version vector to the snapshot's
+/// coverage. Returns the snapshot id.
+///
+/// Nodes whose originating instance is unknown locally are attributed to the
+/// snapshot's author. A node missing from the snapshot is removed when it
+/// originated on a peer the snapshot covers, unless its insert op was logged
+/// here past that coverage; local nodes are never removed.
+fn apply_state_snapshot(payload: &Value, instance_id: &str) -> String {
+    let snapshot_id = payload["snapshot_id"]
+        .as_str()
+        .unwrap_or_else(|| error!("state_snapshot requires 'snapshot_id' in payload"));
+    let empty = Value::Array(Vec::new());
+    let nodes = payload.get("nodes").unwrap_or(&empty);
+    let edges = payload.get("edges").unwrap_or(&empty);
+    let empty_covers = Value::Object(serde_json::Map::new());
+    let covers = payload.get("covers").unwrap_or(&empty_covers);
+    let nodes_sql = sql_escape(&nodes.to_string());
+    let covers_sql = sql_escape(&covers.to_string());
+
+    // One statement, so self-referencing parent_id FKs are checked at its end
+    Spi::run(&format!(
+        "INSERT INTO kerai.nodes (id, instance_id, kind, language, content, parent_id, position, path, metadata)
+         SELECT n.id,
+                COALESCE((SELECT i.id FROM kerai.instances i WHERE i.id = n.instance_id), '{}'::uuid),
+                n.kind, n.language, n.content, n.parent_id, n.position, n.path::ltree,
+                COALESCE(n.metadata, '{{}}'::jsonb)
+         FROM jsonb_to_recordset('{}'::jsonb) AS n(
+             id uuid, instance_id uuid, kind text, language text, content text,
+             parent_id uuid, position int, path text, metadata jsonb
+         )
+         ON CONFLICT (id) DO UPDATE SET
+             kind = EXCLUDED.kind,
+             language = EXCLUDED.language,
+             content = EXCLUDED.content,
+             parent_id = EXCLUDED.parent_id,
+             position = EXCLUDED.position,
+             path = EXCLUDED.path,
+             metadata = EXCLUDED.metadata",
+        sql_escape(instance_id),
+        nodes_sql,
+    ))
+    .unwrap();
+
+    // Covered nodes the snapshot no longer holds were deleted upstream
+    let removed = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(n.id), '[]'::jsonb)
+         FROM kerai.nodes n
+         JOIN kerai.instances i ON i.id = n.instance_id
+         WHERE NOT i.is_self
+           AND '{0}'::jsonb ? i.key_fingerprint
+           AND n.id NOT IN (
+               SELECT s.id FROM jsonb_to_recordset('{1}'::jsonb) AS s(id uuid)
+           )
+           AND NOT EXISTS (
+               SELECT 1 FROM kerai.operations o
+               WHERE o.node_id = n.id AND o.op_type = 'insert_node'
+                 AND o.author_seq > COALESCE(('{0}'::jsonb ->> o.author)::bigint, 0)
+           )",
+        covers_sql, nodes_sql,
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_default();
+    let removed: Vec<String> = removed
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(sql_uuid)
+        .collect();
+    if !removed.is_empty() {
+        let removed_sql = format!("ARRAY[{}]::uuid[]", removed.join(", "));
+        Spi::run(&format!(
+            "DELETE FROM kerai.edges WHERE source_id = ANY({0}) OR target_id = ANY({0})",
+            removed_sql,
+        ))
+        .unwrap();
+        // Surviving children move up past their removed ancestors
+        Spi::run(&format!(
+            "WITH RECURSIVE up AS (
+                SELECT n.id, n.parent_id AS ancestor FROM kerai.nodes n
+                WHERE n.parent_id = ANY({0}) AND NOT n.id = ANY({0})
+                UNION ALL
+                SELECT up.id, p.parent_id FROM up
+                JOIN kerai.nodes p ON p.id = up.ancestor
+                WHERE up.ancestor = ANY({0})
+            )
+            UPDATE kerai.nodes n SET parent_id = up.ancestor
+            FROM up
+            WHERE n.id = up.id
+              AND (up.ancestor IS NULL OR NOT up.ancestor = ANY({0}))",
+            removed_sql,
+        ))
+        .unwrap();
+        Spi::run(&format!(
+            "DELETE FROM kerai.nodes WHERE id = ANY({})",
+            removed_sql,
+        ))
+        .unwrap();
+    }
+
+    Spi::run(&format!(
+        "INSERT INTO kerai.edges (id, source_id, target_id, relation, metadata)
+         SELECT e.id, e.source_id, e.target_id, e.relation, COALESCE(e.metadata, '{{}}'::jsonb)
+         FROM jsonb_to_recordset('{}'::jsonb) AS e(
+             id uuid, source_id uuid, target_id uuid, relation text, metadata jsonb
+         )
+         WHERE EXISTS (SELECT 1 FROM kerai.nodes WHERE id = e.source_id)
+           AND EXISTS (SELECT 1 FROM kerai.nodes WHERE id = e.target_id)
+         ON CONFLICT (source_id, target_id, relation) DO UPDATE SET metadata = EXCLUDED.metadata",
+        sql_escape(&edges.to_string()),
+    ))
+    .unwrap();
+
+    // Edges out of covered snapshot nodes that the snapshot no longer holds
+    Spi::run(&format!(
+        "DELETE FROM kerai.edges x
+         USING kerai.nodes n, kerai.instances i
+         WHERE n.id = x.source_id AND i.id = n.instance_id
+           AND NOT i.is_self
+           AND '{0}'::jsonb ? i.key_fingerprint
+           AND n.id IN (SELECT s.id FROM jsonb_to_recordset('{1}'::jsonb) AS s(id uuid))
+           AND NOT EXISTS (
+               SELECT 1 FROM jsonb_to_recordset('{2}'::jsonb)
+                   AS e(source_id uuid, target_id uuid, relation text)
+               WHERE e.source_id = x.source_id AND e.target_id = x.target_id
+                 AND e.relation = x.relation
+           )
+           AND NOT EXISTS (
+               SELECT 1 FROM kerai.operations o
+               WHERE o.node_id = x.source_id AND o.op_type = 'insert_edge'
+                 AND o.payload->>'target_id' = x.target_id::text
+                 AND o.payload->>'relation' = x.relation
+                 AND o.author_seq > COALESCE(('{0}'::jsonb ->> o.author)::bigint, 0)
+           )",
+        covers_sql,
+        nodes_sql,
+        sql_escape(&edges.to_string()),
+    ))
+    .unwrap();
+
+    if let Some(covers) = covers.as_object() {
+        for (author, seq) in covers {
+            if let Some(seq) = seq.as_i64() {
+                super::clock::advance_author_seq(author, seq);
+            }
+        }
+    }
+
+    snapshot_id.to_string()
+}
@@ -0,0 +1,351 @@
+/// Shadow replay of node/edge operations for `replay_ops_from`.
+///
+/// Ops are replayed into in-memory state rather than the live tables, then
+/// compared field by field with what is materialized.
+use std::collections::{BTreeMap, HashMap};
+
+use pgrx::prelude::*;
+use serde_json::{json, Map, Value};
+
+use crate::sql::{sql_escape, sql_jsonb, sql_uuid};
+
+use super::operations;
+
+/// Op types that touch kerai.nodes / kerai.edges and can be replayed.
+const REPLAYABLE: &[&str] = &[
+    "insert_node",
+    "update_content",
+    "rename_symbol",
+    "update_metadata",
+    "move_node",
+    "delete_node",
+    "insert_edge",
+    "delete_edge",
+];
+
+/// Replayed state of one node. Fields stay `None` when no replayed op
+/// determined them, and are then not compared.
+#[derive(Default)]
+struct ShadowNode {
+    deleted: bool,
+    /// `{instance_id, kind, language, path}` when the insert was replayed.
+    created: Option<Value>,
+    content: Option<Value>,
+    parent_id: Option<Value>,
+    position: Option<Value>,
+    metadata: Map<String, Value>,
+}
+
+type EdgeKey = (String, String, String);
+
+/// Node and edge state derived from a run of replayed ops.
+#[derive(Default)]
+pub struct Shadow {
+    nodes: HashMap<String, ShadowNode>,
+    /// Node ids in first-touched order, so restores insert parents first.
+    order: Vec<String>,
+    /// `Some(metadata)` for an edge that should exist, `None` for a deleted one.
+    edges: BTreeMap<EdgeKey, Option<Value>>,
+}
+
+impl Shadow {
+    /// Replay one op (ops_since-style JSON plus `instance_id`). Returns false
+    /// for op types that don't touch nodes or edges.
+    pub fn replay(&mut self, op: &Value) -> bool {
+        let op_type = op["op_type"].as_str().unwrap_or_default();
+        if !REPLAYABLE.contains(&op_type) {
+            return false;
+        }
+        let Some(node_id) = op["node_id"].as_str().map(str::to_string) else {
+            return false;
+        };
+        let payload = &op["payload"];
+
+        match op_type {
+            "insert_node" => {
+                let node = self.node(&node_id);
+                node.deleted = false;
+                node.created = Some(json!({
+                    "instance_id": op["instance_id"],
+                    "kind": payload["kind"],
+                    "language": payload["language"],
+                    "path": payload["path"],
+                }));
+                node.content = Some(payload["content"].clone());
+                node.parent_id = Some(payload["parent_id"].clone());
+                node.position = Some(payload.get("position").cloned().unwrap_or(json!(0)));
+                node.metadata = payload["metadata"].as_object().cloned().unwrap_or_default();
+            }
+            "update_content" => {
+                self.node(&node_id).content = Some(payload["new_content"].clone());
+            }
+            "rename_symbol" => {
+                let node = self.node(&node_id);
+                // The rename history is only fully known if the insert was replayed
+                if node.created.is_some() || node.metadata.contains_key("rename_history") {
+                    let entry = json!({"from": node.content, "to": payload["new_name"]});
+                    let history = node
+                        .metadata
+                        .entry("rename_history".to_string())
+                        .or_insert_with(|| json!([]));
+                    if let Some(history) = history.as_array_mut() {
+                        history.push(entry);
+                    }
+                }
+                node.content = Some(payload["new_name"].clone());
+            }
+            "update_metadata" => {
+                if let Some(merge) = payload["merge"].as_object() {
+                    let node = self.node(&node_id);
+                    for (key, value) in merge {
+                        node.metadata.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            "move_node" => {
+                let node = self.node(&node_id);
+                if let Some(parent) = payload.get("new_parent_id").filter(|v| v.is_string()) {
+                    node.parent_id = Some(parent.clone());
+                }
+                if let Some(position) = payload.get("new_position").filter(|v| v.is_i64()) {
+                    node.position = Some(position.clone());
+                }
+            }
+            "delete_node" => {
+                let cascade = payload["cascade"].as_bool().unwrap_or(false);
+                self.delete_node(&node_id, cascade);
+            }
+            "insert_edge" | "delete_edge" => {
+                let key = (
+                    node_id,
+                    payload["target_id"].as_str().unwrap_or_default().to_string(),
+                    payload["relation"].as_str().unwrap_or_default().to_string(),
+                );
+                let state = (op_type == "insert_edge")
+                    .then(|| payload.get("metadata").cloned().unwrap_or_else(|| json!({})));
+                self.edges.insert(key, state);
+            }
+            _ => unreachable!(),
+        }
+        true
+    }
+
+    fn node(&mut self, id: &str) -> &mut ShadowNode {
+        if !self.nodes.contains_key(id) {
+            self.order.push(id.to_string());
+        }
+        self.nodes.entry(id.to_string()).or_default()
+    }
+
+    /// Mirror `apply_delete_node` on the shadow: cascade removes known
+    /// descendants, otherwise known children move up to the node's parent.
+    fn delete_node(&mut self, id: &str, cascade: bool) {
+        let parent = self.node(id).parent_id.clone();
+        let child_of =
+            |n: &ShadowNode, p: &str| n.parent_id.as_ref().and_then(Value::as_str) == Some(p);
+
+        let mut gone = vec![id.to_string()];
+        let mut i = 0;
+        while i < gone.len() {
+            let current = gone[i].clone();
+            let children: Vec<String> = self
+                .nodes
+                .iter()
+                .filter(|(cid, n)| !n.deleted && child_of(n, &current) && !gone.contains(cid))
+                .map(|(cid, _)| cid.clone())
+                .collect();
+            if cascade {
+                gone.extend(children);
+            } else {
+                for cid in children {
+                    self.nodes.get_mut(&cid).unwrap().parent_id = parent.clone();
+                }
+            }
+            i += 1;
+        }
+
+        for gid in &gone {
+            self.node(gid).deleted = true;
+        }
+        for (key, state) in self.edges.iter_mut() {
+            if gone.contains(&key.0) || gone.contains(&key.1) {
+                *state = None;
+            }
+        }
+    }
+
+    /// Number of (nodes, edges) the replay determined something about.
+    pub fn counts(&self) -> (usize, usize) {
+        (self.nodes.len(), self.edges.len())
+    }
+
+    /// Compare the shadow with the live tables. Each divergence is
+    /// `{node_id, field, replayed, current}` or `{edge, field, replayed, current}`.
+    pub fn diff(&self) -> Vec<Value> {
+        let mut divergent = Vec::new();
+
+        for id in &self.order {
+            let shadow = &self.nodes[id];
+            let current = Spi::get_one::<pgrx::JsonB>(&format!(
+                "SELECT jsonb_build_object(
+                    'content', content, 'parent_id', parent_id,
+                    'position', position, 'metadata', metadata
+                ) FROM kerai.nodes WHERE id = {}",
+                sql_uuid(id),
+            ))
+            .unwrap()
+            .map(|j| j.0);
+
+            let Some(current) = current else {
+                if !shadow.deleted {
+                    divergent.push(node_diff(id, "exists", json!(true), json!(false)));
+                }
+                continue;
+            };
+            if shadow.deleted {
+                divergent.push(node_diff(id, "exists", json!(false), json!(true)));
+                continue;
+            }
+
+            let fields = [
+                ("content", &shadow.content),
+                ("parent_id", &shadow.parent_id),
+                ("position", &shadow.position),
+            ];
+            for (field, replayed) in fields {
+                if let Some(replayed) = replayed {
+                    if *replayed != current[field] {
+                        let live = current[field].clone();
+                        divergent.push(node_diff(id, field, replayed.clone(), live));
+                    }
+                }
+            }
+            for (key, replayed) in &shadow.metadata {
+                let live = current["metadata"].get(key).cloned().unwrap_or(Value::Null);
+                if *replayed != live {
+                    let field = format!("metadata.{key}");
+                    divergent.push(node_diff(id, &field, replayed.clone(), live));
+                }
+            }
+        }
+
+        for ((source, target, relation), state) in &self.edges {
+            let exists = Spi::get_one::<bool>(&format!(
+                "SELECT EXISTS(SELECT 1 FROM kerai.edges
+                 WHERE source_id = {} AND target_id = {} AND relation = '{}')",
+                sql_uuid(source),
+                sql_uuid(target),
+                sql_escape(relation),
+            ))
+            .unwrap()
+            .unwrap_or(false);
+            if exists != state.is_some() {
+                divergent.push(json!({
+                    "edge": {"source_id": source, "target_id": target, "relation": relation},
+                    "field": "exists",
+                    "replayed": state.is_some(),
+                    "current": exists,
+                }));
+            }
+        }
+
+        divergent
+    }
+
+    /// Rewrite the live tables so each divergence matches the replay.
+    /// Returns how many were repaired; a node that must be restored but
+    /// whose insert wasn't replayed can't be, and is skipped.
+    pub fn repair(&self, divergent: &[Value]) -> usize {
+        let mut repaired = 0;
+
+        for d in divergent {
+            let field = d["field"].as_str().unwrap_or_default();
+
+            if let Some(edge) = d.get("edge") {
+                let source = edge["source_id"].as_str().unwrap_or_default();
+                let key = (
+                    source.to_string(),
+                    edge["target_id"].as_str().unwrap_or_default().to_string(),
+                    edge["relation"].as_str().unwrap_or_default().to_string(),
+                );
+                let op_type = if d["replayed"] == true { "insert_edge" } else { "delete_edge" };
+                let metadata =
+                    self.edges.get(&key).cloned().flatten().unwrap_or_else(|| json!({}));
+                operations::apply(
+                    op_type,
+                    Some(source),
+                    &json!({"target_id": key.1, "relation": key.2, "metadata": metadata}),
+                    "",
+                );
+                repaired += 1;
+                continue;
+            }
+
+            let id = d["node_id"].as_str().unwrap_or_default();
+            let shadow = &self.nodes[id];
+            match field {
+                "exists" if d["replayed"] == false => {
+                    operations::apply("delete_node", Some(id), &json!({"cascade": false}), "");
+                }
+                "exists" => {
+                    let Some(created) = &shadow.created else {
+                        continue;
+                    };
+                    Spi::run(&format!(
+                        "INSERT INTO kerai.nodes (id, instance_id, kind, language, content, parent_id, position, path, metadata)
+                         SELECT {}, (r->>'instance_id')::uuid, r->>'kind', r->>'language', r->>'content',
+                                (r->>'parent_id')::uuid, COALESCE((r->>'position')::int, 0),
+                                (r->>'path')::ltree, COALESCE(r->'metadata', '{{}}'::jsonb)
+                         FROM (SELECT {} AS r) s",
+                        sql_uuid(id),
+                        sql_jsonb(&json!({
+                            "instance_id": created["instance_id"],
+                            "kind": created["kind"],
+                            "language": created["language"],
+                            "path": created["path"],
+                            "content": shadow.content,
+                            "parent_id": shadow.parent_id,
+                            "position": shadow.position,
+                            "metadata": shadow.metadata,
+                        })),
+                    ))
+                    .unwrap();
+                }
+                "content" | "parent_id" | "position" => {
+                    let value = &d["replayed"];
+                    let sql_value = match (field, value.as_str()) {
+                        (_, None) if value.is_null() => "NULL".to_string(),
+                        ("parent_id", Some(p)) => sql_uuid(p),
+                        (_, Some(s)) => format!("'{}'", sql_escape(s)),
+                        _ => value.to_string(),
+                    };
+                    Spi::run(&format!(
+                        "UPDATE kerai.nodes SET {field} = {sql_value} WHERE id = {}",
+                        sql_uuid(id),
+                    ))
+                    .unwrap();
+                }
+                _ => {
+                    let Some(key) = field.strip_prefix("metadata.") else {
+                        continue;
+                    };
+                    let mut merge = Map::new();
+                    merge.insert(key.to_string(), d["replayed"].clone());
+                    operations::apply(
+                        "update_metadata",
+                        Some(id),
+                        &json!({"merge": merge}),
+                        "",
+                    );
+                }
+            }
+            repaired += 1;
+        }
+
+        repaired
+    }
+}
+
+fn node_diff(id: &str, field: &str, replayed: Value, current: Value) -> Value {
+    json!({"node_id": id, "field": field, "replayed": replayed, "current": current})
+}
@@ -1,4 +1,6 @@
 /// Canonical signable data construction and Ed25519 signature verification for CRDT operations.
+use serde_json::Value;
+
 use crate::identity;
 
 /// Build the canonical byte representation of an operation for signing.
@@ -15,15 +17,32 @@ pub fn build_signable(
     format!("{}|{}|{}|{}", op_type, nid, author_seq, payload_json).into_bytes()
 }
 
+/// The signed fields of an operation.
+pub struct OpRecord {
+    pub op_type: String,
+    pub node_id: Option<String>,
+    pub author_seq: i64,
+    /// Payload JSON text, exactly as signed.
+    pub payload: String,
+    pub signature: Vec<u8>,
+}
+
+impl OpRecord {
+    /// Read the signed fields of an op in `ops_since` / `apply_remote_op` form.
+    /// Returns None if one is missing or the signature isn't hex.
+    pub fn from_json(op: &Value) -> Option<Self> {
+        Some(Self {
+            op_type: op["op_type"].as_str()?.to_string(),
+            node_id: op["node_id"].as_str().map(str::to_string),
+            author_seq: op["author_seq"].as_i64()?,
+            payload: op.get("payload")?.to_string(),
+            signature: hex::decode(op["signature"].as_str()?).ok()?,
+        })
+    }
+}
+
 /// Verify an Ed25519 signature over the canonical representation of an operation.
-pub fn verify_op_signature(
-    public_key: &[u8],
-    op_type: &str,
-    node_id: Option<&str>,
-    author_seq: i64,
-    payload_json: &str,
-    signature: &[u8],
-) -> bool {
+pub fn verify_op_signature(public_key: &[u8], op: &OpRecord) -> bool {
     let pk_bytes: [u8; 32] = match public_key.try_into() {
         Ok(b) => b,
         Err(_) => return false,
@@ -32,6 +51,11 @@ pub fn verify_op_signature(
         Ok(k) => k,
         Err(_) => return false,
     };
-    let signable = build_signable(op_type, node_id, author_seq, payload_json);
-    identity::verify_signature(&verifying_key, &signable, signature)
+    let signable = build_signable(
+        &op.op_type,
+        op.node_id.as_deref(),
+        op.author_seq,
+        &op.payload,
+    );
+    identity::verify_signature(&verifying_key, &signable, &op.signature)
 }
@@ -5,8 +5,9 @@
 /// 9 whole digits + implicit decimal + 9 fractional digits.
 use pgrx::prelude::*;
 
+use crate::economy;
 use crate::identity;
-use crate::sql::sql_escape;
+use crate::sql::{is_ltree_path, sql_escape};
 
 /// 1 Koi = 1,000,000,000 nKoi
 pub const NKOI_PER_KOI: i64 = 1_000_000_000;
@@ -96,6 +97,103 @@ fn signed_transfer(
     nonce: i64,
     signature_hex: &str,
     reason: Option<&str>,
+) -> pgrx::JsonB {
+    let message = format!(
+        "transfer:{}:{}:{}:{}",
+        from_wallet_id, to_wallet_id, amount, nonce
+    );
+    execute_signed_transfer(
+        from_wallet_id,
+        to_wallet_id,
+        amount,
+        nonce,
+        &message,
+        signature_hex,
+        reason,
+        None,
+    )
+}
+
+/// Spend from an agent's linked wallet on its behalf.
+///
+/// The agent proves control by signing
+/// "agent_transfer:{agent}:{wallet}:{to}:{amount}:{nonce}" with its wallet's
+/// key. Naming the agent binds the signature to it, so an ordinary
+/// `signed_transfer` signature for the wallet can't be presented as the
+/// agent's. Nonce, signature, and balance are otherwise checked exactly as in
+/// `signed_transfer`. The ledger row is tagged `reference_type = 'agent'` with
+/// the agent's id, and the transfer is recorded in `kerai.agent_activity`.
+#[pg_extern]
+fn agent_transfer(
+    agent_name: &str,
+    to_wallet_id: pgrx::Uuid,
+    amount: i64,
+    nonce: i64,
+    signature_hex: &str,
+    reason: default!(Option<&str>, "NULL"),
+) -> pgrx::JsonB {
+    let agent_id = Spi::get_one::<String>(&format!(
+        "SELECT id::text FROM kerai.agents WHERE name = '{}'",
+        sql_escape(agent_name),
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Agent not found: {}", agent_name));
+
+    let wallet_id = Spi::get_one::<pgrx::Uuid>(&format!(
+        "SELECT wallet_id FROM kerai.agents WHERE id = '{}'::uuid",
+        agent_id,
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Agent '{}' has no linked wallet", agent_name));
+
+    let message = format!(
+        "agent_transfer:{}:{}:{}:{}:{}",
+        agent_name, wallet_id, to_wallet_id, amount, nonce
+    );
+    let mut row = execute_signed_transfer(
+        wallet_id,
+        to_wallet_id,
+        amount,
+        nonce,
+        &message,
+        signature_hex,
+        Some(reason.unwrap_or("agent_transfer")),
+        Some(("agent", &agent_id)),
+    );
+
+    Spi::run(&format!(
+        "INSERT INTO kerai.agent_activity (agent_id, action, details)
+         VALUES ('{}'::uuid, 'transfer', '{}'::jsonb)",
+        agent_id,
+        sql_escape(
+            &serde_json::json!({
+                "ledger_id": row.0["id"],
+                "to_wallet": to_wallet_id.to_string(),
+                "amount": amount,
+                "nonce": nonce,
+            })
+            .to_string()
+        ),
+    ))
+    .unwrap_or_else(|e| error!("Failed to log agent transfer: {}", e));
+
+    row.0["agent"] = serde_json::json!(agent_name);
+    row
+}
+
+/// Shared body of `signed_transfer` and `agent_transfer`. `message` is the
+/// canonical message the source wallet's key must have signed; `reference` is
+/// an optional `(reference_type, reference_id)` recorded on the ledger row.
+#[allow(clippy::too_many_arguments)]
+fn execute_signed_transfer(
+    from_wallet_id: pgrx::Uuid,
+    to_wallet_id: pgrx::Uuid,
+    amount: i64,
+    nonce: i64,
+    message: &str,
+    signature_hex: &str,
+    reason: Option<&str>,
+    reference: Option<(&str, &str)>,
 ) -> pgrx::JsonB {
     if amount <= 0 {
         error!("Transfer amount must be positive");
@@ -116,6 +214,8 @@ fn signed_transfer(
         None => error!("Source wallet not found: {}", from_wallet_id),
     };
 
+    require_single_signer(&from_wallet_id.to_string());
+    economy::require_spendable(&from_wallet_id.to_string());
     let current_nonce = wallet_info.0["nonce"].as_i64().unwrap_or(0);
     let pk_hex = wallet_info.0["public_key"]
         .as_str()
@@ -141,12 +241,6 @@ fn signed_transfer(
         error!("Destination wallet not found: {}", to_wallet_id);
     }
 
-    // Construct canonical message
-    let message = format!(
-        "transfer:{}:{}:{}:{}",
-        from_wallet_id, to_wallet_id, amount, nonce
-    );
-
     // Decode and verify signature
     let sig_bytes = match hex::decode(signature_hex) {
         Ok(b) => b,
@@ -198,11 +292,16 @@ fn signed_transfer(
 
     let reason_str = reason.unwrap_or("signed_transfer");
     let sig_pg = bytes_to_pg_hex(&sig_bytes);
+    let (ref_type_sql, ref_id_sql) = match reference {
+        Some((kind, id)) => (format!("'{}'", sql_escape(kind)), format!("'{}'::uuid", sql_escape(id))),
+        None => ("NULL".to_string(), "NULL".to_string()),
+    };
 
     // Insert ledger entry
     let row = Spi::get_one::<pgrx::JsonB>(&format!(
-        "INSERT INTO kerai.ledger (from_wallet, to_wallet, amount, reason, signature, timestamp)
-         VALUES ('{}'::uuid, '{}'::uuid, {}, '{}', '{}'::bytea, {})
+        "INSERT INTO kerai.ledger (from_wallet, to_wallet, amount, reason, signature, timestamp,
+                                   reference_type, reference_id)
+         VALUES ('{}'::uuid, '{}'::uuid, {}, '{}', '{}'::bytea, {}, {}, {})
          RETURNING jsonb_build_object(
              'id', id,
              'from_wallet', from_wallet,
@@ -217,6 +316,8 @@ fn signed_transfer(
         sql_escape(reason_str),
         sig_pg,
         lamport,
+        ref_type_sql,
+        ref_id_sql,
     ))
     .unwrap()
     .unwrap();
@@ -231,14 +332,726 @@ fn signed_transfer(
     row
 }
 
-/// Total supply: sum all mints (ledger WHERE from_wallet IS NULL).
+/// Atomic multi-input transfer: several wallets jointly fund one payment.
+///
+/// `inputs` is a JSON array of `{wallet_id, amount, nonce, signature}`. Every
+/// input wallet signs the whole transfer, so a signed input can't be lifted
+/// into another one:
+/// "multi_transfer:{to}:{fee}:{w1}:{amount1}:{nonce1},{w2}:...:{reason}",
+/// with inputs sorted by wallet id and `reason` defaulting to "multi_transfer".
+/// Each input must pass the same nonce and balance checks as `signed_transfer`.
+/// Any failure aborts the statement, so either every debit lands or none do.
+///
+/// The recipient is credited the sum of inputs minus the flat fee set by the
+/// `currency`/`multi_transfer_fee` preference (nKoi, default 0), which goes to
+/// the self instance wallet. All ledger rows share a `multi_transfer` reference id.
 #[pg_extern]
-fn total_supply() -> pgrx::JsonB {
-    let total_minted = Spi::get_one::<i64>(
-        "SELECT COALESCE(SUM(amount), 0)::bigint FROM kerai.ledger WHERE from_wallet IS NULL",
+fn multi_transfer(
+    inputs: pgrx::JsonB,
+    to_wallet_id: pgrx::Uuid,
+    reason: Option<&str>,
+) -> pgrx::JsonB {
+    let entries = inputs
+        .0
+        .as_array()
+        .filter(|a| !a.is_empty())
+        .unwrap_or_else(|| error!("multi_transfer requires a non-empty inputs array"));
+
+    struct Input {
+        wallet_id: String,
+        amount: i64,
+        nonce: i64,
+        sig_bytes: Vec<u8>,
+    }
+
+    let mut parsed: Vec<Input> = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let wallet_id = entry["wallet_id"]
+            .as_str()
+            .unwrap_or_else(|| error!("Input {} is missing 'wallet_id'", i));
+        let amount = entry["amount"]
+            .as_i64()
+            .unwrap_or_else(|| error!("Input {} is missing 'amount'", i));
+        let nonce = entry["nonce"]
+            .as_i64()
+            .unwrap_or_else(|| error!("Input {} is missing 'nonce'", i));
+        let signature_hex = entry["signature"]
+            .as_str()
+            .unwrap_or_else(|| error!("Input {} is missing 'signature'", i));
+        if amount <= 0 {
+            error!("Input {} amount must be positive", i);
+        }
+        let sig_bytes = match hex::decode(signature_hex) {
+            Ok(b) => b,
+            Err(e) => error!("Invalid hex in signature for input {}: {}", i, e),
+        };
+        parsed.push(Input {
+            wallet_id: wallet_id.to_lowercase(),
+            amount,
+            nonce,
+            sig_bytes,
+        });
+    }
+
+    // Lock input wallets in a stable order so concurrent multi-transfers can't deadlock
+    parsed.sort_by(|a, b| a.wallet_id.cmp(&b.wallet_id));
+    if parsed.windows(2).any(|w| w[0].wallet_id == w[1].wallet_id) {
+        error!("Each wallet may appear only once in multi_transfer inputs");
+    }
+
+    let to_exists = Spi::get_one::<bool>(&format!(
+        "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id = '{}'::uuid)",
+        to_wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if !to_exists {
+        error!("Destination wallet not found: {}", to_wallet_id);
+    }
+
+    let fee = Spi::get_one::<String>(
+        "SELECT kerai.get_preference('currency', 'multi_transfer_fee')",
+    )
+    .unwrap_or(None)
+    .and_then(|v| v.trim().parse::<i64>().ok())
+    .unwrap_or(0)
+    .max(0);
+    let reason_str = reason.unwrap_or("multi_transfer");
+    let signed_inputs: Vec<String> = parsed
+        .iter()
+        .map(|i| format!("{}:{}:{}", i.wallet_id, i.amount, i.nonce))
+        .collect();
+    let message = format!(
+        "multi_transfer:{}:{}:{}:{}",
+        to_wallet_id,
+        fee,
+        signed_inputs.join(","),
+        reason_str
+    );
+
+    let mut total: i64 = 0;
+    for input in &parsed {
+        if input.wallet_id == to_wallet_id.to_string() {
+            error!("Input wallet {} cannot also be the recipient", input.wallet_id);
+        }
+
+        let wallet_row = Spi::get_one::<pgrx::JsonB>(&format!(
+            "SELECT jsonb_build_object(
+                'public_key', encode(public_key, 'hex'),
+                'nonce', nonce
+            ) FROM kerai.wallets WHERE id = '{}'::uuid FOR UPDATE",
+            sql_escape(&input.wallet_id),
+        ))
+        .unwrap_or(None)
+        .unwrap_or_else(|| error!("Source wallet not found: {}", input.wallet_id));
+        require_single_signer(&input.wallet_id);
+        economy::require_spendable(&input.wallet_id);
+
+        let current_nonce = wallet_row.0["nonce"].as_i64().unwrap_or(0);
+        if input.nonce != current_nonce + 1 {
+            error!(
+                "Invalid nonce for wallet {}: expected {}, got {}",
+                input.wallet_id,
+                current_nonce + 1,
+                input.nonce
+            );
+        }
+
+        let pk_array: [u8; 32] = wallet_row.0["public_key"]
+            .as_str()
+            .and_then(|h| hex::decode(h).ok())
+            .and_then(|b| b.try_into().ok())
+            .unwrap_or_else(|| error!("Stored public key for {} is invalid", input.wallet_id));
+        let verifying_key = match ed25519_dalek::VerifyingKey::from_bytes(&pk_array) {
+            Ok(k) => k,
+            Err(e) => error!("Invalid stored public key: {}", e),
+        };
+        if !identity::verify_signature(&verifying_key, message.as_bytes(), &input.sig_bytes) {
+            error!("Invalid signature for multi_transfer input {}", input.wallet_id);
+        }
+
+        let balance = Spi::get_one::<i64>(&format!(
+            "SELECT COALESCE(
+                (SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE to_wallet = '{0}'::uuid)
+                - (SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE from_wallet = '{0}'::uuid),
+                0
+            )::bigint",
+            sql_escape(&input.wallet_id),
+        ))
+        .unwrap()
+        .unwrap_or(0);
+        if balance < input.amount {
+            error!(
+                "Insufficient balance: wallet {} has {} nKoi but transfer requires {}",
+                input.wallet_id, balance, input.amount
+            );
+        }
+
+        total = total
+            .checked_add(input.amount)
+            .unwrap_or_else(|| error!("multi_transfer total overflows"));
+    }
+
+    if fee >= total {
+        error!(
+            "multi_transfer total {} nKoi does not cover the {} nKoi fee",
+            total, fee
+        );
+    }
+
+    let transfer_id = Spi::get_one::<String>("SELECT gen_random_uuid()::text")
+        .unwrap()
+        .unwrap();
+    let mut lamport = Spi::get_one::<i64>(
+        "SELECT COALESCE(max(timestamp), 0) + 1 FROM kerai.ledger",
     )
     .unwrap()
+    .unwrap_or(1);
+
+    let mut debits = Vec::with_capacity(parsed.len());
+    for input in &parsed {
+        Spi::run(&format!(
+            "INSERT INTO kerai.ledger
+                (from_wallet, to_wallet, amount, reason, reference_id, reference_type, signature, timestamp)
+             VALUES ('{}'::uuid, '{}'::uuid, {}, '{}', '{}'::uuid, 'multi_transfer', '{}'::bytea, {})",
+            sql_escape(&input.wallet_id),
+            to_wallet_id,
+            input.amount,
+            sql_escape(reason_str),
+            transfer_id,
+            bytes_to_pg_hex(&input.sig_bytes),
+            lamport,
+        ))
+        .unwrap();
+        Spi::run(&format!(
+            "UPDATE kerai.wallets SET nonce = {} WHERE id = '{}'::uuid",
+            input.nonce,
+            sql_escape(&input.wallet_id),
+        ))
+        .unwrap();
+        lamport += 1;
+        debits.push(serde_json::json!({
+            "wallet_id": input.wallet_id,
+            "amount": input.amount,
+            "nonce": input.nonce,
+        }));
+    }
+
+    if fee > 0 {
+        let fee_wallet = Spi::get_one::<String>(
+            "SELECT w.id::text FROM kerai.wallets w
+             JOIN kerai.instances i ON w.instance_id = i.id
+             WHERE i.is_self = true AND w.wallet_type = 'instance'",
+        )
+        .unwrap_or(None)
+        .unwrap_or_else(|| error!("Self instance wallet not found to collect the fee"));
+        Spi::run(&format!(
+            "INSERT INTO kerai.ledger
+                (from_wallet, to_wallet, amount, reason, reference_id, reference_type, timestamp)
+             VALUES ('{}'::uuid, '{}'::uuid, {}, 'multi_transfer_fee', '{}'::uuid, 'multi_transfer', {})",
+            to_wallet_id,
+            sql_escape(&fee_wallet),
+            fee,
+            transfer_id,
+            lamport,
+        ))
+        .unwrap();
+    }
+
+    pgrx::JsonB(serde_json::json!({
+        "transfer_id": transfer_id,
+        "to_wallet": to_wallet_id.to_string(),
+        "inputs": debits,
+        "total": total,
+        "fee": fee,
+        "credited": total - fee,
+        "reason": reason_str,
+    }))
+}
+
+/// Set the extra signers of a wallet and how many signers a transfer needs.
+///
+/// `signers` is a JSON array of hex Ed25519 public keys, replacing any set
+/// before; the wallet's own key is always a signer too. The change is approved
+/// like a transfer: `signatures` (`[{public_key, signature}]`) over
+/// "set_signers:{wallet}:{required_signers}:{nonce}:{key1,key2,...}" (keys as
+/// given, lowercase hex) must meet the wallet's current threshold, and `nonce`
+/// must be the next wallet nonce.
+#[pg_extern]
+fn set_wallet_signers(
+    wallet_id: pgrx::Uuid,
+    signers: pgrx::JsonB,
+    required_signers: i32,
+    nonce: i64,
+    signatures: pgrx::JsonB,
+) -> pgrx::JsonB {
+    let keys: Vec<String> = signers
+        .0
+        .as_array()
+        .unwrap_or_else(|| error!("signers must be a JSON array of hex public keys"))
+        .iter()
+        .map(|k| {
+            k.as_str()
+                .unwrap_or_else(|| error!("signers must be a JSON array of hex public keys"))
+                .to_lowercase()
+        })
+        .collect();
+    let verifying_keys: Vec<ed25519_dalek::VerifyingKey> = keys
+        .iter()
+        .map(|k| identity::parse_public_key_hex(k).unwrap_or_else(|e| error!("{}", e)))
+        .collect();
+
+    let (current_nonce, current_required) = wallet_nonce_and_threshold(wallet_id);
+    if nonce != current_nonce + 1 {
+        error!(
+            "Invalid nonce: expected {}, got {}",
+            current_nonce + 1,
+            nonce
+        );
+    }
+
+    let message = format!(
+        "set_signers:{}:{}:{}:{}",
+        wallet_id,
+        required_signers,
+        nonce,
+        keys.join(",")
+    );
+    verify_wallet_signatures(wallet_id, current_required, &message, &signatures.0);
+
+    Spi::run(&format!(
+        "DELETE FROM kerai.wallet_signers WHERE wallet_id = '{}'::uuid",
+        wallet_id,
+    ))
+    .unwrap();
+    for key in &verifying_keys {
+        Spi::run(&format!(
+            "INSERT INTO kerai.wallet_signers (wallet_id, public_key, key_fingerprint)
+             VALUES ('{}'::uuid, '{}'::bytea, '{}')
+             ON CONFLICT DO NOTHING",
+            wallet_id,
+            bytes_to_pg_hex(key.as_bytes()),
+            sql_escape(&identity::fingerprint(key)),
+        ))
+        .unwrap();
+    }
+
+    let signer_count = wallet_signer_keys(wallet_id).len();
+    if required_signers < 1 || required_signers as usize > signer_count {
+        error!(
+            "required_signers must be between 1 and the {} signers of the wallet, got {}",
+            signer_count, required_signers
+        );
+    }
+    Spi::run(&format!(
+        "UPDATE kerai.wallets SET required_signers = {}, nonce = {} WHERE id = '{}'::uuid",
+        required_signers, nonce, wallet_id,
+    ))
+    .unwrap();
+
+    pgrx::JsonB(serde_json::json!({
+        "wallet_id": wallet_id.to_string(),
+        "signers": signer_count,
+        "required_signers": required_signers,
+        "nonce": nonce,
+    }))
+}
+
+/// Transfer from a wallet that needs several approvers (see `set_wallet_signers`).
+///
+/// `signatures` is a JSON array of `{public_key, signature}` (hex), each by one
+/// of the wallet's signers over "multisig_transfer:{from}:{to}:{amount}:{nonce}".
+/// At least the wallet's `required_signers` distinct signers must have signed
+/// validly; a signer appearing twice is an error. Nonce and balance are checked
+/// as in `signed_transfer`.
+#[pg_extern]
+fn multisig_transfer(
+    from_wallet_id: pgrx::Uuid,
+    to_wallet_id: pgrx::Uuid,
+    amount: i64,
+    nonce: i64,
+    signatures: pgrx::JsonB,
+    reason: default!(Option<&str>, "NULL"),
+) -> pgrx::JsonB {
+    if amount <= 0 {
+        error!("Transfer amount must be positive");
+    }
+
+    let (current_nonce, required) = wallet_nonce_and_threshold(from_wallet_id);
+    economy::require_spendable(&from_wallet_id.to_string());
+    if nonce != current_nonce + 1 {
+        error!(
+            "Invalid nonce: expected {}, got {}",
+            current_nonce + 1,
+            nonce
+        );
+    }
+
+    let to_exists = Spi::get_one::<bool>(&format!(
+        "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id = '{}'::uuid)",
+        to_wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if !to_exists {
+        error!("Destination wallet not found: {}", to_wallet_id);
+    }
+
+    let message = format!(
+        "multisig_transfer:{}:{}:{}:{}",
+        from_wallet_id, to_wallet_id, amount, nonce
+    );
+    let sig_bytes = verify_wallet_signatures(from_wallet_id, required, &message, &signatures.0);
+
+    let balance = Spi::get_one::<i64>(&format!(
+        "SELECT COALESCE(
+            (SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE to_wallet = '{0}'::uuid)
+            - (SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE from_wallet = '{0}'::uuid),
+            0
+        )::bigint",
+        from_wallet_id,
+    ))
+    .unwrap()
     .unwrap_or(0);
+    if balance < amount {
+        error!(
+            "Insufficient balance: wallet {} has {} nKoi but transfer requires {}",
+            from_wallet_id, balance, amount
+        );
+    }
+
+    let lamport = Spi::get_one::<i64>("SELECT COALESCE(max(timestamp), 0) + 1 FROM kerai.ledger")
+        .unwrap()
+        .unwrap_or(1);
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "INSERT INTO kerai.ledger (from_wallet, to_wallet, amount, reason, signature, timestamp)
+         VALUES ('{}'::uuid, '{}'::uuid, {}, '{}', '{}'::bytea, {})
+         RETURNING jsonb_build_object(
+             'id', id,
+             'from_wallet', from_wallet,
+             'to_wallet', to_wallet,
+             'amount', amount,
+             'reason', reason,
+             'timestamp', timestamp
+         )",
+        from_wallet_id,
+        to_wallet_id,
+        amount,
+        sql_escape(reason.unwrap_or("multisig_transfer")),
+        bytes_to_pg_hex(&sig_bytes),
+        lamport,
+    ))
+    .unwrap()
+    .unwrap();
+
+    Spi::run(&format!(
+        "UPDATE kerai.wallets SET nonce = {} WHERE id = '{}'::uuid",
+        nonce, from_wallet_id,
+    ))
+    .unwrap();
+
+    row
+}
+
+/// A wallet's (nonce, required_signers), locking its row for the transfer.
+fn wallet_nonce_and_threshold(wallet_id: pgrx::Uuid) -> (i64, i64) {
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object('nonce', nonce, 'required_signers', required_signers)
+         FROM kerai.wallets WHERE id = '{}'::uuid FOR UPDATE",
+        wallet_id,
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Source wallet not found: {}", wallet_id));
+    (
+        row.0["nonce"].as_i64().unwrap_or(0),
+        row.0["required_signers"].as_i64().unwrap_or(1),
+    )
+}
+
+/// Hex public keys allowed to sign for a wallet: its own key plus its
+/// `wallet_signers`.
+fn wallet_signer_keys(wallet_id: pgrx::Uuid) -> Vec<String> {
+    let keys = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(DISTINCT k), '[]'::jsonb) FROM (
+            SELECT encode(public_key, 'hex') AS k FROM kerai.wallets WHERE id = '{0}'::uuid
+            UNION
+            SELECT encode(public_key, 'hex') FROM kerai.wallet_signers WHERE wallet_id = '{0}'::uuid
+        ) s",
+        wallet_id,
+    ))
+    .unwrap_or(None)
+    .map(|j| j.0)
+    .unwrap_or_default();
+    keys.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|k| k.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Check `signatures` (`[{public_key, signature}]`, hex) over `message` against
+/// the wallet's signers, requiring `required` distinct valid ones. Errors on
+/// a repeated or unknown signer and with "Insufficient signatures" when short.
+/// Returns the valid signatures concatenated, for the ledger row.
+fn verify_wallet_signatures(
+    wallet_id: pgrx::Uuid,
+    required: i64,
+    message: &str,
+    signatures: &serde_json::Value,
+) -> Vec<u8> {
+    let entries = signatures
+        .as_array()
+        .unwrap_or_else(|| error!("signatures must be a JSON array of {{public_key, signature}}"));
+    let signer_keys = wallet_signer_keys(wallet_id);
+
+    let mut seen: Vec<String> = Vec::with_capacity(entries.len());
+    let mut valid: Vec<u8> = Vec::new();
+    let mut valid_count: i64 = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        let pk_hex = entry["public_key"]
+            .as_str()
+            .unwrap_or_else(|| error!("Signature {} is missing 'public_key'", i))
+            .to_lowercase();
+        let sig_hex = entry["signature"]
+            .as_str()
+            .unwrap_or_else(|| error!("Signature {} is missing 'signature'", i));
+        if seen.contains(&pk_hex) {
+            error!("Duplicate signature from signer {}", pk_hex);
+        }
+        if !signer_keys.contains(&pk_hex) {
+            error!("{} is not a signer of wallet {}", pk_hex, wallet_id);
+        }
+        seen.push(pk_hex.clone());
+
+        let verifying_key =
+            identity::parse_public_key_hex(&pk_hex).unwrap_or_else(|e| error!("{}", e));
+        let sig_bytes = match hex::decode(sig_hex) {
+            Ok(b) => b,
+            Err(e) => error!("Invalid hex in signature {}: {}", i, e),
+        };
+        if identity::verify_signature(&verifying_key, message.as_bytes(), &sig_bytes) {
+            valid_count += 1;
+            valid.extend_from_slice(&sig_bytes);
+        }
+    }
+
+    if valid_count < required {
+        error!(
+            "Insufficient signatures: {} valid of {} required",
+            valid_count, required
+        );
+    }
+    valid
+}
+
+/// Refuse single-signature spending from a wallet that needs several signers.
+/// Every debit path other than `multisig_transfer` calls this on its source.
+pub(crate) fn require_single_signer(wallet_id: &str) {
+    let required = Spi::get_one::<i32>(&format!(
+        "SELECT required_signers FROM kerai.wallets WHERE id = '{}'::uuid",
+        sql_escape(wallet_id),
+    ))
+    .unwrap_or(None)
+    .unwrap_or(1);
+    if required > 1 {
+        error!(
+            "Wallet {} requires {} signatures; use multisig_transfer",
+            wallet_id, required
+        );
+    }
+}
+
+/// Current nonce of a wallet, erroring if it does not exist.
+fn wallet_nonce(wallet_id: pgrx::Uuid) -> i64 {
+    Spi::get_one::<i64>(&format!(
+        "SELECT nonce FROM kerai.wallets WHERE id = '{}'::uuid",
+        wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| error!("Wallet not found: {}", wallet_id))
+}
+
+/// Nonce diagnostics for a wallet: the last accepted nonce, the next one
+/// `signed_transfer` will accept, and rejected attempts since the last
+/// accepted transfer or reset. `stuck` means a client has been submitting
+/// nonces the server will not take.
+#[pg_extern]
+fn wallet_nonce_status(wallet_id: pgrx::Uuid) -> pgrx::JsonB {
+    let nonce = wallet_nonce(wallet_id);
+
+    let status = Spi::get_one::<pgrx::JsonB>(&format!(
+        "WITH last_ok AS (
+            SELECT GREATEST(
+                (SELECT max(created_at) FROM kerai.ledger
+                 WHERE from_wallet = '{0}'::uuid AND signature IS NOT NULL),
+                (SELECT max(created_at) FROM kerai.nonce_events
+                 WHERE wallet_id = '{0}'::uuid AND event = 'reset')
+            ) AS at
+        ),
+        rejected AS (
+            SELECT r.* FROM kerai.nonce_events r, last_ok
+            WHERE r.wallet_id = '{0}'::uuid AND r.event = 'rejected'
+              AND (last_ok.at IS NULL OR r.created_at > last_ok.at)
+        )
+        SELECT jsonb_build_object(
+            'last_accepted_at', (SELECT at FROM last_ok),
+            'rejected_attempts', (SELECT count(*) FROM rejected),
+            'rejected_nonces', COALESCE(
+                (SELECT jsonb_agg(DISTINCT nonce) FROM rejected), '[]'::jsonb),
+            'last_rejection', (
+                SELECT jsonb_build_object(
+                    'nonce', nonce, 'expected', expected, 'at', created_at)
+                FROM rejected ORDER BY created_at DESC LIMIT 1),
+            'resets', (SELECT count(*) FROM kerai.nonce_events
+                       WHERE wallet_id = '{0}'::uuid AND event = 'reset')
+        )",
+        wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(serde_json::json!({})));
+
+    let mut obj = status.0;
+    let rejected = obj["rejected_attempts"].as_i64().unwrap_or(0);
+    obj["wallet_id"] = serde_json::json!(wallet_id.to_string());
+    obj["nonce"] = serde_json::json!(nonce);
+    obj["next_nonce"] = serde_json::json!(nonce + 1);
+    obj["stuck"] = serde_json::json!(rejected > 0);
+    pgrx::JsonB(obj)
+}
+
+/// Record a nonce that `signed_transfer` rejected. The rejection itself rolls
+/// back with the failed transfer, so clients report it here afterwards.
+/// The report must be signed by one of the wallet's signers over
+/// "nonce_rejection:{wallet_id}:{expected}:{attempted_nonce}", so only the
+/// wallet's holder can mark it stuck. Ignored (returns false) when the nonce
+/// would in fact be accepted.
+#[pg_extern]
+fn record_nonce_rejection(
+    wallet_id: pgrx::Uuid,
+    attempted_nonce: i64,
+    signature_hex: &str,
+) -> bool {
+    let expected = wallet_nonce(wallet_id) + 1;
+    let sig_bytes = match hex::decode(signature_hex) {
+        Ok(b) => b,
+        Err(e) => error!("Invalid hex in signature: {}", e),
+    };
+    let message = format!(
+        "nonce_rejection:{}:{}:{}",
+        wallet_id, expected, attempted_nonce
+    );
+    let signed = wallet_signer_keys(wallet_id).iter().any(|pk_hex| {
+        identity::parse_public_key_hex(pk_hex)
+            .map(|key| identity::verify_signature(&key, message.as_bytes(), &sig_bytes))
+            .unwrap_or(false)
+    });
+    if !signed {
+        error!("Invalid signature for nonce rejection report");
+    }
+
+    if attempted_nonce == expected {
+        return false;
+    }
+    Spi::run(&format!(
+        "INSERT INTO kerai.nonce_events (wallet_id, event, expected, nonce)
+         VALUES ('{}'::uuid, 'rejected', {}, {})",
+        wallet_id, expected, attempted_nonce,
+    ))
+    .unwrap();
+    true
+}
+
+/// Admin recovery: move a wallet's nonce forward so the next accepted transfer
+/// uses `new_nonce + 1`. Signed by the self instance key over
+/// "reset_nonce:{wallet_id}:{current_nonce}:{new_nonce}"; including the current
+/// nonce makes each signature single-use. Nonces only move forward, since
+/// lowering one would let previously signed transfers be replayed.
+#[pg_extern]
+fn reset_nonce(wallet_id: pgrx::Uuid, new_nonce: i64, signature_hex: &str) -> pgrx::JsonB {
+    let current = wallet_nonce(wallet_id);
+    if new_nonce <= current {
+        error!(
+            "New nonce must be greater than the current nonce {}, got {}",
+            current, new_nonce
+        );
+    }
+
+    let pk_hex = Spi::get_one::<String>(
+        "SELECT encode(public_key, 'hex') FROM kerai.instances WHERE is_self = true",
+    )
+    .unwrap()
+    .unwrap_or_else(|| error!("Self instance not found"));
+    let pk_array: [u8; 32] = hex::decode(&pk_hex)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .unwrap_or_else(|| error!("Invalid self instance public key"));
+    let verifying_key = match ed25519_dalek::VerifyingKey::from_bytes(&pk_array) {
+        Ok(k) => k,
+        Err(e) => error!("Invalid self instance public key: {}", e),
+    };
+    let sig_bytes = match hex::decode(signature_hex) {
+        Ok(b) => b,
+        Err(e) => error!("Invalid hex in signature: {}", e),
+    };
+
+    let message = format!("reset_nonce:{}:{}:{}", wallet_id, current, new_nonce);
+    if !identity::verify_signature(&verifying_key, message.as_bytes(), &sig_bytes) {
+        error!("Invalid admin signature for nonce reset");
+    }
+
+    Spi::run(&format!(
+        "UPDATE kerai.wallets SET nonce = {} WHERE id = '{}'::uuid",
+        new_nonce, wallet_id,
+    ))
+    .unwrap();
+    Spi::run(&format!(
+        "INSERT INTO kerai.nonce_events (wallet_id, event, expected, nonce)
+         VALUES ('{}'::uuid, 'reset', {}, {})",
+        wallet_id,
+        current + 1,
+        new_nonce,
+    ))
+    .unwrap();
+
+    pgrx::JsonB(serde_json::json!({
+        "wallet_id": wallet_id.to_string(),
+        "previous_nonce": current,
+        "nonce": new_nonce,
+        "next_nonce": new_nonce + 1,
+    }))
+}
+
+/// Sum of all mints (ledger WHERE from_wallet IS NULL).
+fn minted_total() -> i64 {
+    Spi::get_one::<i64>(
+        "SELECT COALESCE(SUM(amount), 0)::bigint FROM kerai.ledger WHERE from_wallet IS NULL",
+    )
+    .unwrap()
+    .unwrap_or(0)
+}
+
+/// Net balance of burn wallets (see `burn_koi`): what was sent to them less
+/// anything that ever left them.
+fn burned_total() -> i64 {
+    Spi::get_one::<i64>(
+        "SELECT (
+             COALESCE((SELECT SUM(l.amount) FROM kerai.ledger l
+                       JOIN kerai.wallets w ON w.id = l.to_wallet
+                       WHERE w.wallet_type = 'burn'), 0)
+             - COALESCE((SELECT SUM(l.amount) FROM kerai.ledger l
+                         JOIN kerai.wallets w ON w.id = l.from_wallet
+                         WHERE w.wallet_type = 'burn'), 0)
+         )::bigint",
+    )
+    .unwrap()
+    .unwrap_or(0)
+}
+
+/// Total supply: everything minted less everything burned.
+#[pg_extern]
+fn total_supply() -> pgrx::JsonB {
+    let total_minted = minted_total();
+    let total_burned = burned_total();
 
     let total_transactions = Spi::get_one::<i64>(
         "SELECT count(*)::bigint FROM kerai.ledger",
@@ -247,8 +1060,9 @@ fn total_supply() -> pgrx::JsonB {
     .unwrap_or(0);
 
     pgrx::JsonB(serde_json::json!({
-        "total_supply": total_minted,
+        "total_supply": total_minted - total_burned,
         "total_minted": total_minted,
+        "total_burned": total_burned,
         "total_transactions": total_transactions,
     }))
 }
@@ -278,11 +1092,7 @@ fn wallet_share(wallet_id: pgrx::Uuid) -> pgrx::JsonB {
     .unwrap()
     .unwrap_or(0);
 
-    let total = Spi::get_one::<i64>(
-        "SELECT COALESCE(SUM(amount), 0)::bigint FROM kerai.ledger WHERE from_wallet IS NULL",
-    )
-    .unwrap()
-    .unwrap_or(0);
+    let total = minted_total() - burned_total();
 
     let share = if total > 0 {
         format!("{:.18}", balance as f64 / total as f64)
@@ -301,11 +1111,7 @@ fn wallet_share(wallet_id: pgrx::Uuid) -> pgrx::JsonB {
 /// Rich supply overview: total_supply, wallet_count, top holders, recent mints.
 #[pg_extern]
 fn supply_info() -> pgrx::JsonB {
-    let total = Spi::get_one::<i64>(
-        "SELECT COALESCE(SUM(amount), 0)::bigint FROM kerai.ledger WHERE from_wallet IS NULL",
-    )
-    .unwrap()
-    .unwrap_or(0);
+    let total = minted_total() - burned_total();
 
     let wallet_count = Spi::get_one::<i64>(
         "SELECT count(*)::bigint FROM kerai.wallets",
@@ -322,6 +1128,7 @@ fn supply_info() -> pgrx::JsonB {
                     0
                 ) AS balance
             FROM kerai.wallets w
+            WHERE w.wallet_type <> 'burn'
             ORDER BY balance DESC
             LIMIT 10
         ) t",
@@ -349,14 +1156,175 @@ fn supply_info() -> pgrx::JsonB {
     }))
 }
 
-/// Mint reward for work. Looks up reward_schedule, mints to self instance wallet, logs to reward_log.
-/// Returns the mint result or null JSON if work_type is disabled/not found.
+/// Most buckets `supply_history` will return.
+const SUPPLY_HISTORY_MAX_BUCKETS: i64 = 1000;
+
+/// Supply and holder concentration over time. The ledger is cut into buckets of
+/// `bucket` (any interval text, e.g. '1 hour') from the first entry onward, and
+/// each bucket reports balances as of its end: total supply, holders, the Gini
+/// coefficient, the top 10% of holders' share, and the self instance wallet's share.
+/// Returns `{buckets, truncated}`; `buckets` is empty when the ledger is empty.
+/// Only the first `SUPPLY_HISTORY_MAX_BUCKETS` buckets are reported, with
+/// `truncated` set when the ledger runs past them.
 #[pg_extern]
-fn mint_reward(work_type: &str, details: Option<pgrx::JsonB>) -> pgrx::JsonB {
+fn supply_history(bucket: default!(&str, "'1 day'")) -> pgrx::JsonB {
+    let valid = Spi::get_one::<bool>(&format!(
+        "SELECT '{}'::interval > interval '0'",
+        sql_escape(bucket),
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if !valid {
+        error!("bucket must be a positive interval, got '{}'", bucket);
+    }
+    let b = format!("'{}'::interval", sql_escape(bucket));
+
+    let truncated = Spi::get_one::<bool>(&format!(
+        "SELECT count(*) > {max} FROM (
+            SELECT 1 FROM (
+                SELECT min(created_at) AS lo, max(created_at) AS hi FROM kerai.ledger
+            ) bounds, generate_series(lo, hi, {b})
+            WHERE lo IS NOT NULL
+            LIMIT {max} + 1
+        ) s",
+        max = SUPPLY_HISTORY_MAX_BUCKETS,
+    ))
+    .unwrap()
+    .unwrap_or(false);
+
+    let rows = Spi::get_one::<pgrx::JsonB>(&format!(
+        "WITH bounds AS (
+            SELECT min(created_at) AS lo, max(created_at) AS hi FROM kerai.ledger
+        ),
+        buckets AS (
+            SELECT gs AS bucket_start, gs + {b} AS bucket_end
+            FROM bounds, generate_series(lo, hi, {b}) gs
+            WHERE lo IS NOT NULL
+            LIMIT {max}
+        ),
+        moves AS (
+            SELECT to_wallet AS wallet, amount, created_at FROM kerai.ledger
+            UNION ALL
+            SELECT from_wallet, -amount, created_at FROM kerai.ledger WHERE from_wallet IS NOT NULL
+        ),
+        balances AS (
+            SELECT b.bucket_start, m.wallet, SUM(m.amount)::bigint AS balance
+            FROM buckets b
+            JOIN moves m ON m.created_at < b.bucket_end
+            WHERE m.wallet NOT IN (SELECT id FROM kerai.wallets WHERE wallet_type = 'burn')
+            GROUP BY b.bucket_start, m.wallet
+        )
+        SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'bucket_start', b.bucket_start,
+            'bucket_end', b.bucket_end,
+            'minted', (
+                SELECT COALESCE(SUM(l.amount), 0)::bigint FROM kerai.ledger l
+                WHERE l.from_wallet IS NULL
+                AND l.created_at >= b.bucket_start AND l.created_at < b.bucket_end
+            ),
+            'self_balance', COALESCE((
+                SELECT bal.balance FROM balances bal
+                JOIN kerai.wallets w ON w.id = bal.wallet
+                JOIN kerai.instances i ON i.id = w.instance_id
+                WHERE bal.bucket_start = b.bucket_start
+                AND i.is_self = true AND w.wallet_type = 'instance'
+            ), 0),
+            'balances', COALESCE((
+                SELECT jsonb_agg(bal.balance) FROM balances bal
+                WHERE bal.bucket_start = b.bucket_start AND bal.balance > 0
+            ), '[]'::jsonb)
+        ) ORDER BY b.bucket_start), '[]'::jsonb)
+        FROM buckets b",
+        max = SUPPLY_HISTORY_MAX_BUCKETS,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
+
+    let history: Vec<serde_json::Value> = rows
+        .0
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|row| {
+            let mut balances: Vec<i64> = row["balances"]
+                .as_array()
+                .map(|a| a.iter().filter_map(|v| v.as_i64()).collect())
+                .unwrap_or_default();
+            balances.sort_unstable();
+
+            let total: i64 = balances.iter().sum();
+            let top_n = balances.len().div_ceil(10);
+            let top: i64 = balances.iter().rev().take(top_n).sum();
+            let self_balance = row["self_balance"].as_i64().unwrap_or(0);
+            let share = |part: i64| if total > 0 { part as f64 / total as f64 } else { 0.0 };
+
+            serde_json::json!({
+                "bucket_start": row["bucket_start"],
+                "bucket_end": row["bucket_end"],
+                "minted": row["minted"],
+                "total_supply": total,
+                "holders": balances.len(),
+                "gini": gini(&balances),
+                "top10_share": share(top),
+                "self_share": share(self_balance),
+            })
+        })
+        .collect();
+
+    pgrx::JsonB(serde_json::json!({
+        "buckets": history,
+        "truncated": truncated,
+    }))
+}
+
+/// Gini coefficient of ascending-sorted balances: 0 is perfectly even,
+/// approaching 1 when one holder has everything.
+fn gini(sorted: &[i64]) -> f64 {
+    let n = sorted.len() as f64;
+    let total: f64 = sorted.iter().map(|&b| b as f64).sum();
+    if sorted.is_empty() || total <= 0.0 {
+        return 0.0;
+    }
+    let weighted: f64 = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| (2.0 * (i as f64 + 1.0) - n - 1.0) * b as f64)
+        .sum();
+    weighted / (n * total)
+}
+
+/// Mint reward for work. Looks up reward_schedule, mints to the recipient, logs to reward_log.
+/// The scheduled amount is scaled by the most specific scope multiplier covering the
+/// work's target (`details.path`, `details.node_id`, or `details.file`); no match means 1.0.
+/// The recipient is `recipient_wallet` if given, else the wallet in the
+/// `currency`/`reward_recipient` preference, else the self instance wallet.
+/// Returns the mint result or null JSON if work_type is disabled/not found, and
+/// `{"status": "rate_limited"}` without minting once this instance has reached
+/// the work type's `rate_limit` mints within its `rate_window`.
+#[pg_extern]
+fn mint_reward(
+    work_type: &str,
+    details: Option<pgrx::JsonB>,
+    recipient_wallet: default!(Option<pgrx::Uuid>, "NULL"),
+) -> pgrx::JsonB {
     // Look up reward schedule
     let schedule = Spi::get_one::<pgrx::JsonB>(&format!(
-        "SELECT jsonb_build_object('reward', reward, 'enabled', enabled)
-         FROM kerai.reward_schedule WHERE work_type = '{}'",
+        "SELECT jsonb_build_object(
+             'reward', reward,
+             'enabled', enabled,
+             'halving_interval', halving_interval,
+             'minted', (SELECT count(*) FROM kerai.reward_log WHERE work_type = s.work_type),
+             'rate_limit', rate_limit,
+             'rate_window', rate_window::text,
+             'recent', (
+                 SELECT count(*) FROM kerai.reward_log l
+                 WHERE l.work_type = s.work_type
+                 AND l.instance_id = (SELECT id FROM kerai.instances WHERE is_self = true)
+                 AND l.created_at > now() - s.rate_window
+             )
+         )
+         FROM kerai.reward_schedule s WHERE work_type = '{}'",
         sql_escape(work_type),
     ))
     .unwrap_or(None);
@@ -371,18 +1339,34 @@ fn mint_reward(work_type: &str, details: Option<pgrx::JsonB>) -> pgrx::JsonB {
         return pgrx::JsonB(serde_json::json!(null));
     }
 
-    let reward = schedule_info.0["reward"]
+    if let Some(limit) = schedule_info.0["rate_limit"].as_i64() {
+        let recent = schedule_info.0["recent"].as_i64().unwrap_or(0);
+        if recent >= limit {
+            return pgrx::JsonB(serde_json::json!({
+                "status": "rate_limited",
+                "work_type": work_type,
+                "rate_limit": limit,
+                "rate_window": schedule_info.0["rate_window"],
+            }));
+        }
+    }
+
+    let scheduled = schedule_info.0["reward"]
         .as_i64()
         .unwrap_or_else(|| error!("Invalid reward value in schedule"));
+    let halving_interval = schedule_info.0["halving_interval"].as_i64();
+    let minted = schedule_info.0["minted"].as_i64().unwrap_or(0);
+    let halvings = halvings(minted, halving_interval);
+    let base_reward = halved_reward(scheduled, halvings);
 
-    // Get self instance wallet
-    let wallet_id = Spi::get_one::<String>(
-        "SELECT w.id::text FROM kerai.wallets w
-         JOIN kerai.instances i ON w.instance_id = i.id
-         WHERE i.is_self = true AND w.wallet_type = 'instance'",
-    )
-    .unwrap()
-    .unwrap_or_else(|| error!("Self instance wallet not found"));
+    let target = details.as_ref().and_then(|d| reward_target_path(&d.0));
+    let (scope, multiplier) = target
+        .as_deref()
+        .and_then(scope_multiplier)
+        .map_or((None, 1.0), |(s, m)| (Some(s), m));
+    let reward = ((base_reward as f64 * multiplier).round() as i64).max(1);
+
+    let wallet_id = reward_recipient(recipient_wallet);
 
     // Get lamport timestamp
     let lamport = Spi::get_one::<i64>(
@@ -404,15 +1388,18 @@ fn mint_reward(work_type: &str, details: Option<pgrx::JsonB>) -> pgrx::JsonB {
     .unwrap()
     .unwrap();
 
-    // Log to reward_log
-    let details_str = match &details {
-        Some(d) => sql_escape(&d.0.to_string()),
-        None => "{}".to_string(),
-    };
+    // Log to reward_log, noting the multiplier when a scope matched
+    let mut logged = details.map_or_else(|| serde_json::json!({}), |d| d.0);
+    if let (Some(s), serde_json::Value::Object(m)) = (&scope, &mut logged) {
+        m.insert("reward_scope".into(), serde_json::json!(s));
+        m.insert("multiplier".into(), serde_json::json!(multiplier));
+    }
+    let details_str = sql_escape(&logged.to_string());
 
     Spi::run(&format!(
-        "INSERT INTO kerai.reward_log (work_type, reward, wallet_id, details)
-         VALUES ('{}', {}, '{}'::uuid, '{}'::jsonb)",
+        "INSERT INTO kerai.reward_log (work_type, reward, wallet_id, details, instance_id)
+         VALUES ('{}', {}, '{}'::uuid, '{}'::jsonb,
+                 (SELECT id FROM kerai.instances WHERE is_self = true))",
         sql_escape(work_type),
         reward,
         sql_escape(&wallet_id),
@@ -421,13 +1408,202 @@ fn mint_reward(work_type: &str, details: Option<pgrx::JsonB>) -> pgrx::JsonB {
     .unwrap();
 
     pgrx::JsonB(serde_json::json!({
+        "status": "minted",
         "ledger_id": ledger_id,
         "work_type": work_type,
         "reward": reward,
+        "base_reward": base_reward,
+        "halvings": halvings,
+        "multiplier": multiplier,
+        "scope": scope,
         "wallet_id": wallet_id,
     }))
 }
 
+/// Completed halvings after `minted` rewards of a work type.
+fn halvings(minted: i64, halving_interval: Option<i64>) -> u32 {
+    match halving_interval {
+        Some(interval) if interval > 0 => (minted / interval).min(u32::MAX as i64) as u32,
+        _ => 0,
+    }
+}
+
+/// `reward` halved `halvings` times, floored at 1 Koi (or at `reward` itself
+/// when it is already below 1 Koi).
+fn halved_reward(reward: i64, halvings: u32) -> i64 {
+    let halved = reward.checked_shr(halvings).unwrap_or(0);
+    halved.max(reward.min(NKOI_PER_KOI))
+}
+
+/// Wallet credited by `mint_reward`: the explicit recipient, the configured
+/// default recipient, or the self instance wallet. A configured recipient
+/// that no longer resolves falls back to the self instance wallet with a
+/// WARNING, so a stale preference doesn't make every reward fail.
+fn reward_recipient(explicit: Option<pgrx::Uuid>) -> String {
+    if let Some(wallet) = explicit {
+        return Spi::get_one::<String>(&format!(
+            "SELECT id::text FROM kerai.wallets WHERE id = '{}'::uuid",
+            wallet,
+        ))
+        .unwrap_or(None)
+        .unwrap_or_else(|| error!("Reward recipient wallet not found: {}", wallet));
+    }
+
+    let configured =
+        Spi::get_one::<String>("SELECT kerai.get_preference('currency', 'reward_recipient')")
+            .unwrap_or(None)
+            .filter(|w| !w.trim().is_empty());
+    if let Some(wallet) = configured {
+        match recipient_wallet_type(&wallet) {
+            Some(t) if !economy::SYSTEM_WALLET_TYPES.contains(&t.as_str()) => {
+                return wallet.trim().to_lowercase();
+            }
+            _ => warning!(
+                "Configured reward recipient {} is not a wallet that can be paid; \
+                 crediting the self instance wallet",
+                wallet
+            ),
+        }
+    }
+
+    Spi::get_one::<String>(
+        "SELECT w.id::text FROM kerai.wallets w
+         JOIN kerai.instances i ON w.instance_id = i.id
+         WHERE i.is_self = true AND w.wallet_type = 'instance'",
+    )
+    .unwrap()
+    .unwrap_or_else(|| error!("Self instance wallet not found"))
+}
+
+/// Type of the wallet whose id is `value`, or None if there is no such
+/// wallet (including when `value` isn't a UUID).
+fn recipient_wallet_type(value: &str) -> Option<String> {
+    Spi::get_one::<String>(&format!(
+        "SELECT wallet_type FROM kerai.wallets WHERE id::text = lower('{}')",
+        sql_escape(value.trim()),
+    ))
+    .unwrap_or(None)
+}
+
+/// Check a `currency`/`reward_recipient` preference value before it is
+/// stored: it must name an existing wallet that isn't a burn or escrow wallet.
+/// An empty value clears the default.
+pub(crate) fn validate_reward_recipient(value: &str) {
+    if value.trim().is_empty() {
+        return;
+    }
+    match recipient_wallet_type(value) {
+        None => error!("Reward recipient wallet not found: {}", value),
+        Some(t) if economy::SYSTEM_WALLET_TYPES.contains(&t.as_str()) => error!(
+            "Wallet {} is the system {} wallet and can't receive rewards",
+            value, t
+        ),
+        Some(_) => {}
+    }
+}
+
+/// Wallet of the agent named by the session setting `kerai.agent`, if that
+/// agent exists and has a linked wallet. Lets the agent driving a parse
+/// (`SET kerai.agent = 'ci-bot'`) collect the reward for it.
+pub(crate) fn acting_agent_wallet() -> Option<String> {
+    Spi::get_one::<String>(
+        "SELECT wallet_id::text FROM kerai.agents
+         WHERE name = current_setting('kerai.agent', true) AND wallet_id IS NOT NULL",
+    )
+    .unwrap_or(None)
+}
+
+/// Resolve the ltree path a reward's work applies to from its details:
+/// an explicit `path`, a `node_id`, or a parsed `file` name. A `path` that
+/// isn't a valid ltree resolves to nothing, so no scope multiplier applies.
+fn reward_target_path(details: &serde_json::Value) -> Option<String> {
+    if let Some(path) = details.get("path").and_then(|v| v.as_str()) {
+        return is_ltree_path(path).then(|| path.to_string());
+    }
+    let sql = if let Some(node_id) = details.get("node_id").and_then(|v| v.as_str()) {
+        format!(
+            "SELECT path::text FROM kerai.nodes WHERE id::text = '{}'",
+            sql_escape(node_id),
+        )
+    } else if let Some(file) = details.get("file").and_then(|v| v.as_str()) {
+        format!(
+            "SELECT path::text FROM kerai.nodes
+             WHERE kind = 'file' AND content = '{}' AND path IS NOT NULL
+             ORDER BY created_at DESC LIMIT 1",
+            sql_escape(file),
+        )
+    } else {
+        return None;
+    };
+    Spi::get_one::<String>(&sql).unwrap_or(None)
+}
+
+/// Most specific (deepest) scope multiplier covering `path`, if any.
+fn scope_multiplier(path: &str) -> Option<(String, f64)> {
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object('scope', scope::text, 'multiplier', multiplier)
+         FROM kerai.reward_scope_multipliers
+         WHERE '{}'::ltree <@ scope
+         ORDER BY nlevel(scope) DESC
+         LIMIT 1",
+        sql_escape(path),
+    ))
+    .unwrap_or(None)?;
+    let scope = row.0["scope"].as_str()?.to_string();
+    let multiplier = row.0["multiplier"].as_f64()?;
+    Some((scope, multiplier))
+}
+
+/// Set the reward multiplier for work under a scope (e.g. 2.0 for `pkg.core`).
+#[pg_extern]
+fn set_scope_reward_multiplier(scope: &str, multiplier: f64) -> pgrx::JsonB {
+    if !multiplier.is_finite() || multiplier <= 0.0 {
+        error!("Multiplier must be a positive number");
+    }
+
+    Spi::get_one::<pgrx::JsonB>(&format!(
+        "INSERT INTO kerai.reward_scope_multipliers (scope, multiplier)
+         VALUES ('{}'::ltree, {})
+         ON CONFLICT (scope) DO UPDATE SET multiplier = EXCLUDED.multiplier, updated_at = now()
+         RETURNING jsonb_build_object(
+             'scope', scope::text,
+             'multiplier', multiplier,
+             'updated_at', updated_at
+         )",
+        sql_escape(scope),
+        multiplier,
+    ))
+    .unwrap()
+    .unwrap()
+}
+
+/// Remove a scope multiplier. Returns whether one existed.
+#[pg_extern]
+fn remove_scope_reward_multiplier(scope: &str) -> bool {
+    Spi::get_one::<bool>(&format!(
+        "WITH d AS (DELETE FROM kerai.reward_scope_multipliers WHERE scope = '{}'::ltree RETURNING 1)
+         SELECT count(*) > 0 FROM d",
+        sql_escape(scope),
+    ))
+    .unwrap()
+    .unwrap_or(false)
+}
+
+/// List scope multipliers, most specific first.
+#[pg_extern]
+fn get_scope_reward_multipliers() -> pgrx::JsonB {
+    Spi::get_one::<pgrx::JsonB>(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'scope', scope::text,
+            'multiplier', multiplier,
+            'updated_at', updated_at
+        ) ORDER BY nlevel(scope) DESC, scope), '[]'::jsonb)
+        FROM kerai.reward_scope_multipliers",
+    )
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])))
+}
+
 /// Periodic evaluation: check for unrewarded work and mint bonus rewards.
 #[pg_extern]
 fn evaluate_mining() -> pgrx::JsonB {
@@ -548,7 +1724,104 @@ fn evaluate_mining() -> pgrx::JsonB {
     }))
 }
 
-/// List all reward schedule entries.
+/// Set how often the background worker runs `evaluate_mining`, in seconds.
+/// 0 disables scheduled mining.
+#[pg_extern]
+fn set_mining_interval(seconds: i32) -> pgrx::JsonB {
+    if seconds < 0 {
+        error!("Mining interval must be non-negative, got {}", seconds);
+    }
+    Spi::run(&format!(
+        "UPDATE kerai.mining_schedule SET interval_seconds = {}",
+        seconds,
+    ))
+    .unwrap();
+    mining_status()
+}
+
+/// Run `evaluate_mining` if a scheduled run is due. Claiming the run and
+/// stamping `last_run_at` is one UPDATE, so concurrent callers can't both
+/// mint within the same interval. Called by the mining background worker.
+#[pg_extern]
+fn mining_tick() -> pgrx::JsonB {
+    let due = Spi::get_one::<bool>(
+        "UPDATE kerai.mining_schedule SET last_run_at = now()
+         WHERE interval_seconds > 0
+           AND (last_run_at IS NULL
+                OR last_run_at <= now() - make_interval(secs => interval_seconds))
+         RETURNING true",
+    )
+    .unwrap_or(None)
+    .unwrap_or(false);
+
+    if !due {
+        return pgrx::JsonB(serde_json::json!({"ran": false}));
+    }
+
+    let result = evaluate_mining();
+    Spi::run(&format!(
+        "UPDATE kerai.mining_schedule SET last_result = '{}'::jsonb",
+        sql_escape(&result.0.to_string()),
+    ))
+    .unwrap();
+
+    pgrx::JsonB(serde_json::json!({"ran": true, "result": result.0}))
+}
+
+/// Scheduled mining state: interval, last and next run, the last result,
+/// and the work `evaluate_mining` would reward if it ran now.
+#[pg_extern]
+fn mining_status() -> pgrx::JsonB {
+    let schedule = Spi::get_one::<pgrx::JsonB>(
+        "SELECT jsonb_build_object(
+            'interval_seconds', interval_seconds,
+            'enabled', interval_seconds > 0,
+            'last_run_at', last_run_at,
+            'next_run_at', CASE WHEN interval_seconds > 0 THEN
+                COALESCE(last_run_at + make_interval(secs => interval_seconds), now())
+            END,
+            'last_result', last_result
+        ) FROM kerai.mining_schedule",
+    )
+    .unwrap()
+    .unwrap_or_else(|| error!("Mining schedule not initialized"));
+
+    let pending = Spi::get_one::<pgrx::JsonB>(
+        "SELECT jsonb_build_object(
+            'unrewarded_nodes', CASE
+                WHEN EXISTS (SELECT 1 FROM kerai.reward_log
+                             WHERE work_type IN ('parse_file', 'parse_crate', 'parse_markdown'))
+                THEN 0
+                ELSE (SELECT count(*) FROM kerai.nodes)
+            END,
+            'unrewarded_versions', GREATEST(
+                (SELECT count(*) FROM kerai.versions)
+                - (SELECT count(*) FROM kerai.reward_log WHERE work_type = 'create_version'),
+                0
+            ),
+            'version_rate', (SELECT reward FROM kerai.reward_schedule
+                             WHERE work_type = 'create_version' AND enabled = true)
+        )",
+    )
+    .unwrap()
+    .unwrap();
+
+    let nodes = pending.0["unrewarded_nodes"].as_i64().unwrap_or(0);
+    let versions = pending.0["unrewarded_versions"].as_i64().unwrap_or(0);
+    let rate = pending.0["version_rate"].as_i64().unwrap_or(0);
+    let mintable = std::cmp::min(nodes, 100) * NKOI_PER_KOI + versions * rate;
+
+    let mut status = schedule.0;
+    status["pending"] = serde_json::json!({
+        "unrewarded_nodes": nodes,
+        "unrewarded_versions": versions,
+        "mintable": mintable,
+    });
+    pgrx::JsonB(status)
+}
+
+/// List all reward schedule entries. `effective_reward` is what the next
+/// mint pays after halving (before any scope multiplier).
 #[pg_extern]
 fn get_reward_schedule() -> pgrx::JsonB {
     let json = Spi::get_one::<pgrx::JsonB>(
@@ -558,39 +1831,118 @@ fn get_reward_schedule() -> pgrx::JsonB {
                 'work_type', work_type,
                 'reward', reward,
                 'enabled', enabled,
+                'halving_interval', halving_interval,
+                'minted', (SELECT count(*) FROM kerai.reward_log l WHERE l.work_type = s.work_type),
+                'rate_limit', rate_limit,
+                'rate_window', rate_window::text,
                 'updated_at', updated_at
             ) ORDER BY work_type),
             '[]'::jsonb
-        ) FROM kerai.reward_schedule",
+        ) FROM kerai.reward_schedule s",
     )
     .unwrap()
     .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
-    json
+
+    let mut entries = json.0;
+    for entry in entries.as_array_mut().into_iter().flatten() {
+        let reward = entry["reward"].as_i64().unwrap_or(0);
+        let minted = entry["minted"].as_i64().unwrap_or(0);
+        let halvings = halvings(minted, entry["halving_interval"].as_i64());
+        entry["halvings"] = serde_json::json!(halvings);
+        entry["effective_reward"] = serde_json::json!(halved_reward(reward, halvings));
+    }
+    pgrx::JsonB(entries)
 }
 
-/// Create or update a reward schedule entry.
+/// Limit `work_type` to `max_mints` rewards per rolling `window` (interval text)
+/// on this instance; NULL or 0 removes the limit. Further mints inside the
+/// window return `{"status": "rate_limited"}` from `mint_reward`.
 #[pg_extern]
-fn set_reward(work_type: &str, reward: i64, enabled: Option<bool>) -> pgrx::JsonB {
+fn set_reward_rate_limit(
+    work_type: &str,
+    max_mints: Option<i32>,
+    window: default!(&str, "'1 hour'"),
+) -> pgrx::JsonB {
+    let (limit_sql, window_sql) = match max_mints {
+        Some(n) if n < 0 => error!("max_mints must not be negative"),
+        Some(n) if n > 0 => {
+            let valid = Spi::get_one::<bool>(&format!(
+                "SELECT '{}'::interval > interval '0'",
+                sql_escape(window),
+            ))
+            .unwrap()
+            .unwrap_or(false);
+            if !valid {
+                error!("window must be a positive interval, got '{}'", window);
+            }
+            (n.to_string(), format!("'{}'::interval", sql_escape(window)))
+        }
+        _ => ("NULL".to_string(), "NULL".to_string()),
+    };
+
+    Spi::get_one::<pgrx::JsonB>(&format!(
+        "UPDATE kerai.reward_schedule
+         SET rate_limit = {}, rate_window = {}, updated_at = now()
+         WHERE work_type = '{}'
+         RETURNING jsonb_build_object(
+             'work_type', work_type,
+             'rate_limit', rate_limit,
+             'rate_window', rate_window::text
+         )",
+        limit_sql,
+        window_sql,
+        sql_escape(work_type),
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Unknown work type: {}", work_type))
+}
+
+/// Create or update a reward schedule entry. `halving_interval` sets how many
+/// mints pass between halvings; 0 turns halving off and NULL keeps the current
+/// setting.
+#[pg_extern]
+fn set_reward(
+    work_type: &str,
+    reward: i64,
+    enabled: Option<bool>,
+    halving_interval: default!(Option<i64>, "NULL"),
+) -> pgrx::JsonB {
     if reward <= 0 {
         error!("Reward must be positive");
     }
+    if halving_interval.is_some_and(|h| h < 0) {
+        error!("halving_interval must not be negative");
+    }
 
     let enabled_val = enabled.unwrap_or(true);
+    let halving_sql = match halving_interval {
+        Some(h) if h > 0 => h.to_string(),
+        _ => "NULL".to_string(),
+    };
+    let keep_or_set = if halving_interval.is_some() {
+        "EXCLUDED.halving_interval"
+    } else {
+        "kerai.reward_schedule.halving_interval"
+    };
 
     let row = Spi::get_one::<pgrx::JsonB>(&format!(
-        "INSERT INTO kerai.reward_schedule (work_type, reward, enabled)
-         VALUES ('{}', {}, {})
-         ON CONFLICT (work_type) DO UPDATE SET reward = EXCLUDED.reward, enabled = EXCLUDED.enabled, updated_at = now()
+        "INSERT INTO kerai.reward_schedule (work_type, reward, enabled, halving_interval)
+         VALUES ('{0}', {1}, {2}, {3})
+         ON CONFLICT (work_type) DO UPDATE SET reward = EXCLUDED.reward, enabled = EXCLUDED.enabled,
+             halving_interval = {4}, updated_at = now()
          RETURNING jsonb_build_object(
              'id', id,
              'work_type', work_type,
              'reward', reward,
              'enabled', enabled,
+             'halving_interval', halving_interval,
              'updated_at', updated_at
          )",
         sql_escape(work_type),
         reward,
         enabled_val,
+        halving_sql,
+        keep_or_set,
     ))
     .unwrap()
     .unwrap();
@@ -4,6 +4,7 @@
 /// 1 Koi = 1,000,000,000 nKoi (10^9). See currency::NKOI_PER_KOI.
 use pgrx::prelude::*;
 
+use crate::currency;
 use crate::identity;
 use crate::sql::sql_escape;
 
@@ -119,6 +120,47 @@ fn get_wallet(wallet_id: pgrx::Uuid) -> pgrx::JsonB {
     }
 }
 
+/// Resolve a wallet reference to its id. `@label` looks the wallet up by
+/// label and must match exactly one wallet; anything else must be a UUID.
+#[pg_extern]
+pub(crate) fn resolve_wallet(reference: &str) -> pgrx::Uuid {
+    let Some(label) = reference.strip_prefix('@') else {
+        let id = uuid::Uuid::parse_str(reference).unwrap_or_else(|_| {
+            error!("Invalid wallet reference '{}': expected a UUID or @label", reference)
+        });
+        return pgrx::Uuid::from_bytes(*id.as_bytes());
+    };
+
+    let ids = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(id::text ORDER BY created_at), '[]'::jsonb)
+         FROM kerai.wallets WHERE label = '{}'",
+        sql_escape(label),
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| serde_json::json!([]));
+
+    match ids.as_array().map(|a| a.as_slice()).unwrap_or_default() {
+        [] => error!("No wallet labeled '{}'", label),
+        [id] => {
+            let id = uuid::Uuid::parse_str(id.as_str().unwrap_or_default()).unwrap();
+            pgrx::Uuid::from_bytes(*id.as_bytes())
+        }
+        many => error!(
+            "Wallet label '{}' is ambiguous: {} wallets share it, use a wallet id",
+            label,
+            many.len()
+        ),
+    }
+}
+
+/// Get wallet details by label. A leading `@` is optional.
+#[pg_extern]
+fn get_wallet_by_label(label: &str) -> pgrx::JsonB {
+    let label = label.strip_prefix('@').unwrap_or(label);
+    get_wallet(resolve_wallet(&format!("@{}", label)))
+}
+
 /// Compute balance from ledger for any wallet by ID.
 #[pg_extern]
 fn get_wallet_balance(wallet_id: pgrx::Uuid) -> pgrx::JsonB {
@@ -178,6 +220,8 @@ fn transfer_koi(
     if !from_exists {
         error!("Source wallet not found: {}", from_wallet_id);
     }
+    require_spendable(&from_wallet_id.to_string());
+    currency::require_single_signer(&from_wallet_id.to_string());
 
     let to_exists = Spi::get_one::<bool>(&format!(
         "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id = '{}'::uuid)",
@@ -239,6 +283,123 @@ fn transfer_koi(
     row
 }
 
+/// Transfer Koi between wallets given as UUIDs or `@label` references.
+#[pg_extern(name = "transfer_koi")]
+fn transfer_koi_by_ref(
+    from_wallet: &str,
+    to_wallet: &str,
+    amount: i64,
+    reason: Option<&str>,
+) -> pgrx::JsonB {
+    transfer_koi(resolve_wallet(from_wallet), resolve_wallet(to_wallet), amount, reason)
+}
+
+/// Pay several wallets from one in a single transaction.
+///
+/// `payouts` is a JSON array of `{to, amount, reason}`, where `to` is a wallet
+/// UUID or `@label`. Every payout is validated and the sender must cover the
+/// sum before any ledger entry is written; any error aborts the whole batch.
+/// Returns `{count, total, ledger_ids}`.
+#[pg_extern]
+fn batch_transfer(from_wallet_id: pgrx::Uuid, payouts: pgrx::JsonB) -> pgrx::JsonB {
+    let entries = payouts
+        .0
+        .as_array()
+        .unwrap_or_else(|| error!("payouts must be a JSON array of {{to, amount, reason}}"));
+    if entries.is_empty() {
+        error!("payouts must not be empty");
+    }
+
+    let from_exists = Spi::get_one::<bool>(&format!(
+        "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id = '{}'::uuid)",
+        from_wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if !from_exists {
+        error!("Source wallet not found: {}", from_wallet_id);
+    }
+    require_spendable(&from_wallet_id.to_string());
+    currency::require_single_signer(&from_wallet_id.to_string());
+
+    let mut resolved = Vec::with_capacity(entries.len());
+    let mut total: i64 = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        let to = entry["to"]
+            .as_str()
+            .unwrap_or_else(|| error!("Payout {} is missing 'to'", i));
+        let amount = entry["amount"]
+            .as_i64()
+            .unwrap_or_else(|| error!("Payout {} is missing an integer 'amount'", i));
+        if amount <= 0 {
+            error!("Payout {} amount must be positive", i);
+        }
+        let to_wallet_id = resolve_wallet(to);
+        let to_exists = Spi::get_one::<bool>(&format!(
+            "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id = '{}'::uuid)",
+            to_wallet_id,
+        ))
+        .unwrap()
+        .unwrap_or(false);
+        if !to_exists {
+            error!("Destination wallet not found: {}", to_wallet_id);
+        }
+        total = total
+            .checked_add(amount)
+            .unwrap_or_else(|| error!("Batch total overflows"));
+        let reason = entry["reason"]
+            .as_str()
+            .unwrap_or("batch_transfer")
+            .to_string();
+        resolved.push((to_wallet_id, amount, reason));
+    }
+
+    let balance = Spi::get_one::<i64>(&format!(
+        "SELECT COALESCE(
+            (SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE to_wallet = '{0}'::uuid)
+            - (SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE from_wallet = '{0}'::uuid),
+            0
+        )::bigint",
+        from_wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or(0);
+    if balance < total {
+        error!(
+            "Insufficient balance: wallet {} has {} nKoi but batch requires {}",
+            from_wallet_id, balance, total
+        );
+    }
+
+    let mut lamport = Spi::get_one::<i64>("SELECT COALESCE(max(timestamp), 0) FROM kerai.ledger")
+        .unwrap()
+        .unwrap_or(0);
+
+    let mut ledger_ids = Vec::with_capacity(resolved.len());
+    for (to_wallet_id, amount, reason) in &resolved {
+        lamport += 1;
+        let id = Spi::get_one::<String>(&format!(
+            "INSERT INTO kerai.ledger (from_wallet, to_wallet, amount, reason, timestamp)
+             VALUES ('{}'::uuid, '{}'::uuid, {}, '{}', {})
+             RETURNING id::text",
+            from_wallet_id,
+            to_wallet_id,
+            amount,
+            sql_escape(reason),
+            lamport,
+        ))
+        .unwrap()
+        .unwrap();
+        ledger_ids.push(id);
+    }
+
+    pgrx::JsonB(serde_json::json!({
+        "count": ledger_ids.len(),
+        "total": total,
+        "ledger_ids": ledger_ids,
+    }))
+}
+
 /// Mint Koi from verifiable work. from_wallet is NULL (creation).
 /// Only the self instance can mint.
 #[pg_extern]
@@ -303,6 +464,120 @@ fn mint_koi(
     row
 }
 
+/// Remove Koi from circulation by moving them to the system burn wallet.
+/// Burned Koi no longer count toward `total_supply`.
+#[pg_extern]
+fn burn_koi(wallet_id: pgrx::Uuid, amount: i64, reason: Option<&str>) -> pgrx::JsonB {
+    if amount <= 0 {
+        error!("Burn amount must be positive");
+    }
+
+    let exists = Spi::get_one::<bool>(&format!(
+        "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id = '{}'::uuid)",
+        wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if !exists {
+        error!("Wallet not found: {}", wallet_id);
+    }
+    require_spendable(&wallet_id.to_string());
+    currency::require_single_signer(&wallet_id.to_string());
+
+    let balance = Spi::get_one::<i64>(&format!(
+        "SELECT COALESCE(
+            (SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE to_wallet = '{0}'::uuid)
+            - (SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE from_wallet = '{0}'::uuid),
+            0
+        )::bigint",
+        wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or(0);
+    if balance < amount {
+        error!(
+            "Insufficient balance: wallet {} has {} nKoi but burn requires {}",
+            wallet_id, balance, amount
+        );
+    }
+
+    let burn_wallet = system_wallet("burn", "Burn");
+    let lamport = Spi::get_one::<i64>("SELECT COALESCE(max(timestamp), 0) + 1 FROM kerai.ledger")
+        .unwrap()
+        .unwrap_or(1);
+
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "INSERT INTO kerai.ledger (from_wallet, to_wallet, amount, reason, timestamp)
+         VALUES ('{}'::uuid, '{}'::uuid, {}, '{}', {})
+         RETURNING jsonb_build_object(
+             'id', id,
+             'from_wallet', from_wallet,
+             'amount', amount,
+             'reason', reason,
+             'timestamp', timestamp
+         )",
+        wallet_id,
+        sql_escape(&burn_wallet),
+        amount,
+        sql_escape(reason.unwrap_or("burn")),
+        lamport,
+    ))
+    .unwrap()
+    .unwrap();
+    row
+}
+
+/// Id of the system wallet of `wallet_type` (e.g. escrow, burn), created on
+/// first use. Its private key is discarded so nothing can sign for it, and
+/// the generic transfer paths refuse it as a source (see `require_spendable`),
+/// so only the functions that own the wallet type move funds out of it.
+pub(crate) fn system_wallet(wallet_type: &str, label: &str) -> String {
+    if let Some(id) = Spi::get_one::<String>(&format!(
+        "SELECT id::text FROM kerai.wallets WHERE wallet_type = '{}' ORDER BY created_at LIMIT 1",
+        sql_escape(wallet_type),
+    ))
+    .unwrap_or(None)
+    {
+        return id;
+    }
+
+    let mut rng = rand::rngs::OsRng;
+    let verifying_key = ed25519_dalek::SigningKey::generate(&mut rng).verifying_key();
+    Spi::get_one::<String>(&format!(
+        "INSERT INTO kerai.wallets (instance_id, public_key, key_fingerprint, wallet_type, label)
+         SELECT id, '{}'::bytea, '{}', '{}', '{}'
+         FROM kerai.instances WHERE is_self = true
+         RETURNING id::text",
+        bytes_to_pg_hex(verifying_key.as_bytes()),
+        sql_escape(&identity::fingerprint(&verifying_key)),
+        sql_escape(wallet_type),
+        sql_escape(label),
+    ))
+    .unwrap()
+    .unwrap_or_else(|| error!("Self instance not found"))
+}
+
+/// System wallet types that generic transfers may not debit.
+pub(crate) const SYSTEM_WALLET_TYPES: [&str; 2] = ["burn", "escrow"];
+
+/// Error if `wallet_id` is a system wallet (burn or escrow). Every generic
+/// transfer path calls this on its source, so burned Koi stay burned and
+/// escrowed Koi only leave through release or refund.
+pub(crate) fn require_spendable(wallet_id: &str) {
+    let wallet_type = Spi::get_one::<String>(&format!(
+        "SELECT wallet_type FROM kerai.wallets WHERE id = '{}'::uuid",
+        sql_escape(wallet_id),
+    ))
+    .unwrap_or(None)
+    .unwrap_or_default();
+    if SYSTEM_WALLET_TYPES.contains(&wallet_type.as_str()) {
+        error!(
+            "Wallet {} is the system {} wallet; funds can't be transferred out of it",
+            wallet_id, wallet_type
+        );
+    }
+}
+
 /// Return recent ledger entries for a wallet (sent + received).
 #[pg_extern]
 fn wallet_history(wallet_id: pgrx::Uuid, limit: default!(i32, 50)) -> pgrx::JsonB {
@@ -348,3 +623,9 @@ fn wallet_history(wallet_id: pgrx::Uuid, limit: default!(i32, 50)) -> pgrx::Json
     .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
     json
 }
+
+/// Wallet history for a wallet given as a UUID or `@label` reference.
+#[pg_extern(name = "wallet_history")]
+fn wallet_history_by_ref(wallet: &str, limit: default!(i32, 50)) -> pgrx::JsonB {
+    wallet_history(resolve_wallet(wallet), limit)
+}
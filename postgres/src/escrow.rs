@@ -0,0 +1,219 @@
+/// Escrow — Koi held by the system escrow wallet until released or refunded.
+///
+/// A hold moves funds from the sender into the escrow wallet; release pays
+/// them out to a recipient, refund returns them to the sender. Each escrow
+/// settles exactly once. Ledger entries carry `reference_type = 'escrow'`.
+use pgrx::prelude::*;
+
+use crate::currency;
+use crate::economy;
+use crate::sql::sql_escape;
+
+/// Move `amount` from `from_wallet_id` into escrow. Returns the escrow record.
+#[pg_extern]
+fn escrow_hold(from_wallet_id: pgrx::Uuid, amount: i64, reason: Option<&str>) -> pgrx::JsonB {
+    if amount <= 0 {
+        error!("Escrow amount must be positive");
+    }
+
+    let from_exists = Spi::get_one::<bool>(&format!(
+        "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id = '{}'::uuid)",
+        from_wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if !from_exists {
+        error!("Source wallet not found: {}", from_wallet_id);
+    }
+    economy::require_spendable(&from_wallet_id.to_string());
+    currency::require_single_signer(&from_wallet_id.to_string());
+
+    let balance = Spi::get_one::<i64>(&format!(
+        "SELECT COALESCE(
+            (SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE to_wallet = '{0}'::uuid)
+            - (SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE from_wallet = '{0}'::uuid),
+            0
+        )::bigint",
+        from_wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or(0);
+    if balance < amount {
+        error!(
+            "Insufficient balance: wallet {} has {} nKoi but escrow requires {}",
+            from_wallet_id, balance, amount
+        );
+    }
+
+    let reason_str = reason.unwrap_or("escrow");
+    let escrow_id = Spi::get_one::<String>(&format!(
+        "INSERT INTO kerai.escrows (from_wallet, amount, reason)
+         VALUES ('{}'::uuid, {}, '{}')
+         RETURNING id::text",
+        from_wallet_id,
+        amount,
+        sql_escape(reason_str),
+    ))
+    .unwrap()
+    .unwrap();
+
+    let escrow_wallet = escrow_wallet_id();
+    record_movement(
+        &from_wallet_id.to_string(),
+        &escrow_wallet,
+        amount,
+        &format!("escrow_hold: {}", reason_str),
+        &escrow_id,
+    );
+
+    escrow_record(&escrow_id)
+}
+
+/// Pay a held escrow out to `to_wallet_id`.
+#[pg_extern]
+fn escrow_release(escrow_id: pgrx::Uuid, to_wallet_id: pgrx::Uuid) -> pgrx::JsonB {
+    let to_exists = Spi::get_one::<bool>(&format!(
+        "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id = '{}'::uuid)",
+        to_wallet_id,
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if !to_exists {
+        error!("Destination wallet not found: {}", to_wallet_id);
+    }
+
+    settle(escrow_id, Some(to_wallet_id))
+}
+
+/// Return a held escrow to the wallet that funded it.
+#[pg_extern]
+fn escrow_refund(escrow_id: pgrx::Uuid) -> pgrx::JsonB {
+    settle(escrow_id, None)
+}
+
+/// `escrow_hold` by id string, for modules that hold escrows (e.g. bounties).
+/// Returns the new escrow id.
+pub(crate) fn hold(from_wallet_id: &str, amount: i64, reason: &str) -> String {
+    let record = escrow_hold(parse_id(from_wallet_id, "wallet"), amount, Some(reason));
+    record.0["escrow_id"].as_str().unwrap().to_string()
+}
+
+/// `escrow_release` by id string.
+pub(crate) fn release(escrow_id: &str, to_wallet_id: &str) -> pgrx::JsonB {
+    escrow_release(
+        parse_id(escrow_id, "escrow"),
+        parse_id(to_wallet_id, "wallet"),
+    )
+}
+
+/// `escrow_refund` by id string, for modules that hold escrows (e.g. bids).
+pub(crate) fn refund(escrow_id: &str) -> pgrx::JsonB {
+    settle(parse_id(escrow_id, "escrow"), None)
+}
+
+fn parse_id(id: &str, what: &str) -> pgrx::Uuid {
+    let parsed =
+        uuid::Uuid::parse_str(id).unwrap_or_else(|_| error!("Invalid {} id: {}", what, id));
+    pgrx::Uuid::from_bytes(*parsed.as_bytes())
+}
+
+/// Settle a held escrow: release it to `release_to`, or refund it when `None`.
+fn settle(escrow_id: pgrx::Uuid, release_to: Option<pgrx::Uuid>) -> pgrx::JsonB {
+    let (from_wallet, amount, reason) = held_escrow(escrow_id);
+
+    let (status, verb, to_wallet, released_to) = match release_to {
+        Some(to) => (
+            "released",
+            "escrow_release",
+            to.to_string(),
+            format!("'{}'::uuid", to),
+        ),
+        None => ("refunded", "escrow_refund", from_wallet, "NULL".to_string()),
+    };
+    Spi::run(&format!(
+        "UPDATE kerai.escrows
+         SET status = '{}', released_to = {}, settled_at = now()
+         WHERE id = '{}'::uuid",
+        status, released_to, escrow_id,
+    ))
+    .unwrap();
+
+    record_movement(
+        &escrow_wallet_id(),
+        &to_wallet,
+        amount,
+        &format!("{}: {}", verb, reason),
+        &escrow_id.to_string(),
+    );
+
+    escrow_record(&escrow_id.to_string())
+}
+
+/// Lock a held escrow and return (from_wallet, amount, reason).
+/// Errors if it does not exist or has already been settled.
+fn held_escrow(escrow_id: pgrx::Uuid) -> (String, i64, String) {
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+             'from_wallet', from_wallet,
+             'amount', amount,
+             'reason', reason,
+             'status', status
+         )
+         FROM kerai.escrows WHERE id = '{}'::uuid FOR UPDATE",
+        escrow_id,
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Escrow not found: {}", escrow_id));
+
+    let status = row.0["status"].as_str().unwrap_or("");
+    if status != "held" {
+        error!("Escrow {} is already {}", escrow_id, status);
+    }
+    (
+        row.0["from_wallet"].as_str().unwrap_or("").to_string(),
+        row.0["amount"].as_i64().unwrap_or(0),
+        row.0["reason"].as_str().unwrap_or("").to_string(),
+    )
+}
+
+/// Insert a ledger entry referencing the escrow.
+fn record_movement(from_wallet: &str, to_wallet: &str, amount: i64, reason: &str, escrow_id: &str) {
+    let lamport = Spi::get_one::<i64>("SELECT COALESCE(max(timestamp), 0) + 1 FROM kerai.ledger")
+        .unwrap()
+        .unwrap_or(1);
+    Spi::run(&format!(
+        "INSERT INTO kerai.ledger
+             (from_wallet, to_wallet, amount, reason, reference_id, reference_type, timestamp)
+         VALUES ('{}'::uuid, '{}'::uuid, {}, '{}', '{}'::uuid, 'escrow', {})",
+        sql_escape(from_wallet),
+        sql_escape(to_wallet),
+        amount,
+        sql_escape(reason),
+        sql_escape(escrow_id),
+        lamport,
+    ))
+    .unwrap();
+}
+
+fn escrow_wallet_id() -> String {
+    economy::system_wallet("escrow", "Escrow")
+}
+
+fn escrow_record(escrow_id: &str) -> pgrx::JsonB {
+    Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+             'escrow_id', id,
+             'from_wallet', from_wallet,
+             'amount', amount,
+             'reason', reason,
+             'status', status,
+             'released_to', released_to,
+             'created_at', created_at,
+             'settled_at', settled_at
+         )
+         FROM kerai.escrows WHERE id = '{}'::uuid",
+        sql_escape(escrow_id),
+    ))
+    .unwrap()
+    .unwrap()
+}
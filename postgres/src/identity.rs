@@ -67,6 +67,17 @@ pub fn fingerprint(verifying_key: &VerifyingKey) -> String {
     base64::Engine::encode(&base64::engine::general_purpose::STANDARD, hash)
 }
 
+/// Decode a hex-encoded Ed25519 public key, explaining why it was rejected
+pub fn parse_public_key_hex(public_key_hex: &str) -> Result<VerifyingKey, String> {
+    let bytes = hex::decode(public_key_hex.trim())
+        .map_err(|e| format!("Invalid hex public key: {}", e))?;
+    let key_bytes: [u8; 32] = bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| format!("Public key must be 32 bytes (got {})", bytes.len()))?;
+    VerifyingKey::from_bytes(&key_bytes).map_err(|_| "Invalid Ed25519 public key".to_string())
+}
+
 /// Sign data with the signing key
 pub fn sign_data(signing_key: &SigningKey, data: &[u8]) -> Vec<u8> {
     signing_key.sign(data).to_bytes().to_vec()
@@ -1,6 +1,7 @@
-/// Marketplace — Dutch auction engine and market observability.
+/// Marketplace — Dutch and English auction engines and market observability.
 use pgrx::prelude::*;
 
+use crate::pagination;
 use crate::sql::sql_escape;
 
 /// Create a Dutch auction for an attestation. The seller must be the self instance.
@@ -14,22 +15,164 @@ fn create_auction(
     min_bidders: default!(i32, 1),
     open_delay_hours: default!(i32, 24),
 ) -> pgrx::JsonB {
-    if starting_price <= 0 {
-        error!("starting_price must be positive");
+    validate_price_schedule(starting_price, floor_price, price_decrement, decrement_interval_secs);
+    let seller_wallet = require_listable_attestation(attestation_id);
+
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "INSERT INTO kerai.auctions (
+            attestation_id, seller_wallet, starting_price, floor_price,
+            current_price, price_decrement, decrement_interval,
+            min_bidders, open_delay_hours
+        ) VALUES (
+            '{}'::uuid, '{}'::uuid, {}, {},
+            {}, {}, '{} seconds'::interval,
+            {}, {}
+        ) RETURNING jsonb_build_object(
+            'id', id,
+            'attestation_id', attestation_id,
+            'auction_type', auction_type,
+            'starting_price', starting_price,
+            'floor_price', floor_price,
+            'current_price', current_price,
+            'price_decrement', price_decrement,
+            'min_bidders', min_bidders,
+            'status', status,
+            'created_at', created_at
+        )",
+        attestation_id,
+        sql_escape(&seller_wallet),
+        starting_price,
+        floor_price,
+        starting_price, // current_price starts at starting_price
+        price_decrement,
+        decrement_interval_secs,
+        min_bidders,
+        open_delay_hours,
+    ))
+    .unwrap()
+    .unwrap();
+    row
+}
+
+/// Create an English (ascending) auction for an attestation. Bids must beat
+/// the current high bid by `min_increment` until `duration_seconds` elapse;
+/// `settle_auction` then sells to the high bidder at their bid if it meets
+/// `reserve_price`. A bid in the last `extension_seconds` extends the end by
+/// that much. The seller must be the self instance.
+#[pg_extern]
+fn create_english_auction(
+    attestation_id: pgrx::Uuid,
+    reserve_price: i64,
+    min_increment: i64,
+    duration_seconds: i64,
+    extension_seconds: default!(i32, 0),
+    open_delay_hours: default!(i32, 24),
+) -> pgrx::JsonB {
+    if reserve_price < 0 {
+        error!("reserve_price cannot be negative");
     }
-    if floor_price < 0 {
-        error!("floor_price cannot be negative");
+    if min_increment <= 0 {
+        error!("min_increment must be positive");
     }
-    if floor_price >= starting_price {
-        error!("floor_price must be less than starting_price");
+    if duration_seconds <= 0 {
+        error!("duration_seconds must be positive");
     }
-    if price_decrement <= 0 {
-        error!("price_decrement must be positive");
+    if extension_seconds < 0 {
+        error!("extension_seconds cannot be negative");
     }
-    if decrement_interval_secs <= 0 {
-        error!("decrement_interval_secs must be positive");
+    let seller_wallet = require_listable_attestation(attestation_id);
+
+    // current_price tracks the high bid; the Dutch price schedule is unused
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "INSERT INTO kerai.auctions (
+            attestation_id, seller_wallet, auction_type, starting_price, floor_price,
+            current_price, price_decrement, decrement_interval,
+            reserve_price, min_increment, ends_at, extension_seconds, open_delay_hours
+        ) VALUES (
+            '{0}'::uuid, '{1}'::uuid, 'english', {2}, 0,
+            0, 0, '0 seconds'::interval,
+            {2}, {3}, now() + '{4} seconds'::interval, {5}, {6}
+        ) RETURNING jsonb_build_object(
+            'id', id,
+            'attestation_id', attestation_id,
+            'auction_type', auction_type,
+            'reserve_price', reserve_price,
+            'min_increment', min_increment,
+            'current_price', current_price,
+            'ends_at', ends_at,
+            'extension_seconds', extension_seconds,
+            'status', status,
+            'created_at', created_at
+        )",
+        attestation_id,
+        sql_escape(&seller_wallet),
+        reserve_price,
+        min_increment,
+        duration_seconds,
+        extension_seconds,
+        open_delay_hours,
+    ))
+    .unwrap()
+    .unwrap();
+    row
+}
+
+/// Create a sealed-bid second-price (Vickrey) auction for an attestation.
+/// Bidders commit `sha256(amount || salt)` with `commit_sealed_bid` for
+/// `commit_seconds`, then reveal with `reveal_sealed_bid` for `reveal_seconds`;
+/// `settle_auction` awards the highest revealed bid at the second-highest
+/// (never below `reserve_price`). The seller must be the self instance.
+#[pg_extern]
+fn create_sealed_auction(
+    attestation_id: pgrx::Uuid,
+    reserve_price: i64,
+    commit_seconds: i64,
+    reveal_seconds: i64,
+    open_delay_hours: default!(i32, 24),
+) -> pgrx::JsonB {
+    if reserve_price < 0 {
+        error!("reserve_price cannot be negative");
     }
+    if commit_seconds <= 0 || reveal_seconds <= 0 {
+        error!("commit_seconds and reveal_seconds must be positive");
+    }
+    let seller_wallet = require_listable_attestation(attestation_id);
+
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "INSERT INTO kerai.auctions (
+            attestation_id, seller_wallet, auction_type, starting_price, floor_price,
+            current_price, price_decrement, decrement_interval,
+            reserve_price, ends_at, reveal_ends_at, open_delay_hours
+        ) VALUES (
+            '{0}'::uuid, '{1}'::uuid, 'sealed', {2}, 0,
+            0, 0, '0 seconds'::interval,
+            {2}, now() + '{3} seconds'::interval,
+            now() + '{3} seconds'::interval + '{4} seconds'::interval, {5}
+        ) RETURNING jsonb_build_object(
+            'id', id,
+            'attestation_id', attestation_id,
+            'auction_type', auction_type,
+            'reserve_price', reserve_price,
+            'ends_at', ends_at,
+            'reveal_ends_at', reveal_ends_at,
+            'status', status,
+            'created_at', created_at
+        )",
+        attestation_id,
+        sql_escape(&seller_wallet),
+        reserve_price,
+        commit_seconds,
+        reveal_seconds,
+        open_delay_hours,
+    ))
+    .unwrap()
+    .unwrap();
+    row
+}
 
+/// Check an attestation can be auctioned: it exists, belongs to the self
+/// instance, and has no active auction. Returns the self wallet (the seller).
+fn require_listable_attestation(attestation_id: pgrx::Uuid) -> String {
     // Verify attestation exists and belongs to self instance
     let att_exists = Spi::get_one::<bool>(&format!(
         "SELECT EXISTS(
@@ -62,50 +205,20 @@ fn create_auction(
     }
 
     // Get self wallet
-    let seller_wallet = Spi::get_one::<String>(
+    Spi::get_one::<String>(
         "SELECT w.id::text FROM kerai.wallets w
          JOIN kerai.instances i ON w.instance_id = i.id
          WHERE i.is_self = true AND w.wallet_type = 'instance'",
     )
     .unwrap()
-    .unwrap_or_else(|| error!("Self wallet not found"));
-
-    let row = Spi::get_one::<pgrx::JsonB>(&format!(
-        "INSERT INTO kerai.auctions (
-            attestation_id, seller_wallet, starting_price, floor_price,
-            current_price, price_decrement, decrement_interval,
-            min_bidders, open_delay_hours
-        ) VALUES (
-            '{}'::uuid, '{}'::uuid, {}, {},
-            {}, {}, '{} seconds'::interval,
-            {}, {}
-        ) RETURNING jsonb_build_object(
-            'id', id,
-            'attestation_id', attestation_id,
-            'starting_price', starting_price,
-            'floor_price', floor_price,
-            'current_price', current_price,
-            'price_decrement', price_decrement,
-            'min_bidders', min_bidders,
-            'status', status,
-            'created_at', created_at
-        )",
-        attestation_id,
-        sql_escape(&seller_wallet),
-        starting_price,
-        floor_price,
-        starting_price, // current_price starts at starting_price
-        price_decrement,
-        decrement_interval_secs,
-        min_bidders,
-        open_delay_hours,
-    ))
-    .unwrap()
-    .unwrap();
-    row
+    .unwrap_or_else(|| error!("Self wallet not found"))
 }
 
 /// Place a bid on an active auction. Bidder is the self instance wallet.
+/// On an English auction the bid must beat the current high bid by the
+/// auction's `min_increment` and arrive before `ends_at`; a bid within the
+/// last `extension_seconds` pushes `ends_at` back, and the result carries
+/// the (possibly new) `ends_at`.
 #[pg_extern]
 fn place_bid(auction_id: pgrx::Uuid, max_price: i64) -> pgrx::JsonB {
     if max_price <= 0 {
@@ -113,16 +226,60 @@ fn place_bid(auction_id: pgrx::Uuid, max_price: i64) -> pgrx::JsonB {
     }
 
     // Verify auction is active
-    let status = Spi::get_one::<String>(&format!(
-        "SELECT status FROM kerai.auctions WHERE id = '{}'::uuid",
+    let auction = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'status', status,
+            'auction_type', auction_type,
+            'high_bid', (
+                SELECT max(max_price) FROM kerai.bids
+                WHERE auction_id = a.id AND status = 'active'
+            ),
+            'min_increment', min_increment,
+            'ended', ends_at <= now()
+        ) FROM kerai.auctions a WHERE id = '{}'::uuid FOR UPDATE",
         auction_id,
     ))
-    .unwrap_or(None);
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Auction not found: {}", auction_id));
 
-    match status.as_deref() {
-        None => error!("Auction not found: {}", auction_id),
+    match auction.0["status"].as_str() {
         Some("active") => {}
-        Some(s) => error!("Auction is not active, currently '{}'", s),
+        s => error!(
+            "Auction is not active, currently '{}'",
+            s.unwrap_or_default()
+        ),
+    }
+
+    if auction.0["auction_type"].as_str() == Some("sealed") {
+        error!("Sealed-bid auctions take bids through commit_sealed_bid");
+    }
+    let mut ends_at = None;
+    if auction.0["auction_type"].as_str() == Some("english") {
+        if auction.0["ended"].as_bool() == Some(true) {
+            error!("English auction {} has ended", auction_id);
+        }
+        let high_bid = auction.0["high_bid"].as_i64().unwrap_or(0);
+        let min_bid = high_bid + auction.0["min_increment"].as_i64().unwrap_or(1);
+        if max_price < min_bid {
+            error!(
+                "Bid too low: must be at least {} (high bid {} + min_increment)",
+                min_bid, high_bid
+            );
+        }
+        ends_at = Spi::get_one::<pgrx::JsonB>(&format!(
+            "UPDATE kerai.auctions
+             SET current_price = {},
+                 ends_at = CASE
+                     WHEN ends_at - now() < make_interval(secs => extension_seconds)
+                     THEN ends_at + make_interval(secs => extension_seconds)
+                     ELSE ends_at
+                 END
+             WHERE id = '{}'::uuid
+             RETURNING to_jsonb(ends_at)",
+            max_price, auction_id,
+        ))
+        .unwrap()
+        .map(|j| j.0);
     }
 
     // Get self wallet
@@ -134,7 +291,7 @@ fn place_bid(auction_id: pgrx::Uuid, max_price: i64) -> pgrx::JsonB {
     .unwrap()
     .unwrap_or_else(|| error!("Self wallet not found"));
 
-    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+    let mut row = Spi::get_one::<pgrx::JsonB>(&format!(
         "INSERT INTO kerai.bids (auction_id, bidder_wallet, max_price)
          VALUES ('{}'::uuid, '{}'::uuid, {})
          RETURNING jsonb_build_object(
@@ -149,9 +306,304 @@ fn place_bid(auction_id: pgrx::Uuid, max_price: i64) -> pgrx::JsonB {
     ))
     .unwrap()
     .unwrap();
+    if let (Some(end), serde_json::Value::Object(m)) = (ends_at, &mut row.0) {
+        m.insert("ends_at".into(), end);
+    }
     row
 }
 
+/// Withdraw a bid while its auction is still active. Bids hold no funds until
+/// settlement, so there is nothing to refund. On an English auction the price
+/// falls back to the next-highest bid; once its `ends_at` has passed the
+/// auction counts as ended and bids can no longer be retracted.
+#[pg_extern]
+fn retract_bid(bid_id: pgrx::Uuid) -> pgrx::JsonB {
+    let bid = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'auction_id', b.auction_id,
+            'status', b.status,
+            'auction_status', a.status,
+            'auction_type', a.auction_type,
+            'ended', a.ends_at <= now()
+        ) FROM kerai.bids b JOIN kerai.auctions a ON a.id = b.auction_id
+        WHERE b.id = '{}'::uuid FOR UPDATE OF b",
+        bid_id,
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Bid not found: {}", bid_id))
+    .0;
+
+    match bid["auction_status"].as_str().unwrap_or_default() {
+        "active" => {}
+        "settled" | "open_sourced" => {
+            error!("Cannot retract bid {}: auction already settled", bid_id)
+        }
+        s => error!("Cannot retract bid {}: auction is '{}'", bid_id, s),
+    }
+    if bid["auction_type"].as_str() == Some("english") && bid["ended"].as_bool() == Some(true) {
+        error!("Cannot retract bid {}: English auction has ended", bid_id);
+    }
+    if bid["status"].as_str() == Some("withdrawn") {
+        error!("Bid {} is already withdrawn", bid_id);
+    }
+    let auction_id = bid["auction_id"].as_str().unwrap_or_default();
+
+    Spi::run(&format!(
+        "UPDATE kerai.bids SET status = 'withdrawn', withdrawn_at = now() WHERE id = '{}'::uuid",
+        bid_id,
+    ))
+    .unwrap();
+
+    if bid["auction_type"].as_str() == Some("english") {
+        Spi::run(&format!(
+            "UPDATE kerai.auctions SET current_price = COALESCE((
+                SELECT max(max_price) FROM kerai.bids
+                WHERE auction_id = '{0}'::uuid AND status = 'active'
+            ), 0) WHERE id = '{0}'::uuid",
+            sql_escape(auction_id),
+        ))
+        .unwrap();
+    }
+
+    pgrx::JsonB(serde_json::json!({
+        "bid_id": bid_id.to_string(),
+        "auction_id": auction_id,
+        "status": "withdrawn",
+    }))
+}
+
+/// Commit a sealed bid: `commitment` is `sha256(amount || salt)`, with `amount`
+/// as 8 big-endian bytes (`int8send`) and `salt` arbitrary bytes kept secret
+/// until the reveal. Only accepted before the auction's `ends_at`; a bidder may
+/// replace their commitment until then. The bidder defaults to the self wallet.
+#[pg_extern]
+fn commit_sealed_bid(
+    auction_id: pgrx::Uuid,
+    commitment: &[u8],
+    bidder_wallet: default!(Option<pgrx::Uuid>, "NULL"),
+) -> pgrx::JsonB {
+    if commitment.len() != 32 {
+        error!(
+            "commitment must be a 32-byte sha256 digest, got {} bytes",
+            commitment.len()
+        );
+    }
+    let auction = sealed_auction(auction_id);
+    if auction["commit_open"].as_bool() != Some(true) {
+        error!("Commit phase of auction {} has ended", auction_id);
+    }
+    let bidder = bidder_or_self(bidder_wallet);
+    let hex: String = commitment.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "INSERT INTO kerai.sealed_bids (auction_id, bidder_wallet, commitment)
+         VALUES ('{}'::uuid, '{}'::uuid, '\\x{}'::bytea)
+         ON CONFLICT (auction_id, bidder_wallet)
+             DO UPDATE SET commitment = EXCLUDED.commitment, created_at = now()
+         RETURNING jsonb_build_object(
+             'id', id,
+             'auction_id', auction_id,
+             'bidder_wallet', bidder_wallet,
+             'status', status,
+             'created_at', created_at
+         )",
+        auction_id,
+        sql_escape(&bidder),
+        hex,
+    ))
+    .unwrap()
+    .unwrap();
+    row
+}
+
+/// Reveal a sealed bid once commits have closed and before `reveal_ends_at`.
+/// Errors unless `sha256(amount || salt)` matches the bidder's commitment.
+#[pg_extern]
+fn reveal_sealed_bid(
+    auction_id: pgrx::Uuid,
+    amount: i64,
+    salt: &[u8],
+    bidder_wallet: default!(Option<pgrx::Uuid>, "NULL"),
+) -> pgrx::JsonB {
+    use sha2::{Digest, Sha256};
+
+    if amount <= 0 {
+        error!("amount must be positive");
+    }
+    let auction = sealed_auction(auction_id);
+    if auction["commit_open"].as_bool() == Some(true) {
+        error!("Auction {} is still taking commitments", auction_id);
+    }
+    if auction["reveal_open"].as_bool() != Some(true) {
+        error!("Reveal phase of auction {} has ended", auction_id);
+    }
+    let bidder = bidder_or_self(bidder_wallet);
+
+    let commitment = Spi::get_one::<Vec<u8>>(&format!(
+        "SELECT commitment FROM kerai.sealed_bids
+         WHERE auction_id = '{}'::uuid AND bidder_wallet = '{}'::uuid AND status = 'committed'",
+        auction_id,
+        sql_escape(&bidder),
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| {
+        error!(
+            "No unrevealed commitment from {} on auction {}",
+            bidder, auction_id
+        )
+    });
+
+    let mut hasher = Sha256::new();
+    hasher.update(amount.to_be_bytes());
+    hasher.update(salt);
+    if hasher.finalize().as_slice() != commitment.as_slice() {
+        error!("Reveal does not match commitment");
+    }
+
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "UPDATE kerai.sealed_bids
+         SET amount = {}, status = 'revealed', revealed_at = now()
+         WHERE auction_id = '{}'::uuid AND bidder_wallet = '{}'::uuid
+         RETURNING jsonb_build_object(
+             'id', id,
+             'auction_id', auction_id,
+             'bidder_wallet', bidder_wallet,
+             'amount', amount,
+             'status', status,
+             'revealed_at', revealed_at
+         )",
+        amount,
+        auction_id,
+        sql_escape(&bidder),
+    ))
+    .unwrap()
+    .unwrap();
+    row
+}
+
+/// Phase flags of an active sealed-bid auction, erroring for any other auction.
+fn sealed_auction(auction_id: pgrx::Uuid) -> serde_json::Value {
+    let auction = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'status', status,
+            'auction_type', auction_type,
+            'commit_open', now() < ends_at,
+            'reveal_open', now() < reveal_ends_at
+        ) FROM kerai.auctions WHERE id = '{}'::uuid",
+        auction_id,
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Auction not found: {}", auction_id))
+    .0;
+    if auction["auction_type"].as_str() != Some("sealed") {
+        error!("Auction {} is not a sealed-bid auction", auction_id);
+    }
+    if auction["status"].as_str() != Some("active") {
+        error!(
+            "Auction is not active, currently '{}'",
+            auction["status"].as_str().unwrap_or_default()
+        );
+    }
+    auction
+}
+
+/// The given bidder wallet, or the self instance wallet.
+fn bidder_or_self(bidder_wallet: Option<pgrx::Uuid>) -> String {
+    if let Some(w) = bidder_wallet {
+        let exists = Spi::get_one::<bool>(&format!(
+            "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id = '{}'::uuid)",
+            w,
+        ))
+        .unwrap()
+        .unwrap_or(false);
+        if !exists {
+            error!("Bidder wallet not found: {}", w);
+        }
+        return w.to_string();
+    }
+    Spi::get_one::<String>(
+        "SELECT w.id::text FROM kerai.wallets w
+         JOIN kerai.instances i ON w.instance_id = i.id
+         WHERE i.is_self = true AND w.wallet_type = 'instance'",
+    )
+    .unwrap()
+    .unwrap_or_else(|| error!("Self wallet not found"))
+}
+
+/// Longest schedule `auction_preview` will project.
+const PREVIEW_MAX_STEPS: i64 = 1000;
+
+/// Check Dutch auction pricing parameters, erroring on the first bad one.
+fn validate_price_schedule(
+    starting_price: i64,
+    floor_price: i64,
+    price_decrement: i64,
+    decrement_interval_secs: i64,
+) {
+    if starting_price <= 0 {
+        error!("starting_price must be positive");
+    }
+    if floor_price < 0 {
+        error!("floor_price cannot be negative");
+    }
+    if floor_price >= starting_price {
+        error!("floor_price must be less than starting_price");
+    }
+    if price_decrement <= 0 {
+        error!("price_decrement must be positive");
+    }
+    if decrement_interval_secs <= 0 {
+        error!("decrement_interval_secs must be positive");
+    }
+}
+
+/// Price after one tick: drop by the decrement, never below the floor.
+fn next_price(current_price: i64, price_decrement: i64, floor_price: i64) -> i64 {
+    (current_price - price_decrement).max(floor_price)
+}
+
+/// Project an auction's price schedule without creating anything.
+/// Each step is `{t, price, action}` with `t` in seconds from listing,
+/// following `tick_auction` until the floor is hit and the auction opens.
+/// Long schedules stop after 1000 steps with `truncated` set.
+#[pg_extern]
+fn auction_preview(
+    starting_price: i64,
+    floor_price: i64,
+    price_decrement: i64,
+    decrement_interval_secs: i64,
+    open_delay_hours: default!(i32, 24),
+) -> pgrx::JsonB {
+    validate_price_schedule(starting_price, floor_price, price_decrement, decrement_interval_secs);
+
+    let mut schedule = vec![serde_json::json!({"t": 0, "price": starting_price, "action": "listed"})];
+    let mut price = starting_price;
+    let mut step = 0;
+    while price > floor_price && step < PREVIEW_MAX_STEPS {
+        step += 1;
+        price = next_price(price, price_decrement, floor_price);
+        let action = if price <= floor_price { "open_sourced" } else { "price_decremented" };
+        schedule.push(serde_json::json!({
+            "t": step * decrement_interval_secs,
+            "price": price,
+            "action": action,
+        }));
+    }
+    let floor_reached = price <= floor_price;
+
+    pgrx::JsonB(serde_json::json!({
+        "starting_price": starting_price,
+        "floor_price": floor_price,
+        "price_decrement": price_decrement,
+        "decrement_interval_secs": decrement_interval_secs,
+        "open_delay_hours": open_delay_hours,
+        "steps": step,
+        "floor_reached_at": if floor_reached { Some(step * decrement_interval_secs) } else { None },
+        "truncated": !floor_reached,
+        "schedule": schedule,
+    }))
+}
+
 /// Advance the auction clock: decrement price, check floor hit, check settlement conditions.
 #[pg_extern]
 fn tick_auction(auction_id: pgrx::Uuid) -> pgrx::JsonB {
@@ -163,7 +615,8 @@ fn tick_auction(auction_id: pgrx::Uuid) -> pgrx::JsonB {
             'floor_price', floor_price,
             'price_decrement', price_decrement,
             'min_bidders', min_bidders,
-            'status', status
+            'status', status,
+            'auction_type', auction_type
         ) FROM kerai.auctions WHERE id = '{}'::uuid",
         auction_id,
     ))
@@ -179,13 +632,16 @@ fn tick_auction(auction_id: pgrx::Uuid) -> pgrx::JsonB {
     if status != "active" {
         error!("Auction is not active, currently '{}'", status);
     }
+    if obj["auction_type"].as_str() != Some("dutch") {
+        error!("tick_auction only applies to Dutch auctions");
+    }
 
     let current_price = obj["current_price"].as_i64().unwrap();
     let floor_price = obj["floor_price"].as_i64().unwrap();
     let decrement = obj["price_decrement"].as_i64().unwrap();
     let min_bidders = obj["min_bidders"].as_i64().unwrap();
 
-    let new_price = (current_price - decrement).max(floor_price);
+    let new_price = next_price(current_price, decrement, floor_price);
 
     // Check if floor is hit
     if new_price <= floor_price {
@@ -217,7 +673,7 @@ fn tick_auction(auction_id: pgrx::Uuid) -> pgrx::JsonB {
     // Check settlement conditions: enough qualifying bidders?
     let qualifying = Spi::get_one::<i64>(&format!(
         "SELECT count(*)::bigint FROM kerai.bids
-         WHERE auction_id = '{}'::uuid AND max_price >= {}",
+         WHERE auction_id = '{}'::uuid AND status = 'active' AND max_price >= {}",
         auction_id, new_price,
     ))
     .unwrap()
@@ -240,16 +696,25 @@ fn tick_auction(auction_id: pgrx::Uuid) -> pgrx::JsonB {
     }))
 }
 
-/// Settle an active auction: all qualifying bidders pay current_price.
+/// Settle an active auction. Dutch: all qualifying bidders pay current_price.
+/// English: once ended, the high bidder pays their bid if it meets the
+/// reserve, otherwise the auction is marked 'unsold'.
 #[pg_extern]
 fn settle_auction(auction_id: pgrx::Uuid) -> pgrx::JsonB {
     let auction = Spi::get_one::<pgrx::JsonB>(&format!(
         "SELECT jsonb_build_object(
             'id', id,
+            'attestation_id', attestation_id,
             'current_price', current_price,
             'seller_wallet', seller_wallet,
             'min_bidders', min_bidders,
-            'status', status
+            'status', status,
+            'auction_type', auction_type,
+            'reserve_price', reserve_price,
+            'ends_at', ends_at,
+            'ended', ends_at <= now(),
+            'reveal_ends_at', reveal_ends_at,
+            'reveal_ended', reveal_ends_at <= now()
         ) FROM kerai.auctions WHERE id = '{}'::uuid",
         auction_id,
     ))
@@ -265,6 +730,12 @@ fn settle_auction(auction_id: pgrx::Uuid) -> pgrx::JsonB {
     if status != "active" {
         error!("Auction must be 'active' to settle, currently '{}'", status);
     }
+    if obj["auction_type"].as_str() == Some("english") {
+        return settle_english_auction(auction_id, obj);
+    }
+    if obj["auction_type"].as_str() == Some("sealed") {
+        return settle_sealed_auction(auction_id, obj);
+    }
 
     let current_price = obj["current_price"].as_i64().unwrap();
     let seller_wallet = obj["seller_wallet"].as_str().unwrap();
@@ -278,7 +749,7 @@ fn settle_auction(auction_id: pgrx::Uuid) -> pgrx::JsonB {
             'max_price', max_price
         )), '[]'::jsonb)
         FROM kerai.bids
-        WHERE auction_id = '{}'::uuid AND max_price >= {}",
+        WHERE auction_id = '{}'::uuid AND status = 'active' AND max_price >= {}",
         auction_id, current_price,
     ))
     .unwrap()
@@ -327,15 +798,349 @@ fn settle_auction(auction_id: pgrx::Uuid) -> pgrx::JsonB {
     ))
     .unwrap();
 
+    let royalties = pay_royalties(auction_id, obj, total_revenue);
+
     pgrx::JsonB(serde_json::json!({
         "auction_id": auction_id.to_string(),
         "status": "settled",
         "settled_price": current_price,
         "bidder_count": bidder_count,
         "total_revenue": total_revenue,
+        "royalties": royalties["payouts"],
+        "seller_revenue": royalties["seller_revenue"],
     }))
 }
 
+/// Settle an ended English auction: sell to the highest bid at that bid if it
+/// meets the reserve, else mark the auction 'unsold'.
+fn settle_english_auction(
+    auction_id: pgrx::Uuid,
+    auction: &serde_json::Map<String, serde_json::Value>,
+) -> pgrx::JsonB {
+    if auction["ended"].as_bool() != Some(true) {
+        error!(
+            "English auction is still running until {}",
+            auction["ends_at"].as_str().unwrap_or_default()
+        );
+    }
+    let reserve_price = auction["reserve_price"].as_i64().unwrap_or(0);
+    let seller_wallet = auction["seller_wallet"].as_str().unwrap();
+
+    let high_bid = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object('bidder_wallet', bidder_wallet, 'max_price', max_price)
+         FROM kerai.bids WHERE auction_id = '{}'::uuid AND status = 'active'
+         ORDER BY max_price DESC, created_at
+         LIMIT 1",
+        auction_id,
+    ))
+    .unwrap_or(None);
+    let high_price = high_bid.as_ref().and_then(|b| b.0["max_price"].as_i64());
+
+    let winner = match (&high_bid, high_price) {
+        (Some(bid), Some(price)) if price >= reserve_price => {
+            Some((bid.0["bidder_wallet"].as_str().unwrap().to_string(), price))
+        }
+        _ => None,
+    };
+    let Some((bidder_wallet, price)) = winner else {
+        Spi::run(&format!(
+            "UPDATE kerai.auctions SET status = 'unsold', settled_at = now() WHERE id = '{}'::uuid",
+            auction_id,
+        ))
+        .unwrap();
+        return pgrx::JsonB(serde_json::json!({
+            "auction_id": auction_id.to_string(),
+            "status": "unsold",
+            "high_bid": high_price,
+            "reserve_price": reserve_price,
+        }));
+    };
+
+    let lamport =
+        Spi::get_one::<i64>("SELECT COALESCE(max(lamport_ts), 0) + 1 FROM kerai.operations")
+            .unwrap()
+            .unwrap_or(1);
+    Spi::run(&format!(
+        "INSERT INTO kerai.ledger (from_wallet, to_wallet, amount, reason, reference_id, reference_type, timestamp)
+         VALUES ('{}'::uuid, '{}'::uuid, {}, 'auction_settlement', '{}'::uuid, 'auction', {})",
+        sql_escape(&bidder_wallet),
+        sql_escape(seller_wallet),
+        price,
+        auction_id,
+        lamport,
+    ))
+    .unwrap();
+
+    Spi::run(&format!(
+        "UPDATE kerai.auctions
+         SET status = 'settled', settled_price = {}, current_price = {}, settled_at = now()
+         WHERE id = '{}'::uuid",
+        price, price, auction_id,
+    ))
+    .unwrap();
+
+    let royalties = pay_royalties(auction_id, auction, price);
+
+    pgrx::JsonB(serde_json::json!({
+        "auction_id": auction_id.to_string(),
+        "status": "settled",
+        "settled_price": price,
+        "winner_wallet": bidder_wallet,
+        "bidder_count": 1,
+        "total_revenue": price,
+        "royalties": royalties["payouts"],
+        "seller_revenue": royalties["seller_revenue"],
+    }))
+}
+
+/// Settle a sealed-bid auction after its reveal phase. Unrevealed commitments
+/// are forfeited. The highest revealed bid wins if it meets the reserve and
+/// pays the second-highest revealed bid (or the reserve, if higher or alone).
+fn settle_sealed_auction(
+    auction_id: pgrx::Uuid,
+    auction: &serde_json::Map<String, serde_json::Value>,
+) -> pgrx::JsonB {
+    if auction["reveal_ended"].as_bool() != Some(true) {
+        error!(
+            "Sealed-bid auction is still revealing until {}",
+            auction["reveal_ends_at"].as_str().unwrap_or_default()
+        );
+    }
+    let reserve_price = auction["reserve_price"].as_i64().unwrap_or(0);
+    let seller_wallet = auction["seller_wallet"].as_str().unwrap();
+
+    let forfeited = Spi::get_one::<i64>(&format!(
+        "WITH f AS (
+            UPDATE kerai.sealed_bids SET status = 'forfeited'
+            WHERE auction_id = '{}'::uuid AND status = 'committed'
+            RETURNING 1
+        ) SELECT count(*)::bigint FROM f",
+        auction_id,
+    ))
+    .unwrap()
+    .unwrap_or(0);
+
+    let revealed = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'bidder_wallet', bidder_wallet,
+            'amount', amount
+        ) ORDER BY amount DESC, revealed_at), '[]'::jsonb)
+        FROM kerai.sealed_bids
+        WHERE auction_id = '{}'::uuid AND status = 'revealed'",
+        auction_id,
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| serde_json::json!([]));
+    let bids = revealed.as_array().cloned().unwrap_or_default();
+    let high_bid = bids.first().and_then(|b| b["amount"].as_i64());
+
+    let meets_reserve = high_bid.is_some_and(|b| b >= reserve_price);
+    let Some(winner) = bids.first().filter(|_| meets_reserve) else {
+        Spi::run(&format!(
+            "UPDATE kerai.auctions SET status = 'unsold', settled_at = now() WHERE id = '{}'::uuid",
+            auction_id,
+        ))
+        .unwrap();
+        return pgrx::JsonB(serde_json::json!({
+            "auction_id": auction_id.to_string(),
+            "status": "unsold",
+            "high_bid": high_bid,
+            "reserve_price": reserve_price,
+            "revealed": bids.len(),
+            "forfeited": forfeited,
+        }));
+    };
+    let winner_wallet = winner["bidder_wallet"].as_str().unwrap().to_string();
+    let price = bids
+        .get(1)
+        .and_then(|b| b["amount"].as_i64())
+        .unwrap_or(0)
+        .max(reserve_price)
+        .max(1);
+
+    let lamport =
+        Spi::get_one::<i64>("SELECT COALESCE(max(lamport_ts), 0) + 1 FROM kerai.operations")
+            .unwrap()
+            .unwrap_or(1);
+    Spi::run(&format!(
+        "INSERT INTO kerai.ledger (from_wallet, to_wallet, amount, reason, reference_id, reference_type, timestamp)
+         VALUES ('{}'::uuid, '{}'::uuid, {}, 'auction_settlement', '{}'::uuid, 'auction', {})",
+        sql_escape(&winner_wallet),
+        sql_escape(seller_wallet),
+        price,
+        auction_id,
+        lamport,
+    ))
+    .unwrap();
+
+    Spi::run(&format!(
+        "UPDATE kerai.auctions
+         SET status = 'settled', settled_price = {}, current_price = {}, settled_at = now()
+         WHERE id = '{}'::uuid",
+        price, price, auction_id,
+    ))
+    .unwrap();
+
+    let royalties = pay_royalties(auction_id, auction, price);
+
+    pgrx::JsonB(serde_json::json!({
+        "auction_id": auction_id.to_string(),
+        "status": "settled",
+        "settled_price": price,
+        "winner_wallet": winner_wallet,
+        "winning_bid": high_bid,
+        "revealed": bids.len(),
+        "forfeited": forfeited,
+        "bidder_count": 1,
+        "total_revenue": price,
+        "royalties": royalties["payouts"],
+        "seller_revenue": royalties["seller_revenue"],
+    }))
+}
+
+/// Set the royalty splits for an attestation owned by the self instance,
+/// replacing any existing ones. `splits` is `[{"wallet": uuid, "basis_points": n}]`;
+/// shares must total at most 10000 bps and an empty array clears them.
+#[pg_extern]
+fn set_royalties(attestation_id: pgrx::Uuid, splits: pgrx::JsonB) -> pgrx::JsonB {
+    let owned = Spi::get_one::<bool>(&format!(
+        "SELECT EXISTS(
+            SELECT 1 FROM kerai.attestations a
+            JOIN kerai.instances i ON a.instance_id = i.id
+            WHERE a.id = '{}'::uuid AND i.is_self = true
+        )",
+        attestation_id,
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if !owned {
+        error!(
+            "Attestation not found or not owned by this instance: {}",
+            attestation_id
+        );
+    }
+
+    let entries = splits
+        .0
+        .as_array()
+        .unwrap_or_else(|| error!("splits must be a JSON array"));
+    let mut parsed: Vec<(String, i64)> = Vec::with_capacity(entries.len());
+    let mut total_bps: i64 = 0;
+    for entry in entries {
+        let wallet = entry["wallet"]
+            .as_str()
+            .unwrap_or_else(|| error!("Each split needs a 'wallet'"));
+        let bps = entry["basis_points"]
+            .as_i64()
+            .unwrap_or_else(|| error!("Each split needs integer 'basis_points'"));
+        if bps <= 0 {
+            error!("basis_points must be positive, got {} for {}", bps, wallet);
+        }
+        let exists = Spi::get_one::<bool>(&format!(
+            "SELECT EXISTS(SELECT 1 FROM kerai.wallets WHERE id::text = '{}')",
+            sql_escape(wallet),
+        ))
+        .unwrap()
+        .unwrap_or(false);
+        if !exists {
+            error!("Royalty wallet not found: {}", wallet);
+        }
+        if parsed.iter().any(|(w, _)| w == wallet) {
+            error!("Duplicate royalty wallet: {}", wallet);
+        }
+        total_bps += bps;
+        parsed.push((wallet.to_string(), bps));
+    }
+    if total_bps > 10000 {
+        error!("Royalty splits total {} bps, exceeding 10000", total_bps);
+    }
+
+    Spi::run(&format!(
+        "DELETE FROM kerai.royalties WHERE attestation_id = '{}'::uuid",
+        attestation_id,
+    ))
+    .unwrap();
+    for (wallet, bps) in &parsed {
+        Spi::run(&format!(
+            "INSERT INTO kerai.royalties (attestation_id, wallet_id, basis_points)
+             VALUES ('{}'::uuid, '{}'::uuid, {})",
+            attestation_id,
+            sql_escape(wallet),
+            bps,
+        ))
+        .unwrap();
+    }
+
+    pgrx::JsonB(serde_json::json!({
+        "attestation_id": attestation_id.to_string(),
+        "splits": parsed
+            .iter()
+            .map(|(w, b)| serde_json::json!({"wallet": w, "basis_points": b}))
+            .collect::<Vec<_>>(),
+        "total_basis_points": total_bps,
+    }))
+}
+
+/// Pay the attestation's royalty splits out of the seller's settlement revenue.
+/// Each share is floored; the rounding remainder stays with the seller.
+/// Returns `{"payouts": [...], "seller_revenue": n}`.
+fn pay_royalties(
+    auction_id: pgrx::Uuid,
+    auction: &serde_json::Map<String, serde_json::Value>,
+    total_revenue: i64,
+) -> serde_json::Value {
+    let attestation_id = auction["attestation_id"].as_str().unwrap();
+    let seller_wallet = auction["seller_wallet"].as_str().unwrap();
+
+    let splits = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'wallet', wallet_id,
+            'basis_points', basis_points
+        ) ORDER BY basis_points DESC, wallet_id), '[]'::jsonb)
+        FROM kerai.royalties WHERE attestation_id = '{}'::uuid",
+        sql_escape(attestation_id),
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| serde_json::json!([]));
+
+    let mut payouts = Vec::new();
+    let mut paid: i64 = 0;
+    for split in splits.as_array().into_iter().flatten() {
+        let wallet = split["wallet"].as_str().unwrap();
+        let bps = split["basis_points"].as_i64().unwrap();
+        let amount = (total_revenue as i128 * bps as i128 / 10000) as i64;
+        if amount > 0 && wallet != seller_wallet {
+            let lamport =
+                Spi::get_one::<i64>("SELECT COALESCE(max(timestamp), 0) + 1 FROM kerai.ledger")
+                    .unwrap()
+                    .unwrap_or(1);
+            Spi::run(&format!(
+                "INSERT INTO kerai.ledger (from_wallet, to_wallet, amount, reason, reference_id, reference_type, timestamp)
+                 VALUES ('{}'::uuid, '{}'::uuid, {}, 'auction_royalty', '{}'::uuid, 'auction', {})",
+                sql_escape(seller_wallet),
+                sql_escape(wallet),
+                amount,
+                auction_id,
+                lamport,
+            ))
+            .unwrap();
+            paid += amount;
+        }
+        payouts.push(serde_json::json!({
+            "wallet": wallet,
+            "basis_points": bps,
+            "amount": amount,
+        }));
+    }
+
+    serde_json::json!({
+        "payouts": payouts,
+        "seller_revenue": total_revenue - paid,
+    })
+}
+
 /// Mark a settled auction as open-sourced (post-settlement release).
 #[pg_extern]
 fn open_source_auction(auction_id: pgrx::Uuid) -> pgrx::JsonB {
@@ -365,12 +1170,105 @@ fn open_source_auction(auction_id: pgrx::Uuid) -> pgrx::JsonB {
     }))
 }
 
+/// Push back when a settled auction's content is open-sourced.
+///
+/// Only the seller (this instance) may extend, and only while the delay is
+/// still running: before settlement, or after it but before
+/// `settled_at + open_delay_hours`. Open-sourced content can't be pulled back.
+#[pg_extern]
+fn extend_open_delay(auction_id: pgrx::Uuid, additional_hours: i32) -> pgrx::JsonB {
+    if additional_hours <= 0 {
+        error!("additional_hours must be positive");
+    }
+
+    let auction = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'status', au.status,
+            'open_sourced', au.open_sourced,
+            'elapsed', au.settled_at IS NOT NULL
+                AND au.settled_at + au.open_delay_hours * interval '1 hour' <= now(),
+            'is_seller', EXISTS(
+                SELECT 1 FROM kerai.wallets w
+                JOIN kerai.instances i ON w.instance_id = i.id
+                WHERE w.id = au.seller_wallet AND i.is_self = true
+            )
+        ) FROM kerai.auctions au WHERE au.id = '{}'::uuid",
+        auction_id,
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Auction not found: {}", auction_id));
+    let obj = &auction.0;
+
+    if !obj["is_seller"].as_bool().unwrap_or(false) {
+        error!("Only the seller can extend the open-source delay of auction {}", auction_id);
+    }
+    if obj["open_sourced"].as_bool().unwrap_or(false) {
+        error!("Auction {} has already been open-sourced", auction_id);
+    }
+    if obj["elapsed"].as_bool().unwrap_or(false) {
+        error!("Open-source delay for auction {} has already elapsed", auction_id);
+    }
+
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "UPDATE kerai.auctions
+         SET open_delay_hours = open_delay_hours + {}
+         WHERE id = '{}'::uuid
+         RETURNING jsonb_build_object(
+            'auction_id', id,
+            'status', status,
+            'open_delay_hours', open_delay_hours,
+            'open_source_at', settled_at + open_delay_hours * interval '1 hour'
+         )",
+        additional_hours, auction_id,
+    ))
+    .unwrap()
+    .unwrap();
+    row
+}
+
+/// Settled auctions whose open-source delay has elapsed, oldest due first —
+/// ready for `open_source_auction` to release them into the Koi Pond.
+#[pg_extern]
+fn open_source_due() -> pgrx::JsonB {
+    Spi::get_one::<pgrx::JsonB>(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'auction_id', d.id,
+            'attestation_id', d.attestation_id,
+            'settled_price', d.settled_price,
+            'settled_at', d.settled_at,
+            'open_delay_hours', d.open_delay_hours,
+            'due_at', d.due_at
+        ) ORDER BY d.due_at), '[]'::jsonb)
+        FROM (
+            SELECT au.*, au.settled_at + au.open_delay_hours * interval '1 hour' AS due_at
+            FROM kerai.auctions au
+            WHERE au.status = 'settled' AND NOT COALESCE(au.open_sourced, false)
+        ) d
+        WHERE d.due_at <= now()",
+    )
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])))
+}
+
 /// Browse active auctions with optional filters.
+///
+/// When `query` (an ltree path) is given, results are ranked by a relevance
+/// score instead of price. The score blends scope proximity (0.6), the
+/// attestation's `avg_weight` (0.25), and auction recency (0.15); each
+/// component is exposed alongside the total. The other arguments remain
+/// hard filters.
+///
+/// With `limit` or `cursor`, returns one page `{items, total_count, next_cursor}`
+/// in auction creation order instead (see `pagination`); relevance is still
+/// reported per item but doesn't order the page.
 #[pg_extern]
 fn market_browse(
     scope_filter: Option<&str>,
     max_price: Option<i64>,
     status_filter: Option<&str>,
+    query: default!(Option<&str>, "NULL"),
+    limit: default!(Option<i32>, "NULL"),
+    cursor: default!(Option<&str>, "NULL"),
 ) -> pgrx::JsonB {
     let mut conditions = Vec::new();
 
@@ -386,33 +1284,85 @@ fn market_browse(
         conditions.push(format!("au.current_price <= {}", price));
     }
 
-    let where_clause = if conditions.is_empty() {
-        String::new()
+    let where_clause = pagination::where_clause(&conditions);
+
+    // Scope proximity: 1/(1+level distance) along the same lineage, otherwise
+    // half the shared-prefix fraction for unrelated branches.
+    let (score_columns, order_by) = match query {
+        Some(q) => {
+            let q = format!("'{}'::ltree", sql_escape(q));
+            (
+                format!(
+                    "CASE
+                        WHEN at.scope <@ {q} OR at.scope @> {q}
+                            THEN 1.0 / (1 + abs(nlevel(at.scope) - nlevel({q})))
+                        ELSE 0.5 * nlevel(lca(at.scope, {q}))::float8
+                            / greatest(nlevel(at.scope), nlevel({q}))
+                    END AS scope_score,
+                    (least(greatest(at.avg_weight, -1.0), 1.0) + 1.0) / 2.0 AS weight_score,
+                    exp(-extract(epoch FROM now() - au.created_at) / 2592000.0) AS recency_score"
+                ),
+                "score DESC, current_price ASC",
+            )
+        }
+        None => (
+            "NULL::float8 AS scope_score, NULL::float8 AS weight_score, NULL::float8 AS recency_score"
+                .to_string(),
+            "current_price ASC",
+        ),
+    };
+
+    let relevance = if query.is_some() {
+        ", 'relevance', jsonb_build_object(
+                'score', round(score::numeric, 4),
+                'scope', round(scope_score::numeric, 4),
+                'weight', round(weight_score::numeric, 4),
+                'recency', round(recency_score::numeric, 4)
+            )"
     } else {
-        format!("WHERE {}", conditions.join(" AND "))
+        ""
     };
 
+    let object = format!(
+        "jsonb_build_object(
+                'auction_id', id,
+                'attestation_id', attestation_id,
+                'scope', scope::text,
+                'claim_type', claim_type,
+                'current_price', current_price,
+                'floor_price', floor_price,
+                'starting_price', starting_price,
+                'status', status,
+                'min_bidders', min_bidders,
+                'bid_count', bid_count,
+                'created_at', created_at{relevance}
+            )"
+    );
+    let from = format!(
+        "(
+            SELECT *, 0.6 * COALESCE(scope_score, 0)
+                    + 0.25 * COALESCE(weight_score, 0)
+                    + 0.15 * COALESCE(recency_score, 0) AS score
+            FROM (
+                SELECT au.id, au.attestation_id, at.scope, at.claim_type,
+                       au.current_price, au.floor_price, au.starting_price,
+                       au.status, au.min_bidders, au.created_at,
+                       (SELECT count(*) FROM kerai.bids b
+                        WHERE b.auction_id = au.id AND b.status = 'active') AS bid_count,
+                       {score_columns}
+                FROM kerai.auctions au
+                JOIN kerai.attestations at ON au.attestation_id = at.id
+                {where_clause}
+            ) scored
+        ) ranked"
+    );
+
+    if pagination::requested(limit, cursor) {
+        return pagination::keyset_page(&object, &from, &[], "ranked", limit, cursor);
+    }
+
     let json = Spi::get_one::<pgrx::JsonB>(&format!(
-        "SELECT COALESCE(
-            jsonb_agg(jsonb_build_object(
-                'auction_id', au.id,
-                'attestation_id', au.attestation_id,
-                'scope', at.scope::text,
-                'claim_type', at.claim_type,
-                'current_price', au.current_price,
-                'floor_price', au.floor_price,
-                'starting_price', au.starting_price,
-                'status', au.status,
-                'min_bidders', au.min_bidders,
-                'bid_count', (SELECT count(*) FROM kerai.bids b WHERE b.auction_id = au.id),
-                'created_at', au.created_at
-            ) ORDER BY au.current_price ASC),
-            '[]'::jsonb
-        )
-        FROM kerai.auctions au
-        JOIN kerai.attestations at ON au.attestation_id = at.id
-        {}",
-        where_clause,
+        "SELECT COALESCE(jsonb_agg({object} ORDER BY {order_by}), '[]'::jsonb) FROM {from}",
     ))
     .unwrap()
     .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
@@ -459,6 +1409,7 @@ fn market_status(auction_id: pgrx::Uuid) -> pgrx::JsonB {
             jsonb_agg(jsonb_build_object(
                 'id', b.id,
                 'max_price', b.max_price,
+                'status', b.status,
                 'created_at', b.created_at
             ) ORDER BY b.created_at),
             '[]'::jsonb
@@ -512,7 +1463,7 @@ fn market_balance() -> pgrx::JsonB {
 
     let active_bids = Spi::get_one::<i64>(&format!(
         "SELECT count(*)::bigint FROM kerai.bids
-         WHERE bidder_wallet = '{}'::uuid
+         WHERE bidder_wallet = '{}'::uuid AND status = 'active'
            AND auction_id IN (SELECT id FROM kerai.auctions WHERE status = 'active')",
         sql_escape(&self_wallet),
     ))
@@ -110,6 +110,11 @@ fn load_model_config(agent_id: &str) -> Result<ModelConfig, String> {
             .get("context_len")
             .and_then(|v| v.as_u64())
             .unwrap_or(16) as usize,
+        // Models stored before LayerNorm existed keep running without it
+        layer_norm: config_json
+            .get("layer_norm")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
     })
 }
 
@@ -120,6 +125,8 @@ fn bytes_to_pg_hex(bytes: &[u8]) -> String {
 
 /// Create a new MicroGPT model for an agent.
 /// Builds vocabulary from graph nodes, initializes random weights, stores to DB.
+/// With `seed`, initialization is deterministic: the same seed, config and
+/// vocabulary give identical weights. The seed is recorded in the config.
 #[pg_extern]
 fn create_model(
     agent_name: &str,
@@ -128,6 +135,7 @@ fn create_model(
     n_layers: default!(Option<i32>, "NULL"),
     context_len: default!(Option<i32>, "NULL"),
     scope: default!(Option<&str>, "NULL"),
+    seed: default!(Option<i64>, "NULL"),
 ) -> pgrx::JsonB {
     let agent_id = agent_id_by_name(agent_name).unwrap_or_else(|e| error!("{e}"));
 
@@ -145,6 +153,7 @@ fn create_model(
         n_heads: n_heads.unwrap_or(4) as usize,
         n_layers: n_layers.unwrap_or(1) as usize,
         context_len: context_len.unwrap_or(16) as usize,
+        layer_norm: true,
     };
 
     // Validate config
@@ -162,6 +171,8 @@ fn create_model(
         "n_heads": config.n_heads,
         "n_layers": config.n_layers,
         "context_len": config.context_len,
+        "layer_norm": config.layer_norm,
+        "seed": seed,
     });
     let config_sql = format!(
         "UPDATE kerai.agents SET config = '{}'::jsonb WHERE id = '{}'::uuid",
@@ -169,8 +180,8 @@ fn create_model(
     );
     Spi::run(&config_sql).unwrap_or_else(|e| error!("Failed to update agent config: {e}"));
 
-    // Initialize model with random weights
-    let model = MicroGPT::new(config.clone());
+    // Initialize model with random (or seeded) weights
+    let model = MicroGPT::with_seed(config.clone(), seed.map(|s| s as u64));
     let param_count = model.param_count();
     let param_bytes = param_count * 4;
 
@@ -187,6 +198,89 @@ fn create_model(
         "context_len": config.context_len,
         "param_count": param_count,
         "param_bytes": param_bytes,
+        "seed": seed,
+    }))
+}
+
+/// Fork a trained model: copy `base_agent`'s config, vocabulary and weights
+/// to `new_agent`, which must already exist and have no model of its own.
+/// Training the fork continues from the copied weights (fine-tuning), and
+/// its config records `forked_from` for lineage.
+#[pg_extern]
+fn fork_model(base_agent: &str, new_agent: &str) -> pgrx::JsonB {
+    let base_id = agent_id_by_name(base_agent).unwrap_or_else(|e| error!("{e}"));
+    let new_id = agent_id_by_name(new_agent).unwrap_or_else(|e| error!("{e}"));
+    if base_id == new_id {
+        error!("Cannot fork a model onto itself");
+    }
+
+    let existing_sql = format!(
+        "SELECT count(*)::int FROM kerai.model_weights WHERE agent_id = '{new_id}'::uuid"
+    );
+    let existing: i32 = Spi::get_one(&existing_sql).ok().flatten().unwrap_or(0);
+    if existing > 0 {
+        error!("Agent '{}' already has a model; delete it before forking", new_agent);
+    }
+
+    // Ensures the base actually has a model before anything is copied
+    let config = load_model_config(&base_id).unwrap_or_else(|e| error!("{e}"));
+    let version_sql = format!(
+        "SELECT max(version)::int FROM kerai.model_weights WHERE agent_id = '{base_id}'::uuid"
+    );
+    let base_version: i32 = Spi::get_one(&version_sql)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| error!("No weights found for agent '{}'", base_agent));
+
+    // Copy config, stamping lineage
+    let config_json = serde_json::json!({
+        "vocab_size": config.vocab_size,
+        "dim": config.dim,
+        "n_heads": config.n_heads,
+        "n_layers": config.n_layers,
+        "context_len": config.context_len,
+        "layer_norm": config.layer_norm,
+        "forked_from": {
+            "agent": base_agent,
+            "agent_id": base_id,
+            "weights_version": base_version,
+        },
+    });
+    let config_sql = format!(
+        "UPDATE kerai.agents SET config = config || '{}'::jsonb WHERE id = '{}'::uuid",
+        config_json.to_string().replace('\'', "''"),
+        new_id
+    );
+    Spi::run(&config_sql).unwrap_or_else(|e| error!("Failed to update agent config: {e}"));
+
+    // Copy vocabulary (replacing any stale entries) and weights
+    let del_vocab = format!(
+        "DELETE FROM kerai.model_vocab WHERE model_id = '{new_id}'::uuid"
+    );
+    let copy_vocab = format!(
+        "INSERT INTO kerai.model_vocab (model_id, node_id, token_idx)
+         SELECT '{new_id}'::uuid, node_id, token_idx
+         FROM kerai.model_vocab WHERE model_id = '{base_id}'::uuid"
+    );
+    let copy_weights = format!(
+        "INSERT INTO kerai.model_weights (agent_id, tensor_name, tensor_data, shape)
+         SELECT '{new_id}'::uuid, tensor_name, tensor_data, shape
+         FROM kerai.model_weights WHERE agent_id = '{base_id}'::uuid"
+    );
+    Spi::run(&del_vocab).unwrap_or_else(|e| error!("Failed to clear vocab: {e}"));
+    Spi::run(&copy_vocab).unwrap_or_else(|e| error!("Failed to copy vocab: {e}"));
+    Spi::run(&copy_weights).unwrap_or_else(|e| error!("Failed to copy weights: {e}"));
+
+    pgrx::JsonB(serde_json::json!({
+        "status": "forked",
+        "agent": new_agent,
+        "forked_from": base_agent,
+        "weights_version": base_version,
+        "vocab_size": config.vocab_size,
+        "dim": config.dim,
+        "n_heads": config.n_heads,
+        "n_layers": config.n_layers,
+        "context_len": config.context_len,
     }))
 }
 
@@ -360,6 +454,89 @@ fn predict_next(
     }))
 }
 
+/// Attention weights from a forward pass over `context`, per layer and head.
+/// Each head's `weights[q][k]` is how much position q attends to position k
+/// (causal, so k <= q). `last` is the final position's row — the one that
+/// drives `predict_next` — labelled by node; `attended` averages it over all
+/// layers and heads. Context nodes missing from the vocabulary are listed
+/// under `skipped`.
+#[pg_extern]
+fn model_attention(agent_name: &str, context: Vec<pgrx::Uuid>) -> pgrx::JsonB {
+    let agent_id = agent_id_by_name(agent_name).unwrap_or_else(|e| error!("{e}"));
+    let config = load_model_config(&agent_id).unwrap_or_else(|e| error!("{e}"));
+    let model = load_weights(&agent_id, &config).unwrap_or_else(|e| error!("{e}"));
+
+    let context_uuids: Vec<String> = context.into_iter().map(uuid_to_string).collect();
+    let positions = walks::uuids_to_positions(&agent_id, &context_uuids)
+        .unwrap_or_else(|e| error!("{e}"));
+
+    let mut nodes: Vec<&str> = Vec::new();
+    let mut tokens: Vec<usize> = Vec::new();
+    let mut skipped: Vec<&str> = Vec::new();
+    for (uuid, idx) in context_uuids.iter().zip(&positions) {
+        match idx {
+            Some(i) => {
+                nodes.push(uuid);
+                tokens.push(*i);
+            }
+            None => skipped.push(uuid),
+        }
+    }
+
+    if tokens.is_empty() {
+        error!("No context nodes found in model vocabulary");
+    }
+
+    // forward() only reads the first context_len tokens
+    let seq_len = tokens.len().min(config.context_len);
+    nodes.truncate(seq_len);
+    let (_, cache) = model.forward(&tokens);
+
+    let n_heads = config.n_heads;
+    let mut attended = vec![0.0f64; seq_len];
+    let layers: Vec<serde_json::Value> = cache
+        .layer_caches
+        .iter()
+        .enumerate()
+        .map(|(l, lc)| {
+            let heads: Vec<serde_json::Value> = (0..n_heads)
+                .map(|h| {
+                    let head = &lc.attn_weights.data[h * seq_len * seq_len..(h + 1) * seq_len * seq_len];
+                    let weights: Vec<&[f32]> = head.chunks(seq_len).collect();
+                    let last = weights[seq_len - 1];
+                    for (k, w) in last.iter().enumerate() {
+                        attended[k] += *w as f64;
+                    }
+                    serde_json::json!({
+                        "head": h,
+                        "weights": weights,
+                        "last": nodes.iter().zip(last).map(|(node, w)| {
+                            serde_json::json!({"node_id": node, "weight": w})
+                        }).collect::<Vec<_>>(),
+                    })
+                })
+                .collect();
+            serde_json::json!({"layer": l, "heads": heads})
+        })
+        .collect();
+
+    let total_heads = (cache.layer_caches.len() * n_heads).max(1) as f64;
+
+    pgrx::JsonB(serde_json::json!({
+        "agent": agent_name,
+        "context": nodes.iter().enumerate().map(|(pos, node)| {
+            serde_json::json!({"position": pos, "node_id": node})
+        }).collect::<Vec<_>>(),
+        "skipped": skipped,
+        "n_layers": cache.layer_caches.len(),
+        "n_heads": n_heads,
+        "layers": layers,
+        "attended": nodes.iter().zip(&attended).map(|(node, w)| {
+            serde_json::json!({"node_id": node, "weight": w / total_heads})
+        }).collect::<Vec<_>>(),
+    }))
+}
+
 /// FTS candidates re-ranked by neural model.
 #[pg_extern]
 fn neural_search(
@@ -376,11 +553,11 @@ fn neural_search(
     // Get FTS candidates
     let escaped_query = query_text.replace('\'', "''");
     let fts_sql = format!(
-        "SELECT id::text, ts_rank(to_tsvector('english', COALESCE(content, '')),
+        "SELECT id::text, ts_rank(content_tsv,
                                   plainto_tsquery('english', '{}')) AS rank,
                 kind, path::text
          FROM kerai.nodes
-         WHERE to_tsvector('english', COALESCE(content, ''))
+         WHERE content_tsv
                @@ plainto_tsquery('english', '{}')
          ORDER BY rank DESC
          LIMIT {}",
@@ -642,17 +819,32 @@ fn model_info(agent_name: &str) -> pgrx::JsonB {
     let param_count = {
         let dim = config.dim;
         let n_layers = config.n_layers;
-        let per_layer = 4 * dim * dim + dim * 4 * dim + 4 * dim * dim + 2 * dim;
-        config.vocab_size * dim + config.context_len * dim + n_layers * per_layer + dim
+        // Each LayerNorm has a gain and a bias of `dim` each
+        let per_layer = 4 * dim * dim + dim * 4 * dim + 4 * dim * dim + 4 * dim;
+        config.vocab_size * dim + config.context_len * dim + n_layers * per_layer + 2 * dim
     };
 
+    // Lineage, if this model was forked from another agent's, and the init seed
+    let agent_config_sql =
+        format!("SELECT config FROM kerai.agents WHERE id = '{agent_id}'::uuid");
+    let agent_config = Spi::get_one::<pgrx::JsonB>(&agent_config_sql)
+        .ok()
+        .flatten()
+        .map(|j| j.0)
+        .unwrap_or(serde_json::Value::Null);
+    let forked_from = agent_config["forked_from"].clone();
+    let seed = agent_config["seed"].clone();
+
     pgrx::JsonB(serde_json::json!({
         "agent": agent_name,
+        "forked_from": forked_from,
+        "seed": seed,
         "vocab_size": config.vocab_size,
         "dim": config.dim,
         "n_heads": config.n_heads,
         "n_layers": config.n_layers,
         "context_len": config.context_len,
+        "layer_norm": config.layer_norm,
         "param_count": param_count,
         "weight_bytes": total_bytes,
         "weight_tensors": weight_count,
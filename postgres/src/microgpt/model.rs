@@ -1,5 +1,8 @@
 use std::collections::HashMap;
 
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
 use super::optimizer::Adam;
 use super::tensor::Tensor;
 
@@ -11,6 +14,9 @@ pub struct ModelConfig {
     pub n_heads: usize,
     pub n_layers: usize,
     pub context_len: usize,
+    /// Apply LayerNorm before attention, the MLP and the lm_head. When false
+    /// the norms are identity (their parameters are kept but unused).
+    pub layer_norm: bool,
 }
 
 impl Default for ModelConfig {
@@ -21,21 +27,109 @@ impl Default for ModelConfig {
             n_heads: 4,
             n_layers: 1,
             context_len: 16,
+            layer_norm: true,
         }
     }
 }
 
+const NORM_EPS: f32 = 1e-5;
+
+/// Layer normalization over the last dimension with a learnable gain and
+/// bias: y = (x - mean) / sqrt(var + eps) * gain + bias.
+#[derive(Clone)]
+pub struct LayerNorm {
+    pub gain: Tensor, // [dim]
+    pub bias: Tensor, // [dim]
+}
+
+impl LayerNorm {
+    /// Identity-initialized norm: gain 1, bias 0.
+    pub fn new(dim: usize) -> Self {
+        Self {
+            gain: Tensor::ones(&[dim]),
+            bias: Tensor::zeros(&[dim]),
+        }
+    }
+
+    pub fn numel(&self) -> usize {
+        self.gain.numel() + self.bias.numel()
+    }
+
+    /// Normalize each row of `x` [..., dim].
+    pub fn forward(&self, x: &Tensor) -> Tensor {
+        let dim = self.gain.numel();
+        let mut data = vec![0.0f32; x.data.len()];
+        for (row, out) in x.data.chunks(dim).zip(data.chunks_mut(dim)) {
+            let (mean, inv_std) = Self::moments(row);
+            for j in 0..dim {
+                out[j] = (row[j] - mean) * inv_std * self.gain.data[j] + self.bias.data[j];
+            }
+        }
+        Tensor {
+            data,
+            shape: x.shape.clone(),
+        }
+    }
+
+    /// Exact gradients given the forward input `x` and upstream `d_y`.
+    /// Returns (d_x, d_gain, d_bias).
+    pub fn backward(&self, x: &Tensor, d_y: &Tensor) -> (Tensor, Tensor, Tensor) {
+        let dim = self.gain.numel();
+        let mut d_x = vec![0.0f32; x.data.len()];
+        let mut d_gain = Tensor::zeros(&[dim]);
+        let mut d_bias = Tensor::zeros(&[dim]);
+        let mut x_hat = vec![0.0f32; dim];
+        let mut d_x_hat = vec![0.0f32; dim];
+        for ((row, dy), dx) in x
+            .data
+            .chunks(dim)
+            .zip(d_y.data.chunks(dim))
+            .zip(d_x.chunks_mut(dim))
+        {
+            let (mean, inv_std) = Self::moments(row);
+            for j in 0..dim {
+                x_hat[j] = (row[j] - mean) * inv_std;
+                d_x_hat[j] = dy[j] * self.gain.data[j];
+                d_gain.data[j] += dy[j] * x_hat[j];
+                d_bias.data[j] += dy[j];
+            }
+            // dx = (dx_hat - mean(dx_hat) - x_hat * mean(dx_hat * x_hat)) / std
+            let mean_d = d_x_hat.iter().sum::<f32>() / dim as f32;
+            let mean_dx = d_x_hat.iter().zip(&x_hat).map(|(d, h)| d * h).sum::<f32>() / dim as f32;
+            for j in 0..dim {
+                dx[j] = (d_x_hat[j] - mean_d - x_hat[j] * mean_dx) * inv_std;
+            }
+        }
+        (
+            Tensor {
+                data: d_x,
+                shape: x.shape.clone(),
+            },
+            d_gain,
+            d_bias,
+        )
+    }
+
+    /// Row mean and 1 / sqrt(variance + eps).
+    fn moments(row: &[f32]) -> (f32, f32) {
+        let n = row.len() as f32;
+        let mean = row.iter().sum::<f32>() / n;
+        let var = row.iter().map(|&v| (v - mean) * (v - mean)).sum::<f32>() / n;
+        (mean, 1.0 / (var + NORM_EPS).sqrt())
+    }
+}
+
 /// A single transformer layer.
 #[derive(Clone)]
 pub struct TransformerLayer {
-    pub q_proj: Tensor,  // [dim, dim]
-    pub k_proj: Tensor,  // [dim, dim]
-    pub v_proj: Tensor,  // [dim, dim]
-    pub o_proj: Tensor,  // [dim, dim]
-    pub ff_up: Tensor,   // [dim, 4*dim]
-    pub ff_down: Tensor, // [4*dim, dim]
-    pub norm1: Tensor,   // [dim]
-    pub norm2: Tensor,   // [dim]
+    pub q_proj: Tensor,   // [dim, dim]
+    pub k_proj: Tensor,   // [dim, dim]
+    pub v_proj: Tensor,   // [dim, dim]
+    pub o_proj: Tensor,   // [dim, dim]
+    pub ff_up: Tensor,    // [dim, 4*dim]
+    pub ff_down: Tensor,  // [4*dim, dim]
+    pub norm1: LayerNorm, // before attention
+    pub norm2: LayerNorm, // before the MLP
 }
 
 /// MicroGPT: a tiny causal transformer over graph nodes.
@@ -45,7 +139,7 @@ pub struct MicroGPT {
     pub token_emb: Tensor, // [vocab_size, dim]
     pub pos_emb: Tensor,   // [context_len, dim]
     pub layers: Vec<TransformerLayer>,
-    pub final_norm: Tensor, // [dim]
+    pub final_norm: LayerNorm,
     // lm_head is weight-tied to token_emb (transposed)
 }
 
@@ -53,6 +147,7 @@ pub struct MicroGPT {
 pub struct ForwardCache {
     pub embedded: Tensor,                                                     // [seq, dim]
     pub layer_caches: Vec<LayerCache>,
+    pub final_input: Tensor,                                                  // before final norm [seq, dim]
     pub final_normed: Tensor,                                                 // [seq, dim]
     pub logits: Tensor,                                                       // [seq, vocab]
 }
@@ -74,25 +169,38 @@ pub struct LayerCache {
 impl MicroGPT {
     /// Initialize with Xavier random weights.
     pub fn new(config: ModelConfig) -> Self {
+        Self::init(config, &mut rand::thread_rng())
+    }
+
+    /// Initialize with Xavier weights from a seeded RNG, or randomly when
+    /// `seed` is None. The same seed and config always give the same weights.
+    pub fn with_seed(config: ModelConfig, seed: Option<u64>) -> Self {
+        match seed {
+            Some(seed) => Self::init(config, &mut StdRng::seed_from_u64(seed)),
+            None => Self::new(config),
+        }
+    }
+
+    fn init(config: ModelConfig, rng: &mut impl Rng) -> Self {
         let dim = config.dim;
         let layers = (0..config.n_layers)
             .map(|_| TransformerLayer {
-                q_proj: Tensor::randn_xavier(&[dim, dim]),
-                k_proj: Tensor::randn_xavier(&[dim, dim]),
-                v_proj: Tensor::randn_xavier(&[dim, dim]),
-                o_proj: Tensor::randn_xavier(&[dim, dim]),
-                ff_up: Tensor::randn_xavier(&[dim, 4 * dim]),
-                ff_down: Tensor::randn_xavier(&[4 * dim, dim]),
-                norm1: Tensor::ones(&[dim]),
-                norm2: Tensor::ones(&[dim]),
+                q_proj: Tensor::randn_xavier_with(&[dim, dim], rng),
+                k_proj: Tensor::randn_xavier_with(&[dim, dim], rng),
+                v_proj: Tensor::randn_xavier_with(&[dim, dim], rng),
+                o_proj: Tensor::randn_xavier_with(&[dim, dim], rng),
+                ff_up: Tensor::randn_xavier_with(&[dim, 4 * dim], rng),
+                ff_down: Tensor::randn_xavier_with(&[4 * dim, dim], rng),
+                norm1: LayerNorm::new(dim),
+                norm2: LayerNorm::new(dim),
             })
             .collect();
 
         Self {
-            token_emb: Tensor::randn_xavier(&[config.vocab_size, dim]),
-            pos_emb: Tensor::randn_xavier(&[config.context_len, dim]),
+            token_emb: Tensor::randn_xavier_with(&[config.vocab_size, dim], rng),
+            pos_emb: Tensor::randn_xavier_with(&[config.context_len, dim], rng),
             layers,
-            final_norm: Tensor::ones(&[dim]),
+            final_norm: LayerNorm::new(dim),
             config,
         }
     }
@@ -125,10 +233,13 @@ impl MicroGPT {
             p.extend_from_slice(&layer.o_proj.data);
             p.extend_from_slice(&layer.ff_up.data);
             p.extend_from_slice(&layer.ff_down.data);
-            p.extend_from_slice(&layer.norm1.data);
-            p.extend_from_slice(&layer.norm2.data);
+            p.extend_from_slice(&layer.norm1.gain.data);
+            p.extend_from_slice(&layer.norm1.bias.data);
+            p.extend_from_slice(&layer.norm2.gain.data);
+            p.extend_from_slice(&layer.norm2.bias.data);
         }
-        p.extend_from_slice(&self.final_norm.data);
+        p.extend_from_slice(&self.final_norm.gain.data);
+        p.extend_from_slice(&self.final_norm.bias.data);
         p
     }
 
@@ -150,10 +261,13 @@ impl MicroGPT {
             read(&mut layer.o_proj);
             read(&mut layer.ff_up);
             read(&mut layer.ff_down);
-            read(&mut layer.norm1);
-            read(&mut layer.norm2);
+            read(&mut layer.norm1.gain);
+            read(&mut layer.norm1.bias);
+            read(&mut layer.norm2.gain);
+            read(&mut layer.norm2.bias);
         }
-        read(&mut self.final_norm);
+        read(&mut self.final_norm.gain);
+        read(&mut self.final_norm.bias);
     }
 
     /// Forward pass: token indices → logits [seq_len, vocab_size].
@@ -175,8 +289,8 @@ impl MicroGPT {
         for layer in &self.layers {
             let input = x.clone();
 
-            // Pre-norm (RMSNorm)
-            let normed1 = x.rms_norm(&layer.norm1, 1e-5);
+            // Pre-norm
+            let normed1 = self.norm(&layer.norm1, &x);
             let normed1_2d = normed1.as_2d(); // [seq, dim]
 
             // Multi-head self-attention
@@ -251,7 +365,7 @@ impl MicroGPT {
             let post_attn = input.add(&attn_proj);
 
             // Pre-norm for FFN
-            let normed2 = post_attn.rms_norm(&layer.norm2, 1e-5);
+            let normed2 = self.norm(&layer.norm2, &post_attn);
             let normed2_2d = normed2.as_2d();
 
             // Feed-forward: up → relu → down
@@ -281,7 +395,7 @@ impl MicroGPT {
         }
 
         // Final norm
-        let final_normed = x.rms_norm(&self.final_norm, 1e-5);
+        let final_normed = self.norm(&self.final_norm, &x);
 
         // Logits via weight-tied lm_head: [seq, dim] x [dim, vocab] = [seq, vocab]
         let lm_head = self.token_emb.transpose(); // [dim, vocab]
@@ -290,6 +404,7 @@ impl MicroGPT {
         let cache = ForwardCache {
             embedded,
             layer_caches,
+            final_input: x,
             final_normed,
             logits: logits.clone(),
         };
@@ -342,40 +457,10 @@ impl MicroGPT {
         // d_final_normed = d_logits @ token_emb  [seq, vocab] x [vocab, dim] = [seq, dim]
         let mut d_x = d_logits.matmul(&self.token_emb);
 
-        // --- d_final_norm (simplified RMSNorm grad — pass through scale) ---
-        let mut d_final_norm = Tensor::zeros(&[dim]);
-        {
-            let x_pre = if self.config.n_layers > 0 {
-                // x before final_norm is the output of the last layer
-                let last = &cache.layer_caches[self.config.n_layers - 1];
-                last.post_attn.add(&cache.layer_caches[self.config.n_layers - 1].ff_hidden.matmul(
-                    &self.layers[self.config.n_layers - 1].ff_down,
-                ))
-            } else {
-                cache.embedded.clone()
-            };
-            // Approximate: d_gamma_j = sum_over_positions(d_y_j * x_j / rms)
-            for i in 0..seq_len {
-                let slice = &x_pre.data[i * dim..(i + 1) * dim];
-                let rms =
-                    (slice.iter().map(|&v| v * v).sum::<f32>() / dim as f32 + 1e-5).sqrt();
-                for j in 0..dim {
-                    d_final_norm.data[j] += d_x.data[i * dim + j] * slice[j] / rms;
-                }
-            }
-            // Propagate d_x through RMSNorm (simplified: scale by gamma/rms)
-            let mut d_x_through = Tensor::zeros(&[seq_len, dim]);
-            for i in 0..seq_len {
-                let slice = &x_pre.data[i * dim..(i + 1) * dim];
-                let rms =
-                    (slice.iter().map(|&v| v * v).sum::<f32>() / dim as f32 + 1e-5).sqrt();
-                for j in 0..dim {
-                    d_x_through.data[i * dim + j] =
-                        d_x.data[i * dim + j] * self.final_norm.data[j] / rms;
-                }
-            }
-            d_x = d_x_through;
-        }
+        // --- Final norm ---
+        let (d_pre_final, d_final_norm) =
+            self.norm_backward(&self.final_norm, &cache.final_input, &d_x);
+        d_x = d_pre_final;
 
         // --- Layer gradients (reverse order) ---
         let mut layer_grads: Vec<LayerGrads> = Vec::with_capacity(self.config.n_layers);
@@ -403,28 +488,9 @@ impl MicroGPT {
             // d_normed2 = d_ff_pre_relu @ ff_up^T  [seq, 4*dim] x [4*dim, dim] = [seq, dim]
             let d_normed2 = d_ff_pre_relu.matmul(&layer.ff_up.transpose());
 
-            // d_norm2 (RMSNorm gamma grad)
-            let mut d_norm2 = Tensor::zeros(&[dim]);
-            for i in 0..seq_len {
-                let slice = &lc.post_attn.data[i * dim..(i + 1) * dim];
-                let rms =
-                    (slice.iter().map(|&v| v * v).sum::<f32>() / dim as f32 + 1e-5).sqrt();
-                for j in 0..dim {
-                    d_norm2.data[j] += d_normed2.data[i * dim + j] * slice[j] / rms;
-                }
-            }
-
-            // Propagate through norm2 to post_attn
-            let mut d_post_attn = d_x.clone(); // residual path
-            for i in 0..seq_len {
-                let slice = &lc.post_attn.data[i * dim..(i + 1) * dim];
-                let rms =
-                    (slice.iter().map(|&v| v * v).sum::<f32>() / dim as f32 + 1e-5).sqrt();
-                for j in 0..dim {
-                    d_post_attn.data[i * dim + j] +=
-                        d_normed2.data[i * dim + j] * layer.norm2.data[j] / rms;
-                }
-            }
+            // Through norm2, plus the residual path, to post_attn
+            let (d_norm2_in, d_norm2) = self.norm_backward(&layer.norm2, &lc.post_attn, &d_normed2);
+            let d_post_attn = d_x.add(&d_norm2_in);
 
             // --- Attention residual: d_post_attn flows to input and attn_proj ---
             let d_attn_proj = d_post_attn.clone(); // [seq, dim]
@@ -499,28 +565,9 @@ impl MicroGPT {
                 .add(&d_k.matmul(&layer.k_proj.transpose()))
                 .add(&d_v.matmul(&layer.v_proj.transpose()));
 
-            // d_norm1 (RMSNorm gamma grad)
-            let mut d_norm1 = Tensor::zeros(&[dim]);
-            for i in 0..seq_len {
-                let slice = &lc.input.data[i * dim..(i + 1) * dim];
-                let rms =
-                    (slice.iter().map(|&v| v * v).sum::<f32>() / dim as f32 + 1e-5).sqrt();
-                for j in 0..dim {
-                    d_norm1.data[j] += d_normed1.data[i * dim + j] * slice[j] / rms;
-                }
-            }
-
-            // Propagate through norm1 to layer input
-            let mut d_input = d_post_attn; // residual path
-            for i in 0..seq_len {
-                let slice = &lc.input.data[i * dim..(i + 1) * dim];
-                let rms =
-                    (slice.iter().map(|&v| v * v).sum::<f32>() / dim as f32 + 1e-5).sqrt();
-                for j in 0..dim {
-                    d_input.data[i * dim + j] +=
-                        d_normed1.data[i * dim + j] * layer.norm1.data[j] / rms;
-                }
-            }
+            // Through norm1, plus the residual path, to the layer input
+            let (d_norm1_in, d_norm1) = self.norm_backward(&layer.norm1, &lc.input, &d_normed1);
+            let d_input = d_post_attn.add(&d_norm1_in);
 
             d_x = d_input;
 
@@ -578,13 +625,41 @@ impl MicroGPT {
             grads.extend_from_slice(&lg.d_o_proj.data);
             grads.extend_from_slice(&lg.d_ff_up.data);
             grads.extend_from_slice(&lg.d_ff_down.data);
-            grads.extend_from_slice(&lg.d_norm1.data);
-            grads.extend_from_slice(&lg.d_norm2.data);
+            grads.extend_from_slice(&lg.d_norm1.0.data);
+            grads.extend_from_slice(&lg.d_norm1.1.data);
+            grads.extend_from_slice(&lg.d_norm2.0.data);
+            grads.extend_from_slice(&lg.d_norm2.1.data);
         }
-        grads.extend_from_slice(&d_final_norm.data);
+        grads.extend_from_slice(&d_final_norm.0.data);
+        grads.extend_from_slice(&d_final_norm.1.data);
         grads
     }
 
+    /// Apply `norm` to `x`, or pass `x` through when layer norm is disabled.
+    fn norm(&self, norm: &LayerNorm, x: &Tensor) -> Tensor {
+        if self.config.layer_norm {
+            norm.forward(x)
+        } else {
+            x.clone()
+        }
+    }
+
+    /// Backward of `norm`: returns d_x and (d_gain, d_bias).
+    fn norm_backward(
+        &self,
+        norm: &LayerNorm,
+        x: &Tensor,
+        d_y: &Tensor,
+    ) -> (Tensor, (Tensor, Tensor)) {
+        if self.config.layer_norm {
+            let (d_x, d_gain, d_bias) = norm.backward(x, d_y);
+            (d_x, (d_gain, d_bias))
+        } else {
+            let dim = norm.gain.numel();
+            (d_y.clone(), (Tensor::zeros(&[dim]), Tensor::zeros(&[dim])))
+        }
+    }
+
     /// Backward pass with explicit token indices for proper embedding gradients.
     pub fn backward_with_tokens(
         &self,
@@ -646,7 +721,8 @@ impl MicroGPT {
         let mut map = HashMap::new();
         map.insert("token_emb".to_string(), self.token_emb.clone());
         map.insert("pos_emb".to_string(), self.pos_emb.clone());
-        map.insert("final_norm".to_string(), self.final_norm.clone());
+        map.insert("final_norm".to_string(), self.final_norm.gain.clone());
+        map.insert("final_norm.bias".to_string(), self.final_norm.bias.clone());
         for (i, layer) in self.layers.iter().enumerate() {
             map.insert(format!("layer{}.q_proj", i), layer.q_proj.clone());
             map.insert(format!("layer{}.k_proj", i), layer.k_proj.clone());
@@ -654,17 +730,32 @@ impl MicroGPT {
             map.insert(format!("layer{}.o_proj", i), layer.o_proj.clone());
             map.insert(format!("layer{}.ff_up", i), layer.ff_up.clone());
             map.insert(format!("layer{}.ff_down", i), layer.ff_down.clone());
-            map.insert(format!("layer{}.norm1", i), layer.norm1.clone());
-            map.insert(format!("layer{}.norm2", i), layer.norm2.clone());
+            map.insert(format!("layer{}.norm1", i), layer.norm1.gain.clone());
+            map.insert(format!("layer{}.norm1.bias", i), layer.norm1.bias.clone());
+            map.insert(format!("layer{}.norm2", i), layer.norm2.gain.clone());
+            map.insert(format!("layer{}.norm2.bias", i), layer.norm2.bias.clone());
         }
         map
     }
 
-    /// Deserialize model from a weight map.
+    /// Deserialize model from a weight map. Norm gains are stored under the
+    /// norm's name and biases under `<name>.bias`; a missing bias (weights
+    /// saved before norms had one) loads as zeros.
     pub fn from_weight_map(config: ModelConfig, map: &HashMap<String, Tensor>) -> Self {
         let token_emb = map.get("token_emb").expect("missing token_emb").clone();
         let pos_emb = map.get("pos_emb").expect("missing pos_emb").clone();
-        let final_norm = map.get("final_norm").expect("missing final_norm").clone();
+        let norm = |name: &str| {
+            let gain = map
+                .get(name)
+                .unwrap_or_else(|| panic!("missing {}", name))
+                .clone();
+            let bias = map
+                .get(&format!("{}.bias", name))
+                .cloned()
+                .unwrap_or_else(|| Tensor::zeros(&gain.shape));
+            LayerNorm { gain, bias }
+        };
+        let final_norm = norm("final_norm");
         let layers = (0..config.n_layers)
             .map(|i| TransformerLayer {
                 q_proj: map
@@ -691,14 +782,8 @@ impl MicroGPT {
                     .get(&format!("layer{}.ff_down", i))
                     .expect("missing ff_down")
                     .clone(),
-                norm1: map
-                    .get(&format!("layer{}.norm1", i))
-                    .expect("missing norm1")
-                    .clone(),
-                norm2: map
-                    .get(&format!("layer{}.norm2", i))
-                    .expect("missing norm2")
-                    .clone(),
+                norm1: norm(&format!("layer{}.norm1", i)),
+                norm2: norm(&format!("layer{}.norm2", i)),
             })
             .collect();
 
@@ -758,6 +843,6 @@ struct LayerGrads {
     d_o_proj: Tensor,
     d_ff_up: Tensor,
     d_ff_down: Tensor,
-    d_norm1: Tensor,
-    d_norm2: Tensor,
+    d_norm1: (Tensor, Tensor), // (gain, bias)
+    d_norm2: (Tensor, Tensor),
 }
@@ -26,13 +26,13 @@ impl Tensor {
         }
     }
 
-    /// Xavier-initialized random tensor: N(0, sqrt(2 / (fan_in + fan_out))).
-    pub fn randn_xavier(shape: &[usize]) -> Self {
+    /// Xavier-initialized random tensor drawing from `rng`:
+    /// N(0, sqrt(2 / (fan_in + fan_out))). A seeded `rng` gives reproducible weights.
+    pub fn randn_xavier_with(shape: &[usize], rng: &mut impl Rng) -> Self {
         let n: usize = shape.iter().product();
         let fan_in = if shape.len() >= 2 { shape[shape.len() - 1] } else { shape[0] };
         let fan_out = shape[0];
         let std = (2.0 / (fan_in + fan_out) as f64).sqrt() as f32;
-        let mut rng = rand::thread_rng();
         let data: Vec<f32> = (0..n)
             .map(|_| {
                 // Box-Muller transform for normal distribution
@@ -217,27 +217,6 @@ impl Tensor {
         }
     }
 
-    /// RMSNorm: x / rms(x) * gamma, where rms = sqrt(mean(x^2) + eps).
-    /// `gamma` has shape [dim], self has shape [..., dim].
-    pub fn rms_norm(&self, gamma: &Tensor, eps: f32) -> Tensor {
-        let dim = *self.shape.last().unwrap();
-        assert_eq!(gamma.data.len(), dim);
-        let n = self.data.len() / dim;
-        let mut data = vec![0.0f32; self.data.len()];
-        for i in 0..n {
-            let start = i * dim;
-            let slice = &self.data[start..start + dim];
-            let rms = (slice.iter().map(|&x| x * x).sum::<f32>() / dim as f32 + eps).sqrt();
-            for j in 0..dim {
-                data[start + j] = slice[j] / rms * gamma.data[j];
-            }
-        }
-        Tensor {
-            data,
-            shape: self.shape.clone(),
-        }
-    }
-
     /// Cross-entropy loss for next-token prediction.
     /// `logits` is [seq_len, vocab_size], `targets` is a slice of target indices.
     /// Returns average loss over the sequence.
@@ -445,21 +424,4 @@ mod tests {
         assert_eq!(tr.shape, vec![3, 2]);
         assert_eq!(tr.data, vec![1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
     }
-
-    #[test]
-    fn test_rms_norm() {
-        let x = Tensor {
-            data: vec![1.0, 2.0, 3.0, 4.0],
-            shape: vec![2, 2],
-        };
-        let gamma = Tensor {
-            data: vec![1.0, 1.0],
-            shape: vec![2],
-        };
-        let normed = x.rms_norm(&gamma, 1e-5);
-        // rms([1,2]) = sqrt((1+4)/2) = sqrt(2.5) ≈ 1.5811
-        let rms = (2.5f32 + 1e-5).sqrt();
-        assert!((normed.data[0] - 1.0 / rms).abs() < 1e-4);
-        assert!((normed.data[1] - 2.0 / rms).abs() < 1e-4);
-    }
 }
@@ -93,6 +93,40 @@ pub fn uuids_to_indices(agent_id: &str, uuids: &[String]) -> Result<Vec<usize>,
     Ok(indices)
 }
 
+/// Map each UUID to its token index, keeping order and duplicates.
+/// Nodes outside the model vocabulary map to `None`.
+pub fn uuids_to_positions(agent_id: &str, uuids: &[String]) -> Result<Vec<Option<usize>>, String> {
+    if uuids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let uuid_list: String = uuids
+        .iter()
+        .map(|u| format!("'{}'::uuid", u.replace('\'', "''")))
+        .collect::<Vec<_>>()
+        .join(",");
+    let sql = format!(
+        "SELECT node_id::text, token_idx FROM kerai.model_vocab
+         WHERE model_id = '{agent_id}'::uuid AND node_id IN ({uuid_list})"
+    );
+
+    let mut vocab = std::collections::HashMap::new();
+    Spi::connect(|client| {
+        let tup_table = client
+            .select(&sql, None, &[])
+            .map_err(|e| format!("SPI error: {e}"))?;
+        for row in tup_table {
+            let node = row.get_by_name::<String, _>("node_id").ok().flatten();
+            let idx = row.get_by_name::<i32, _>("token_idx").ok().flatten();
+            if let (Some(node), Some(idx)) = (node, idx) {
+                vocab.insert(node, idx as usize);
+            }
+        }
+        Ok::<(), String>(())
+    })?;
+
+    Ok(uuids.iter().map(|u| vocab.get(u).copied()).collect())
+}
+
 /// Map (token_index, probability) pairs back to (UUID, probability).
 pub fn indices_to_uuids(
     agent_id: &str,
@@ -0,0 +1,86 @@
+/// Keyset pagination for the `list_*` functions.
+///
+/// Pages are ordered by `(created_at, id)` rather than by offset, so rows
+/// inserted while a caller is paging can't shift later pages into duplicates
+/// or gaps. The cursor is the opaque `created_at|id` of the last row served.
+use pgrx::prelude::*;
+
+use crate::sql::{sql_escape, sql_uuid};
+
+/// Page size used when only a cursor is given.
+pub(crate) const DEFAULT_PAGE_SIZE: i32 = 50;
+
+/// Whether the caller asked for a page rather than the whole array.
+pub(crate) fn requested(limit: Option<i32>, cursor: Option<&str>) -> bool {
+    limit.is_some() || cursor.is_some()
+}
+
+/// One page of `object` (a jsonb expression) over `from`, filtered by
+/// `conditions` and keyed on `{key}.created_at, {key}.id`.
+///
+/// Returns `{items, total_count, next_cursor}`; `total_count` counts every
+/// row matching `conditions` and `next_cursor` is null on the last page.
+pub(crate) fn keyset_page(
+    object: &str,
+    from: &str,
+    conditions: &[String],
+    key: &str,
+    limit: Option<i32>,
+    cursor: Option<&str>,
+) -> pgrx::JsonB {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, 1000);
+
+    let filter = where_clause(conditions);
+    let mut page_conditions = conditions.to_vec();
+    if let Some(cursor) = cursor {
+        let (created_at, id) = parse_cursor(cursor);
+        page_conditions.push(format!(
+            "({key}.created_at, {key}.id) > ('{}'::timestamptz, {})",
+            sql_escape(created_at),
+            sql_uuid(id),
+        ));
+    }
+    let page_filter = where_clause(&page_conditions);
+
+    Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'items', COALESCE(jsonb_agg(obj ORDER BY rn) FILTER (WHERE rn <= {limit}), '[]'::jsonb),
+            'total_count', (SELECT count(*) FROM {from} {filter}),
+            'next_cursor', CASE WHEN count(*) > {limit}
+                                THEN max(row_cursor) FILTER (WHERE rn = {limit}) END
+        )
+        FROM (
+            SELECT {object} AS obj,
+                   {key}.created_at::text || '|' || {key}.id::text AS row_cursor,
+                   row_number() OVER (ORDER BY {key}.created_at, {key}.id) AS rn
+            FROM {from} {page_filter}
+            ORDER BY {key}.created_at, {key}.id
+            LIMIT {}
+        ) page",
+        limit + 1,
+    ))
+    .unwrap()
+    .unwrap()
+}
+
+/// `WHERE a AND b` for the given conditions, or nothing when there are none.
+pub(crate) fn where_clause(conditions: &[String]) -> String {
+    if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    }
+}
+
+/// Split a `created_at|id` cursor, rejecting anything malformed.
+fn parse_cursor(cursor: &str) -> (&str, &str) {
+    match cursor.rsplit_once('|') {
+        Some((created_at, id)) if !created_at.is_empty() && uuid::Uuid::parse_str(id).is_ok() => {
+            (created_at, id)
+        }
+        _ => error!(
+            "Invalid cursor '{}': expected the next_cursor of a previous page",
+            cursor
+        ),
+    }
+}
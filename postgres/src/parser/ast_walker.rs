@@ -1,7 +1,10 @@
 /// Recursive AST walker that converts syn types into NodeRow/EdgeRow vectors.
+use std::collections::HashMap;
+
 use serde_json::{json, Value};
 use uuid::Uuid;
 
+use super::doctest_extractor;
 use super::kinds::Kind;
 use super::metadata;
 use super::path_builder::PathContext;
@@ -53,6 +56,11 @@ impl WalkCtx {
         span_end: Option<i32>,
     ) -> String {
         let id = Uuid::new_v4().to_string();
+        let mut meta = meta;
+        if let (Some(start), Some(end), Value::Object(ref mut m)) = (span_start, span_end, &mut meta) {
+            m.insert("start_line".into(), json!(start));
+            m.insert("end_line".into(), json!(end));
+        }
         self.nodes.push(NodeRow {
             id: id.clone(),
             instance_id: self.instance_id.clone(),
@@ -118,6 +126,26 @@ pub fn walk_file(
     instance_id: &str,
     path_ctx: PathContext,
 ) -> (Vec<NodeRow>, Vec<EdgeRow>) {
+    let mut out = (Vec::new(), Vec::new());
+    walk_file_batched(file, file_node_id, instance_id, path_ctx, usize::MAX, |nodes, edges| {
+        out.0.extend(nodes);
+        out.1.extend(edges);
+    });
+    out
+}
+
+/// Walk a syn::File, handing rows to `sink` in batches of whole top-level
+/// items once at least `batch_size` nodes have accumulated.
+///
+/// Edges never cross items, so each batch can be inserted on its own.
+pub fn walk_file_batched(
+    file: &syn::File,
+    file_node_id: &str,
+    instance_id: &str,
+    path_ctx: PathContext,
+    batch_size: usize,
+    mut sink: impl FnMut(Vec<NodeRow>, Vec<EdgeRow>),
+) {
     let mut ctx = WalkCtx {
         instance_id: instance_id.to_string(),
         nodes: Vec::new(),
@@ -133,12 +161,131 @@ pub fn walk_file(
     // Walk items
     for (pos, item) in file.items.iter().enumerate() {
         walk_item(&mut ctx, item, file_node_id, pos as i32);
+        if ctx.nodes.len() >= batch_size {
+            flush_batch(&mut ctx, &mut sink);
+        }
+    }
+
+    flush_batch(&mut ctx, &mut sink);
+}
+
+/// Attach doctests to the pending rows and hand them to `sink`.
+fn flush_batch(ctx: &mut WalkCtx, sink: &mut impl FnMut(Vec<NodeRow>, Vec<EdgeRow>)) {
+    extract_doctests(ctx);
+    sink(std::mem::take(&mut ctx.nodes), std::mem::take(&mut ctx.edges));
+}
+
+/// Classify fenced Rust blocks in each item's doc comments as `doctest` nodes.
+///
+/// Doc comment lines are regrouped by owner (in position order), scanned for
+/// doctests, and each doctest is added as a child of the owning item with a
+/// `documents` edge, mirroring how doc comments themselves are linked.
+/// Doctests take the positions after the owner's last child.
+fn extract_doctests(ctx: &mut WalkCtx) {
+    let doc_kind = Kind::DocComment.as_str();
+    let mut owners: Vec<String> = Vec::new();
+    let mut docs: HashMap<String, Vec<(i32, String)>> = HashMap::new();
+    let mut paths: HashMap<&str, Option<String>> = HashMap::new();
+    let mut next_position: HashMap<&str, i32> = HashMap::new();
+    for node in &ctx.nodes {
+        paths.insert(&node.id, node.path.clone());
+        if let Some(parent) = &node.parent_id {
+            let next = next_position.entry(parent).or_insert(0);
+            *next = (*next).max(node.position + 1);
+        }
+        if node.kind != doc_kind {
+            continue;
+        }
+        if let Some(parent) = &node.parent_id {
+            let lines = docs.entry(parent.clone()).or_insert_with(|| {
+                owners.push(parent.clone());
+                Vec::new()
+            });
+            lines.push((node.position, node.content.clone().unwrap_or_default()));
+        }
     }
 
-    (ctx.nodes, ctx.edges)
+    let mut found = Vec::new();
+    for owner in owners {
+        let mut lines = docs.remove(&owner).unwrap_or_default();
+        lines.sort_by_key(|(pos, _)| *pos);
+        let text: Vec<String> = lines.into_iter().map(|(_, l)| l).collect();
+        let doctests = doctest_extractor::extract_doctests(&text.join("\n"));
+        if doctests.is_empty() {
+            continue;
+        }
+
+        let owner_path = paths
+            .get(owner.as_str())
+            .cloned()
+            .unwrap_or_else(|| ctx.path_ctx.path());
+        let first_position = next_position.get(owner.as_str()).copied().unwrap_or(0);
+        found.push((owner, owner_path, first_position, doctests));
+    }
+
+    for (owner, owner_path, first_position, doctests) in found {
+        for (i, doctest) in doctests.into_iter().enumerate() {
+            let id = Uuid::new_v4().to_string();
+            ctx.nodes.push(NodeRow {
+                id: id.clone(),
+                instance_id: ctx.instance_id.clone(),
+                kind: Kind::Doctest.as_str().to_string(),
+                language: Some("rust".to_string()),
+                content: Some(doctest.code.clone()),
+                parent_id: Some(owner.clone()),
+                position: first_position + i as i32,
+                path: owner_path.clone(),
+                metadata: json!({
+                    "attributes": doctest.attributes,
+                    "doc_line": doctest.start_line,
+                    "line_count": doctest.code.lines().count(),
+                }),
+                span_start: None,
+                span_end: None,
+            });
+            ctx.new_edge(&id, &owner, "documents");
+        }
+    }
 }
 
 fn walk_item(ctx: &mut WalkCtx, item: &syn::Item, parent_id: &str, position: i32) {
+    // The item's own node is always the first one its walker creates
+    let first = ctx.nodes.len();
+    walk_item_kind(ctx, item, parent_id, position);
+
+    // Record cfg predicates on every item kind, not just the ones whose
+    // metadata helper already looks at attributes
+    if let (Some(attrs), Some(node)) = (item_attrs(item), ctx.nodes.get_mut(first)) {
+        if let Value::Object(ref mut m) = node.metadata {
+            metadata::extract_cfg(attrs, m);
+        }
+    }
+}
+
+/// Outer attributes of an item, for the kinds that carry them.
+pub(crate) fn item_attrs(item: &syn::Item) -> Option<&[syn::Attribute]> {
+    let attrs = match item {
+        syn::Item::Fn(i) => &i.attrs,
+        syn::Item::Struct(i) => &i.attrs,
+        syn::Item::Enum(i) => &i.attrs,
+        syn::Item::Impl(i) => &i.attrs,
+        syn::Item::Trait(i) => &i.attrs,
+        syn::Item::Mod(i) => &i.attrs,
+        syn::Item::Use(i) => &i.attrs,
+        syn::Item::Const(i) => &i.attrs,
+        syn::Item::Static(i) => &i.attrs,
+        syn::Item::Type(i) => &i.attrs,
+        syn::Item::Macro(i) => &i.attrs,
+        syn::Item::ExternCrate(i) => &i.attrs,
+        syn::Item::ForeignMod(i) => &i.attrs,
+        syn::Item::Union(i) => &i.attrs,
+        syn::Item::TraitAlias(i) => &i.attrs,
+        _ => return None,
+    };
+    Some(attrs)
+}
+
+fn walk_item_kind(ctx: &mut WalkCtx, item: &syn::Item, parent_id: &str, position: i32) {
     match item {
         syn::Item::Fn(item_fn) => walk_fn(ctx, item_fn, parent_id, position),
         syn::Item::Struct(item_struct) => walk_struct(ctx, item_struct, parent_id, position),
@@ -178,6 +325,7 @@ fn walk_item(ctx: &mut WalkCtx, item: &syn::Item, parent_id: &str, position: i32
 fn walk_fn(ctx: &mut WalkCtx, item_fn: &syn::ItemFn, parent_id: &str, position: i32) {
     let name = item_fn.sig.ident.to_string();
     let mut meta = metadata::fn_metadata(&item_fn.sig, &item_fn.vis);
+    metadata::insert_calls(&mut meta, &item_fn.block);
     insert_source(&mut meta, item_fn);
     let span = item_fn.sig.ident.span();
 
@@ -387,6 +535,7 @@ fn walk_impl_item(ctx: &mut WalkCtx, item: &syn::ImplItem, parent_id: &str, posi
         syn::ImplItem::Fn(method) => {
             let name = method.sig.ident.to_string();
             let mut meta = metadata::fn_metadata(&method.sig, &method.vis);
+            metadata::insert_calls(&mut meta, &method.block);
             insert_source(&mut meta, method);
             let span = method.sig.ident.span();
 
@@ -491,7 +640,7 @@ fn walk_trait(ctx: &mut WalkCtx, item: &syn::ItemTrait, parent_id: &str, positio
         position,
         meta,
         span_start_line(span),
-        span_end_line(span),
+        span_end_line(item.brace_token.span.close()),
     );
 
     for (i, attr) in item.attrs.iter().enumerate() {
@@ -510,6 +659,10 @@ fn walk_trait_item(ctx: &mut WalkCtx, item: &syn::TraitItem, parent_id: &str, po
         syn::TraitItem::Fn(method) => {
             let name = method.sig.ident.to_string();
             let mut meta = metadata::fn_metadata(&method.sig, &syn::Visibility::Inherited);
+            meta["provided"] = json!(method.default.is_some());
+            if let Some(body) = &method.default {
+                metadata::insert_calls(&mut meta, body);
+            }
             insert_source(&mut meta, method);
             let span = method.sig.ident.span();
 
@@ -540,7 +693,8 @@ fn walk_trait_item(ctx: &mut WalkCtx, item: &syn::TraitItem, parent_id: &str, po
         }
         syn::TraitItem::Type(t) => {
             let name = t.ident.to_string();
-            let mut meta = json!({});
+            let bounds: Vec<String> = t.bounds.iter().map(to_token_string).collect();
+            let mut meta = json!({"provided": t.default.is_some(), "bounds": bounds});
             insert_source(&mut meta, t);
             ctx.path_ctx.push(&name);
             ctx.new_node(
@@ -556,7 +710,7 @@ fn walk_trait_item(ctx: &mut WalkCtx, item: &syn::TraitItem, parent_id: &str, po
         }
         syn::TraitItem::Const(c) => {
             let name = c.ident.to_string();
-            let mut meta = json!({});
+            let mut meta = json!({"provided": c.default.is_some(), "ty": to_token_string(&c.ty)});
             insert_source(&mut meta, c);
             ctx.path_ctx.push(&name);
             ctx.new_node(
@@ -73,6 +73,24 @@ pub const C_IDENT: &str = "c_ident";
 // Catch-all
 pub const C_OTHER: &str = "c_other";
 
+/// All C kinds, for kind validation and `kerai.list_kinds()`.
+pub const ALL: &[&str] = &[
+    C_INCLUDE, C_DEFINE, C_MACRO, C_IFDEF,
+    C_IF_DIRECTIVE, C_PRAGMA, C_FUNCTION, C_DECLARATION,
+    C_TYPEDEF, C_STRUCT, C_UNION, C_ENUM,
+    C_FIELD, C_ENUMERATOR, C_PARAM, C_INIT_DECLARATOR,
+    C_POINTER_DECL, C_ARRAY_DECL, C_FUNC_DECL, C_PAREN_DECL,
+    C_BLOCK, C_IF, C_FOR, C_WHILE,
+    C_DO_WHILE, C_SWITCH, C_CASE, C_RETURN,
+    C_BREAK, C_CONTINUE, C_GOTO, C_LABEL,
+    C_EXPR_STMT, C_CALL, C_BINARY, C_UNARY,
+    C_ASSIGNMENT, C_TERNARY, C_FIELD_ACCESS, C_SUBSCRIPT,
+    C_CAST, C_SIZEOF, C_PAREN, C_UPDATE,
+    C_PRIMITIVE_TYPE, C_SIZED_TYPE, C_TYPE_IDENT, C_NUMBER_LIT,
+    C_STRING_LIT, C_CHAR_LIT, C_TRUE, C_FALSE,
+    C_NULL, C_IDENT, C_OTHER,
+];
+
 /// Map a tree-sitter C node kind string to a kerai C kind constant.
 pub fn ts_kind_to_c_kind(ts_kind: &str) -> &'static str {
     match ts_kind {
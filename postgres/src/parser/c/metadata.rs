@@ -1,7 +1,7 @@
 /// C-specific metadata extraction from tree-sitter nodes.
 use serde_json::{json, Value};
 
-use crate::parser::treesitter::cursor::node_text;
+use crate::parser::treesitter::cursor::{call_counts, node_text};
 
 use super::walker;
 
@@ -80,6 +80,13 @@ pub fn function_metadata(node: &tree_sitter::Node, source: &str) -> Value {
     // Check for static storage class
     meta.insert("static".into(), json!(walker::has_storage_class_pub(node, source, "static")));
 
+    if let Some(body) = node.child_by_field_name("body") {
+        let calls = call_counts(&body, source);
+        if !calls.is_empty() {
+            meta.insert("calls".into(), json!(calls));
+        }
+    }
+
     meta.insert("source".into(), json!(node_text(node, source)));
     Value::Object(meta)
 }
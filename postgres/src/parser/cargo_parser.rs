@@ -9,10 +9,47 @@ use super::path_builder::PathContext;
 /// A row to be inserted into kerai.nodes.
 use super::ast_walker::NodeRow;
 
+/// `[workspace]` section of a root manifest.
+pub struct WorkspaceSpec {
+    pub members: Vec<String>,
+    pub exclude: Vec<String>,
+    /// The root manifest also declares a `[package]` (an implicit member).
+    pub has_package: bool,
+    pub resolver: Option<String>,
+}
+
+/// Read the `[workspace]` section of a Cargo.toml, if it has one.
+pub fn read_workspace(cargo_path: &Path) -> Result<Option<WorkspaceSpec>, String> {
+    let content =
+        std::fs::read_to_string(cargo_path).map_err(|e| format!("Failed to read Cargo.toml: {}", e))?;
+    let parsed: toml::Table =
+        content.parse().map_err(|e| format!("Failed to parse Cargo.toml: {}", e))?;
+
+    let Some(ws) = parsed.get("workspace").and_then(|w| w.as_table()) else {
+        return Ok(None);
+    };
+    let strings = |key: &str| -> Vec<String> {
+        ws.get(key)
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|v| v.as_str()).map(String::from).collect())
+            .unwrap_or_default()
+    };
+
+    Ok(Some(WorkspaceSpec {
+        members: strings("members"),
+        exclude: strings("exclude"),
+        has_package: parsed.contains_key("package"),
+        resolver: ws.get("resolver").and_then(|r| r.as_str()).map(String::from),
+    }))
+}
+
 /// Parse a Cargo.toml file and return nodes for the crate and its dependencies.
+/// `parent_id` places the crate under a workspace node.
 pub fn parse_cargo_toml(
     cargo_path: &Path,
     instance_id: &str,
+    parent_id: Option<&str>,
+    position: i32,
 ) -> Result<(Vec<NodeRow>, String, String), String> {
     let content =
         std::fs::read_to_string(cargo_path).map_err(|e| format!("Failed to read Cargo.toml: {}", e))?;
@@ -60,8 +97,8 @@ pub fn parse_cargo_toml(
         kind: Kind::Crate.as_str().to_string(),
         language: Some("rust".to_string()),
         content: Some(crate_name.clone()),
-        parent_id: None,
-        position: 0,
+        parent_id: parent_id.map(String::from),
+        position,
         path: path_ctx.path(),
         metadata: Value::Object(crate_meta),
         span_start: None,
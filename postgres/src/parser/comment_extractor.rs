@@ -147,7 +147,10 @@ pub fn extract_comments(source: &str, exclusions: &[(usize, usize)]) -> Vec<Comm
                 is_block_style: false,
             });
         } else if trimmed.starts_with("//") {
-            let text = trimmed.strip_prefix("//").unwrap_or("").trim_start();
+            // Only the space after the marker is dropped, so indentation
+            // inside the comment (code samples, nested lists) survives
+            let text = trimmed.strip_prefix("//").unwrap_or("");
+            let text = text.strip_prefix(' ').unwrap_or(text);
             comments.push(CommentInfo {
                 line: line_num,
                 col,
@@ -259,6 +262,14 @@ mod tests {
         assert!(!comments[0].is_block_style);
     }
 
+    #[test]
+    fn test_line_comment_keeps_indentation() {
+        let source = "// example:\n//     let x = 1;\nfn main() {}\n";
+        let comments = extract_comments(source, &[]);
+        assert_eq!(comments[0].text, "example:");
+        assert_eq!(comments[1].text, "    let x = 1;");
+    }
+
     #[test]
     fn test_doc_comments() {
         let source = "/// doc\n//! inner\nfn main() {}\n";
@@ -32,3 +32,146 @@ pub fn discover_rs_files(crate_root: &Path) -> Result<Vec<PathBuf>, String> {
     files.sort();
     Ok(files)
 }
+
+/// A workspace member directory resolved from `members` globs.
+pub struct WorkspaceMember {
+    /// Path relative to the workspace root, `/`-separated.
+    pub rel_path: String,
+    pub dir: PathBuf,
+    /// The directory is a git submodule listed in `.gitmodules`.
+    pub submodule: bool,
+}
+
+/// Resolve workspace `members` patterns to directories, dropping `exclude`d
+/// paths and duplicates (a directory matched by several patterns, or the
+/// root package listed as a member, is returned once). Patterns support `*`
+/// and `?` within a path segment, as Cargo's glob members do. Directories
+/// matched by a pattern but lacking a Cargo.toml are returned in `skipped`.
+pub fn expand_workspace_members(
+    root: &Path,
+    members: &[String],
+    exclude: &[String],
+) -> (Vec<WorkspaceMember>, Vec<(String, String)>) {
+    let submodules = read_gitmodules(root);
+    let excluded: Vec<PathBuf> = exclude.iter().map(|e| normalize_rel(e)).collect();
+    let mut seen = std::collections::HashSet::new();
+    if let Ok(canon) = root.canonicalize() {
+        seen.insert(canon);
+    }
+
+    let mut resolved = Vec::new();
+    let mut skipped = Vec::new();
+    for pattern in members {
+        let mut matches = Vec::new();
+        glob_dirs(root, &normalize_rel(pattern), &mut matches);
+        matches.sort();
+
+        for dir in matches {
+            let rel = dir.strip_prefix(root).unwrap_or(&dir).to_path_buf();
+            let rel_path = rel.to_string_lossy().replace('\\', "/");
+            if excluded.iter().any(|e| rel.starts_with(e)) {
+                continue;
+            }
+            let canon = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+            if !seen.insert(canon) {
+                continue;
+            }
+            let submodule = submodules.contains(&rel);
+            if !dir.join("Cargo.toml").exists() {
+                let reason = if submodule {
+                    "submodule not checked out"
+                } else {
+                    "no Cargo.toml"
+                };
+                skipped.push((rel_path, reason.to_string()));
+                continue;
+            }
+            resolved.push(WorkspaceMember {
+                rel_path,
+                dir,
+                submodule,
+            });
+        }
+    }
+    (resolved, skipped)
+}
+
+/// Strip `./` prefixes and trailing slashes from a manifest-relative path.
+fn normalize_rel(p: &str) -> PathBuf {
+    Path::new(p)
+        .components()
+        .filter(|c| !matches!(c, std::path::Component::CurDir))
+        .collect()
+}
+
+/// Expand a relative glob pattern into existing directories under `base`.
+fn glob_dirs(base: &Path, pattern: &Path, out: &mut Vec<PathBuf>) {
+    let mut components = pattern.components();
+    let Some(first) = components.next() else {
+        if base.is_dir() {
+            out.push(base.to_path_buf());
+        }
+        return;
+    };
+    let rest = components.as_path();
+    let segment = first.as_os_str().to_string_lossy();
+
+    if !segment.contains(['*', '?']) {
+        let next = base.join(&*segment);
+        if next.is_dir() {
+            glob_dirs(&next, rest, out);
+        }
+        return;
+    }
+
+    let Ok(entries) = std::fs::read_dir(base) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || name == "target" {
+            continue;
+        }
+        if entry.path().is_dir() && wildcard_match(&segment, &name) {
+            glob_dirs(&entry.path(), rest, out);
+        }
+    }
+}
+
+/// Match `name` against a pattern where `*` is any run and `?` any one char.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    let (mut pi, mut ni) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+            pi += 1;
+            ni += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            backtrack = Some((pi, ni));
+            pi += 1;
+        } else if let Some((bp, bn)) = backtrack {
+            pi = bp + 1;
+            ni = bn + 1;
+            backtrack = Some((bp, bn + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Submodule paths declared in `.gitmodules` at the workspace root.
+fn read_gitmodules(root: &Path) -> Vec<PathBuf> {
+    let Ok(content) = std::fs::read_to_string(root.join(".gitmodules")) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            (key.trim() == "path").then(|| normalize_rel(value.trim()))
+        })
+        .collect()
+}
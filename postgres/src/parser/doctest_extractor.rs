@@ -0,0 +1,131 @@
+/// Extract rustdoc doctests from doc comment text.
+///
+/// Doc comments are stored one `doc_comment` node per line. Once the lines for
+/// an item are joined back together, fenced code blocks whose info string is
+/// empty, `rust`, or only rustdoc attributes (`ignore`, `no_run`, ...) are
+/// doctests; blocks tagged with another language (`text`, `sh`) are not.
+
+/// Rustdoc attributes that keep a fenced block a Rust doctest.
+const RUSTDOC_ATTRS: &[&str] = &[
+    "rust",
+    "ignore",
+    "no_run",
+    "should_panic",
+    "compile_fail",
+    "test_harness",
+    "standalone_crate",
+];
+
+/// A doctest found in a doc comment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Doctest {
+    /// Code between the fences, with the doc comment's leading space removed.
+    pub code: String,
+    /// Info string attributes, e.g. `["no_run"]`.
+    pub attributes: Vec<String>,
+    /// Zero-based line offset of the opening fence within the doc text.
+    pub start_line: usize,
+}
+
+/// Whether a fence info string marks a Rust doctest.
+fn is_rust_info(info: &str) -> bool {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+        .all(|t| RUSTDOC_ATTRS.contains(&t) || t.starts_with("edition"))
+}
+
+/// A fenced block that has been opened but not yet closed.
+struct OpenFence {
+    fence: String,
+    info: String,
+    lines: Vec<String>,
+    start_line: usize,
+}
+
+/// Find all doctests in joined doc comment text (one line per `///`).
+pub fn extract_doctests(doc: &str) -> Vec<Doctest> {
+    let mut doctests = Vec::new();
+    let mut open: Option<OpenFence> = None;
+
+    for (idx, raw) in doc.lines().enumerate() {
+        let line = raw.strip_prefix(' ').unwrap_or(raw);
+        let trimmed = line.trim();
+
+        match open.take() {
+            Some(block) if trimmed == block.fence => {
+                if is_rust_info(&block.info) {
+                    doctests.push(Doctest {
+                        code: block.lines.join("\n"),
+                        attributes: block
+                            .info
+                            .split(|c: char| c == ',' || c.is_whitespace())
+                            .filter(|t| !t.is_empty() && *t != "rust")
+                            .map(|t| t.to_string())
+                            .collect(),
+                        start_line: block.start_line,
+                    });
+                }
+            }
+            Some(mut block) => {
+                block.lines.push(line.to_string());
+                open = Some(block);
+            }
+            None => {
+                if trimmed.starts_with("```") {
+                    let ticks = trimmed.chars().take_while(|c| *c == '`').count();
+                    open = Some(OpenFence {
+                        fence: "`".repeat(ticks),
+                        info: trimmed[ticks..].trim().to_string(),
+                        lines: Vec::new(),
+                        start_line: idx,
+                    });
+                }
+            }
+        }
+    }
+
+    doctests
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_fence_is_doctest() {
+        let doc = " Adds one.\n\n ```\n assert_eq!(add_one(1), 2);\n ```";
+        let tests = extract_doctests(doc);
+        assert_eq!(tests.len(), 1);
+        assert_eq!(tests[0].code, "assert_eq!(add_one(1), 2);");
+        assert!(tests[0].attributes.is_empty());
+        assert_eq!(tests[0].start_line, 2);
+    }
+
+    #[test]
+    fn test_attributes_kept() {
+        let doc = " ```rust,no_run\n let x = 1;\n ```";
+        let tests = extract_doctests(doc);
+        assert_eq!(tests.len(), 1);
+        assert_eq!(tests[0].attributes, vec!["no_run".to_string()]);
+    }
+
+    #[test]
+    fn test_other_language_skipped() {
+        let doc = " ```text\n not rust\n ```\n ```sh\n ls\n ```";
+        assert!(extract_doctests(doc).is_empty());
+    }
+
+    #[test]
+    fn test_multiple_blocks() {
+        let doc = " ```\n a();\n ```\n between\n ```ignore\n b();\n c();\n ```";
+        let tests = extract_doctests(doc);
+        assert_eq!(tests.len(), 2);
+        assert_eq!(tests[1].code, "b();\nc();");
+        assert_eq!(tests[1].attributes, vec!["ignore".to_string()]);
+    }
+
+    #[test]
+    fn test_unclosed_fence_ignored() {
+        assert!(extract_doctests(" ```\n a();").is_empty());
+    }
+}
@@ -1,7 +1,7 @@
 /// Go-specific metadata extraction from tree-sitter nodes.
 use serde_json::{json, Value};
 
-use crate::parser::treesitter::cursor::node_text;
+use crate::parser::treesitter::cursor::{call_counts, node_text};
 
 /// Whether a Go identifier is exported (starts with uppercase).
 pub fn is_exported(name: &str) -> bool {
@@ -29,6 +29,13 @@ pub fn func_metadata(node: &tree_sitter::Node, source: &str) -> Value {
         meta.insert("type_parameters".into(), json!(node_text(&type_params, source)));
     }
 
+    if let Some(body) = node.child_by_field_name("body") {
+        let calls = call_counts(&body, source);
+        if !calls.is_empty() {
+            meta.insert("calls".into(), json!(calls));
+        }
+    }
+
     meta.insert("source".into(), json!(node_text(node, source)));
     Value::Object(meta)
 }
@@ -56,6 +63,13 @@ pub fn method_metadata(node: &tree_sitter::Node, source: &str) -> Value {
         meta.insert("returns".into(), json!(node_text(&result, source)));
     }
 
+    if let Some(body) = node.child_by_field_name("body") {
+        let calls = call_counts(&body, source);
+        if !calls.is_empty() {
+            meta.insert("calls".into(), json!(calls));
+        }
+    }
+
     meta.insert("source".into(), json!(node_text(node, source)));
     Value::Object(meta)
 }
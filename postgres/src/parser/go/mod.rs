@@ -23,7 +23,7 @@ mod walker;
 ///
 /// Returns JSON: `{file, language, nodes, edges, elapsed_ms}`.
 #[pg_extern]
-fn parse_go_source(source: &str, filename: &str) -> pgrx::JsonB {
+pub(crate) fn parse_go_source(source: &str, filename: &str) -> pgrx::JsonB {
     let start = Instant::now();
     let instance_id = super::get_self_instance_id();
 
@@ -288,6 +288,7 @@ pub(crate) fn parse_go_single(
 
     inserter::insert_nodes(&nodes);
     inserter::insert_edges(&edges);
+    inserter::link_call_edges(&nodes);
 
     (node_count, edge_count)
 }
@@ -0,0 +1,307 @@
+/// Same-crate symbol table and `use` resolution for Rust.
+///
+/// Items are keyed by the module they live in, counted from the crate root:
+/// `src/net/tcp.rs` is module `net::tcp`, and inline `mod` blocks add to the
+/// module of their file. `use` paths (`crate::`, `self::`, `super::`, or
+/// relative) are resolved against that table, which backs both the `imports`
+/// edges written after a crate is parsed and `kerai.definition`.
+use pgrx::prelude::*;
+use serde_json::json;
+
+use crate::sql::{sql_jsonb, sql_uuid};
+
+/// Kinds that define a nameable item.
+pub(crate) const DEFINING_KINDS: &[&str] = &[
+    "fn", "struct", "enum", "trait", "const", "static", "type_alias", "union", "macro_def",
+    "module",
+];
+
+/// A named node in a crate: a defining item, an inline module, a file
+/// (as the module it defines), or a `use` item (named by its source).
+#[derive(Debug, Clone)]
+pub(crate) struct Symbol {
+    pub id: String,
+    pub kind: String,
+    pub name: String,
+    pub path: Option<String>,
+    pub file_id: String,
+    pub file: String,
+    /// Module containing the symbol, from the crate root.
+    pub module: Vec<String>,
+}
+
+/// One binding a `use` item brings into scope.
+#[derive(Debug, PartialEq)]
+pub(crate) struct UseImport {
+    /// Path as written, e.g. `["crate", "net", "Tcp"]`.
+    pub path: Vec<String>,
+    /// Name bound in scope (the alias for `as` imports, `*` for globs).
+    pub binding: String,
+    pub glob: bool,
+}
+
+/// Module a file defines, from its crate-relative filename.
+/// `src/lib.rs` / `src/main.rs` are the root; `src/a/mod.rs` is `a`.
+pub(crate) fn file_module(filename: &str) -> Vec<String> {
+    let trimmed = filename.strip_prefix("src/").unwrap_or(filename);
+    let trimmed = trimmed.strip_suffix(".rs").unwrap_or(trimmed);
+    let mut segments: Vec<String> = trimmed
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect();
+    if matches!(segments.last().map(String::as_str), Some("lib" | "main" | "mod")) {
+        segments.pop();
+    }
+    segments
+}
+
+/// Flatten a stored `use` item into the bindings it introduces.
+/// Returns nothing if the source doesn't parse as a `use` item.
+pub(crate) fn flatten_use(source: &str) -> Vec<UseImport> {
+    let mut out = Vec::new();
+    if let Ok(item) = syn::parse_str::<syn::ItemUse>(source) {
+        if item.leading_colon.is_none() {
+            flatten_tree(&item.tree, &mut Vec::new(), &mut out);
+        }
+    }
+    out
+}
+
+fn flatten_tree(tree: &syn::UseTree, prefix: &mut Vec<String>, out: &mut Vec<UseImport>) {
+    match tree {
+        syn::UseTree::Path(p) => {
+            prefix.push(p.ident.to_string());
+            flatten_tree(&p.tree, prefix, out);
+            prefix.pop();
+        }
+        syn::UseTree::Name(n) => {
+            let name = n.ident.to_string();
+            push_binding(prefix, &name, None, out);
+        }
+        syn::UseTree::Rename(r) => {
+            let name = r.ident.to_string();
+            push_binding(prefix, &name, Some(r.rename.to_string()), out);
+        }
+        syn::UseTree::Glob(_) => out.push(UseImport {
+            path: prefix.clone(),
+            binding: "*".to_string(),
+            glob: true,
+        }),
+        syn::UseTree::Group(g) => {
+            for item in &g.items {
+                flatten_tree(item, prefix, out);
+            }
+        }
+    }
+}
+
+/// `self` in a group (`use a::{self, B}`) binds the prefix itself.
+fn push_binding(prefix: &[String], name: &str, rename: Option<String>, out: &mut Vec<UseImport>) {
+    let mut path = prefix.to_vec();
+    if name != "self" {
+        path.push(name.to_string());
+    }
+    let Some(last) = path.last().cloned() else {
+        return;
+    };
+    out.push(UseImport {
+        path,
+        binding: rename.unwrap_or(last),
+        glob: false,
+    });
+}
+
+/// Resolve a path written in module `current` to a path from the crate root.
+/// Returns None when `super` walks past the root.
+pub(crate) fn absolute_path(path: &[String], current: &[String]) -> Option<Vec<String>> {
+    let (mut base, mut rest) = match path.first().map(String::as_str) {
+        Some("crate") => (Vec::new(), &path[1..]),
+        Some("self") => (current.to_vec(), &path[1..]),
+        _ => (current.to_vec(), path),
+    };
+    while rest.first().map(String::as_str) == Some("super") {
+        base.pop()?;
+        rest = &rest[1..];
+    }
+    base.extend(rest.iter().cloned());
+    Some(base)
+}
+
+/// All symbols in one crate (or one standalone file).
+pub(crate) struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Load the symbols under `root`: a crate node's files, or a single file node.
+    pub fn load(root: &str) -> Self {
+        let kinds = DEFINING_KINDS
+            .iter()
+            .chain(&["use"])
+            .map(|k| format!("'{k}'"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let rows = Spi::get_one::<pgrx::JsonB>(&format!(
+            "WITH RECURSIVE files AS (
+                SELECT id, content, path FROM kerai.nodes
+                WHERE kind = 'file' AND (id = {root} OR parent_id = {root})
+            ), scope AS (
+                SELECT f.id AS file_id, f.content AS file, n.id, n.kind, n.content, n.path,
+                       '[]'::jsonb AS mods
+                FROM files f JOIN kerai.nodes n ON n.parent_id = f.id
+                UNION ALL
+                SELECT s.file_id, s.file, n.id, n.kind, n.content, n.path,
+                       s.mods || to_jsonb(s.content)
+                FROM scope s JOIN kerai.nodes n ON n.parent_id = s.id
+                WHERE s.kind = 'module'
+            )
+            SELECT COALESCE(jsonb_agg(jsonb_build_object(
+                'id', id, 'kind', kind, 'name', content, 'path', path::text,
+                'file_id', file_id, 'file', file, 'mods', mods, 'is_file', is_file
+            )), '[]'::jsonb)
+            FROM (
+                SELECT id, kind, content, path, file_id, file, mods, false AS is_file
+                FROM scope WHERE kind IN ({kinds}) AND content IS NOT NULL
+                UNION ALL
+                SELECT id, 'file', content, path, id, content, '[]'::jsonb, true FROM files
+            ) s",
+            root = sql_uuid(root),
+        ))
+        .unwrap()
+        .map(|j| j.0)
+        .unwrap_or_else(|| json!([]));
+
+        let mut symbols = Vec::new();
+        for row in rows.as_array().into_iter().flatten() {
+            let text = |key: &str| row[key].as_str().unwrap_or_default().to_string();
+            let file = text("file");
+            let mut module = file_module(&file);
+            let name = if row["is_file"] == true {
+                // A file names the module it defines; crate roots name nothing
+                match module.pop() {
+                    Some(name) => name,
+                    None => continue,
+                }
+            } else {
+                let mods = row["mods"].as_array().into_iter().flatten();
+                module.extend(mods.filter_map(|m| m.as_str()).map(str::to_string));
+                text("name")
+            };
+            symbols.push(Symbol {
+                id: text("id"),
+                kind: text("kind"),
+                name,
+                path: row["path"].as_str().map(str::to_string),
+                file_id: text("file_id"),
+                file,
+                module,
+            });
+        }
+        SymbolTable { symbols }
+    }
+
+    /// Defining symbols (items, modules, files) named `name` directly in `module`.
+    pub fn lookup(&self, module: &[String], name: &str) -> Vec<&Symbol> {
+        self.symbols
+            .iter()
+            .filter(|s| s.kind != "use" && s.name == name && s.module == module)
+            .collect()
+    }
+
+    /// Resolve an absolute or module-relative path written in `current`.
+    /// Relative paths that don't resolve are retried from the crate root.
+    pub fn resolve_path(&self, path: &[String], current: &[String]) -> Vec<&Symbol> {
+        let mut found = self.lookup_absolute(absolute_path(path, current));
+        let relative = !matches!(path.first().map(String::as_str), Some("crate" | "self" | "super"));
+        if found.is_empty() && relative {
+            found = self.lookup_absolute(Some(path.to_vec()));
+        }
+        found
+    }
+
+    fn lookup_absolute(&self, full: Option<Vec<String>>) -> Vec<&Symbol> {
+        match full.as_deref() {
+            Some([module @ .., name]) => self.lookup(module, name),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Bindings of a `use` symbol with the node each resolves to.
+    /// Glob imports resolve to the module they expand.
+    pub fn resolve_use<'a>(&'a self, use_symbol: &Symbol) -> Vec<(UseImport, &'a Symbol)> {
+        let mut resolved = Vec::new();
+        for import in flatten_use(&use_symbol.name) {
+            let targets = self.resolve_path(&import.path, &use_symbol.module);
+            if let Some(target) = targets.first() {
+                resolved.push((import, *target));
+            }
+        }
+        resolved
+    }
+}
+
+/// Link each `use` item under a crate to the item, module, or file it brings
+/// into scope with an `imports` edge carrying `{name, glob}`. Imports from
+/// other crates get no edge.
+pub(crate) fn link_imports(crate_node_id: &str) -> usize {
+    let table = SymbolTable::load(crate_node_id);
+    let mut linked = 0;
+    for use_symbol in table.symbols.iter().filter(|s| s.kind == "use") {
+        for (import, target) in table.resolve_use(use_symbol) {
+            Spi::run(&format!(
+                "INSERT INTO kerai.edges (source_id, target_id, relation, metadata)
+                 VALUES ({}, {}, 'imports', {})
+                 ON CONFLICT (source_id, target_id, relation) DO NOTHING",
+                sql_uuid(&use_symbol.id),
+                sql_uuid(&target.id),
+                sql_jsonb(&json!({"name": import.binding, "glob": import.glob})),
+            ))
+            .expect("Failed to link imports");
+            linked += 1;
+        }
+    }
+    linked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segs(path: &str) -> Vec<String> {
+        path.split("::").map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_file_module() {
+        assert!(file_module("src/lib.rs").is_empty());
+        assert!(file_module("main.rs").is_empty());
+        assert_eq!(file_module("src/net/tcp.rs"), segs("net::tcp"));
+        assert_eq!(file_module("src/net/mod.rs"), segs("net"));
+    }
+
+    #[test]
+    fn test_flatten_use() {
+        let imports = flatten_use("use crate :: net :: { self , tcp :: Stream as S , udp :: * } ;");
+        assert_eq!(
+            imports,
+            vec![
+                UseImport { path: segs("crate::net"), binding: "net".into(), glob: false },
+                UseImport { path: segs("crate::net::tcp::Stream"), binding: "S".into(), glob: false },
+                UseImport { path: segs("crate::net::udp"), binding: "*".into(), glob: true },
+            ]
+        );
+        assert!(flatten_use("use ::std::io;").is_empty());
+        assert!(flatten_use("not a use").is_empty());
+    }
+
+    #[test]
+    fn test_absolute_path() {
+        let current = segs("net::tcp");
+        assert_eq!(absolute_path(&segs("crate::a::B"), &current), Some(segs("a::B")));
+        assert_eq!(absolute_path(&segs("self::B"), &current), Some(segs("net::tcp::B")));
+        assert_eq!(absolute_path(&segs("super::udp::B"), &current), Some(segs("net::udp::B")));
+        assert_eq!(absolute_path(&segs("super::super::super::B"), &current), None);
+        assert_eq!(absolute_path(&segs("inner::B"), &current), Some(segs("net::tcp::inner::B")));
+    }
+}
@@ -1,4 +1,6 @@
 /// Batch SPI INSERT for nodes and edges.
+use std::collections::{HashMap, HashSet, VecDeque};
+
 use pgrx::prelude::*;
 
 use super::ast_walker::{EdgeRow, NodeRow};
@@ -56,6 +58,31 @@ pub fn delete_file_nodes(instance_id: &str, filename: &str) {
     .ok();
 }
 
+/// Delete every node below `root_id` and the edges touching them, keeping
+/// the root node itself.
+pub fn delete_descendants(root_id: &str) {
+    let descendants = format!(
+        "WITH RECURSIVE descendants AS (
+            SELECT id FROM kerai.nodes WHERE parent_id = {}
+            UNION ALL
+            SELECT n.id FROM kerai.nodes n
+            JOIN descendants d ON n.parent_id = d.id
+        )",
+        sql_uuid(root_id),
+    );
+    Spi::run(&format!(
+        "{descendants}
+        DELETE FROM kerai.edges WHERE source_id IN (SELECT id FROM descendants)
+            OR target_id IN (SELECT id FROM descendants)",
+    ))
+    .expect("Failed to delete descendant edges");
+    Spi::run(&format!(
+        "{descendants}
+        DELETE FROM kerai.nodes WHERE id IN (SELECT id FROM descendants)",
+    ))
+    .expect("Failed to delete descendant nodes");
+}
+
 /// Insert nodes in batches.
 pub fn insert_nodes(nodes: &[NodeRow]) {
     for batch in nodes.chunks(BATCH_SIZE) {
@@ -121,3 +148,443 @@ pub fn insert_edges(edges: &[EdgeRow]) {
         Spi::run(&sql).expect("Failed to insert edges batch");
     }
 }
+
+/// Counts from an in-place re-parse.
+pub struct DiffStats {
+    pub reused: usize,
+    pub inserted: usize,
+    pub deleted: usize,
+    /// Removed nodes kept because version history or a model refers to them
+    pub kept: usize,
+}
+
+/// Replace a file's nodes in place, keeping the UUIDs of nodes that survive.
+///
+/// Existing nodes under the file are matched to the freshly parsed rows by
+/// (kind, path, content), in document order when several share a key. Matched
+/// rows keep the old id and are updated; unmatched new rows are inserted;
+/// old nodes with no match are deleted along with edges, associations, and
+/// perspectives that point at them; task scopes and repository roots pointing
+/// at them are cleared. Nodes with edit history or in a model's vocabulary are
+/// kept, with their ancestors. Edges between the file's own nodes are
+/// regenerated. `file_node` must be the first row, followed by its descendants.
+pub fn diff_file_nodes(
+    instance_id: &str,
+    filename: &str,
+    file_node: NodeRow,
+    nodes: Vec<NodeRow>,
+    edges: Vec<EdgeRow>,
+) -> DiffStats {
+    let old = load_file_subtree(instance_id, filename);
+    let old_ids: Vec<String> = old.iter().map(|(id, _, _)| id.clone()).collect();
+
+    let mut available: HashMap<NodeKey, VecDeque<String>> = HashMap::new();
+    for (id, key, _) in old {
+        available.entry(key).or_default().push_back(id);
+    }
+
+    // Assign each new row an id: the oldest unclaimed match, or its fresh one
+    let mut rows: Vec<NodeRow> = std::iter::once(file_node).chain(nodes).collect();
+    let mut id_map: HashMap<String, String> = HashMap::new();
+    let mut reused_ids: HashSet<String> = HashSet::new();
+    for row in &rows {
+        let key = (row.kind.clone(), row.path.clone(), row.content.clone());
+        if let Some(old_id) = available.get_mut(&key).and_then(|q| q.pop_front()) {
+            reused_ids.insert(old_id.clone());
+            id_map.insert(row.id.clone(), old_id);
+        }
+    }
+    for row in &mut rows {
+        if let Some(id) = id_map.get(&row.id) {
+            row.id = id.clone();
+        }
+        if let Some(pid) = row.parent_id.as_ref().and_then(|p| id_map.get(p)) {
+            row.parent_id = Some(pid.clone());
+        }
+    }
+    let (reused, inserted): (Vec<NodeRow>, Vec<NodeRow>) =
+        rows.into_iter().partition(|r| reused_ids.contains(&r.id));
+    let removed: Vec<String> = old_ids
+        .iter()
+        .filter(|id| !reused_ids.contains(*id))
+        .cloned()
+        .collect();
+    let pinned = pinned_nodes(&removed);
+    let deleted: Vec<String> = removed
+        .into_iter()
+        .filter(|id| !pinned.contains(id))
+        .collect();
+
+    // Edges among the file's own nodes are rebuilt from the new parse
+    for batch in old_ids.chunks(BATCH_SIZE) {
+        let list = uuid_array(batch);
+        Spi::run(&format!(
+            "DELETE FROM kerai.edges WHERE source_id = ANY({list}) AND target_id = ANY({list})",
+        ))
+        .expect("Failed to delete file edges");
+    }
+
+    insert_nodes(&inserted);
+    update_nodes(&reused);
+
+    for batch in deleted.chunks(BATCH_SIZE) {
+        let list = uuid_array(batch);
+        for (table, a, b) in [
+            ("edges", "source_id", "target_id"),
+            ("associations", "source_id", "target_id"),
+            ("perspectives", "node_id", "context_id"),
+        ] {
+            Spi::run(&format!(
+                "DELETE FROM kerai.{table} WHERE {a} = ANY({list}) OR {b} = ANY({list})",
+            ))
+            .expect("Failed to delete references to removed nodes");
+        }
+        for (table, column) in [("tasks", "scope_node_id"), ("repositories", "node_id")] {
+            Spi::run(&format!(
+                "UPDATE kerai.{table} SET {column} = NULL WHERE {column} = ANY({list})",
+            ))
+            .expect("Failed to clear references to removed nodes");
+        }
+    }
+    // One statement: parent_id is checked at statement end, so a removed
+    // subtree can't trip the FK the way parent-first batches would
+    if !deleted.is_empty() {
+        Spi::run(&format!(
+            "DELETE FROM kerai.nodes WHERE id = ANY({})",
+            uuid_array(&deleted),
+        ))
+        .expect("Failed to delete removed nodes");
+    }
+
+    let edges: Vec<EdgeRow> = edges
+        .into_iter()
+        .map(|mut e| {
+            if let Some(id) = id_map.get(&e.source_id) {
+                e.source_id = id.clone();
+            }
+            if let Some(id) = id_map.get(&e.target_id) {
+                e.target_id = id.clone();
+            }
+            e
+        })
+        .collect();
+    insert_edges(&edges);
+
+    link_impl_edges(reused.iter().chain(&inserted));
+    link_call_edges(reused.iter().chain(&inserted));
+
+    DiffStats {
+        reused: reused.len(),
+        inserted: inserted.len(),
+        deleted: deleted.len(),
+        kept: pinned.len(),
+    }
+}
+
+/// Removed nodes that must stay because edit history (`versions`) or a model
+/// vocabulary (`model_vocab`) refers to them, plus their removed ancestors so
+/// the kept nodes' parents still exist.
+fn pinned_nodes(removed: &[String]) -> HashSet<String> {
+    if removed.is_empty() {
+        return HashSet::new();
+    }
+    let list = uuid_array(removed);
+    let pinned = Spi::get_one::<pgrx::JsonB>(&format!(
+        "WITH RECURSIVE pinned AS (
+            SELECT n.id, n.parent_id FROM kerai.nodes n
+            WHERE n.id = ANY({list})
+              AND (EXISTS (SELECT 1 FROM kerai.versions v WHERE v.node_id = n.id)
+                   OR EXISTS (SELECT 1 FROM kerai.model_vocab m WHERE m.node_id = n.id))
+            UNION
+            SELECT p.id, p.parent_id FROM kerai.nodes p
+            JOIN pinned c ON p.id = c.parent_id
+            WHERE p.id = ANY({list})
+        )
+        SELECT COALESCE(jsonb_agg(id), '[]'::jsonb) FROM pinned",
+    ))
+    .expect("Failed to find referenced nodes")
+    .map(|j| j.0)
+    .unwrap_or_default();
+    pinned
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|id| id.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Metadata keys that only record where a node sits in the file.
+const POSITION_KEYS: [&str; 4] = ["start_line", "end_line", "line", "doc_line"];
+
+/// Compare freshly parsed descendants of a file against its stored subtree
+/// without writing anything.
+///
+/// Nodes are paired by (kind, path, content) in document order, as in
+/// [`diff_file_nodes`]. Returns `{added, removed, modified}` arrays of
+/// `{kind, content, path}`; a pair counts as modified when its metadata differs
+/// once line numbers are set aside, so code that merely moved isn't reported.
+pub fn preview_file_diff(file_id: &str, nodes: &[NodeRow]) -> serde_json::Value {
+    // The first row is the file node itself
+    let old: Vec<_> = load_subtree(&format!("id = {}", sql_uuid(file_id)))
+        .into_iter()
+        .skip(1)
+        .collect();
+
+    let mut available: HashMap<&NodeKey, VecDeque<usize>> = HashMap::new();
+    for (i, (_, key, _)) in old.iter().enumerate() {
+        available.entry(key).or_default().push_back(i);
+    }
+
+    let mut claimed = vec![false; old.len()];
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+    for row in nodes {
+        let key = (row.kind.clone(), row.path.clone(), row.content.clone());
+        match available.get_mut(&key).and_then(|q| q.pop_front()) {
+            Some(i) => {
+                claimed[i] = true;
+                if without_positions(&old[i].2) != without_positions(&row.metadata) {
+                    modified.push(diff_entry(&key));
+                }
+            }
+            None => added.push(diff_entry(&key)),
+        }
+    }
+    let removed: Vec<_> = old
+        .iter()
+        .zip(&claimed)
+        .filter(|(_, claimed)| !**claimed)
+        .map(|((_, key, _), _)| diff_entry(key))
+        .collect();
+
+    serde_json::json!({"added": added, "removed": removed, "modified": modified})
+}
+
+/// A `{kind, content, path}` entry of a [`preview_file_diff`] result.
+fn diff_entry((kind, path, content): &NodeKey) -> serde_json::Value {
+    serde_json::json!({"kind": kind, "content": content, "path": path})
+}
+
+/// `metadata` with [`POSITION_KEYS`] removed.
+fn without_positions(metadata: &serde_json::Value) -> serde_json::Value {
+    let mut metadata = metadata.clone();
+    if let Some(obj) = metadata.as_object_mut() {
+        for key in POSITION_KEYS {
+            obj.remove(key);
+        }
+    }
+    metadata
+}
+
+/// Link impl blocks to the trait they implement (`implements`) and the type
+/// they are for (`for`), resolving by name across everything ingested.
+///
+/// Covers impls among `nodes` and impls elsewhere whose trait or type is among
+/// `nodes`, so it doesn't matter which file is parsed first. When a name is
+/// defined more than once, a definition in the impl's own crate wins.
+/// Unresolved names get no edge.
+pub fn link_impl_edges<'a>(nodes: impl IntoIterator<Item = &'a NodeRow>) {
+    const LINKABLE: [&str; 6] = ["impl", "trait", "struct", "enum", "union", "type_alias"];
+    let ids: Vec<String> = nodes
+        .into_iter()
+        .filter(|n| LINKABLE.contains(&n.kind.as_str()))
+        .map(|n| n.id.clone())
+        .collect();
+
+    for batch in ids.chunks(BATCH_SIZE) {
+        let list = uuid_array(batch);
+        // Impls stored before type_name was recorded carry self_name instead
+        for (relation, name, kinds) in [
+            ("implements", "i.metadata->>'trait_name'", "'trait'"),
+            (
+                "for",
+                "COALESCE(i.metadata->>'type_name', i.metadata->>'self_name')",
+                "'struct', 'enum', 'union', 'type_alias'",
+            ),
+        ] {
+            // Impls among the batch, plus impls naming a definition among
+            // it; both are index lookups (primary key, idx_nodes_impl_*)
+            Spi::run(&format!(
+                "WITH impls AS (
+                     SELECT id FROM kerai.nodes WHERE id = ANY({list}) AND kind = 'impl'
+                     UNION
+                     SELECT i.id FROM kerai.nodes d
+                     JOIN kerai.nodes i ON i.kind = 'impl' AND {name} = d.content
+                     WHERE d.id = ANY({list}) AND d.kind IN ({kinds})
+                 )
+                 INSERT INTO kerai.edges (source_id, target_id, relation)
+                 SELECT DISTINCT ON (i.id) i.id, t.id, '{relation}'
+                 FROM impls
+                 JOIN kerai.nodes i ON i.id = impls.id
+                 JOIN kerai.nodes t
+                   ON t.content = {name} AND t.kind IN ({kinds})
+                 WHERE NOT EXISTS (
+                     SELECT 1 FROM kerai.edges e
+                     WHERE e.source_id = i.id AND e.relation = '{relation}'
+                 )
+                 ORDER BY i.id,
+                   (subltree(t.path, 0, 1) = subltree(i.path, 0, 1)) DESC NULLS LAST,
+                   t.created_at
+                 ON CONFLICT (source_id, target_id, relation) DO NOTHING",
+            ))
+            .expect("Failed to link impl edges");
+        }
+    }
+}
+
+/// Kinds that can be the caller or callee of a `calls` edge.
+const CALLABLE_KINDS: [&str; 4] = ["fn", "go_func", "go_method", "c_function"];
+
+/// Link functions to what they call with one `calls` edge per caller and
+/// callee, carrying `{count}` from the caller's `calls` metadata.
+///
+/// Like [`link_impl_edges`], covers callers among `nodes` and callers
+/// elsewhere that call a function among `nodes` by name. Callees resolve
+/// within the caller's crate or file, nearest in the tree first; names that
+/// don't resolve (std, other crates) get no edge. A caller's `calls` edges are
+/// rebuilt each time so counts follow re-parses.
+pub fn link_call_edges<'a>(nodes: impl IntoIterator<Item = &'a NodeRow>) {
+    let ids: Vec<String> = nodes
+        .into_iter()
+        .filter(|n| CALLABLE_KINDS.contains(&n.kind.as_str()))
+        .map(|n| n.id.clone())
+        .collect();
+    let kinds = CALLABLE_KINDS.map(|k| format!("'{k}'")).join(", ");
+
+    for batch in ids.chunks(BATCH_SIZE) {
+        let list = uuid_array(batch);
+        // Each arm of the union is an index lookup (primary key, and the
+        // idx_nodes_calls GIN index), so no parse scans the whole table
+        let callers = format!(
+            "f.kind IN ({kinds}) AND f.metadata ? 'calls'
+             AND f.id IN (
+                 SELECT id FROM kerai.nodes WHERE id = ANY({list})
+                 UNION
+                 SELECT id FROM kerai.nodes
+                 WHERE metadata ? 'calls' AND metadata->'calls' ?| ARRAY(
+                     SELECT content FROM kerai.nodes
+                     WHERE id = ANY({list}) AND content IS NOT NULL
+                 )
+             )"
+        );
+        Spi::run(&format!(
+            "DELETE FROM kerai.edges e USING kerai.nodes f
+             WHERE e.source_id = f.id AND e.relation = 'calls' AND {callers}",
+        ))
+        .expect("Failed to clear call edges");
+        Spi::run(&format!(
+            "INSERT INTO kerai.edges (source_id, target_id, relation, metadata)
+             SELECT source_id, target_id, 'calls', jsonb_build_object('count', sum(count))
+             FROM (
+                 SELECT DISTINCT ON (f.id, c.key) f.id AS source_id, t.id AS target_id,
+                        c.value::int AS count
+                 FROM kerai.nodes f
+                 CROSS JOIN LATERAL jsonb_each_text(f.metadata->'calls') c
+                 JOIN kerai.nodes t
+                   ON t.content = c.key AND t.kind IN ({kinds})
+                  AND t.language IS NOT DISTINCT FROM f.language
+                  AND subltree(t.path, 0, 1) = subltree(f.path, 0, 1)
+                 WHERE {callers}
+                 ORDER BY f.id, c.key, nlevel(lca(t.path, f.path)) DESC, t.created_at
+             ) resolved
+             GROUP BY source_id, target_id
+             ON CONFLICT (source_id, target_id, relation) DO NOTHING",
+        ))
+        .expect("Failed to link call edges");
+    }
+}
+
+/// Matching key for re-parse: (kind, path, content).
+type NodeKey = (String, Option<String>, Option<String>);
+
+/// Load every node under a file in depth-first document order.
+fn load_file_subtree(
+    instance_id: &str,
+    filename: &str,
+) -> Vec<(String, NodeKey, serde_json::Value)> {
+    load_subtree(&format!(
+        "instance_id = {} AND kind = 'file' AND content = '{}'",
+        sql_uuid(instance_id),
+        sql_escape(filename),
+    ))
+}
+
+/// Load the nodes matching `root_condition` and everything under them, in
+/// depth-first document order, with their metadata.
+fn load_subtree(root_condition: &str) -> Vec<(String, NodeKey, serde_json::Value)> {
+    let mut out = Vec::new();
+
+    Spi::connect(|client| {
+        let query = format!(
+            "WITH RECURSIVE subtree AS (
+                SELECT id, kind, path, content, metadata, ARRAY[position] AS sort_key
+                FROM kerai.nodes
+                WHERE {root_condition}
+                UNION ALL
+                SELECT n.id, n.kind, n.path, n.content, n.metadata, s.sort_key || n.position
+                FROM kerai.nodes n
+                JOIN subtree s ON n.parent_id = s.id
+            )
+            SELECT id::text AS id, kind, path::text AS path, content, metadata
+            FROM subtree
+            ORDER BY sort_key",
+        );
+
+        let result = client.select(&query, None, &[]).unwrap();
+        for row in result {
+            let id: String = row.get_by_name::<String, _>("id").unwrap().unwrap_or_default();
+            let kind: String = row.get_by_name::<String, _>("kind").unwrap().unwrap_or_default();
+            let path = row.get_by_name::<String, _>("path").unwrap();
+            let content = row.get_by_name::<String, _>("content").unwrap();
+            let metadata = row
+                .get_by_name::<pgrx::JsonB, _>("metadata")
+                .unwrap()
+                .map_or(serde_json::Value::Null, |m| m.0);
+            out.push((id, (kind, path, content), metadata));
+        }
+    });
+
+    out
+}
+
+/// Update kept nodes in batches with their re-parsed position, parent, and metadata.
+fn update_nodes(nodes: &[NodeRow]) {
+    for batch in nodes.chunks(BATCH_SIZE) {
+        let values: Vec<String> = batch
+            .iter()
+            .map(|node| {
+                format!(
+                    "({}, {}, {}, {}, {}::text, {})",
+                    sql_uuid(&node.id),
+                    match &node.parent_id {
+                        Some(pid) => sql_uuid(pid),
+                        None => "NULL::uuid".to_string(),
+                    },
+                    node.position,
+                    match &node.path {
+                        Some(p) => sql_ltree(p),
+                        None => "NULL::ltree".to_string(),
+                    },
+                    sql_opt_text(&node.language),
+                    sql_jsonb(&node.metadata),
+                )
+            })
+            .collect();
+
+        Spi::run(&format!(
+            "UPDATE kerai.nodes n
+             SET parent_id = v.parent_id, position = v.position, path = v.path,
+                 language = v.language, metadata = v.metadata
+             FROM (VALUES {}) AS v(id, parent_id, position, path, language, metadata)
+             WHERE n.id = v.id",
+            values.join(", "),
+        ))
+        .expect("Failed to update nodes batch");
+    }
+}
+
+/// Format ids as a SQL `uuid[]` literal.
+fn uuid_array(ids: &[String]) -> String {
+    let items: Vec<String> = ids.iter().map(|id| sql_uuid(id)).collect();
+    format!("ARRAY[{}]::uuid[]", items.join(", "))
+}
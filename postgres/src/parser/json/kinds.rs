@@ -0,0 +1,9 @@
+/// JSON node kind constants, prefixed with `json_` to avoid collisions
+/// with other parsers' kinds in the `kerai.nodes.kind` column.
+pub const JSON_OBJECT: &str = "json_object";
+pub const JSON_ARRAY: &str = "json_array";
+pub const JSON_MEMBER: &str = "json_member";
+pub const JSON_VALUE: &str = "json_value";
+
+/// All JSON kinds, for kind validation and `kerai.list_kinds()`.
+pub const ALL: &[&str] = &[JSON_OBJECT, JSON_ARRAY, JSON_MEMBER, JSON_VALUE];
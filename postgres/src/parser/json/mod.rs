@@ -0,0 +1,163 @@
+/// JSON parser module — JSON documents → kerai.nodes.
+///
+/// The file node holds the top-level value. Objects and arrays become
+/// `json_object`/`json_array` nodes, each object key a `json_member` whose
+/// single child is its value, and scalars `json_value` nodes with the value's
+/// JSON text as content and its type in `metadata.type`.
+use pgrx::prelude::*;
+use serde_json::{json, Value};
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::parser::ast_walker::NodeRow;
+use crate::parser::inserter;
+use crate::parser::kinds::Kind;
+use crate::parser::path_builder::PathContext;
+
+pub mod kinds;
+
+/// Parse JSON text directly into kerai.nodes.
+///
+/// Returns JSON: `{file, language, nodes, edges, elapsed_ms}`.
+#[pg_extern]
+pub(crate) fn parse_json_source(source: &str, filename: &str) -> pgrx::JsonB {
+    let start = Instant::now();
+    let instance_id = super::get_self_instance_id();
+
+    // Delete existing nodes for this filename (idempotent re-parse)
+    inserter::delete_file_nodes(&instance_id, filename);
+
+    let node_count = parse_json_single(source, filename, &instance_id, None);
+
+    let elapsed = start.elapsed();
+    pgrx::JsonB(json!({
+        "file": filename,
+        "language": "json",
+        "nodes": node_count,
+        "edges": 0,
+        "elapsed_ms": elapsed.as_millis() as u64,
+    }))
+}
+
+/// Parse JSON source, insert nodes, return the node count.
+///
+/// `parent_id` allows parenting the file node under a repo directory node.
+pub(crate) fn parse_json_single(
+    source: &str,
+    filename: &str,
+    instance_id: &str,
+    parent_id: Option<&str>,
+) -> usize {
+    let value: Value = match serde_json::from_str(source) {
+        Ok(v) => v,
+        Err(e) => {
+            warning!("Failed to parse JSON source {}: {}", filename, e);
+            return 0;
+        }
+    };
+
+    let file_node_id = Uuid::new_v4().to_string();
+    let mut path_ctx = PathContext::with_root(filename);
+    let mut nodes = vec![NodeRow {
+        id: file_node_id.clone(),
+        instance_id: instance_id.to_string(),
+        kind: Kind::File.as_str().to_string(),
+        language: Some("json".to_string()),
+        content: Some(filename.to_string()),
+        parent_id: parent_id.map(|s| s.to_string()),
+        position: 0,
+        path: path_ctx.path(),
+        metadata: json!({"line_count": source.lines().count()}),
+        span_start: None,
+        span_end: None,
+    }];
+    walk_value(
+        &value,
+        &file_node_id,
+        0,
+        instance_id,
+        &mut path_ctx,
+        &mut nodes,
+    );
+
+    inserter::insert_nodes(&nodes);
+    nodes.len()
+}
+
+/// Append the node for `value` (and its descendants) under `parent_id`.
+fn walk_value(
+    value: &Value,
+    parent_id: &str,
+    position: i32,
+    instance_id: &str,
+    path_ctx: &mut PathContext,
+    nodes: &mut Vec<NodeRow>,
+) {
+    let id = Uuid::new_v4().to_string();
+    let (kind, content, metadata) = match value {
+        Value::Object(map) => (kinds::JSON_OBJECT, None, json!({"len": map.len()})),
+        Value::Array(items) => (kinds::JSON_ARRAY, None, json!({"len": items.len()})),
+        scalar => (
+            kinds::JSON_VALUE,
+            Some(scalar.to_string()),
+            json!({"type": scalar_type(scalar)}),
+        ),
+    };
+    nodes.push(NodeRow {
+        id: id.clone(),
+        instance_id: instance_id.to_string(),
+        kind: kind.to_string(),
+        language: Some("json".to_string()),
+        content,
+        parent_id: Some(parent_id.to_string()),
+        position,
+        path: path_ctx.path(),
+        metadata,
+        span_start: None,
+        span_end: None,
+    });
+
+    match value {
+        Value::Object(map) => {
+            for (i, (key, child)) in map.iter().enumerate() {
+                path_ctx.push(key);
+                let member_id = Uuid::new_v4().to_string();
+                nodes.push(NodeRow {
+                    id: member_id.clone(),
+                    instance_id: instance_id.to_string(),
+                    kind: kinds::JSON_MEMBER.to_string(),
+                    language: Some("json".to_string()),
+                    content: Some(key.clone()),
+                    parent_id: Some(id.clone()),
+                    position: i as i32,
+                    path: path_ctx.path(),
+                    metadata: json!({}),
+                    span_start: None,
+                    span_end: None,
+                });
+                walk_value(child, &member_id, 0, instance_id, path_ctx, nodes);
+                path_ctx.pop();
+            }
+        }
+        Value::Array(items) => {
+            for (i, child) in items.iter().enumerate() {
+                path_ctx.push(&i.to_string());
+                walk_value(child, &id, i as i32, instance_id, path_ctx, nodes);
+                path_ctx.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The JSON type name of a scalar value.
+fn scalar_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
@@ -1,9 +1,18 @@
-/// Node kind enum — type-safe representation of AST node kinds.
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use pgrx::prelude::*;
+use serde_json::json;
 
+use crate::sql::sql_text;
+
+/// Node kind enum — type-safe representation of AST node kinds.
+///
 /// All possible node kinds stored in kerai.nodes.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Kind {
     // Top-level
+    Workspace,
     Crate,
     Module,
     File,
@@ -32,6 +41,7 @@ pub enum Kind {
     // Attributes and comments
     Attribute,
     DocComment,
+    Doctest,
     Comment,
     CommentBlock,
 
@@ -141,6 +151,7 @@ impl Kind {
     pub fn as_str(&self) -> &'static str {
         match self {
             // Top-level
+            Kind::Workspace => "workspace",
             Kind::Crate => "crate",
             Kind::Module => "module",
             Kind::File => "file",
@@ -166,6 +177,7 @@ impl Kind {
             // Attributes and comments
             Kind::Attribute => "attribute",
             Kind::DocComment => "doc_comment",
+            Kind::Doctest => "doctest",
             Kind::Comment => "comment",
             Kind::CommentBlock => "comment_block",
             // Blocks and statements
@@ -262,12 +274,12 @@ impl Kind {
 
     /// All Kind variants, for exhaustive iteration and testing.
     pub const ALL: &'static [Kind] = &[
-        Kind::Crate, Kind::Module, Kind::File,
+        Kind::Workspace, Kind::Crate, Kind::Module, Kind::File,
         Kind::Fn, Kind::Struct, Kind::Enum, Kind::Variant, Kind::Field,
         Kind::Impl, Kind::Trait, Kind::TypeAlias, Kind::Const, Kind::Static,
         Kind::Use, Kind::ExternCrate, Kind::ForeignMod, Kind::Union, Kind::TraitAlias,
         Kind::MacroDef, Kind::MacroCall,
-        Kind::Attribute, Kind::DocComment, Kind::Comment, Kind::CommentBlock,
+        Kind::Attribute, Kind::DocComment, Kind::Doctest, Kind::Comment, Kind::CommentBlock,
         Kind::Block, Kind::StmtLocal, Kind::StmtExpr,
         Kind::ExprCall, Kind::ExprMethodCall, Kind::ExprIf, Kind::ExprMatch,
         Kind::ExprMatchArm, Kind::ExprClosure, Kind::ExprBlock, Kind::ExprLoop,
@@ -305,6 +317,7 @@ impl std::str::FromStr for Kind {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
+            "workspace" => Ok(Kind::Workspace),
             "crate" => Ok(Kind::Crate),
             "module" => Ok(Kind::Module),
             "file" => Ok(Kind::File),
@@ -327,6 +340,7 @@ impl std::str::FromStr for Kind {
             "macro_call" => Ok(Kind::MacroCall),
             "attribute" => Ok(Kind::Attribute),
             "doc_comment" => Ok(Kind::DocComment),
+            "doctest" => Ok(Kind::Doctest),
             "comment" => Ok(Kind::Comment),
             "comment_block" => Ok(Kind::CommentBlock),
             "block" => Ok(Kind::Block),
@@ -412,3 +426,69 @@ impl std::str::FromStr for Kind {
     }
 }
 
+/// Every known kind grouped by language, as `(language, kinds)` pairs.
+/// Rust kinds double as the shared structural kinds (`file`, `comment`, ...).
+pub fn known_kinds() -> Vec<(&'static str, Vec<&'static str>)> {
+    let (csv, rust): (Vec<&'static str>, Vec<&'static str>) = Kind::ALL
+        .iter()
+        .map(|k| k.as_str())
+        .partition(|k| k.starts_with("csv_"));
+    vec![
+        ("rust", rust),
+        ("go", super::go::kinds::ALL.to_vec()),
+        ("c", super::c::kinds::ALL.to_vec()),
+        ("latex", super::latex::kinds::ALL.to_vec()),
+        ("markdown", super::markdown::kinds::ALL.to_vec()),
+        ("csv", csv),
+        ("json", super::json::kinds::ALL.to_vec()),
+        ("repo", crate::repo::kinds::ALL.to_vec()),
+    ]
+}
+
+/// Whether `kind` is a kind string produced by any parser.
+#[pg_extern]
+pub fn is_known_kind(kind: &str) -> bool {
+    static KNOWN: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    KNOWN
+        .get_or_init(|| {
+            known_kinds()
+                .into_iter()
+                .flat_map(|(_, kinds)| kinds)
+                .collect()
+        })
+        .contains(kind)
+}
+
+/// List all valid node kinds, grouped by language.
+#[pg_extern]
+fn list_kinds() -> pgrx::JsonB {
+    let map: serde_json::Map<String, serde_json::Value> = known_kinds()
+        .into_iter()
+        .map(|(lang, kinds)| (lang.to_string(), json!(kinds)))
+        .collect();
+    pgrx::JsonB(serde_json::Value::Object(map))
+}
+
+/// Enable or disable strict kind validation for node inserts.
+/// Stored as the `config/strict_kinds` preference and enforced by a trigger
+/// on kerai.nodes, so it covers apply_op, the parsers, and raw SQL alike.
+/// Returns the new mode plus the number of existing nodes with unknown kinds.
+#[pg_extern]
+fn validate_kinds(strict: bool) -> pgrx::JsonB {
+    Spi::run(&format!(
+        "SELECT kerai.set_preference('config', 'strict_kinds', {})",
+        sql_text(if strict { "true" } else { "false" }),
+    ))
+    .expect("Failed to store strict_kinds preference");
+
+    let unknown = Spi::get_one::<i64>(
+        "SELECT count(*)::bigint FROM kerai.nodes WHERE NOT kerai.is_known_kind(kind)",
+    )
+    .unwrap_or(None)
+    .unwrap_or(0);
+
+    pgrx::JsonB(json!({
+        "strict": strict,
+        "unknown_nodes": unknown,
+    }))
+}
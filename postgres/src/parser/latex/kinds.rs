@@ -52,6 +52,18 @@ pub const BIB_FIELD: &str = "bib_field";
 // Catch-all
 pub const LATEX_OTHER: &str = "latex_other";
 
+/// All LaTeX and BibTeX kinds, for kind validation and `kerai.list_kinds()`.
+pub const ALL: &[&str] = &[
+    LATEX_DOCUMENT, LATEX_PREAMBLE, LATEX_DOCUMENTCLASS, LATEX_USEPACKAGE,
+    LATEX_PART, LATEX_CHAPTER, LATEX_SECTION, LATEX_SUBSECTION,
+    LATEX_SUBSUBSECTION, LATEX_PARAGRAPH, LATEX_ENVIRONMENT, LATEX_MATH_ENV,
+    LATEX_FIGURE, LATEX_TABLE, LATEX_THEOREM, LATEX_DEFINITION,
+    LATEX_PROOF, LATEX_INLINE_MATH, LATEX_DISPLAY_MATH, LATEX_CITATION,
+    LATEX_LABEL, LATEX_REF, LATEX_CAPTION, LATEX_FOOTNOTE,
+    LATEX_INPUT, LATEX_INCLUDE, LATEX_COMMAND, LATEX_TEXT,
+    BIB_ENTRY, BIB_FIELD, LATEX_OTHER,
+];
+
 /// Semantic environments that get specialized kind constants.
 const THEOREM_ENVS: &[&str] = &[
     "theorem", "lemma", "proposition", "corollary", "conjecture", "claim",
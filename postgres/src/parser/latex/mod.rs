@@ -1,6 +1,7 @@
 /// LaTeX parser module — LaTeX/BibTeX source → kerai.nodes + kerai.edges via tree-sitter + biblatex.
 use pgrx::prelude::*;
 use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::time::Instant;
 use uuid::Uuid;
@@ -10,6 +11,7 @@ use crate::parser::inserter;
 use crate::parser::kinds::Kind;
 use crate::parser::path_builder::PathContext;
 use crate::parser::treesitter::{self, TsLanguage};
+use crate::sql::{sql_jsonb, sql_uuid};
 
 pub mod kinds;
 mod metadata;
@@ -18,9 +20,11 @@ mod bibtex;
 
 /// Parse LaTeX source text directly into kerai.nodes and kerai.edges.
 ///
-/// Returns JSON: `{file, language, nodes, edges, elapsed_ms}`.
+/// Returns JSON: `{file, language, nodes, edges, cites, elapsed_ms}`, where
+/// `cites` is the number of citation keys linked to a `bib_entry` after
+/// re-running `link_citations` over everything ingested so far.
 #[pg_extern]
-fn parse_latex_source(source: &str, filename: &str) -> pgrx::JsonB {
+pub(crate) fn parse_latex_source(source: &str, filename: &str) -> pgrx::JsonB {
     let start = Instant::now();
     let instance_id = super::get_self_instance_id();
 
@@ -39,19 +43,26 @@ fn parse_latex_source(source: &str, filename: &str) -> pgrx::JsonB {
         ));
     }
 
+    let cites = if node_count > 0 {
+        resolve_citations()["linked"].clone()
+    } else {
+        json!(0)
+    };
+
     let elapsed = start.elapsed();
     pgrx::JsonB(json!({
         "file": filename,
         "language": "latex",
         "nodes": node_count,
         "edges": edge_count,
+        "cites": cites,
         "elapsed_ms": elapsed.as_millis() as u64,
     }))
 }
 
 /// Parse a LaTeX file from disk into kerai.nodes and kerai.edges.
 ///
-/// Returns JSON: `{file, language, nodes, edges, elapsed_ms}`.
+/// Returns JSON: `{file, language, nodes, edges, cites, elapsed_ms}`.
 #[pg_extern]
 fn parse_latex_file(path: &str) -> pgrx::JsonB {
     let start = Instant::now();
@@ -85,21 +96,28 @@ fn parse_latex_file(path: &str) -> pgrx::JsonB {
         ));
     }
 
+    let cites = if node_count > 0 {
+        resolve_citations()["linked"].clone()
+    } else {
+        json!(0)
+    };
+
     let elapsed = start.elapsed();
     pgrx::JsonB(json!({
         "file": filename,
         "language": "latex",
         "nodes": node_count,
         "edges": edge_count,
+        "cites": cites,
         "elapsed_ms": elapsed.as_millis() as u64,
     }))
 }
 
 /// Parse BibTeX source text directly into kerai.nodes and kerai.edges.
 ///
-/// Returns JSON: `{file, language, nodes, edges, elapsed_ms}`.
+/// Returns JSON: `{file, language, nodes, edges, cites, elapsed_ms}`.
 #[pg_extern]
-fn parse_bibtex_source(source: &str, filename: &str) -> pgrx::JsonB {
+pub(crate) fn parse_bibtex_source(source: &str, filename: &str) -> pgrx::JsonB {
     let start = Instant::now();
     let instance_id = super::get_self_instance_id();
 
@@ -118,19 +136,26 @@ fn parse_bibtex_source(source: &str, filename: &str) -> pgrx::JsonB {
         ));
     }
 
+    let cites = if node_count > 0 {
+        resolve_citations()["linked"].clone()
+    } else {
+        json!(0)
+    };
+
     let elapsed = start.elapsed();
     pgrx::JsonB(json!({
         "file": filename,
         "language": "bibtex",
         "nodes": node_count,
         "edges": edge_count,
+        "cites": cites,
         "elapsed_ms": elapsed.as_millis() as u64,
     }))
 }
 
 /// Parse a BibTeX file from disk into kerai.nodes and kerai.edges.
 ///
-/// Returns JSON: `{file, language, nodes, edges, elapsed_ms}`.
+/// Returns JSON: `{file, language, nodes, edges, cites, elapsed_ms}`.
 #[pg_extern]
 fn parse_bibtex_file(path: &str) -> pgrx::JsonB {
     let start = Instant::now();
@@ -164,35 +189,159 @@ fn parse_bibtex_file(path: &str) -> pgrx::JsonB {
         ));
     }
 
+    let cites = if node_count > 0 {
+        resolve_citations()["linked"].clone()
+    } else {
+        json!(0)
+    };
+
     let elapsed = start.elapsed();
     pgrx::JsonB(json!({
         "file": filename,
         "language": "bibtex",
         "nodes": node_count,
         "edges": edge_count,
+        "cites": cites,
         "elapsed_ms": elapsed.as_millis() as u64,
     }))
 }
 
 /// Link citation nodes to bib_entry nodes across the database.
 ///
-/// Finds all `latex_citation` nodes and matches their keys to `bib_entry` nodes,
-/// creating `cites` edges. This should be called after parsing both .tex and .bib files.
+/// Finds all `latex_citation` nodes and matches their keys (case-sensitively)
+/// to `bib_entry` nodes, creating `cites` edges. Runs automatically after each
+/// .tex or .bib parse, so it only needs calling by hand after bulk imports.
+/// Keys defined by more than one `bib_entry` are not linked and are reported
+/// under `ambiguous`.
 ///
-/// Returns JSON: `{linked, unresolved}`.
+/// Returns JSON: `{linked, unresolved, ambiguous: [{key, entries}], elapsed_ms}`.
 #[pg_extern]
 fn link_citations() -> pgrx::JsonB {
     let start = Instant::now();
+
+    let has_entries =
+        Spi::get_one::<bool>("SELECT EXISTS (SELECT 1 FROM kerai.nodes WHERE kind = 'bib_entry')")
+            .unwrap()
+            .unwrap_or(false);
+    if !has_entries {
+        return pgrx::JsonB(json!({
+            "linked": 0,
+            "unresolved": 0,
+            "ambiguous": [],
+            "elapsed_ms": start.elapsed().as_millis() as u64,
+            "message": "No bib_entry nodes found. Parse .bib files first."
+        }));
+    }
+
+    let mut result = resolve_citations();
+    result["elapsed_ms"] = json!(start.elapsed().as_millis() as u64);
+    pgrx::JsonB(result)
+}
+
+/// Bib entries under a .bib file node that no `\cite` anywhere refers to.
+///
+/// Returns JSON: `{uncited: [{id, key, entry_type, title}], ambiguous: [{key, entries}]}`,
+/// where `ambiguous` lists this file's keys that are also defined elsewhere.
+#[pg_extern]
+fn uncited_entries(bib: pgrx::Uuid) -> pgrx::JsonB {
+    let scope = subtree_sql(&bib.to_string());
+    let uncited = Spi::get_one::<pgrx::JsonB>(&format!(
+        "WITH scope AS ({scope})
+        SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', n.id,
+            'key', n.content,
+            'entry_type', n.metadata->>'entry_type',
+            'title', n.metadata->>'title'
+        ) ORDER BY n.position), '[]'::jsonb)
+        FROM kerai.nodes n
+        JOIN scope s ON s.id = n.id
+        WHERE n.kind = '{BIB_ENTRY}'
+          AND NOT EXISTS (
+              SELECT 1 FROM kerai.nodes c
+              WHERE c.kind = '{LATEX_CITATION}' AND c.metadata->'keys' ? n.content
+          )",
+        BIB_ENTRY = kinds::BIB_ENTRY,
+        LATEX_CITATION = kinds::LATEX_CITATION,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(json!([])));
+
+    let keys = Spi::get_one::<pgrx::JsonB>(&format!(
+        "WITH scope AS ({scope})
+        SELECT COALESCE(jsonb_agg(DISTINCT n.content), '[]'::jsonb)
+        FROM kerai.nodes n JOIN scope s ON s.id = n.id
+        WHERE n.kind = '{}'",
+        kinds::BIB_ENTRY,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(json!([])));
+
+    pgrx::JsonB(json!({
+        "uncited": uncited.0,
+        "ambiguous": ambiguous_keys(&keys.0),
+    }))
+}
+
+/// Citation keys under a .tex file node with no matching `bib_entry`.
+///
+/// Returns JSON: `{undefined: [{citation_id, key, line}], ambiguous: [{key, entries}]}`,
+/// where `ambiguous` lists cited keys defined by more than one `bib_entry`.
+#[pg_extern]
+fn undefined_citations(tex: pgrx::Uuid) -> pgrx::JsonB {
+    let scope = subtree_sql(&tex.to_string());
+    let undefined = Spi::get_one::<pgrx::JsonB>(&format!(
+        "WITH scope AS ({scope})
+        SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'citation_id', n.id,
+            'key', k.key,
+            'line', (n.metadata->>'line')::int
+        ) ORDER BY (n.metadata->>'line')::int, k.key), '[]'::jsonb)
+        FROM kerai.nodes n
+        JOIN scope s ON s.id = n.id
+        CROSS JOIN LATERAL jsonb_array_elements_text(n.metadata->'keys') AS k(key)
+        WHERE n.kind = '{LATEX_CITATION}'
+          AND NOT EXISTS (
+              SELECT 1 FROM kerai.nodes b
+              WHERE b.kind = '{BIB_ENTRY}' AND b.content = k.key
+          )",
+        BIB_ENTRY = kinds::BIB_ENTRY,
+        LATEX_CITATION = kinds::LATEX_CITATION,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(json!([])));
+
+    let keys = Spi::get_one::<pgrx::JsonB>(&format!(
+        "WITH scope AS ({scope})
+        SELECT COALESCE(jsonb_agg(DISTINCT k.key), '[]'::jsonb)
+        FROM kerai.nodes n
+        JOIN scope s ON s.id = n.id
+        CROSS JOIN LATERAL jsonb_array_elements_text(n.metadata->'keys') AS k(key)
+        WHERE n.kind = '{}'",
+        kinds::LATEX_CITATION,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(json!([])));
+
+    pgrx::JsonB(json!({
+        "undefined": undefined.0,
+        "ambiguous": ambiguous_keys(&keys.0),
+    }))
+}
+
+/// Link every citation key to its unique `bib_entry`, skipping ambiguous keys.
+///
+/// Returns JSON: `{linked, unresolved, ambiguous: [{key, entries}]}`.
+fn resolve_citations() -> serde_json::Value {
     let mut linked = 0u64;
     let mut unresolved = 0u64;
 
-    // Build a map of cite_key → bib_entry node ID
-    let mut bib_map: std::collections::HashMap<String, String> = std::collections::HashMap::new();
+    // Build a map of cite_key → bib_entry node IDs
+    let mut bib_map: HashMap<String, Vec<String>> = HashMap::new();
 
     Spi::connect(|client| {
         let result = client
             .select(
-                "SELECT id::text, content FROM kerai.nodes WHERE kind = 'bib_entry'",
+                "SELECT id::text, content FROM kerai.nodes WHERE kind = 'bib_entry' ORDER BY id",
                 None,
                 &[],
             )
@@ -208,20 +357,11 @@ fn link_citations() -> pgrx::JsonB {
                 .expect("content column")
                 .unwrap_or_default();
             if !key.is_empty() {
-                bib_map.insert(key, id);
+                bib_map.entry(key).or_default().push(id);
             }
         }
     });
 
-    if bib_map.is_empty() {
-        return pgrx::JsonB(json!({
-            "linked": 0,
-            "unresolved": 0,
-            "elapsed_ms": start.elapsed().as_millis() as u64,
-            "message": "No bib_entry nodes found. Parse .bib files first."
-        }));
-    }
-
     // Find all citation nodes and their keys
     let mut citations: Vec<(String, Vec<String>)> = Vec::new();
 
@@ -259,20 +399,25 @@ fn link_citations() -> pgrx::JsonB {
 
     // Create edges for matched citations
     let mut edges: Vec<EdgeRow> = Vec::new();
+    let mut ambiguous: BTreeMap<String, Vec<String>> = BTreeMap::new();
 
     for (cite_node_id, keys) in &citations {
         for key in keys {
-            if let Some(bib_node_id) = bib_map.get(key) {
-                edges.push(EdgeRow {
-                    id: Uuid::new_v4().to_string(),
-                    source_id: cite_node_id.clone(),
-                    target_id: bib_node_id.clone(),
-                    relation: "cites".to_string(),
-                    metadata: json!({"key": key}),
-                });
-                linked += 1;
-            } else {
-                unresolved += 1;
+            match bib_map.get(key).map(Vec::as_slice) {
+                Some([bib_node_id]) => {
+                    edges.push(EdgeRow {
+                        id: Uuid::new_v4().to_string(),
+                        source_id: cite_node_id.clone(),
+                        target_id: bib_node_id.clone(),
+                        relation: "cites".to_string(),
+                        metadata: json!({"key": key}),
+                    });
+                    linked += 1;
+                }
+                Some(entries) if !entries.is_empty() => {
+                    ambiguous.insert(key.clone(), entries.to_vec());
+                }
+                _ => unresolved += 1,
             }
         }
     }
@@ -281,12 +426,52 @@ fn link_citations() -> pgrx::JsonB {
         inserter::insert_edges(&edges);
     }
 
-    let elapsed = start.elapsed();
-    pgrx::JsonB(json!({
+    json!({
         "linked": linked,
         "unresolved": unresolved,
-        "elapsed_ms": elapsed.as_millis() as u64,
-    }))
+        "ambiguous": ambiguous
+            .into_iter()
+            .map(|(key, entries)| json!({"key": key, "entries": entries}))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Of the given keys, those defined by more than one `bib_entry`, as
+/// `[{key, entries}]`.
+fn ambiguous_keys(keys: &serde_json::Value) -> serde_json::Value {
+    if keys.as_array().is_none_or(|k| k.is_empty()) {
+        return json!([]);
+    }
+    Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object('key', d.key, 'entries', d.entries)
+                                   ORDER BY d.key), '[]'::jsonb)
+        FROM (
+            SELECT n.content AS key, jsonb_agg(n.id ORDER BY n.id) AS entries
+            FROM kerai.nodes n
+            WHERE n.kind = '{}'
+              AND n.content IN (SELECT jsonb_array_elements_text({}))
+            GROUP BY n.content
+            HAVING count(*) > 1
+        ) d",
+        kinds::BIB_ENTRY,
+        sql_jsonb(keys),
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(json!([])))
+    .0
+}
+
+/// Recursive `SELECT id` over a node and all its descendants.
+fn subtree_sql(root_id: &str) -> String {
+    format!(
+        "WITH RECURSIVE sub AS (
+            SELECT id FROM kerai.nodes WHERE id = {}
+            UNION ALL
+            SELECT n.id FROM kerai.nodes n JOIN sub ON n.parent_id = sub.id
+        )
+        SELECT id FROM sub",
+        sql_uuid(root_id),
+    )
 }
 
 /// Parse LaTeX source, insert nodes/edges, return counts.
@@ -328,7 +328,8 @@ fn walk_citation(
     cmd_name: &str,
 ) {
     let source = ctx.source.clone();
-    let meta = metadata::citation_metadata(node, &source, cmd_name);
+    let mut meta = metadata::citation_metadata(node, &source, cmd_name);
+    meta["line"] = json!(span_start_line(node));
 
     let keys: Vec<String> = meta
         .get("keys")
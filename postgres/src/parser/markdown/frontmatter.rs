@@ -0,0 +1,151 @@
+/// YAML-style frontmatter (`--- ... ---` at the top of a document).
+///
+/// Only the flat subset used in practice is understood: `key: value` lines,
+/// inline lists (`tags: [a, b]`), and block lists (`- item` lines under a key
+/// with no value). Anything else makes the block malformed, and the caller
+/// parses the whole source as ordinary markdown.
+use serde_json::{Map, Value};
+
+/// A frontmatter block split off the start of a document.
+#[derive(Debug)]
+pub struct Frontmatter {
+    /// The block exactly as written, from the opening to the closing fence.
+    pub raw: String,
+    pub fields: Map<String, Value>,
+}
+
+/// Split leading frontmatter from `source`, returning it and the remaining body.
+/// Returns None when there is no frontmatter or it is malformed.
+pub fn split(source: &str) -> Option<(Frontmatter, &str)> {
+    let mut lines = source.split_inclusive('\n');
+    let first = lines.next()?;
+    if first.trim_end() != "---" {
+        return None;
+    }
+
+    let mut offset = first.len();
+    let mut fields = Map::new();
+    let mut list_key: Option<String> = None;
+    let mut closed = false;
+
+    for line in lines {
+        offset += line.len();
+        let text = line.trim_end();
+        if text == "---" || text == "..." {
+            closed = true;
+            break;
+        }
+        if text.trim().is_empty() || text.trim_start().starts_with('#') {
+            continue;
+        }
+
+        if let Some(item) = text.trim_start().strip_prefix("- ") {
+            let key = list_key.as_ref()?;
+            if let Some(Value::Array(items)) = fields.get_mut(key) {
+                items.push(scalar(item));
+                continue;
+            }
+            return None;
+        }
+
+        if text.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let (key, value) = text.split_once(':')?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return None;
+        }
+        let value = value.trim();
+        if value.is_empty() {
+            list_key = Some(key.to_string());
+            fields.insert(key.to_string(), Value::Array(Vec::new()));
+        } else {
+            list_key = None;
+            fields.insert(key.to_string(), parse_value(value)?);
+        }
+    }
+
+    if !closed || fields.is_empty() {
+        return None;
+    }
+
+    let raw = source[..offset].trim_end_matches(['\n', '\r']).to_string();
+    let body = source[offset..].trim_start_matches(['\n', '\r']);
+    Some((Frontmatter { raw, fields }, body))
+}
+
+/// Parse a value: an inline `[a, b]` list or a scalar.
+fn parse_value(value: &str) -> Option<Value> {
+    if let Some(inner) = value.strip_prefix('[') {
+        let inner = inner.strip_suffix(']')?;
+        let items = inner
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(scalar)
+            .collect();
+        return Some(Value::Array(items));
+    }
+    Some(scalar(value))
+}
+
+/// Parse a scalar: quoted string, boolean, integer, or bare string.
+fn scalar(value: &str) -> Value {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return Value::String(value[1..value.len() - 1].to_string());
+        }
+    }
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => value
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_split_fields_and_body() {
+        let source = "---\ntitle: \"Intro\"\ndraft: false\nweight: 3\ntags: [rust, db]\nauthors:\n  - ada\n  - lin\n---\n\n# Heading\n";
+        let (fm, body) = split(source).unwrap();
+        assert_eq!(fm.raw, "---\ntitle: \"Intro\"\ndraft: false\nweight: 3\ntags: [rust, db]\nauthors:\n  - ada\n  - lin\n---");
+        assert_eq!(body, "# Heading\n");
+        assert_eq!(
+            Value::Object(fm.fields),
+            json!({
+                "title": "Intro",
+                "draft": false,
+                "weight": 3,
+                "tags": ["rust", "db"],
+                "authors": ["ada", "lin"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_no_frontmatter() {
+        assert!(split("# Title\n\n---\n").is_none());
+        assert!(split("").is_none());
+    }
+
+    #[test]
+    fn test_malformed_frontmatter() {
+        // Unclosed
+        assert!(split("---\ntitle: x\n\nBody\n").is_none());
+        // Not key: value
+        assert!(split("---\njust a sentence\n---\n").is_none());
+        // A thematic break pair with nothing between
+        assert!(split("---\n---\n").is_none());
+        // List item without a key
+        assert!(split("---\n- orphan\n---\n").is_none());
+    }
+}
@@ -22,3 +22,14 @@ pub const STRIKETHROUGH: &str = "strikethrough";
 pub const INLINE_CODE: &str = "inline_code";
 pub const HARD_BREAK: &str = "hard_break";
 pub const HTML_BLOCK: &str = "html_block";
+pub const FRONTMATTER: &str = "frontmatter";
+
+/// All markdown kinds, for kind validation and `kerai.list_kinds()`.
+pub const ALL: &[&str] = &[
+    DOCUMENT, HEADING, PARAGRAPH, BLOCKQUOTE,
+    LIST, LIST_ITEM, CODE_BLOCK, THEMATIC_BREAK,
+    LINK, IMAGE, TABLE, TABLE_HEAD,
+    TABLE_ROW, TABLE_CELL, FOOTNOTE, TEXT,
+    EMPHASIS, STRONG, STRIKETHROUGH, INLINE_CODE,
+    HARD_BREAK, HTML_BLOCK, FRONTMATTER,
+];
@@ -44,6 +44,7 @@ fn delete_markdown_nodes(instance_id: &str, filename: &str) {
     .ok();
 }
 
+mod frontmatter;
 #[allow(dead_code)]
 pub mod kinds;
 mod walker;
@@ -52,7 +53,7 @@ mod walker;
 ///
 /// Returns JSON: `{file, nodes, edges, elapsed_ms}`.
 #[pg_extern]
-fn parse_markdown(source: &str, filename: &str) -> pgrx::JsonB {
+pub(crate) fn parse_markdown(source: &str, filename: &str) -> pgrx::JsonB {
     let start = Instant::now();
     let instance_id = super::get_self_instance_id();
 
@@ -94,6 +95,17 @@ pub(crate) fn parse_markdown_single(
 ) -> (usize, usize) {
     let path_ctx = PathContext::with_root(filename);
 
+    // Split off leading frontmatter; malformed blocks stay in the body
+    let (front, body) = match frontmatter::split(source) {
+        Some((fm, body)) => (Some(fm), body),
+        None => (None, source),
+    };
+
+    let mut doc_metadata = json!({"line_count": source.lines().count()});
+    if let Some(fm) = &front {
+        doc_metadata["frontmatter"] = serde_json::Value::Object(fm.fields.clone());
+    }
+
     // Create document root node
     let doc_node_id = Uuid::new_v4().to_string();
     let doc_node = NodeRow {
@@ -105,16 +117,40 @@ pub(crate) fn parse_markdown_single(
         parent_id: parent_id.map(|s| s.to_string()),
         position: 0,
         path: path_ctx.path(),
-        metadata: json!({"line_count": source.lines().count()}),
+        metadata: doc_metadata,
         span_start: None,
         span_end: None,
     };
     inserter::insert_nodes(&[doc_node]);
 
+    // Frontmatter fields become the node's metadata, so find_by_metadata can match them
+    let front_count = match front {
+        Some(fm) => {
+            let mut fm_path = PathContext::with_root(filename);
+            fm_path.push(kinds::FRONTMATTER);
+            inserter::insert_nodes(&[NodeRow {
+                id: Uuid::new_v4().to_string(),
+                instance_id: instance_id.to_string(),
+                kind: kinds::FRONTMATTER.to_string(),
+                language: Some("markdown".to_string()),
+                content: Some(fm.raw),
+                parent_id: Some(doc_node_id.clone()),
+                position: 0,
+                path: fm_path.path(),
+                metadata: serde_json::Value::Object(fm.fields),
+                span_start: None,
+                span_end: None,
+            }]);
+            1
+        }
+        None => 0,
+    };
+
     // Walk markdown and collect nodes/edges
-    let (nodes, edges) = walker::walk_markdown(source, filename, instance_id, &doc_node_id);
+    let (nodes, edges) =
+        walker::walk_markdown(body, filename, instance_id, &doc_node_id, front_count as i32);
 
-    let node_count = nodes.len() + 1; // +1 for document node
+    let node_count = nodes.len() + 1 + front_count; // +1 for document node
     let edge_count = edges.len();
 
     inserter::insert_nodes(&nodes);
@@ -15,11 +15,15 @@ struct StackEntry {
 }
 
 /// Walk markdown source and produce NodeRow/EdgeRow vectors.
+///
+/// Positions start at `first_position`, leaving room for nodes the caller
+/// places ahead of the body (frontmatter).
 pub fn walk_markdown(
     source: &str,
     filename: &str,
     instance_id: &str,
     document_node_id: &str,
+    first_position: i32,
 ) -> (Vec<NodeRow>, Vec<EdgeRow>) {
     let opts = Options::ENABLE_TABLES
         | Options::ENABLE_FOOTNOTES
@@ -38,7 +42,7 @@ pub fn walk_markdown(
     // Each entry: (heading_level, node_id)
     let mut heading_stack: Vec<(u8, String)> = Vec::new();
 
-    let mut position: i32 = 0;
+    let mut position = first_position;
     let mut text_accum = String::new();
     let mut current_link: Option<(String, String)> = None; // (url, title)
 
@@ -24,50 +24,144 @@ pub fn visibility_str(vis: &syn::Visibility) -> &'static str {
 pub fn fn_metadata(sig: &syn::Signature, vis: &syn::Visibility) -> Value {
     let mut m = Map::new();
     m.insert("visibility".into(), json!(visibility_str(vis)));
-    if sig.asyncness.is_some() {
-        m.insert("async".into(), json!(true));
-    }
-    if sig.unsafety.is_some() {
-        m.insert("unsafe".into(), json!(true));
-    }
-    if sig.constness.is_some() {
-        m.insert("const".into(), json!(true));
-    }
-    if sig.abi.is_some() {
-        let abi = sig
-            .abi
+    m.insert("signature".into(), json!(quote::quote!(#sig).to_string()));
+    m.insert("is_async".into(), json!(sig.asyncness.is_some()));
+    m.insert("is_const".into(), json!(sig.constness.is_some()));
+    m.insert("is_unsafe".into(), json!(sig.unsafety.is_some()));
+    if let Some(abi) = &sig.abi {
+        // A bare `extern fn` uses the C ABI
+        let name = abi
+            .name
             .as_ref()
-            .and_then(|a| a.name.as_ref())
-            .map(|n| n.value());
-        if let Some(abi) = abi {
-            m.insert("abi".into(), json!(abi));
-        }
+            .map(|n| n.value())
+            .unwrap_or_else(|| "C".into());
+        m.insert("abi".into(), json!(name));
+    }
+    insert_generics(&sig.generics, &mut m);
+    Value::Object(m)
+}
+
+/// Record generic parameters under `generics` and where-clause predicates
+/// under `where`, so reconstruction can re-emit them.
+///
+/// Parameters are `{kind: "lifetime"|"type"|"const", name, bounds, ty, default}`
+/// (`bounds` for lifetime and type params, `ty` for const params, `default`
+/// when given). Predicates are `{bounded, bounds}`, plus `for` for a
+/// higher-ranked `for<'a>` binder.
+pub fn insert_generics(generics: &syn::Generics, m: &mut Map<String, Value>) {
+    fn tokens(t: impl quote::ToTokens) -> String {
+        quote::quote!(#t).to_string()
     }
-    if !sig.generics.params.is_empty() {
-        let params: Vec<String> = sig
-            .generics
+
+    if !generics.params.is_empty() {
+        let params: Vec<Value> = generics
             .params
             .iter()
-            .map(|p| quote::quote!(#p).to_string())
+            .map(|p| match p {
+                syn::GenericParam::Lifetime(l) => json!({
+                    "kind": "lifetime",
+                    "name": tokens(&l.lifetime),
+                    "bounds": l.bounds.iter().map(tokens).collect::<Vec<_>>(),
+                }),
+                syn::GenericParam::Type(t) => {
+                    let mut param = json!({
+                        "kind": "type",
+                        "name": t.ident.to_string(),
+                        "bounds": t.bounds.iter().map(tokens).collect::<Vec<_>>(),
+                    });
+                    if let Some(default) = &t.default {
+                        param["default"] = json!(tokens(default));
+                    }
+                    param
+                }
+                syn::GenericParam::Const(c) => {
+                    let mut param = json!({
+                        "kind": "const",
+                        "name": c.ident.to_string(),
+                        "ty": tokens(&c.ty),
+                    });
+                    if let Some(default) = &c.default {
+                        param["default"] = json!(tokens(default));
+                    }
+                    param
+                }
+            })
             .collect();
         m.insert("generics".into(), json!(params));
     }
-    Value::Object(m)
+
+    if let Some(where_clause) = &generics.where_clause {
+        if !where_clause.predicates.is_empty() {
+            let preds: Vec<Value> = where_clause
+                .predicates
+                .iter()
+                .map(|pred| match pred {
+                    syn::WherePredicate::Lifetime(p) => json!({
+                        "bounded": tokens(&p.lifetime),
+                        "bounds": p.bounds.iter().map(tokens).collect::<Vec<_>>(),
+                    }),
+                    syn::WherePredicate::Type(p) => {
+                        let mut out = json!({
+                            "bounded": tokens(&p.bounded_ty),
+                            "bounds": p.bounds.iter().map(tokens).collect::<Vec<_>>(),
+                        });
+                        if let Some(binder) = &p.lifetimes {
+                            let lifetimes: Vec<String> =
+                                binder.lifetimes.iter().map(tokens).collect();
+                            out["for"] = json!(lifetimes);
+                        }
+                        out
+                    }
+                    other => json!({"predicate": tokens(other)}),
+                })
+                .collect();
+            m.insert("where".into(), json!(preds));
+        }
+    }
+}
+
+/// Record how many times a function body calls each callee under `calls`,
+/// keyed by the last path segment or method name. Items nested in the body
+/// are their own nodes and aren't counted.
+pub fn insert_calls(meta: &mut Value, block: &syn::Block) {
+    use std::collections::BTreeMap;
+    use syn::visit::Visit;
+
+    struct CallCounter(BTreeMap<String, u32>);
+
+    impl<'ast> Visit<'ast> for CallCounter {
+        fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+            if let syn::Expr::Path(p) = &*call.func {
+                if let Some(last) = p.path.segments.last() {
+                    *self.0.entry(last.ident.to_string()).or_default() += 1;
+                }
+            }
+            syn::visit::visit_expr_call(self, call);
+        }
+
+        fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+            *self.0.entry(call.method.to_string()).or_default() += 1;
+            syn::visit::visit_expr_method_call(self, call);
+        }
+
+        fn visit_item(&mut self, _item: &'ast syn::Item) {}
+    }
+
+    let mut counter = CallCounter(BTreeMap::new());
+    counter.visit_block(block);
+    if counter.0.is_empty() {
+        return;
+    }
+    if let Value::Object(m) = meta {
+        m.insert("calls".into(), json!(counter.0));
+    }
 }
 
 /// Extract metadata from a struct.
 pub fn struct_metadata(item: &syn::ItemStruct, vis: &syn::Visibility) -> Value {
     let mut m = Map::new();
     m.insert("visibility".into(), json!(visibility_str(vis)));
-    if !item.generics.params.is_empty() {
-        let params: Vec<String> = item
-            .generics
-            .params
-            .iter()
-            .map(|p| quote::quote!(#p).to_string())
-            .collect();
-        m.insert("generics".into(), json!(params));
-    }
+    insert_generics(&item.generics, &mut m);
     extract_derives(&item.attrs, &mut m);
     extract_cfg(&item.attrs, &mut m);
     Value::Object(m)
@@ -77,15 +171,7 @@ pub fn struct_metadata(item: &syn::ItemStruct, vis: &syn::Visibility) -> Value {
 pub fn enum_metadata(item: &syn::ItemEnum, vis: &syn::Visibility) -> Value {
     let mut m = Map::new();
     m.insert("visibility".into(), json!(visibility_str(vis)));
-    if !item.generics.params.is_empty() {
-        let params: Vec<String> = item
-            .generics
-            .params
-            .iter()
-            .map(|p| quote::quote!(#p).to_string())
-            .collect();
-        m.insert("generics".into(), json!(params));
-    }
+    insert_generics(&item.generics, &mut m);
     extract_derives(&item.attrs, &mut m);
     extract_cfg(&item.attrs, &mut m);
     Value::Object(m)
@@ -98,15 +184,7 @@ pub fn trait_metadata(item: &syn::ItemTrait, vis: &syn::Visibility) -> Value {
     if item.unsafety.is_some() {
         m.insert("unsafe".into(), json!(true));
     }
-    if !item.generics.params.is_empty() {
-        let params: Vec<String> = item
-            .generics
-            .params
-            .iter()
-            .map(|p| quote::quote!(#p).to_string())
-            .collect();
-        m.insert("generics".into(), json!(params));
-    }
+    insert_generics(&item.generics, &mut m);
     if !item.supertraits.is_empty() {
         let supers: Vec<String> = item
             .supertraits
@@ -125,27 +203,44 @@ pub fn impl_metadata(item: &syn::ItemImpl) -> Value {
     if item.unsafety.is_some() {
         m.insert("unsafe".into(), json!(true));
     }
+    // trait_name is null for inherent impls
+    let mut trait_name = Value::Null;
     if let Some((_, ref trait_path, _)) = item.trait_ {
         m.insert(
             "trait".into(),
             json!(quote::quote!(#trait_path).to_string()),
         );
+        if let Some(last) = trait_path.segments.last() {
+            trait_name = json!(last.ident.to_string());
+        }
     }
+    m.insert("trait_name".into(), trait_name);
     let self_ty = &item.self_ty;
     m.insert("self_ty".into(), json!(quote::quote!(#self_ty).to_string()));
-    if !item.generics.params.is_empty() {
-        let params: Vec<String> = item
-            .generics
-            .params
-            .iter()
-            .map(|p| quote::quote!(#p).to_string())
-            .collect();
-        m.insert("generics".into(), json!(params));
+    if let Some(name) = type_name(self_ty) {
+        m.insert("type_name".into(), json!(name));
     }
+    insert_generics(&item.generics, &mut m);
     extract_cfg(&item.attrs, &mut m);
     Value::Object(m)
 }
 
+/// Name of the type an impl is for: the last path segment, looking through
+/// references, parens, and groups (`impl Trait for &Foo<T>` gives `Foo`).
+fn type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .map(|last| last.ident.to_string()),
+        syn::Type::Reference(r) => type_name(&r.elem),
+        syn::Type::Paren(p) => type_name(&p.elem),
+        syn::Type::Group(g) => type_name(&g.elem),
+        _ => None,
+    }
+}
+
 /// Extract metadata from a const item.
 pub fn const_metadata(vis: &syn::Visibility) -> Value {
     let mut m = Map::new();
@@ -216,14 +311,84 @@ fn extract_derives(attrs: &[syn::Attribute], m: &mut Map<String, Value>) {
 }
 
 /// Extract #[cfg(...)] conditions from attributes.
-fn extract_cfg(attrs: &[syn::Attribute], m: &mut Map<String, Value>) {
+///
+/// `cfg` keeps the attribute text; `cfg_expr` holds the parsed predicate
+/// (several attributes are combined under `all`) so reconstruction can
+/// evaluate it against a feature set.
+pub fn extract_cfg(attrs: &[syn::Attribute], m: &mut Map<String, Value>) {
     let mut cfgs = Vec::new();
+    let mut exprs = Vec::new();
     for attr in attrs {
         if attr.path().is_ident("cfg") {
             cfgs.push(quote::quote!(#attr).to_string());
+            if let Some(expr) = cfg_expr(attr) {
+                exprs.push(expr);
+            }
         }
     }
     if !cfgs.is_empty() {
         m.insert("cfg".into(), json!(cfgs));
     }
+    match exprs.len() {
+        0 => {}
+        1 => {
+            m.insert("cfg_expr".into(), exprs.remove(0));
+        }
+        _ => {
+            m.insert("cfg_expr".into(), json!({"all": exprs}));
+        }
+    }
+}
+
+/// Parse a `#[cfg(...)]` attribute into a predicate tree:
+/// `{"key": "test"}`, `{"key": "feature", "value": "x"}`,
+/// `{"all": [..]}`, `{"any": [..]}`, `{"not": ..}`.
+/// Returns None for non-cfg or malformed attributes.
+pub fn cfg_expr(attr: &syn::Attribute) -> Option<Value> {
+    if !attr.path().is_ident("cfg") {
+        return None;
+    }
+    let mut preds = Vec::new();
+    attr.parse_nested_meta(|meta| {
+        preds.push(cfg_predicate(&meta)?);
+        Ok(())
+    })
+    .ok()?;
+    if preds.len() == 1 {
+        preds.pop()
+    } else {
+        None
+    }
+}
+
+fn cfg_predicate(meta: &syn::meta::ParseNestedMeta) -> syn::Result<Value> {
+    let key = meta
+        .path
+        .get_ident()
+        .map(ToString::to_string)
+        .ok_or_else(|| meta.error("expected cfg identifier"))?;
+
+    match key.as_str() {
+        "all" | "any" | "not" => {
+            let mut inner = Vec::new();
+            meta.parse_nested_meta(|nested| {
+                inner.push(cfg_predicate(&nested)?);
+                Ok(())
+            })?;
+            if key == "not" {
+                if inner.len() != 1 {
+                    return Err(meta.error("not() takes exactly one predicate"));
+                }
+                return Ok(json!({"not": inner.remove(0)}));
+            }
+            let mut m = Map::new();
+            m.insert(key, json!(inner));
+            Ok(Value::Object(m))
+        }
+        _ if meta.input.peek(syn::Token![=]) => {
+            let lit: syn::LitStr = meta.value()?.parse()?;
+            Ok(json!({"key": key, "value": lit.value()}))
+        }
+        _ => Ok(json!({"key": key})),
+    }
 }
@@ -11,15 +11,17 @@ mod cargo_parser;
 #[allow(dead_code)]
 mod comment_extractor;
 mod crate_walker;
+mod doctest_extractor;
 mod flag_parser;
+pub(crate) mod import_linker;
 #[allow(dead_code)]
 pub(crate) mod inserter;
 pub mod kinds;
 #[allow(dead_code)]
-mod metadata;
+pub(crate) mod metadata;
 mod normalizer;
 #[allow(dead_code)]
-mod path_builder;
+pub(crate) mod path_builder;
 pub mod markdown;
 mod suggestion_rules;
 mod treesitter;
@@ -27,12 +29,26 @@ pub mod go;
 pub mod c;
 pub mod latex;
 pub mod csv;
+pub mod json;
 
 use ast_walker::NodeRow;
 use comment_extractor::{CommentBlock, CommentPlacement};
 use kinds::Kind;
 use path_builder::PathContext;
 
+/// Mint a parse reward, crediting the acting agent's wallet when the session
+/// names one (see `currency::acting_agent_wallet`), else the default recipient.
+fn mint_parse_reward(work_type: &str, details: &serde_json::Value) {
+    let recipient = crate::currency::acting_agent_wallet()
+        .map_or_else(|| "NULL".to_string(), |w| crate::sql::sql_uuid(&w));
+    let _ = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT kerai.mint_reward('{}', {}, {})",
+        work_type,
+        crate::sql::sql_jsonb(details),
+        recipient,
+    ));
+}
+
 /// Get the self instance ID from the database.
 pub(crate) fn get_self_instance_id() -> String {
     Spi::get_one::<String>("SELECT id::text FROM kerai.instances WHERE is_self = true")
@@ -41,6 +57,10 @@ pub(crate) fn get_self_instance_id() -> String {
 }
 
 /// Parse an entire Rust crate into kerai.nodes and kerai.edges.
+///
+/// If the Cargo.toml declares a `[workspace]`, every member crate (resolved
+/// from `members` globs minus `exclude`, plus the root package if any) is
+/// parsed under a single `workspace` node and the totals cover all members.
 #[pg_extern]
 fn parse_crate(path: &str) -> pgrx::JsonB {
     let start = Instant::now();
@@ -57,19 +77,53 @@ fn parse_crate(path: &str) -> pgrx::JsonB {
 
     let instance_id = get_self_instance_id();
 
-    // Parse Cargo.toml
+    let workspace = cargo_parser::read_workspace(&cargo_path)
+        .unwrap_or_else(|e| pgrx::error!("Failed to parse Cargo.toml: {}", e));
+    if let Some(spec) = workspace {
+        return parse_workspace(crate_root, &spec, &instance_id, start);
+    }
+
+    let stats = parse_crate_dir(crate_root, &instance_id, None, 0)
+        .unwrap_or_else(|e| pgrx::error!("{}", e));
+
+    pgrx::JsonB(json!({
+        "crate": stats.name,
+        "files": stats.files,
+        "nodes": stats.nodes,
+        "edges": stats.edges,
+        "elapsed_ms": start.elapsed().as_millis() as u64,
+    }))
+}
+
+/// Totals from parsing one crate directory.
+struct CrateStats {
+    name: String,
+    files: usize,
+    nodes: usize,
+    edges: usize,
+}
+
+/// Parse one crate (manifest plus src/ tree), optionally under a workspace node,
+/// and mint its `parse_crate` reward.
+fn parse_crate_dir(
+    crate_root: &Path,
+    instance_id: &str,
+    parent_id: Option<&str>,
+    position: i32,
+) -> Result<CrateStats, String> {
+    let cargo_path = crate_root.join("Cargo.toml");
     let (cargo_nodes, crate_node_id, crate_name) =
-        cargo_parser::parse_cargo_toml(&cargo_path, &instance_id)
-            .unwrap_or_else(|e| pgrx::error!("Failed to parse Cargo.toml: {}", e));
+        cargo_parser::parse_cargo_toml(&cargo_path, instance_id, parent_id, position)
+            .map_err(|e| format!("Failed to parse Cargo.toml: {}", e))?;
+
+    // Discover .rs files before inserting anything so a bad member leaves no trace
+    let rs_files = crate_walker::discover_rs_files(crate_root)
+        .map_err(|e| format!("Failed to discover .rs files: {}", e))?;
 
     inserter::insert_nodes(&cargo_nodes);
     let mut total_nodes = cargo_nodes.len();
     let mut total_edges = 0usize;
 
-    // Discover .rs files
-    let rs_files = crate_walker::discover_rs_files(crate_root)
-        .unwrap_or_else(|e| pgrx::error!("Failed to discover .rs files: {}", e));
-
     let file_count = rs_files.len();
 
     for (file_idx, file_path) in rs_files.iter().enumerate() {
@@ -90,7 +144,7 @@ fn parse_crate(path: &str) -> pgrx::JsonB {
         let (nodes, edges) = parse_single_file(
             &source,
             &filename,
-            &instance_id,
+            instance_id,
             Some(&crate_node_id),
             &crate_name,
             file_idx as i32,
@@ -100,7 +154,8 @@ fn parse_crate(path: &str) -> pgrx::JsonB {
         total_edges += edges;
     }
 
-    let elapsed = start.elapsed();
+    // Link `use` items to what they import now that every file is in
+    total_edges += import_linker::link_imports(&crate_node_id);
 
     // Auto-mint reward for crate parsing
     let details = json!({
@@ -109,18 +164,154 @@ fn parse_crate(path: &str) -> pgrx::JsonB {
         "nodes": total_nodes,
         "edges": total_edges,
     });
-    let details_str = details.to_string().replace('\'', "''");
-    let _ = Spi::get_one::<pgrx::JsonB>(&format!(
-        "SELECT kerai.mint_reward('parse_crate', '{}'::jsonb)",
-        details_str,
-    ));
+    mint_parse_reward("parse_crate", &details);
+
+    Ok(CrateStats {
+        name: crate_name,
+        files: file_count,
+        nodes: total_nodes,
+        edges: total_edges,
+    })
+}
+
+/// Parse every member of a Cargo workspace under one `workspace` node.
+/// Members that fail to parse, including by raising an ERROR, are rolled back
+/// and reported in `skipped` rather than aborting. Member paths are nested
+/// under the workspace's path. Re-parsing reuses the existing workspace node
+/// and replaces everything below it.
+fn parse_workspace(
+    root: &Path,
+    spec: &cargo_parser::WorkspaceSpec,
+    instance_id: &str,
+    start: Instant,
+) -> pgrx::JsonB {
+    let name = root
+        .canonicalize()
+        .ok()
+        .and_then(|p| p.file_name().map(|f| f.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "workspace".to_string());
+
+    let (members, mut skipped) =
+        crate_walker::expand_workspace_members(root, &spec.members, &spec.exclude);
+
+    let mut meta = serde_json::Map::new();
+    meta.insert("members".into(), json!(spec.members));
+    if !spec.exclude.is_empty() {
+        meta.insert("exclude".into(), json!(spec.exclude));
+    }
+    if let Some(ref resolver) = spec.resolver {
+        meta.insert("resolver".into(), json!(resolver));
+    }
+    let meta = serde_json::Value::Object(meta);
+    let workspace_path = PathContext::with_root(&name)
+        .path()
+        .unwrap_or_else(|| "workspace".to_string());
+
+    let existing = Spi::get_one::<String>(&format!(
+        "SELECT id::text FROM kerai.nodes
+         WHERE instance_id = {} AND kind = '{}' AND content = '{}' AND parent_id IS NULL
+         LIMIT 1",
+        crate::sql::sql_uuid(instance_id),
+        Kind::Workspace.as_str(),
+        crate::sql::sql_escape(&name),
+    ))
+    .unwrap_or(None);
+    let workspace_id = match existing {
+        Some(id) => {
+            inserter::delete_descendants(&id);
+            Spi::run(&format!(
+                "UPDATE kerai.nodes SET metadata = {} WHERE id = {}",
+                crate::sql::sql_jsonb(&meta),
+                crate::sql::sql_uuid(&id),
+            ))
+            .expect("Failed to update workspace node");
+            id
+        }
+        None => {
+            let id = Uuid::new_v4().to_string();
+            inserter::insert_nodes(&[NodeRow {
+                id: id.clone(),
+                instance_id: instance_id.to_string(),
+                kind: Kind::Workspace.as_str().to_string(),
+                language: Some("rust".to_string()),
+                content: Some(name.clone()),
+                parent_id: None,
+                position: 0,
+                path: Some(workspace_path.clone()),
+                metadata: meta,
+                span_start: None,
+                span_end: None,
+            }]);
+            id
+        }
+    };
+
+    // The root package, if any, is an implicit member
+    let mut dirs: Vec<(String, std::path::PathBuf, bool)> = Vec::new();
+    if spec.has_package {
+        dirs.push((".".to_string(), root.to_path_buf(), false));
+    }
+    dirs.extend(members.into_iter().map(|m| (m.rel_path, m.dir, m.submodule)));
+
+    let mut crates = Vec::new();
+    let (mut files, mut nodes, mut edges) = (0usize, 0usize, 0usize);
+    for (position, (rel_path, dir, submodule)) in dirs.into_iter().enumerate() {
+        // In a subtransaction, so a member's ERROR only rolls back that member
+        let outcome = crate::subxact::try_subtransaction(|| {
+            parse_crate_dir(&dir, instance_id, Some(&workspace_id), position as i32)
+        })
+        .unwrap_or_else(|e| Err(crate::subxact::caught_message(e)));
+        match outcome {
+            Ok(stats) => {
+                files += stats.files;
+                nodes += stats.nodes;
+                edges += stats.edges;
+                crates.push(json!({
+                    "crate": stats.name,
+                    "path": rel_path,
+                    "submodule": submodule,
+                    "files": stats.files,
+                    "nodes": stats.nodes,
+                    "edges": stats.edges,
+                }));
+            }
+            Err(e) => {
+                warning!("Skipping workspace member {}: {}", rel_path, e);
+                skipped.push((rel_path, e));
+            }
+        }
+    }
+
+    // Members are parsed with their crate name as the path root; nest them
+    Spi::run(&format!(
+        "WITH RECURSIVE descendants AS (
+            SELECT id FROM kerai.nodes WHERE parent_id = {0}
+            UNION ALL
+            SELECT n.id FROM kerai.nodes n
+            JOIN descendants d ON n.parent_id = d.id
+        )
+        UPDATE kerai.nodes SET path = {1} || path
+        WHERE id IN (SELECT id FROM descendants) AND path IS NOT NULL",
+        crate::sql::sql_uuid(&workspace_id),
+        crate::sql::sql_ltree(&workspace_path),
+    ))
+    .expect("Failed to nest member paths under the workspace");
+
+    let skipped: Vec<serde_json::Value> = skipped
+        .into_iter()
+        .map(|(path, reason)| json!({"path": path, "reason": reason}))
+        .collect();
 
     pgrx::JsonB(json!({
-        "crate": crate_name,
-        "files": file_count,
-        "nodes": total_nodes,
-        "edges": total_edges,
-        "elapsed_ms": elapsed.as_millis() as u64,
+        "workspace": name,
+        "workspace_id": workspace_id,
+        "crates": crates.len(),
+        "members": crates,
+        "skipped": skipped,
+        "files": files,
+        "nodes": nodes + 1,
+        "edges": edges,
+        "elapsed_ms": start.elapsed().as_millis() as u64,
     }))
 }
 
@@ -143,6 +334,10 @@ fn parse_file(path: &str) -> pgrx::JsonB {
         .map(|f| f.to_string_lossy().to_string())
         .unwrap_or_else(|| path.to_string());
 
+    if let Some(unchanged) = unchanged_file(&instance_id, &filename, &source, start) {
+        return unchanged;
+    }
+
     // Delete existing nodes for this file (idempotent re-parse)
     inserter::delete_file_nodes(&instance_id, &filename);
 
@@ -152,11 +347,7 @@ fn parse_file(path: &str) -> pgrx::JsonB {
     // Auto-mint reward for file parsing
     if node_count > 0 {
         let details = json!({"file": filename, "nodes": node_count, "edges": edge_count});
-        let details_str = details.to_string().replace('\'', "''");
-        let _ = Spi::get_one::<pgrx::JsonB>(&format!(
-            "SELECT kerai.mint_reward('parse_file', '{}'::jsonb)",
-            details_str,
-        ));
+        mint_parse_reward("parse_file", &details);
     }
 
     let elapsed = start.elapsed();
@@ -169,25 +360,122 @@ fn parse_file(path: &str) -> pgrx::JsonB {
 }
 
 /// Parse Rust source text directly (not from a file).
+///
+/// When the stored file node was parsed from the same normalized source, the
+/// existing nodes are kept and `{file, status: "unchanged"}` is returned.
+///
+/// With `{"profile": true}` in `options`, the result also carries a `profile`
+/// object of per-phase wall times in milliseconds (see [`ParseProfile`]).
 #[pg_extern]
-fn parse_source(source: &str, filename: &str) -> pgrx::JsonB {
+fn parse_source(
+    source: &str,
+    filename: &str,
+    options: default!(Option<pgrx::JsonB>, "NULL"),
+) -> pgrx::JsonB {
     let start = Instant::now();
     let instance_id = get_self_instance_id();
+    let profile_requested = options
+        .as_ref()
+        .and_then(|o| o.0.get("profile"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    if let Some(unchanged) = unchanged_file(&instance_id, filename, source, start) {
+        return unchanged;
+    }
 
     // Delete existing nodes for this filename (idempotent)
     inserter::delete_file_nodes(&instance_id, filename);
 
-    let (node_count, edge_count) =
-        parse_single_file(source, filename, &instance_id, None, filename, 0);
+    let mut profile = ParseProfile::default();
+    let (node_count, edge_count) = parse_single_file_profiled(
+        source,
+        filename,
+        &instance_id,
+        None,
+        filename,
+        0,
+        &mut profile,
+    );
 
     // Auto-mint reward for source parsing
     if node_count > 0 {
         let details = json!({"file": filename, "nodes": node_count, "edges": edge_count});
-        let details_str = details.to_string().replace('\'', "''");
-        let _ = Spi::get_one::<pgrx::JsonB>(&format!(
-            "SELECT kerai.mint_reward('parse_file', '{}'::jsonb)",
-            details_str,
-        ));
+        mint_parse_reward("parse_file", &details);
+    }
+
+    let elapsed = start.elapsed();
+    let mut result = json!({
+        "file": filename,
+        "nodes": node_count,
+        "edges": edge_count,
+        "elapsed_ms": elapsed.as_millis() as u64,
+    });
+    if profile_requested {
+        result["profile"] = profile.to_json();
+    }
+    pgrx::JsonB(result)
+}
+
+/// Re-parse Rust source in place, preserving the UUIDs of unchanged nodes.
+///
+/// Unlike `parse_source`, existing nodes are matched by kind, path, and content
+/// and keep their ids, so perspectives and associations attached to them
+/// survive edits. Only new nodes are inserted and only removed ones deleted.
+/// If the source fails to parse, the stored file is left untouched.
+///
+/// Files over the streaming or size limits (see `parse_source`) are too large
+/// to diff in memory; they are replaced and parsed as `parse_source` would,
+/// and no ids are kept.
+#[pg_extern]
+fn parse_source_diff(source: &str, filename: &str) -> pgrx::JsonB {
+    let start = Instant::now();
+    let instance_id = get_self_instance_id();
+
+    let limits = ParseLimits::load();
+    if source.len() > limits.stream_bytes || source.lines().count() > limits.stream_lines {
+        inserter::delete_file_nodes(&instance_id, filename);
+        let (node_count, edge_count) =
+            parse_single_file(source, filename, &instance_id, None, filename, 0);
+        if node_count > 0 {
+            let details = json!({"file": filename, "nodes": node_count, "edges": edge_count});
+            mint_parse_reward("parse_file", &details);
+        }
+        return pgrx::JsonB(json!({
+            "file": filename,
+            "nodes": node_count,
+            "edges": edge_count,
+            "reused": 0,
+            "inserted": node_count,
+            "deleted": 0,
+            "kept": 0,
+            "skipped_diff": "size_limit",
+            "elapsed_ms": start.elapsed().as_millis() as u64,
+        }));
+    }
+
+    let (node_count, edge_count, stats) =
+        match build_file_rows(
+            source,
+            filename,
+            &instance_id,
+            None,
+            filename,
+            0,
+            &mut ParseProfile::default(),
+        ) {
+            Some((file_node, nodes, edges)) => {
+                let counts = (nodes.len() + 1, edges.len());
+                let stats =
+                    inserter::diff_file_nodes(&instance_id, filename, file_node, nodes, edges);
+                (counts.0, counts.1, Some(stats))
+            }
+            None => (0, 0, None),
+        };
+
+    if let Some(stats) = stats.as_ref().filter(|s| s.inserted > 0) {
+        let details = json!({"file": filename, "nodes": stats.inserted, "edges": edge_count});
+        mint_parse_reward("parse_file", &details);
     }
 
     let elapsed = start.elapsed();
@@ -195,22 +483,350 @@ fn parse_source(source: &str, filename: &str) -> pgrx::JsonB {
         "file": filename,
         "nodes": node_count,
         "edges": edge_count,
+        "reused": stats.as_ref().map_or(0, |s| s.reused),
+        "inserted": stats.as_ref().map_or(0, |s| s.inserted),
+        "deleted": stats.as_ref().map_or(0, |s| s.deleted),
+        "kept": stats.as_ref().map_or(0, |s| s.kept),
         "elapsed_ms": elapsed.as_millis() as u64,
     }))
 }
 
+/// Preview what re-parsing a stored Rust file with `new_source` would change.
+///
+/// Parses `new_source` into rows without inserting them and compares them to
+/// the nodes under `file_id` the way `parse_source_diff` pairs them. Returns
+/// `{file, added, removed, modified}`, each an array of `{kind, content, path}`.
+/// Nothing is written.
+#[pg_extern]
+fn diff_file(file_id: pgrx::Uuid, new_source: &str) -> pgrx::JsonB {
+    let file_id = file_id.to_string();
+    let stored = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object('instance_id', instance_id, 'filename', content, \
+         'path', path::text, 'language', language) \
+         FROM kerai.nodes WHERE id = {} AND kind = 'file'",
+        crate::sql::sql_uuid(&file_id),
+    ))
+    .ok()
+    .flatten()
+    .unwrap_or_else(|| pgrx::error!("File node not found: {}", file_id))
+    .0;
+    if stored["language"].as_str().is_some_and(|l| l != "rust") {
+        pgrx::error!(
+            "diff_file only supports Rust files, not {}",
+            stored["language"]
+        );
+    }
+    let filename = stored["filename"].as_str().unwrap_or_default();
+    let instance_id = stored["instance_id"].as_str().unwrap_or_default();
+    let path_root = stored["path"].as_str().unwrap_or(filename);
+
+    let Some((_, nodes, _)) = build_file_rows(
+        new_source,
+        filename,
+        instance_id,
+        None,
+        path_root,
+        0,
+        &mut ParseProfile::default(),
+    ) else {
+        pgrx::error!("Failed to parse new source for {}", filename);
+    };
+
+    let mut diff = inserter::preview_file_diff(&file_id, &nodes);
+    diff["file"] = json!(filename);
+    pgrx::JsonB(diff)
+}
+
+/// SHA-256 hex digest of normalized source, stored as the file node's `source_hash`.
+fn source_hash(normalized: &str) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+/// The `{file, status: "unchanged"}` result when `filename` is already stored
+/// from source with the same normalized hash, so re-parsing can be skipped.
+fn unchanged_file(
+    instance_id: &str,
+    filename: &str,
+    source: &str,
+    start: Instant,
+) -> Option<pgrx::JsonB> {
+    let hash = source_hash(&normalizer::normalize(source));
+    let stored = Spi::get_one::<bool>(&format!(
+        "SELECT EXISTS (SELECT 1 FROM kerai.nodes \
+         WHERE instance_id = {} AND kind = 'file' AND content = {} \
+         AND metadata->>'source_hash' = {})",
+        crate::sql::sql_uuid(instance_id),
+        crate::sql::sql_text(filename),
+        crate::sql::sql_text(&hash),
+    ))
+    .ok()
+    .flatten()
+    .unwrap_or(false);
+
+    stored.then(|| {
+        pgrx::JsonB(json!({
+            "file": filename,
+            "status": "unchanged",
+            "elapsed_ms": start.elapsed().as_millis() as u64,
+        }))
+    })
+}
+
+/// Parse source text with the parser matching the filename's extension.
+///
+/// Delegates to the Rust, Go, C, markdown, LaTeX, BibTeX, CSV or JSON parser
+/// and returns that parser's stats with a `language` field added. A CSV file
+/// is loaded into a table in the `csv` schema under a project named after it. Files with no parser are
+/// stored as a single `repo_opaque_text` node, the same as repository ingestion.
+///
+/// When the extension is missing or ambiguous (`.txt`), the language is sniffed
+/// from a `#!` line or the content and recorded on the file node as
+/// `detected_language`/`detected_by`. An explicit `language` skips detection.
+#[pg_extern]
+fn parse_auto(
+    source: &str,
+    filename: &str,
+    language: default!(Option<&str>, "NULL"),
+) -> pgrx::JsonB {
+    use crate::repo::language_detect::{sniff_language, AMBIGUOUS_EXTENSIONS};
+
+    let ext = Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let (language, detected_by) = match language {
+        Some(lang) => (lang.to_lowercase(), "explicit"),
+        None => {
+            let by_ext = extension_language(&ext).or_else(|| data_language(&ext));
+            let sniffed = if ext.is_empty() || AMBIGUOUS_EXTENSIONS.contains(&ext.as_str()) {
+                sniff_language(source)
+            } else {
+                None
+            };
+            match (sniffed, by_ext) {
+                (Some((lang, by)), _) => (lang, by.as_str()),
+                (None, Some(lang)) => (lang.to_string(), "extension"),
+                (None, None) => return store_opaque_text(source, filename, None),
+            }
+        }
+    };
+
+    let result = match language.as_str() {
+        "rust" => parse_source(source, filename, None),
+        "go" => go::parse_go_source(source, filename),
+        "c" => c::parse_c_source(source, filename),
+        "markdown" => markdown::parse_markdown(source, filename),
+        "latex" => latex::parse_latex_source(source, filename),
+        "bibtex" => latex::parse_bibtex_source(source, filename),
+        "csv" => csv::parse_csv_source(source, filename),
+        "json" => json::parse_json_source(source, filename),
+        _ => return store_opaque_text(source, filename, Some((&language, detected_by))),
+    };
+
+    if detected_by != "extension" {
+        Spi::run(&format!(
+            "UPDATE kerai.nodes \
+             SET metadata = COALESCE(metadata, '{{}}'::jsonb) || jsonb_build_object('detected_language', {}, 'detected_by', {}) \
+             WHERE instance_id = {} AND parent_id IS NULL AND kind IN ('file', 'document') AND content = {}",
+            crate::sql::sql_text(&language),
+            crate::sql::sql_text(detected_by),
+            crate::sql::sql_uuid(&get_self_instance_id()),
+            crate::sql::sql_text(filename),
+        ))
+        .expect("Failed to record detected language");
+    }
+
+    let mut value = result.0;
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("language".into(), json!(language));
+        if detected_by != "extension" {
+            obj.insert("detected_by".into(), json!(detected_by));
+        }
+    }
+    pgrx::JsonB(value)
+}
+
+/// Parse a file from disk with the parser its extension selects.
+///
+/// Dispatches through the same extension table as `parse_auto` and
+/// `parallel_parse`, returning that parser's `{file, nodes, edges, elapsed_ms}`
+/// plus `language`. `filename` is the name recorded on the file node (default:
+/// the file's basename). Extensions with no parser aren't read and return
+/// `{file, status: "skipped", reason: "unsupported_extension"}`.
+#[pg_extern]
+fn parse_any_file(path: &str, filename: default!(Option<&str>, "NULL")) -> pgrx::JsonB {
+    let file_path = Path::new(path);
+    let ext = file_path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if extension_language(&ext).is_none() {
+        return pgrx::JsonB(json!({
+            "file": path,
+            "status": "skipped",
+            "reason": "unsupported_extension",
+        }));
+    }
+
+    if !file_path.exists() {
+        pgrx::error!("File does not exist: {}", path);
+    }
+    let source = std::fs::read_to_string(file_path)
+        .unwrap_or_else(|e| pgrx::error!("Failed to read file: {}", e));
+
+    let filename = match filename {
+        Some(name) => name.to_string(),
+        None => file_path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string()),
+    };
+    parse_auto(&source, &filename, None)
+}
+
+/// Parser language for a file extension, if kerai has a parser for it.
+fn extension_language(ext: &str) -> Option<&'static str> {
+    match ext {
+        "rs" => Some("rust"),
+        "go" => Some("go"),
+        "c" | "h" => Some("c"),
+        "md" | "markdown" => Some("markdown"),
+        "tex" | "sty" | "cls" => Some("latex"),
+        "bib" => Some("bibtex"),
+        _ => None,
+    }
+}
+
+/// Parser language for a data-file extension. Only `parse_auto` dispatches
+/// these: parsing a CSV creates a table, which directory walks shouldn't do
+/// for every data file they pass.
+fn data_language(ext: &str) -> Option<&'static str> {
+    match ext {
+        "csv" => Some("csv"),
+        "json" => Some("json"),
+        _ => None,
+    }
+}
+
+/// Store unparseable text as a top-level `repo_opaque_text` node.
+/// `detected` is a `(language, detected_by)` pair that overrides extension
+/// classification when the caller has already identified the language.
+fn store_opaque_text(
+    source: &str,
+    filename: &str,
+    detected: Option<(&str, &str)>,
+) -> pgrx::JsonB {
+    use crate::repo::kinds::REPO_OPAQUE_TEXT;
+    use crate::repo::language_detect::{classify, LanguageClass};
+
+    let start = Instant::now();
+    let instance_id = get_self_instance_id();
+
+    let language = match detected {
+        Some((lang, _)) => lang.to_string(),
+        None => match classify(filename, Some(source.as_bytes())) {
+            LanguageClass::Parseable(lang) => lang.as_str().to_string(),
+            LanguageClass::OpaqueText(lang) => lang,
+            LanguageClass::Binary => "text".to_string(),
+        },
+    };
+
+    // Replace any previous opaque node for this filename
+    Spi::run(&format!(
+        "DELETE FROM kerai.nodes \
+         WHERE instance_id = {} AND kind = '{}' AND parent_id IS NULL \
+         AND metadata->>'path' = {}",
+        crate::sql::sql_uuid(&instance_id),
+        REPO_OPAQUE_TEXT,
+        crate::sql::sql_text(filename),
+    ))
+    .expect("Failed to delete previous opaque node");
+
+    let size = source.len();
+    let (stored, truncated) = truncate_opaque(source);
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+
+    let mut metadata = json!({
+        "path": filename,
+        "size": size,
+        "line_count": source.lines().count(),
+        "truncated": truncated,
+        "source": stored,
+    });
+    if let Some((lang, by)) = detected {
+        metadata["detected_language"] = json!(lang);
+        metadata["detected_by"] = json!(by);
+    }
+
+    let node = NodeRow {
+        id: Uuid::new_v4().to_string(),
+        instance_id: instance_id.clone(),
+        kind: REPO_OPAQUE_TEXT.to_string(),
+        language: Some(language.clone()),
+        content: Some(name.to_string()),
+        parent_id: None,
+        position: 0,
+        path: None,
+        metadata,
+        span_start: None,
+        span_end: None,
+    };
+    inserter::insert_nodes(&[node]);
+
+    pgrx::JsonB(json!({
+        "file": filename,
+        "language": language,
+        "nodes": 1,
+        "edges": 0,
+        "elapsed_ms": start.elapsed().as_millis() as u64,
+    }))
+}
+
+/// Cap opaque text at `OPAQUE_TEXT_MAX` bytes on a char boundary; the flag
+/// reports whether anything was cut.
+fn truncate_opaque(source: &str) -> (&str, bool) {
+    use crate::repo::tree_walker::OPAQUE_TEXT_MAX;
+
+    if source.len() <= OPAQUE_TEXT_MAX {
+        return (source, false);
+    }
+    let mut end = OPAQUE_TEXT_MAX;
+    while !source.is_char_boundary(end) {
+        end -= 1;
+    }
+    (&source[..end], true)
+}
+
 /// Parse a directory tree in parallel using pg_background workers.
 ///
-/// Walks the directory, discovers parseable files (.rs, .go, .c, .h, .md),
+/// Walks the directory, discovers parseable files (any extension
+/// `parse_any_file` handles, plus extensionless or `.txt` files, whose
+/// language is sniffed by `parse_auto`),
 /// and processes them through a sliding-window worker pool that keeps
 /// `max_workers` background workers saturated without exceeding capacity.
 ///
 /// As each worker completes, a new file is immediately launched from the
 /// queue, maintaining full throughput without over-demanding pg_background.
 ///
+/// With `max_pool` set, the window adapts between `min_pool` and `max_pool`:
+/// it grows by one when a worker finishes faster than the running average and
+/// halves on a launch failure. `max_workers` and pg_background's own limit
+/// still cap the pool. The summary's `metrics` report in-flight saturation,
+/// launch failures, and the spread of worker wall times.
+///
 /// Requires the pg_background extension to be installed.
 #[pg_extern]
-fn parallel_parse(path: &str, max_workers: default!(i32, 0)) -> pgrx::JsonB {
+fn parallel_parse(
+    path: &str,
+    max_workers: default!(i32, 0),
+    min_pool: default!(i32, 0),
+    max_pool: default!(i32, 0),
+) -> pgrx::JsonB {
+    use crate::repo::language_detect::AMBIGUOUS_EXTENSIONS;
+
     let start = Instant::now();
     let root = Path::new(path);
     let num_cpus = std::thread::available_parallelism()
@@ -278,34 +894,19 @@ fn parallel_parse(path: &str, max_workers: default!(i32, 0)) -> pgrx::JsonB {
             .map(|e| e.to_string_lossy().to_string())
             .unwrap_or_default();
 
-        let cmd = match ext.as_str() {
-            "rs" => {
-                format!(
-                    "SELECT kerai.parse_source(pg_read_file('{}'), '{}')",
-                    abs_path,
-                    filename.replace('\'', "''")
-                )
-            }
-            "go" => format!("SELECT kerai.parse_go_file('{}')", abs_path),
-            "c" | "h" => format!("SELECT kerai.parse_c_file('{}')", abs_path),
-            "md" => {
-                let safe_name = filename.replace('\'', "''");
-                format!(
-                    "SELECT kerai.parse_markdown(pg_read_file('{}'), '{}')",
-                    abs_path, safe_name
-                )
-            }
-            "tex" | "sty" | "cls" => {
-                let safe_name = filename.replace('\'', "''");
-                format!(
-                    "SELECT kerai.parse_latex_source(pg_read_file('{}'), '{}')",
-                    abs_path, safe_name
-                )
+        let safe_name = filename.replace('\'', "''");
+        let cmd = match ext.to_lowercase().as_str() {
+            e if extension_language(e).is_some() => {
+                format!("SELECT kerai.parse_any_file('{}', '{}')", abs_path, safe_name)
             }
-            "bib" => {
-                let safe_name = filename.replace('\'', "''");
+            // No or ambiguous extension: parse_auto sniffs shebang/content and
+            // stores anything it can't classify as repo_opaque_text
+            e if e.is_empty() || AMBIGUOUS_EXTENSIONS.contains(&e) => {
+                if looks_binary(file_path) {
+                    continue;
+                }
                 format!(
-                    "SELECT kerai.parse_bibtex_source(pg_read_file('{}'), '{}')",
+                    "SELECT kerai.parse_auto(pg_read_file('{}'), '{}')",
                     abs_path, safe_name
                 )
             }
@@ -330,50 +931,74 @@ fn parallel_parse(path: &str, max_workers: default!(i32, 0)) -> pgrx::JsonB {
     // Reverse so we can pop from the back efficiently (LIFO as queue drain)
     queue.reverse();
 
+    let mut window = PoolWindow::new(pool_size, min_pool, max_pool);
+
     // Sliding-window worker pool
-    let mut inflight: VecDeque<(String, i32, i64)> = VecDeque::new(); // (filename, pid, cookie)
+    let mut inflight: VecDeque<Inflight> = VecDeque::new();
     let mut total_nodes = 0u64;
     let mut total_edges = 0u64;
     let mut results: Vec<serde_json::Value> = Vec::new();
     let mut launched = 0usize;
     let mut failed_launches = 0usize;
-
-    // Fill the initial window
-    while inflight.len() < pool_size {
-        if let Some((filename, cmd)) = queue.pop() {
-            if let Some(handle) = launch_worker(&filename, &cmd) {
-                inflight.push_back(handle);
-                launched += 1;
-            } else {
-                failed_launches += 1;
+    let mut metrics = PoolMetrics::new();
+
+    // Until the first worker is collected, the pool is still on its initial fill
+    let mut filling = true;
+
+    loop {
+        // Fill the window from the queue
+        while inflight.len() < window.size {
+            let Some((filename, cmd)) = queue.pop() else {
+                break;
+            };
+            match launch_worker(&filename, &cmd) {
+                Some((filename, pid, cookie)) => {
+                    metrics.observe(inflight.len());
+                    inflight.push_back(Inflight { filename, pid, cookie, started: Instant::now() });
+                    launched += 1;
+                    metrics.observe_peak(inflight.len());
+                }
+                None => {
+                    metrics.launch_failures += 1;
+                    match window.on_launch_failure(filling) {
+                        // Back off and let the pool drain before retrying
+                        LaunchFailure::Retry => {
+                            queue.push((filename, cmd));
+                            break;
+                        }
+                        // Count the one file and keep filling
+                        LaunchFailure::Skip => failed_launches += 1,
+                        // Stop launching to avoid cascading failures.
+                        // Remaining queued files will be reported in the summary.
+                        LaunchFailure::Stop => {
+                            failed_launches += queue.len() + 1;
+                            queue.clear();
+                            break;
+                        }
+                    }
+                }
             }
-        } else {
-            break;
         }
-    }
 
-    // Drain-and-refill: wait for oldest, collect result, launch next
-    while let Some((filename, pid, cookie)) = inflight.pop_front() {
+        // Wait for the oldest worker and collect its result
+        let Some(done) = inflight.pop_front() else {
+            break;
+        };
+        filling = false;
+        metrics.observe(inflight.len() + 1);
         collect_worker_result(
-            &filename, pid, cookie,
+            &done.filename, done.pid, done.cookie,
             &mut total_nodes, &mut total_edges, &mut results,
         );
-
-        // Launch replacement from queue
-        if let Some((next_filename, next_cmd)) = queue.pop() {
-            if let Some(handle) = launch_worker(&next_filename, &next_cmd) {
-                inflight.push_back(handle);
-                launched += 1;
-            } else {
-                // Launch failed — re-queue and stop launching to avoid cascading failures.
-                // Remaining queued files will be reported in the summary.
-                queue.push((next_filename, next_cmd));
-                failed_launches += queue.len() + 1;
-                queue.clear();
-            }
-        }
+        let wall_ms = done.started.elapsed().as_secs_f64() * 1000.0;
+        let fast = metrics.worker_wall_ms.is_empty() || wall_ms < metrics.avg_wall_ms();
+        metrics.worker_wall_ms.push(wall_ms);
+        window.on_worker_done(fast);
     }
 
+    // Files left behind when the pool drained after a launch failure
+    failed_launches += queue.len();
+
     let elapsed = start.elapsed();
 
     let mut summary = json!({
@@ -385,6 +1010,13 @@ fn parallel_parse(path: &str, max_workers: default!(i32, 0)) -> pgrx::JsonB {
         "max_workers": pool_size,
         "results": results,
         "elapsed_ms": elapsed.as_millis() as u64,
+        "metrics": metrics.summary(),
+        "pool": {
+            "mode": if window.adaptive { "adaptive" } else { "fixed" },
+            "min_pool": window.min,
+            "max_pool": window.max,
+            "final_window": window.size,
+        },
     });
 
     if failed_launches > 0 {
@@ -394,6 +1026,153 @@ fn parallel_parse(path: &str, max_workers: default!(i32, 0)) -> pgrx::JsonB {
     pgrx::JsonB(summary)
 }
 
+/// Whether the start of a file contains a null byte (git's binary heuristic).
+fn looks_binary(path: &Path) -> bool {
+    use std::io::Read;
+
+    let mut buf = [0u8; 8192];
+    match std::fs::File::open(path).and_then(|mut f| f.read(&mut buf)) {
+        Ok(n) => buf[..n].contains(&0),
+        Err(_) => true,
+    }
+}
+
+/// A launched pg_background worker awaiting collection.
+struct Inflight {
+    filename: String,
+    pid: i32,
+    cookie: i64,
+    started: Instant,
+}
+
+/// What `parallel_parse` does after a worker fails to launch.
+#[derive(Debug, PartialEq)]
+enum LaunchFailure {
+    /// Requeue the file and wait for the shrunken pool to drain
+    Retry,
+    /// Give up on this file and keep launching
+    Skip,
+    /// Give up on this file and everything still queued
+    Stop,
+}
+
+/// The number of workers `parallel_parse` keeps in flight.
+///
+/// Fixed mode holds the window at the pool size. Adaptive mode (`max_pool`
+/// set) starts at `min_pool`, grows by one when a worker finishes faster
+/// than average, and halves on a launch failure, always within the pool size.
+struct PoolWindow {
+    adaptive: bool,
+    min: usize,
+    max: usize,
+    size: usize,
+}
+
+impl PoolWindow {
+    fn new(pool_size: usize, min_pool: i32, max_pool: i32) -> Self {
+        let adaptive = max_pool > 0;
+        let (min, max) = if adaptive {
+            let hi = (max_pool as usize).min(pool_size).max(1);
+            let lo = (min_pool.max(1) as usize).min(hi);
+            (lo, hi)
+        } else {
+            (pool_size, pool_size)
+        };
+        Self {
+            adaptive,
+            min,
+            max,
+            size: min,
+        }
+    }
+
+    /// React to a failed launch. A fixed pool skips files that fail during
+    /// its initial fill and stops at the first failure after that.
+    fn on_launch_failure(&mut self, filling: bool) -> LaunchFailure {
+        if self.adaptive && self.size > self.min {
+            self.size = (self.size / 2).max(self.min);
+            LaunchFailure::Retry
+        } else if !self.adaptive && filling {
+            LaunchFailure::Skip
+        } else {
+            LaunchFailure::Stop
+        }
+    }
+
+    /// Grow an adaptive window after a worker finished faster than average.
+    fn on_worker_done(&mut self, fast: bool) {
+        if self.adaptive && fast && self.size < self.max {
+            self.size += 1;
+        }
+    }
+}
+
+/// Saturation and timing metrics for a `parallel_parse` run.
+struct PoolMetrics {
+    last_event: Instant,
+    /// Sum of in-flight count × seconds, for the time-weighted average
+    inflight_secs: f64,
+    elapsed_secs: f64,
+    peak_inflight: usize,
+    launch_failures: usize,
+    worker_wall_ms: Vec<f64>,
+}
+
+impl PoolMetrics {
+    fn new() -> Self {
+        Self {
+            last_event: Instant::now(),
+            inflight_secs: 0.0,
+            elapsed_secs: 0.0,
+            peak_inflight: 0,
+            launch_failures: 0,
+            worker_wall_ms: Vec::new(),
+        }
+    }
+
+    /// Account for time spent with `inflight` workers running since the last event.
+    fn observe(&mut self, inflight: usize) {
+        let dt = self.last_event.elapsed().as_secs_f64();
+        self.last_event = Instant::now();
+        self.inflight_secs += inflight as f64 * dt;
+        self.elapsed_secs += dt;
+    }
+
+    fn observe_peak(&mut self, inflight: usize) {
+        self.peak_inflight = self.peak_inflight.max(inflight);
+    }
+
+    fn avg_wall_ms(&self) -> f64 {
+        self.worker_wall_ms.iter().sum::<f64>() / self.worker_wall_ms.len().max(1) as f64
+    }
+
+    fn summary(&self) -> serde_json::Value {
+        let mut walls = self.worker_wall_ms.clone();
+        walls.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let pct = |p: f64| -> f64 {
+            if walls.is_empty() {
+                return 0.0;
+            }
+            let idx = ((walls.len() - 1) as f64 * p).round() as usize;
+            walls[idx]
+        };
+
+        json!({
+            "avg_inflight": if self.elapsed_secs > 0.0 { self.inflight_secs / self.elapsed_secs } else { 0.0 },
+            "peak_inflight": self.peak_inflight,
+            "launch_failures": self.launch_failures,
+            "worker_wall_ms": {
+                "count": walls.len(),
+                "min": walls.first().copied().unwrap_or(0.0),
+                "p50": pct(0.5),
+                "p90": pct(0.9),
+                "max": walls.last().copied().unwrap_or(0.0),
+                "avg": self.avg_wall_ms(),
+            },
+        })
+    }
+}
+
 /// Launch a single pg_background worker. Returns (filename, pid, cookie) or None on failure.
 fn launch_worker(filename: &str, cmd: &str) -> Option<(String, i32, i64)> {
     let safe_cmd = cmd.replace('\'', "''");
@@ -424,52 +1203,347 @@ fn launch_worker(filename: &str, cmd: &str) -> Option<(String, i32, i64)> {
 /// Wait for a pg_background worker and collect its parse result.
 fn collect_worker_result(
     filename: &str,
-    pid: i32,
-    cookie: i64,
-    total_nodes: &mut u64,
-    total_edges: &mut u64,
-    results: &mut Vec<serde_json::Value>,
-) {
-    let wait_sql = format!("SELECT pg_background_wait_v2({}, {})", pid, cookie);
-    let _ = Spi::run(&wait_sql);
+    pid: i32,
+    cookie: i64,
+    total_nodes: &mut u64,
+    total_edges: &mut u64,
+    results: &mut Vec<serde_json::Value>,
+) {
+    let wait_sql = format!("SELECT pg_background_wait_v2({}, {})", pid, cookie);
+    let _ = Spi::run(&wait_sql);
+
+    let result_sql = format!(
+        "SELECT result FROM pg_background_result_v2({}, {}) AS (result jsonb)",
+        pid, cookie
+    );
+
+    match Spi::get_one::<pgrx::JsonB>(&result_sql) {
+        Ok(Some(pgrx::JsonB(val))) => {
+            let nodes = val.get("nodes").and_then(|v| v.as_u64()).unwrap_or(0);
+            let edges = val.get("edges").and_then(|v| v.as_u64()).unwrap_or(0);
+            *total_nodes += nodes;
+            *total_edges += edges;
+            results.push(json!({
+                "file": filename,
+                "nodes": nodes,
+                "edges": edges,
+            }));
+        }
+        Ok(None) => {
+            results.push(json!({"file": filename, "error": "no result"}));
+        }
+        Err(e) => {
+            results.push(json!({"file": filename, "error": e.to_string()}));
+        }
+    }
+}
+
+/// Wall time spent in each phase of parsing a single Rust file, in ms.
+#[derive(Debug, Default)]
+struct ParseProfile {
+    /// Source normalization and `kerai:` directive scanning.
+    normalize_ms: f64,
+    /// `syn::parse_file`.
+    syn_ms: f64,
+    /// AST walk into node and edge rows.
+    walk_ms: f64,
+    /// Comment extraction, grouping, and matching to AST nodes.
+    comments_ms: f64,
+    /// Suggestion rules and suggestion status bookkeeping.
+    suggestions_ms: f64,
+    /// Node and edge inserts, including impl linking.
+    insert_ms: f64,
+}
+
+impl ParseProfile {
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "normalize_ms": self.normalize_ms,
+            "syn_ms": self.syn_ms,
+            "walk_ms": self.walk_ms,
+            "comments_ms": self.comments_ms,
+            "suggestions_ms": self.suggestions_ms,
+            "insert_ms": self.insert_ms,
+        })
+    }
+}
+
+/// Milliseconds since `mark`, resetting `mark` to now.
+fn lap(mark: &mut Instant) -> f64 {
+    let ms = mark.elapsed().as_secs_f64() * 1000.0;
+    *mark = Instant::now();
+    ms
+}
+
+/// Parse a single Rust file's source, insert nodes/edges, return counts.
+///
+/// `parent_id` allows parenting the file node under a repo directory node.
+pub(crate) fn parse_single_file(
+    source: &str,
+    filename: &str,
+    instance_id: &str,
+    parent_id: Option<&str>,
+    path_root: &str,
+    position: i32,
+) -> (usize, usize) {
+    parse_single_file_profiled(
+        source,
+        filename,
+        instance_id,
+        parent_id,
+        path_root,
+        position,
+        &mut ParseProfile::default(),
+    )
+}
+
+/// [`parse_single_file`], recording per-phase timings into `profile`.
+fn parse_single_file_profiled(
+    source: &str,
+    filename: &str,
+    instance_id: &str,
+    parent_id: Option<&str>,
+    path_root: &str,
+    position: i32,
+    profile: &mut ParseProfile,
+) -> (usize, usize) {
+    let limits = ParseLimits::load();
+    if source.len() > limits.max_bytes {
+        warning!(
+            "{} is {} bytes, over the {}-byte parse limit; storing as opaque text",
+            filename,
+            source.len(),
+            limits.max_bytes
+        );
+        store_oversized_file(source, filename, instance_id, parent_id, position);
+        return (1, 0);
+    }
+    if source.len() > limits.stream_bytes || source.lines().count() > limits.stream_lines {
+        return parse_file_streaming(
+            source,
+            filename,
+            instance_id,
+            parent_id,
+            path_root,
+            position,
+            profile,
+        );
+    }
+
+    let Some((file_node, nodes, edges)) =
+        build_file_rows(source, filename, instance_id, parent_id, path_root, position, profile)
+    else {
+        return (0, 0);
+    };
+
+    let node_count = nodes.len() + 1; // +1 for file node
+    let edge_count = edges.len();
+
+    let mut mark = Instant::now();
+    inserter::insert_nodes(&[file_node]);
+    inserter::insert_nodes(&nodes);
+    inserter::insert_edges(&edges);
+
+    inserter::link_impl_edges(&nodes);
+    inserter::link_call_edges(&nodes);
+    profile.insert_ms = lap(&mut mark);
+
+    (node_count, edge_count)
+}
+
+/// Size thresholds for Rust files, from `config` preferences.
+///
+/// Files over `parse_stream_lines` lines or `parse_stream_bytes` bytes are
+/// parsed item by item with incremental inserts; files over
+/// `parse_max_bytes` are not parsed at all and kept as opaque text.
+struct ParseLimits {
+    stream_lines: usize,
+    stream_bytes: usize,
+    max_bytes: usize,
+}
+
+impl ParseLimits {
+    const DEFAULT_STREAM_LINES: usize = 50_000;
+    const DEFAULT_STREAM_BYTES: usize = 4 * 1024 * 1024; // 4 MB
+    const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024; // 64 MB
+
+    fn load() -> Self {
+        let get = |key: &str, default: usize| {
+            Spi::get_one::<String>(&format!(
+                "SELECT kerai.get_preference('config', '{key}')"
+            ))
+            .ok()
+            .flatten()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .unwrap_or(default)
+        };
+        Self {
+            stream_lines: get("parse_stream_lines", Self::DEFAULT_STREAM_LINES),
+            stream_bytes: get("parse_stream_bytes", Self::DEFAULT_STREAM_BYTES),
+            max_bytes: get("parse_max_bytes", Self::DEFAULT_MAX_BYTES),
+        }
+    }
+}
+
+/// Store a Rust file that is too large to parse as a single
+/// `repo_opaque_text` node (source truncated as for other opaque text).
+fn store_oversized_file(
+    source: &str,
+    filename: &str,
+    instance_id: &str,
+    parent_id: Option<&str>,
+    position: i32,
+) {
+    use crate::repo::kinds::REPO_OPAQUE_TEXT;
+
+    // Replace the copy left by a previous oversized parse
+    Spi::run(&format!(
+        "DELETE FROM kerai.nodes \
+         WHERE instance_id = {} AND kind = '{}' AND parent_id IS NOT DISTINCT FROM {} \
+         AND metadata->>'path' = {}",
+        crate::sql::sql_uuid(instance_id),
+        REPO_OPAQUE_TEXT,
+        parent_id.map_or("NULL".to_string(), crate::sql::sql_uuid),
+        crate::sql::sql_text(filename),
+    ))
+    .ok();
+
+    let (stored, truncated) = truncate_opaque(source);
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+    inserter::insert_nodes(&[NodeRow {
+        id: Uuid::new_v4().to_string(),
+        instance_id: instance_id.to_string(),
+        kind: REPO_OPAQUE_TEXT.to_string(),
+        language: Some("rust".to_string()),
+        content: Some(name.to_string()),
+        parent_id: parent_id.map(|s| s.to_string()),
+        position,
+        path: None,
+        metadata: json!({
+            "path": filename,
+            "size": source.len(),
+            "line_count": source.lines().count(),
+            "truncated": truncated,
+            "skipped": "size_limit",
+            "source": stored,
+        }),
+        span_start: None,
+        span_end: None,
+    }]);
+}
+
+/// Parse a very large Rust file, inserting rows one batch of top-level items
+/// at a time instead of materializing every row first.
+///
+/// Comments are matched against a compact index of node start lines kept
+/// across batches. Suggestion rules are skipped; the file node is marked
+/// `streamed`.
+fn parse_file_streaming(
+    source: &str,
+    filename: &str,
+    instance_id: &str,
+    parent_id: Option<&str>,
+    path_root: &str,
+    position: i32,
+    profile: &mut ParseProfile,
+) -> (usize, usize) {
+    const STREAM_BATCH: usize = 5_000;
 
-    let result_sql = format!(
-        "SELECT result FROM pg_background_result_v2({}, {}) AS (result jsonb)",
-        pid, cookie
-    );
+    let mut mark = Instant::now();
+    let normalized = normalizer::normalize(source);
+    let directives = flag_parser::parse_kerai_directives(&normalized);
+    let kerai_flags = flag_parser::build_flags_metadata(&directives);
+    profile.normalize_ms = lap(&mut mark);
 
-    match Spi::get_one::<pgrx::JsonB>(&result_sql) {
-        Ok(Some(pgrx::JsonB(val))) => {
-            let nodes = val.get("nodes").and_then(|v| v.as_u64()).unwrap_or(0);
-            let edges = val.get("edges").and_then(|v| v.as_u64()).unwrap_or(0);
-            *total_nodes += nodes;
-            *total_edges += edges;
-            results.push(json!({
-                "file": filename,
-                "nodes": nodes,
-                "edges": edges,
-            }));
-        }
-        Ok(None) => {
-            results.push(json!({"file": filename, "error": "no result"}));
-        }
+    let syn_file = match syn::parse_file(&normalized) {
+        Ok(f) => f,
         Err(e) => {
-            results.push(json!({"file": filename, "error": e.to_string()}));
+            warning!("Failed to parse {}: {}", filename, e);
+            return (0, 0);
         }
+    };
+    profile.syn_ms = lap(&mut mark);
+
+    let file_node_id = Uuid::new_v4().to_string();
+    let path_ctx = PathContext::with_root(path_root);
+
+    let mut file_metadata = json!({
+        "line_count": normalized.lines().count(),
+        "source_hash": source_hash(&normalized),
+        "streamed": true,
+    });
+    if let Some(flags) = kerai_flags {
+        file_metadata["kerai_flags"] = flags;
     }
+    inserter::insert_nodes(&[NodeRow {
+        id: file_node_id.clone(),
+        instance_id: instance_id.to_string(),
+        kind: Kind::File.as_str().to_string(),
+        language: Some("rust".to_string()),
+        content: Some(filename.to_string()),
+        parent_id: parent_id.map(|s| s.to_string()),
+        position,
+        path: path_ctx.path(),
+        metadata: file_metadata,
+        span_start: None,
+        span_end: None,
+    }]);
+
+    let mut node_count = 1; // file node
+    let mut edge_count = 0;
+    let mut spans: Vec<(i32, String)> = Vec::new();
+    let mut insert_secs = 0.0;
+    ast_walker::walk_file_batched(
+        &syn_file,
+        &file_node_id,
+        instance_id,
+        path_ctx,
+        STREAM_BATCH,
+        |mut nodes, edges| {
+            position_by_line(&mut nodes, &file_node_id);
+            spans.extend(ast_spans(&nodes).into_iter().map(|(line, id)| (line, id.to_string())));
+
+            let insert_start = Instant::now();
+            inserter::insert_nodes(&nodes);
+            inserter::insert_edges(&edges);
+            inserter::link_impl_edges(&nodes);
+            inserter::link_call_edges(&nodes);
+            insert_secs += insert_start.elapsed().as_secs_f64();
+
+            node_count += nodes.len();
+            edge_count += edges.len();
+        },
+    );
+    profile.insert_ms = insert_secs * 1000.0;
+    profile.walk_ms = lap(&mut mark) - profile.insert_ms;
+
+    let span_refs = spans.iter().map(|(line, id)| (*line, id.as_str())).collect();
+    let (comment_nodes, comment_edges) =
+        build_comment_rows(&syn_file, &normalized, span_refs, &file_node_id, instance_id);
+    inserter::insert_nodes(&comment_nodes);
+    inserter::insert_edges(&comment_edges);
+    node_count += comment_nodes.len();
+    edge_count += comment_edges.len();
+    profile.comments_ms = lap(&mut mark);
+
+    (node_count, edge_count)
 }
 
-/// Parse a single Rust file's source, insert nodes/edges, return counts.
+/// Parse a single Rust file's source into rows without inserting them.
 ///
-/// `parent_id` allows parenting the file node under a repo directory node.
-pub(crate) fn parse_single_file(
+/// Returns the file node followed by its descendants and edges, or `None`
+/// if the source does not parse.
+fn build_file_rows(
     source: &str,
     filename: &str,
     instance_id: &str,
     parent_id: Option<&str>,
     path_root: &str,
     position: i32,
-) -> (usize, usize) {
+    profile: &mut ParseProfile,
+) -> Option<(NodeRow, Vec<NodeRow>, Vec<ast_walker::EdgeRow>)> {
+    let mut mark = Instant::now();
+
     // 1. Normalize source
     let normalized = normalizer::normalize(source);
 
@@ -494,20 +1568,26 @@ pub(crate) fn parse_single_file(
         })
         .collect();
 
+    profile.normalize_ms = lap(&mut mark);
+
     // 2. Parse with syn
     let syn_file = match syn::parse_file(&normalized) {
         Ok(f) => f,
         Err(e) => {
             warning!("Failed to parse {}: {}", filename, e);
-            return (0, 0);
+            return None;
         }
     };
+    profile.syn_ms = lap(&mut mark);
 
     // 3. Create file node (with kerai_flags if present)
     let file_node_id = Uuid::new_v4().to_string();
     let path_ctx = PathContext::with_root(path_root);
 
-    let mut file_metadata = json!({"line_count": normalized.lines().count()});
+    let mut file_metadata = json!({
+        "line_count": normalized.lines().count(),
+        "source_hash": source_hash(&normalized),
+    });
     if let Some(ref flags) = kerai_flags {
         file_metadata
             .as_object_mut()
@@ -529,94 +1609,22 @@ pub(crate) fn parse_single_file(
         span_end: None,
     };
 
-    inserter::insert_nodes(&[file_node]);
-
     // 4. Walk AST
     let (mut nodes, mut edges) =
         ast_walker::walk_file(&syn_file, &file_node_id, instance_id, path_ctx);
 
     // 4b. Normalize top-level item positions to use span_start (line numbers)
     // so they interleave correctly with comments (which also use line numbers).
-    for node in &mut nodes {
-        if node.parent_id.as_deref() == Some(&file_node_id) {
-            if let Some(start) = node.span_start {
-                node.position = start;
-            }
-        }
-    }
-
-    // 5. Collect string literal exclusion zones
-    let exclusions = comment_extractor::collect_string_spans(&syn_file);
-
-    // 6. Extract comments with exclusion zones
-    let raw_comments = comment_extractor::extract_comments(&normalized, &exclusions);
-
-    // 7. Group consecutive line comments into blocks
-    let mut blocks = comment_extractor::group_comments(raw_comments);
-
-    // Filter out doc comments (already handled via syn attributes)
-    blocks.retain(|b| !b.is_doc);
-
-    // Also filter out kerai directive comments (flags and suggestion acks)
-    blocks.retain(|b| {
-        let first_line = b.lines.first().map(|l| l.as_str()).unwrap_or("");
-        !first_line.starts_with(" kerai:")
-            && !first_line.starts_with("kerai:")
-    });
-
-    // 8. Match comment blocks to AST nodes (sets placement)
-    let matches = match_comments_to_ast(&mut blocks, &nodes);
-
-    // 9. Create NodeRow + EdgeRow for each comment block
-    for (block_idx, block) in blocks.iter().enumerate() {
-        let comment_id = Uuid::new_v4().to_string();
-        let kind = if !block.is_block_style && block.lines.len() > 1 {
-            Kind::CommentBlock
-        } else {
-            Kind::Comment
-        };
-        let style = if block.is_block_style { "block" } else { "line" };
-        let placement = match block.placement {
-            CommentPlacement::Above => "above",
-            CommentPlacement::Trailing => "trailing",
-            CommentPlacement::Between => "between",
-            CommentPlacement::Eof => "eof",
-        };
-
-        let content = block.lines.join("\n");
+    position_by_line(&mut nodes, &file_node_id);
+    profile.walk_ms = lap(&mut mark);
 
-        nodes.push(NodeRow {
-            id: comment_id.clone(),
-            instance_id: instance_id.to_string(),
-            kind: kind.as_str().to_string(),
-            language: Some("rust".to_string()),
-            content: Some(content),
-            parent_id: Some(file_node_id.clone()),
-            position: block.start_line as i32,
-            path: None,
-            metadata: json!({
-                "start_line": block.start_line,
-                "end_line": block.end_line,
-                "col": block.col,
-                "placement": placement,
-                "style": style,
-                "line_count": block.lines.len(),
-            }),
-            span_start: Some(block.start_line as i32),
-            span_end: Some(block.end_line as i32),
-        });
+    // 5–9. Extract comments and match them to AST nodes
+    let (comment_nodes, comment_edges) =
+        build_comment_rows(&syn_file, &normalized, ast_spans(&nodes), &file_node_id, instance_id);
+    nodes.extend(comment_nodes);
+    edges.extend(comment_edges);
 
-        // Create "documents" edge if matched to a node
-        if let Some(ref target_id) = matches[block_idx] {
-            edges.push(ast_walker::EdgeRow {
-                id: Uuid::new_v4().to_string(),
-                source_id: comment_id,
-                target_id: target_id.clone(),
-                relation: "documents".to_string(),
-                metadata: json!({"placement": placement}),
-            });
-        }
-    }
+    profile.comments_ms = lap(&mut mark);
 
     // 10. Run suggestion rules
     let skip_suggestions = kerai_flags
@@ -728,14 +1736,9 @@ pub(crate) fn parse_single_file(
         // Update status of previous suggestions based on what we found in the source
         update_suggestion_statuses(&prev_suggestions, &findings, &file_node_id);
     }
+    profile.suggestions_ms = lap(&mut mark);
 
-    let node_count = nodes.len() + 1; // +1 for file node
-    let edge_count = edges.len();
-
-    inserter::insert_nodes(&nodes);
-    inserter::insert_edges(&edges);
-
-    (node_count, edge_count)
+    Some((file_node, nodes, edges))
 }
 
 /// Query previously dismissed suggestion rule+target pairs for a file.
@@ -832,17 +1835,109 @@ fn simple_hash(s: &str) -> String {
     format!("{:016x}", hasher.finish())
 }
 
-/// Match comment blocks to AST nodes and classify placement.
-///
-/// Returns a Vec with one entry per block: Some(node_id) for the target,
-/// or None for eof comments.
-fn match_comments_to_ast(
-    blocks: &mut [CommentBlock],
-    nodes: &[NodeRow],
-) -> Vec<Option<String>> {
-    // Build sorted list of top-level AST nodes by span_start
-    // (only nodes with span info, excluding comments themselves)
-    let mut ast_spans: Vec<(i32, &str)> = nodes
+/// Use the start line as the position of the file's direct children.
+fn position_by_line(nodes: &mut [NodeRow], file_node_id: &str) {
+    for node in nodes {
+        if node.parent_id.as_deref() == Some(file_node_id) {
+            if let Some(start) = node.span_start {
+                node.position = start;
+            }
+        }
+    }
+}
+
+/// Extract regular comments from `normalized` source, match them against
+/// `ast_spans` (see [`ast_spans`]), and build their rows and `documents` edges.
+fn build_comment_rows(
+    syn_file: &syn::File,
+    normalized: &str,
+    ast_spans: Vec<(i32, &str)>,
+    file_node_id: &str,
+    instance_id: &str,
+) -> (Vec<NodeRow>, Vec<ast_walker::EdgeRow>) {
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+
+    // 5. Collect string literal exclusion zones
+    let exclusions = comment_extractor::collect_string_spans(syn_file);
+
+    // 6. Extract comments with exclusion zones
+    let raw_comments = comment_extractor::extract_comments(normalized, &exclusions);
+
+    // 7. Group consecutive line comments into blocks
+    let mut blocks = comment_extractor::group_comments(raw_comments);
+
+    // Filter out doc comments (already handled via syn attributes)
+    blocks.retain(|b| !b.is_doc);
+
+    // Also filter out kerai directive comments (flags and suggestion acks)
+    blocks.retain(|b| {
+        let first_line = b.lines.first().map(|l| l.as_str()).unwrap_or("");
+        !first_line.starts_with(" kerai:")
+            && !first_line.starts_with("kerai:")
+    });
+
+    // 8. Match comment blocks to AST nodes (sets placement)
+    let matches = match_comments_to_spans(&mut blocks, ast_spans);
+
+    // 9. Create NodeRow + EdgeRow for each comment block
+    for (block_idx, block) in blocks.iter().enumerate() {
+        let comment_id = Uuid::new_v4().to_string();
+        let kind = if !block.is_block_style && block.lines.len() > 1 {
+            Kind::CommentBlock
+        } else {
+            Kind::Comment
+        };
+        let style = if block.is_block_style { "block" } else { "line" };
+        let placement = match block.placement {
+            CommentPlacement::Above => "above",
+            CommentPlacement::Trailing => "trailing",
+            CommentPlacement::Between => "between",
+            CommentPlacement::Eof => "eof",
+        };
+
+        let content = block.lines.join("\n");
+
+        nodes.push(NodeRow {
+            id: comment_id.clone(),
+            instance_id: instance_id.to_string(),
+            kind: kind.as_str().to_string(),
+            language: Some("rust".to_string()),
+            content: Some(content),
+            parent_id: Some(file_node_id.to_string()),
+            position: block.start_line as i32,
+            path: None,
+            metadata: json!({
+                "start_line": block.start_line,
+                "end_line": block.end_line,
+                "col": block.col,
+                "placement": placement,
+                "style": style,
+                "line_count": block.lines.len(),
+            }),
+            span_start: Some(block.start_line as i32),
+            span_end: Some(block.end_line as i32),
+        });
+
+        // Create "documents" edge if matched to a node
+        if let Some(ref target_id) = matches[block_idx] {
+            edges.push(ast_walker::EdgeRow {
+                id: Uuid::new_v4().to_string(),
+                source_id: comment_id,
+                target_id: target_id.clone(),
+                relation: "documents".to_string(),
+                metadata: json!({"placement": placement}),
+            });
+        }
+    }
+
+    (nodes, edges)
+}
+
+/// Start lines of AST nodes that comments can attach to (everything with span
+/// info except comments themselves).
+fn ast_spans(nodes: &[NodeRow]) -> Vec<(i32, &str)> {
+    nodes
         .iter()
         .filter(|n| {
             n.span_start.is_some()
@@ -850,7 +1945,17 @@ fn match_comments_to_ast(
                 && n.kind != Kind::CommentBlock.as_str()
         })
         .map(|n| (n.span_start.unwrap(), n.id.as_str()))
-        .collect();
+        .collect()
+}
+
+/// Match comment blocks to AST nodes and classify placement.
+///
+/// Returns a Vec with one entry per block: Some(node_id) for the target,
+/// or None for eof comments.
+fn match_comments_to_spans(
+    blocks: &mut [CommentBlock],
+    mut ast_spans: Vec<(i32, &str)>,
+) -> Vec<Option<String>> {
     ast_spans.sort_by_key(|&(line, _)| line);
 
     let mut results = Vec::with_capacity(blocks.len());
@@ -914,3 +2019,66 @@ fn match_comments_to_ast(
 
     results
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_window_adaptive() {
+        let mut window = PoolWindow::new(8, 2, 6);
+        assert!(window.adaptive);
+        assert_eq!((window.min, window.max, window.size), (2, 6, 2));
+
+        // Fast workers grow the window up to max_pool
+        for _ in 0..10 {
+            window.on_worker_done(true);
+        }
+        assert_eq!(window.size, 6);
+        window.on_worker_done(false);
+        assert_eq!(window.size, 6);
+
+        // Failures halve it down to min_pool, then stop
+        assert_eq!(window.on_launch_failure(false), LaunchFailure::Retry);
+        assert_eq!(window.size, 3);
+        assert_eq!(window.on_launch_failure(false), LaunchFailure::Retry);
+        assert_eq!(window.size, 2);
+        assert_eq!(window.on_launch_failure(false), LaunchFailure::Stop);
+
+        // max_pool is capped by the pool size
+        let capped = PoolWindow::new(4, 1, 16);
+        assert_eq!((capped.min, capped.max), (1, 4));
+    }
+
+    #[test]
+    fn test_pool_window_fixed() {
+        let mut window = PoolWindow::new(4, 0, 0);
+        assert!(!window.adaptive);
+        assert_eq!(window.size, 4);
+        window.on_worker_done(true);
+        assert_eq!(window.size, 4);
+
+        // Failures during the initial fill skip one file; later ones stop
+        assert_eq!(window.on_launch_failure(true), LaunchFailure::Skip);
+        assert_eq!(window.on_launch_failure(false), LaunchFailure::Stop);
+    }
+
+    #[test]
+    fn test_pool_metrics_summary() {
+        let mut metrics = PoolMetrics::new();
+        metrics.observe_peak(3);
+        metrics.observe_peak(1);
+        metrics.launch_failures = 2;
+        metrics.worker_wall_ms = vec![40.0, 10.0, 30.0, 20.0];
+
+        let summary = metrics.summary();
+        assert_eq!(summary["peak_inflight"], 3);
+        assert_eq!(summary["launch_failures"], 2);
+        let walls = &summary["worker_wall_ms"];
+        assert_eq!(walls["count"], 4);
+        assert_eq!(walls["min"], 10.0);
+        assert_eq!(walls["max"], 40.0);
+        assert_eq!(walls["avg"], 25.0);
+        assert_eq!(walls["p50"], 30.0);
+    }
+}
@@ -1,4 +1,5 @@
 /// Generic tree-sitter node helpers shared across language walkers.
+use std::collections::BTreeMap;
 
 /// Extract the source text for a tree-sitter node.
 pub fn node_text<'a>(node: &tree_sitter::Node, source: &'a str) -> &'a str {
@@ -14,3 +15,24 @@ pub fn span_start_line(node: &tree_sitter::Node) -> i32 {
 pub fn span_end_line(node: &tree_sitter::Node) -> i32 {
     (node.end_position().row + 1) as i32
 }
+
+/// Count the `call_expression`s under `node` by callee name: a bare
+/// identifier, or the field of a selector (`pkg.Foo`, `s->op`). Calls
+/// through anything else (`fns[i]()`, `(*fp)()`) aren't counted.
+pub fn call_counts(node: &tree_sitter::Node, source: &str) -> BTreeMap<String, u32> {
+    let mut counts = BTreeMap::new();
+    let mut stack = vec![*node];
+    while let Some(n) = stack.pop() {
+        if n.kind() == "call_expression" {
+            if let Some(func) = n.child_by_field_name("function") {
+                let callee = func.child_by_field_name("field").unwrap_or(func);
+                if matches!(callee.kind(), "identifier" | "field_identifier") {
+                    *counts.entry(node_text(&callee, source).to_string()).or_default() += 1;
+                }
+            }
+        }
+        let mut cursor = n.walk();
+        stack.extend(n.named_children(&mut cursor));
+    }
+    counts
+}
@@ -1,7 +1,9 @@
 /// Peer management — register, list, get, remove peer instances.
 use pgrx::prelude::*;
 
+use crate::crdt;
 use crate::identity;
+use crate::pagination;
 use crate::sql::sql_escape;
 
 /// Register a peer instance. Decodes hex public key, computes fingerprint,
@@ -13,15 +15,9 @@ fn register_peer(
     endpoint: Option<&str>,
     connection: Option<&str>,
 ) -> pgrx::JsonB {
-    let pk_bytes = hex::decode(public_key_hex)
-        .unwrap_or_else(|_| error!("Invalid hex public_key"));
-    if pk_bytes.len() != 32 {
-        error!("Public key must be 32 bytes (got {})", pk_bytes.len());
-    }
-    let pk_hex_pg: String = pk_bytes.iter().map(|b| format!("{:02x}", b)).collect();
-    let pk_array: [u8; 32] = pk_bytes.try_into().unwrap();
-    let verifying_key = ed25519_dalek::VerifyingKey::from_bytes(&pk_array)
-        .unwrap_or_else(|_| error!("Invalid Ed25519 public key"));
+    let verifying_key =
+        identity::parse_public_key_hex(public_key_hex).unwrap_or_else(|e| error!("{}", e));
+    let pk_hex_pg = hex::encode(verifying_key.as_bytes());
     let fp = identity::fingerprint(&verifying_key);
     let endpoint_sql = match endpoint {
         Some(e) => format!("'{}'", sql_escape(e)),
@@ -83,23 +79,43 @@ fn register_peer(
     }))
 }
 
-/// List all non-self peer instances as a JSON array.
+/// List all non-self peer instances as a JSON array, by name.
+///
+/// With `limit` or `cursor`, returns one page `{items, total_count, next_cursor}`
+/// in creation order instead (see `pagination`).
 #[pg_extern]
-fn list_peers() -> pgrx::JsonB {
-    let json = Spi::get_one::<pgrx::JsonB>(
-        "SELECT COALESCE(
-            jsonb_agg(jsonb_build_object(
-                'id', id,
-                'name', name,
-                'key_fingerprint', key_fingerprint,
-                'endpoint', endpoint,
-                'connection', connection,
-                'last_seen', last_seen,
-                'public_key', encode(public_key, 'hex')
-            ) ORDER BY name),
-            '[]'::jsonb
-        ) FROM kerai.instances WHERE is_self = false",
-    )
+fn list_peers(
+    limit: default!(Option<i32>, "NULL"),
+    cursor: default!(Option<&str>, "NULL"),
+) -> pgrx::JsonB {
+    let conditions = vec!["i.is_self = false".to_string()];
+    let object = "jsonb_build_object(
+                'id', i.id,
+                'name', i.name,
+                'key_fingerprint', i.key_fingerprint,
+                'endpoint', i.endpoint,
+                'connection', i.connection,
+                'last_seen', i.last_seen,
+                'trust_level', i.trust_level,
+                'public_key', encode(i.public_key, 'hex')
+            )";
+
+    if pagination::requested(limit, cursor) {
+        return pagination::keyset_page(
+            object,
+            "kerai.instances i",
+            &conditions,
+            "i",
+            limit,
+            cursor,
+        );
+    }
+
+    let json = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg({object} ORDER BY i.name), '[]'::jsonb)
+        FROM kerai.instances i {}",
+        pagination::where_clause(&conditions),
+    ))
     .unwrap()
     .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
     json
@@ -116,6 +132,7 @@ fn get_peer(fingerprint: &str) -> pgrx::JsonB {
             'endpoint', endpoint,
             'connection', connection,
             'last_seen', last_seen,
+            'trust_level', trust_level,
             'public_key', encode(public_key, 'hex'),
             'is_self', is_self
         ) FROM kerai.instances WHERE key_fingerprint = '{}'",
@@ -163,6 +180,75 @@ fn remove_peer(name: &str) -> pgrx::JsonB {
     }))
 }
 
+/// Set how much a peer's synced ops are trusted.
+///
+/// - `full`: ops are applied to local state
+/// - `read_only`: ops are recorded in the op log but not applied
+/// - `untrusted`: ops are rejected
+///
+/// The self instance is always `full` and cannot be changed. Raising a peer to
+/// `full` applies the ops recorded from it while it was `read_only`.
+#[pg_extern]
+fn set_peer_trust(name: &str, level: &str) -> pgrx::JsonB {
+    if !matches!(level, "full" | "read_only" | "untrusted") {
+        error!(
+            "Invalid trust level '{}' (expected full, read_only, or untrusted)",
+            level
+        );
+    }
+
+    let is_self = Spi::get_one::<bool>(&format!(
+        "SELECT is_self FROM kerai.instances WHERE name = '{}'",
+        sql_escape(name),
+    ))
+    .unwrap_or(None);
+
+    match is_self {
+        Some(true) => error!("Cannot change trust level of self instance"),
+        None => error!("Peer not found: {}", name),
+        _ => {}
+    }
+
+    let updated = Spi::get_two::<String, String>(&format!(
+        "UPDATE kerai.instances i SET trust_level = '{}'
+         FROM (SELECT id, trust_level FROM kerai.instances WHERE name = '{}' AND is_self = false) old
+         WHERE i.id = old.id
+         RETURNING i.id::text, old.trust_level",
+        level,
+        sql_escape(name),
+    ))
+    .unwrap_or((None, None));
+    let (instance_id, previous) = updated;
+
+    let applied_ops = match instance_id {
+        Some(id) if level == "full" => crdt::apply_recorded_ops(&id),
+        _ => 0,
+    };
+
+    pgrx::JsonB(serde_json::json!({
+        "name": name,
+        "trust_level": level,
+        "previous": previous,
+        "applied_ops": applied_ops,
+    }))
+}
+
+/// Canonical fingerprint of a hex-encoded Ed25519 public key, as
+/// `register_peer` would compute it. Nothing is registered.
+#[pg_extern]
+fn fingerprint_of(public_key_hex: &str) -> String {
+    let verifying_key =
+        identity::parse_public_key_hex(public_key_hex).unwrap_or_else(|e| error!("{}", e));
+    identity::fingerprint(&verifying_key)
+}
+
+/// Whether `fingerprint` is the canonical fingerprint of the public key.
+/// Errors (rather than returning false) when the key itself is malformed.
+#[pg_extern]
+fn verify_fingerprint(public_key_hex: &str, fingerprint: &str) -> bool {
+    fingerprint_of(public_key_hex) == fingerprint.trim()
+}
+
 /// Return the self instance's public key as a hex string.
 #[pg_extern]
 fn self_public_key_hex() -> String {
@@ -1,7 +1,10 @@
-/// Perspective and association CRUD — weighted views of the codebase.
+/// Perspective and association CRUD — weighted views of the codebase,
+/// plus signed bundles that carry an agent's views across instances.
 use pgrx::prelude::*;
+use serde_json::json;
 
-use crate::sql::sql_escape;
+use crate::identity;
+use crate::sql::{sql_escape, sql_text, sql_uuid};
 
 /// Resolve agent name to agent_id. Errors if not found.
 fn resolve_agent(name: &str) -> String {
@@ -30,29 +33,27 @@ fn set_perspective(
     let agent_id = resolve_agent(agent_name);
     let nid = node_id.to_string();
 
-    let ctx_sql = match context_id {
-        Some(c) => format!("'{}'::uuid", c),
-        None => "NULL".to_string(),
-    };
-    let reasoning_sql = match reasoning {
-        Some(r) => format!("'{}'", sql_escape(r)),
-        None => "NULL".to_string(),
-    };
+    // Recorded as a CRDT op so the perspective syncs to peers with its origin instance
+    let mut payload = serde_json::json!({
+        "agent_id": agent_id,
+        "agent_name": agent_name,
+        "node_id": nid,
+        "weight": weight,
+    });
+    if let Some(c) = context_id {
+        payload["context_id"] = serde_json::json!(c.to_string());
+    }
+    if let Some(r) = reasoning {
+        payload["reasoning"] = serde_json::json!(r);
+    }
 
-    let pid = Spi::get_one::<String>(&format!(
-        "INSERT INTO kerai.perspectives (agent_id, node_id, weight, context_id, reasoning)
-         VALUES ('{}'::uuid, '{}'::uuid, {}, {}, {})
-         ON CONFLICT (agent_id, node_id, context_id)
-         DO UPDATE SET weight = EXCLUDED.weight, reasoning = EXCLUDED.reasoning, updated_at = now()
-         RETURNING id::text",
-        sql_escape(&agent_id),
-        sql_escape(&nid),
-        weight,
-        ctx_sql,
-        reasoning_sql,
+    let op = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT kerai.apply_op('set_perspective', NULL, '{}'::jsonb)",
+        sql_escape(&payload.to_string()),
     ))
     .unwrap()
     .unwrap();
+    let pid = op.0["node_id"].as_str().unwrap_or("").to_string();
 
     pgrx::JsonB(serde_json::json!({
         "id": pid,
@@ -79,11 +80,8 @@ fn delete_perspective(
     };
 
     let deleted = Spi::get_one::<i64>(&format!(
-        "WITH deleted AS (
-            DELETE FROM kerai.perspectives
-            WHERE agent_id = '{}'::uuid AND node_id = '{}'::uuid {}
-            RETURNING id
-        ) SELECT count(*)::bigint FROM deleted",
+        "SELECT count(*)::bigint FROM kerai.perspectives
+         WHERE agent_id = '{}'::uuid AND node_id = '{}'::uuid {}",
         sql_escape(&agent_id),
         sql_escape(&nid),
         ctx_clause,
@@ -91,6 +89,18 @@ fn delete_perspective(
     .unwrap()
     .unwrap_or(0);
 
+    if deleted > 0 {
+        let mut payload = serde_json::json!({"agent_id": agent_id, "node_id": nid});
+        if let Some(c) = context_id {
+            payload["context_id"] = serde_json::json!(c.to_string());
+        }
+        Spi::run(&format!(
+            "SELECT kerai.apply_op('delete_perspective', NULL, '{}'::jsonb)",
+            sql_escape(&payload.to_string()),
+        ))
+        .unwrap();
+    }
+
     pgrx::JsonB(serde_json::json!({
         "deleted": deleted > 0,
         "agent": agent_name,
@@ -272,3 +282,326 @@ fn get_associations(
     .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
     json
 }
+
+/// Stable key for a node: kind, path, and content instead of its UUID,
+/// so the same code re-parsed or parsed on another instance still matches.
+const NODE_KEY_SQL: &str =
+    "jsonb_build_object('kind', n.kind, 'path', n.path::text, 'content', n.content)";
+
+/// Export an agent's perspectives and associations as a signed bundle.
+/// Nodes are keyed by `{kind, path, content}` so the bundle can be
+/// re-attached with `import_perspectives` after a re-parse or on another instance.
+#[pg_extern]
+fn export_perspectives(agent_name: &str) -> pgrx::JsonB {
+    let agent_id = resolve_agent(agent_name);
+    let signing_key = identity::load_signing_key()
+        .unwrap_or_else(|| error!("No signing key found — identity not initialized"));
+
+    let mut bundle = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'agent', jsonb_build_object('name', ag.name, 'kind', ag.kind, 'model', ag.model),
+            'perspectives', (
+                SELECT COALESCE(jsonb_agg(jsonb_build_object(
+                    'node', {key},
+                    'context', (SELECT {key} FROM kerai.nodes n WHERE n.id = p.context_id),
+                    'weight', p.weight,
+                    'reasoning', p.reasoning
+                ) ORDER BY n.path, n.kind, n.content), '[]'::jsonb)
+                FROM kerai.perspectives p
+                JOIN kerai.nodes n ON n.id = p.node_id
+                WHERE p.agent_id = ag.id
+            ),
+            'associations', (
+                SELECT COALESCE(jsonb_agg(jsonb_build_object(
+                    'source', (SELECT {key} FROM kerai.nodes n WHERE n.id = a.source_id),
+                    'target', (SELECT {key} FROM kerai.nodes n WHERE n.id = a.target_id),
+                    'relation', a.relation,
+                    'weight', a.weight,
+                    'reasoning', a.reasoning
+                ) ORDER BY a.relation, a.weight DESC), '[]'::jsonb)
+                FROM kerai.associations a
+                WHERE a.agent_id = ag.id
+            ),
+            'exported_at', now()
+        ) FROM kerai.agents ag WHERE ag.id = {agent_id}",
+        key = NODE_KEY_SQL,
+        agent_id = sql_uuid(&agent_id),
+    ))
+    .unwrap()
+    .unwrap()
+    .0;
+
+    bundle["public_key"] = json!(hex::encode(signing_key.verifying_key().as_bytes()));
+    let signature = identity::sign_data(&signing_key, bundle.to_string().as_bytes());
+    bundle["signature"] = json!(hex::encode(signature));
+    pgrx::JsonB(bundle)
+}
+
+/// Re-attach a bundle from `export_perspectives` to matching nodes here.
+/// The agent is registered if it doesn't exist. Entries whose nodes don't
+/// resolve to exactly one node are reported under `unmatched`.
+///
+/// The bundle must be signed by a known, trusted instance, and an existing
+/// agent only accepts bundles from the instance it originates from (this one
+/// for local agents). A new agent records the signer as its origin.
+#[pg_extern]
+fn import_perspectives(bundle: pgrx::JsonB) -> pgrx::JsonB {
+    let mut body = bundle.0;
+    let signer = verify_bundle(&mut body);
+    let signer_instance = trusted_instance_id(&signer);
+
+    let agent = &body["agent"];
+    let agent_name = agent["name"]
+        .as_str()
+        .unwrap_or_else(|| error!("Bundle is missing agent name"));
+    let origin = Spi::get_one::<String>(&format!(
+        "SELECT COALESCE(a.origin_instance_id, i.id)::text
+         FROM kerai.agents a, kerai.instances i
+         WHERE a.name = {} AND i.is_self",
+        sql_text(agent_name),
+    ))
+    .unwrap_or(None);
+    match origin {
+        Some(origin) if origin != signer_instance => error!(
+            "Bundle signer {} is not the origin instance of agent '{}'",
+            signer, agent_name
+        ),
+        Some(_) => {}
+        None => {
+            Spi::run(&format!(
+                "SELECT kerai.register_agent({}, {}, {}, NULL)",
+                sql_text(agent_name),
+                sql_text(agent["kind"].as_str().unwrap_or("llm")),
+                agent["model"]
+                    .as_str()
+                    .map(sql_text)
+                    .unwrap_or_else(|| "NULL".to_string()),
+            ))
+            .unwrap();
+            Spi::run(&format!(
+                "UPDATE kerai.agents SET origin_instance_id = NULLIF(
+                     {}, (SELECT id FROM kerai.instances WHERE is_self)
+                 )
+                 WHERE name = {}",
+                sql_uuid(&signer_instance),
+                sql_text(agent_name),
+            ))
+            .unwrap();
+        }
+    }
+
+    let mut unmatched = Vec::new();
+    let mut perspectives = 0;
+    for p in body["perspectives"].as_array().into_iter().flatten() {
+        let node = match resolve_node_key(&p["node"]) {
+            Ok(id) => id,
+            Err(reason) => {
+                unmatched.push(json!({"perspective": p, "reason": reason}));
+                continue;
+            }
+        };
+        let context = if p["context"].is_null() {
+            "NULL".to_string()
+        } else {
+            match resolve_node_key(&p["context"]) {
+                Ok(id) => sql_uuid(&id),
+                Err(reason) => {
+                    unmatched.push(json!({"perspective": p, "reason": format!("context {reason}")}));
+                    continue;
+                }
+            }
+        };
+        Spi::run(&format!(
+            "SELECT kerai.set_perspective({}, {}, {}, {}, {})",
+            sql_text(agent_name),
+            sql_uuid(&node),
+            p["weight"].as_f64().unwrap_or(0.0),
+            context,
+            p["reasoning"].as_str().map(sql_text).unwrap_or_else(|| "NULL".to_string()),
+        ))
+        .unwrap();
+        perspectives += 1;
+    }
+
+    let mut associations = 0;
+    for a in body["associations"].as_array().into_iter().flatten() {
+        let (source, target) = match (resolve_node_key(&a["source"]), resolve_node_key(&a["target"])) {
+            (Ok(source), Ok(target)) => (source, target),
+            (Err(reason), _) => {
+                unmatched.push(json!({"association": a, "reason": format!("source {reason}")}));
+                continue;
+            }
+            (_, Err(reason)) => {
+                unmatched.push(json!({"association": a, "reason": format!("target {reason}")}));
+                continue;
+            }
+        };
+        Spi::run(&format!(
+            "SELECT kerai.set_association({}, {}, {}, {}, {}, {})",
+            sql_text(agent_name),
+            sql_uuid(&source),
+            sql_uuid(&target),
+            a["weight"].as_f64().unwrap_or(0.0),
+            sql_text(a["relation"].as_str().unwrap_or("")),
+            a["reasoning"].as_str().map(sql_text).unwrap_or_else(|| "NULL".to_string()),
+        ))
+        .unwrap();
+        associations += 1;
+    }
+
+    pgrx::JsonB(json!({
+        "agent": agent_name,
+        "signer": signer,
+        "perspectives": perspectives,
+        "associations": associations,
+        "unmatched": unmatched,
+    }))
+}
+
+/// Merge `from_agent`'s perspectives onto `into_agent`. Perspectives only
+/// `from_agent` holds are copied over; where both rate the same node and
+/// context with different weights, `strategy` decides: 'max' keeps the higher
+/// weight, 'avg' the mean, 'prefer_into' leaves `into_agent`'s weight as is.
+/// `from_agent` is unchanged. Returns `{merged, created, conflicts_resolved}`.
+#[pg_extern]
+fn merge_perspectives(into_agent: &str, from_agent: &str, strategy: &str) -> pgrx::JsonB {
+    if !["max", "avg", "prefer_into"].contains(&strategy) {
+        error!(
+            "Invalid merge strategy '{}'. Must be one of: max, avg, prefer_into",
+            strategy
+        );
+    }
+    let into_id = resolve_agent(into_agent);
+    let from_id = resolve_agent(from_agent);
+    if into_id == from_id {
+        error!("Cannot merge agent '{}' into itself", into_agent);
+    }
+
+    let rows = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'node_id', f.node_id,
+            'context_id', f.context_id,
+            'from_weight', f.weight,
+            'from_reasoning', f.reasoning,
+            'into_weight', i.weight,
+            'into_reasoning', i.reasoning
+        )), '[]'::jsonb)
+        FROM kerai.perspectives f
+        LEFT JOIN kerai.perspectives i
+          ON i.agent_id = {} AND i.node_id = f.node_id
+         AND i.context_id IS NOT DISTINCT FROM f.context_id
+        WHERE f.agent_id = {}",
+        sql_uuid(&into_id),
+        sql_uuid(&from_id),
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| json!([]));
+
+    let mut merged = 0;
+    let mut created = 0;
+    let mut conflicts_resolved = 0;
+    for row in rows.as_array().into_iter().flatten() {
+        let from_weight = row["from_weight"].as_f64().unwrap_or(0.0);
+        let (weight, reasoning) = match row["into_weight"].as_f64() {
+            None => {
+                created += 1;
+                (from_weight, &row["from_reasoning"])
+            }
+            Some(into_weight) if into_weight == from_weight => continue,
+            Some(into_weight) => {
+                conflicts_resolved += 1;
+                let weight = match strategy {
+                    "max" => into_weight.max(from_weight),
+                    "avg" => (into_weight + from_weight) / 2.0,
+                    _ => into_weight,
+                };
+                if weight == into_weight {
+                    continue;
+                }
+                (weight, &row["into_reasoning"])
+            }
+        };
+        let context = row["context_id"].as_str().map(sql_uuid);
+        let reasoning = reasoning.as_str().map(sql_text);
+        Spi::run(&format!(
+            "SELECT kerai.set_perspective({}, {}, {}, {}, {})",
+            sql_text(into_agent),
+            sql_uuid(row["node_id"].as_str().unwrap_or_default()),
+            weight,
+            context.as_deref().unwrap_or("NULL"),
+            reasoning.as_deref().unwrap_or("NULL"),
+        ))
+        .unwrap();
+        merged += 1;
+    }
+
+    pgrx::JsonB(json!({
+        "into_agent": into_agent,
+        "from_agent": from_agent,
+        "strategy": strategy,
+        "merged": merged,
+        "created": created,
+        "conflicts_resolved": conflicts_resolved,
+    }))
+}
+
+/// Check a bundle's signature against its embedded public key, stripping the
+/// signature so `body` is left as it was signed. Returns the signer fingerprint.
+fn verify_bundle(body: &mut serde_json::Value) -> String {
+    let signature = body
+        .as_object_mut()
+        .and_then(|o| o.remove("signature"))
+        .and_then(|s| s.as_str().and_then(|s| hex::decode(s).ok()))
+        .unwrap_or_else(|| error!("Bundle is missing a valid signature"));
+    let key = identity::parse_public_key_hex(body["public_key"].as_str().unwrap_or(""))
+        .unwrap_or_else(|e| error!("Bundle public key rejected: {}", e));
+    if !identity::verify_signature(&key, body.to_string().as_bytes(), &signature) {
+        error!("Bundle signature does not match its contents");
+    }
+    identity::fingerprint(&key)
+}
+
+/// Id of the instance with this key fingerprint. Errors when the signer is
+/// not a known instance or is marked untrusted.
+fn trusted_instance_id(fingerprint: &str) -> String {
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object('id', id, 'trust_level', trust_level)
+         FROM kerai.instances WHERE key_fingerprint = {}",
+        sql_text(fingerprint),
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Bundle signer {} is not a known instance", fingerprint))
+    .0;
+    if row["trust_level"] == "untrusted" {
+        error!("Bundle signer {} is untrusted", fingerprint);
+    }
+    row["id"].as_str().unwrap_or_default().to_string()
+}
+
+/// Find the single node matching a `{kind, path, content}` key.
+fn resolve_node_key(key: &serde_json::Value) -> Result<String, String> {
+    let field = |name: &str, cast: &str| match key[name].as_str() {
+        Some(v) => format!("n.{name} = {}{cast}", sql_text(v)),
+        None => format!("n.{name} IS NULL"),
+    };
+    let Some(kind) = key["kind"].as_str() else {
+        return Err("node key has no kind".to_string());
+    };
+    let ids = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(n.id::text), '[]'::jsonb) FROM kerai.nodes n
+         WHERE n.kind = {} AND {} AND {}",
+        sql_text(kind),
+        field("path", "::ltree"),
+        field("content", ""),
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| json!([]));
+
+    match ids.as_array().map(Vec::as_slice) {
+        Some([id]) => Ok(id.as_str().unwrap_or_default().to_string()),
+        Some([]) | None => Err("no matching node".to_string()),
+        Some(many) => Err(format!("{} matching nodes", many.len())),
+    }
+}
@@ -1,5 +1,6 @@
 use pgrx::prelude::*;
 
+use crate::currency;
 use crate::sql::sql_text;
 
 /// Get a preference value for the self instance.
@@ -16,8 +17,14 @@ fn get_preference(category: &str, key: &str) -> Option<String> {
 }
 
 /// Set (upsert) a preference for the self instance.
+///
+/// Preferences other modules act on are checked first, so a bad value is
+/// refused here rather than failing wherever it is read.
 #[pg_extern]
 fn set_preference(category: &str, key: &str, value: &str) -> &'static str {
+    if (category, key) == ("currency", "reward_recipient") {
+        currency::validate_reward_recipient(value);
+    }
     Spi::run(&format!(
         "INSERT INTO kerai.preferences (instance_id, category, key, value) \
          VALUES (\
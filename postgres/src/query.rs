@@ -1,17 +1,41 @@
-/// Query & Navigation — find, refs, tree, children, ancestors, search.
+/// Query & Navigation — find, refs, tree, children, ancestors, edges, traversal, call graph, search, DOT export, explain.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
 use pgrx::prelude::*;
 use serde_json::json;
 
-use crate::sql::sql_escape;
+use crate::sql::{sql_escape, sql_ltree, sql_text};
+use crate::subxact;
 
-/// Search nodes by content pattern (ILIKE) with optional kind filter and limit.
+/// Search nodes by content pattern with optional kind filter and limit.
+///
+/// `mode` is `like` (default: `pattern` is an ILIKE pattern) or `regex`
+/// (`pattern` is a POSIX regex matched case-sensitively with `~`). A regex
+/// search runs under the `config/query_max_ms` statement timeout (default
+/// 5000 ms) and errors if a pathological pattern runs past it.
 ///
 /// Returns JSON array of `{id, kind, content, path, parent_id, metadata}`.
+/// With `highlight`, each result also carries `highlights: [{start, end, text}]`
+/// marking the literal parts of the pattern (or the regex matches) in `content`
+/// (character offsets).
 #[pg_extern]
-fn find(pattern: &str, kind_filter: Option<&str>, limit: Option<i32>) -> pgrx::JsonB {
+fn find(
+    pattern: &str,
+    kind_filter: Option<&str>,
+    limit: Option<i32>,
+    highlight: default!(bool, false),
+    mode: default!(&str, "'like'"),
+) -> pgrx::JsonB {
     let limit_val = limit.unwrap_or(50).max(1).min(1000);
     let escaped_pattern = sql_escape(pattern);
 
+    let operator = match mode {
+        "like" => "ILIKE",
+        "regex" => "~",
+        other => error!("Invalid mode '{}'. Must be one of: like, regex", other),
+    };
+
     let kind_clause = match kind_filter {
         Some(k) => format!("AND kind = '{}'", sql_escape(k)),
         None => String::new(),
@@ -28,21 +52,130 @@ fn find(pattern: &str, kind_filter: Option<&str>, limit: Option<i32>) -> pgrx::J
                 'metadata', metadata
             ) AS r
             FROM kerai.nodes
-            WHERE content ILIKE '{}' {}
+            WHERE content {} '{}' {}
             ORDER BY kind, content
             LIMIT {}
         ) sub",
-        escaped_pattern, kind_clause, limit_val,
+        operator, escaped_pattern, kind_clause, limit_val,
+    );
+
+    let mut result = if mode == "regex" {
+        let max_ms = QueryBudget::load(None).max_ms;
+        with_statement_timeout(max_ms, || Spi::get_one::<pgrx::JsonB>(&sql).unwrap())
+            .unwrap_or_else(|| {
+                error!(
+                    "Regex pattern '{}' timed out after {} ms; simplify it or add a kind filter",
+                    pattern, max_ms
+                )
+            })
+    } else {
+        Spi::get_one::<pgrx::JsonB>(&sql).unwrap()
+    }
+    .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
+
+    if highlight {
+        let segments = if mode == "regex" {
+            // Highlight only patterns the regex crate reads the same way
+            regex::Regex::new(pattern).into_iter().collect()
+        } else {
+            like_segments(pattern)
+        };
+        for row in result.0.as_array_mut().into_iter().flatten() {
+            let content = row["content"].as_str().unwrap_or("");
+            row["highlights"] = json!(like_highlights(content, &segments));
+        }
+    }
+    result
+}
+
+/// Run `f` with Postgres's statement timeout armed for `max_ms`, returning
+/// `None` if it fires. `statement_timeout` itself is only armed per top-level
+/// statement, so this arms the same timer directly for the nested query.
+/// An already-running statement timeout is left alone, and so is `max_ms` 0.
+fn with_statement_timeout<T>(max_ms: u64, f: impl FnOnce() -> T) -> Option<T> {
+    use pgrx::pg_sys::TimeoutId::STATEMENT_TIMEOUT;
+
+    let arm = max_ms > 0 && unsafe { !pgrx::pg_sys::get_timeout_active(STATEMENT_TIMEOUT) };
+    if arm {
+        let ms = max_ms.min(i32::MAX as u64) as i32;
+        unsafe { pgrx::pg_sys::enable_timeout_after(STATEMENT_TIMEOUT, ms) };
+    }
+
+    // In a subtransaction, so the caller's transaction survives the timeout
+    let result = subxact::try_subtransaction(f);
+    if arm {
+        unsafe { pgrx::pg_sys::disable_timeout(STATEMENT_TIMEOUT, false) };
+    }
+
+    match result {
+        Ok(value) => Some(value),
+        Err(err) => {
+            // A user cancel shares the error code; only swallow our timeout
+            let ours = match &err {
+                pgrx::pg_sys::panic::CaughtError::PostgresError(report) => {
+                    arm && report.sql_error_code() == pgrx::PgSqlErrorCode::ERRCODE_QUERY_CANCELED
+                        && report.message().contains("statement timeout")
+                }
+                _ => false,
+            };
+            if !ours {
+                err.rethrow();
+            }
+            None
+        }
+    }
+}
+
+/// Find nodes whose metadata contains `filter` (`metadata @> filter`),
+/// with optional kind filter and limit.
+///
+/// e.g. `find_by_metadata('{"exported": true}', 'fn', NULL)` for exported Go
+/// functions, or `'{"static": true}'` for C statics.
+///
+/// Returns JSON array of `{id, kind, content, path, parent_id, metadata}`.
+#[pg_extern]
+fn find_by_metadata(
+    filter: pgrx::JsonB,
+    kind_filter: Option<&str>,
+    limit: Option<i32>,
+) -> pgrx::JsonB {
+    let limit_val = limit.unwrap_or(50).max(1).min(1000);
+
+    let kind_clause = match kind_filter {
+        Some(k) => format!("AND kind = '{}'", sql_escape(k)),
+        None => String::new(),
+    };
+
+    let sql = format!(
+        "SELECT COALESCE(jsonb_agg(r), '[]'::jsonb) FROM (
+            SELECT jsonb_build_object(
+                'id', id,
+                'kind', kind,
+                'content', content,
+                'path', path::text,
+                'parent_id', parent_id,
+                'metadata', metadata
+            ) AS r
+            FROM kerai.nodes
+            WHERE metadata @> '{}'::jsonb {}
+            ORDER BY kind, path::text, position
+            LIMIT {}
+        ) sub",
+        sql_escape(&filter.0.to_string()),
+        kind_clause,
+        limit_val,
     );
 
     Spi::get_one::<pgrx::JsonB>(&sql)
         .unwrap()
-        .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])))
+        .unwrap_or_else(|| pgrx::JsonB(json!([])))
 }
 
 /// Find all definitions, references, and impl blocks for a symbol.
 ///
-/// Returns `{symbol, definitions: [...], references: [...], impls: [...]}`.
+/// Returns `{symbol, definitions: [...], references: [...], impls: [...], traits: [...], implementors: [...]}`,
+/// where `traits` lists trait definitions named `symbol` with their supertraits and associated
+/// items, and `implementors` lists trait impls of `symbol` (see [`implementors`]).
 #[pg_extern]
 fn refs(symbol: &str) -> pgrx::JsonB {
     let escaped = sql_escape(symbol);
@@ -98,6 +231,31 @@ fn refs(symbol: &str) -> pgrx::JsonB {
         escaped,
     );
 
+    // Traits: trait definitions with supertraits and associated items in order
+    let traits_sql = format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', t.id,
+            'content', t.content,
+            'path', t.path::text,
+            'supertraits', COALESCE(t.metadata->'supertraits', '[]'::jsonb),
+            'items', (
+                SELECT COALESCE(jsonb_agg(jsonb_build_object(
+                    'id', c.id,
+                    'kind', c.kind,
+                    'content', c.content,
+                    'provided', COALESCE((c.metadata->>'provided')::boolean, false)
+                ) ORDER BY c.position), '[]'::jsonb)
+                FROM kerai.nodes c
+                WHERE c.parent_id = t.id AND c.kind IN ('fn', 'type_alias', 'const')
+            )
+        ) ORDER BY t.path::text), '[]'::jsonb)
+        FROM kerai.nodes t
+        WHERE t.kind = 'trait' AND t.content = '{}'",
+        escaped,
+    );
+
+    let implementors_sql = implementors_sql(&escaped);
+
     let definitions = Spi::get_one::<pgrx::JsonB>(&defs_sql)
         .unwrap()
         .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
@@ -107,12 +265,236 @@ fn refs(symbol: &str) -> pgrx::JsonB {
     let impls = Spi::get_one::<pgrx::JsonB>(&impls_sql)
         .unwrap()
         .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
+    let traits = Spi::get_one::<pgrx::JsonB>(&traits_sql)
+        .unwrap()
+        .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
+    let implementors = Spi::get_one::<pgrx::JsonB>(&implementors_sql)
+        .unwrap()
+        .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
 
     pgrx::JsonB(serde_json::json!({
         "symbol": symbol,
         "definitions": definitions.0,
         "references": references.0,
         "impls": impls.0,
+        "traits": traits.0,
+        "implementors": implementors.0,
+    }))
+}
+
+/// List the types with a trait impl for `trait_name`, matched on the impl's
+/// last trait path segment so traits outside the graph (`Display`) count too.
+/// A `std::fmt::Display` argument matches on `Display`.
+///
+/// Returns `[{id, content, path, type, type_id, trait, trait_id}]`: `id` is the
+/// impl block, `type` its type name, and `type_id`/`trait_id` the definitions
+/// its `for`/`implements` edges point to (null when not in the graph).
+#[pg_extern]
+fn implementors(trait_name: &str) -> pgrx::JsonB {
+    let name = trait_name.rsplit("::").next().unwrap_or(trait_name).trim();
+    Spi::get_one::<pgrx::JsonB>(&implementors_sql(&sql_escape(name)))
+        .unwrap()
+        .unwrap_or_else(|| pgrx::JsonB(json!([])))
+}
+
+/// List every method defined on `type_name` across all of its impl blocks,
+/// inherent and trait, in any file. A `::` path matches on its last segment.
+///
+/// Returns `[{method, id, impl_id, trait_name, visibility, signature}]`,
+/// inherent methods (null `trait_name`) first, then by trait and method name.
+#[pg_extern]
+fn impl_methods(type_name: &str) -> pgrx::JsonB {
+    let name = type_name.rsplit("::").next().unwrap_or(type_name).trim();
+    let sql = format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'method', m.content,
+            'id', m.id,
+            'impl_id', i.id,
+            'trait_name', i.metadata->>'trait_name',
+            'visibility', m.metadata->>'visibility',
+            'signature', m.metadata->>'signature'
+        ) ORDER BY i.metadata->>'trait_name' NULLS FIRST, m.content, m.path::text), '[]'::jsonb)
+        FROM kerai.nodes i
+        JOIN kerai.nodes m ON m.parent_id = i.id AND m.kind = 'fn'
+        WHERE i.kind = 'impl'
+          AND COALESCE(i.metadata->>'type_name', i.metadata->>'self_name') = '{}'",
+        sql_escape(name),
+    );
+    Spi::get_one::<pgrx::JsonB>(&sql)
+        .unwrap()
+        .unwrap_or_else(|| pgrx::JsonB(json!([])))
+}
+
+/// Trait impl blocks whose `trait_name` is `escaped_name`, with the nodes
+/// their `implements` and `for` edges resolve to.
+fn implementors_sql(escaped_name: &str) -> String {
+    format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', i.id,
+            'content', i.content,
+            'path', i.path::text,
+            'type', COALESCE(ty.content, i.metadata->>'type_name', i.metadata->>'self_ty'),
+            'type_id', ty.id,
+            'trait', i.metadata->>'trait',
+            'trait_id', t.id
+        ) ORDER BY COALESCE(ty.content, i.metadata->>'type_name', i.metadata->>'self_ty'),
+                   i.path::text), '[]'::jsonb)
+        FROM kerai.nodes i
+        LEFT JOIN kerai.edges te ON te.source_id = i.id AND te.relation = 'implements'
+        LEFT JOIN kerai.nodes t ON t.id = te.target_id
+        LEFT JOIN kerai.edges fe ON fe.source_id = i.id AND fe.relation = 'for'
+        LEFT JOIN kerai.nodes ty ON ty.id = fe.target_id
+        WHERE i.kind = 'impl' AND i.metadata->>'trait_name' = '{}'",
+        escaped_name,
+    )
+}
+
+/// Resolve a symbol to its most likely definition as seen from `from_file`
+/// — go-to-definition over the ingested graph (same-crate Rust resolution).
+///
+/// `symbol` is a name or a `::` path. Candidates are ranked:
+/// 1. `local` — defined in the file's own module
+/// 2. `import` — brought in by a `use` in the file
+/// 3. `glob_import` — found through a `use ...::*` in the file
+/// 4. `path` — a qualified `symbol` resolved from the file's module
+/// 5. `crate` — defined elsewhere in the same crate
+/// 6. `external` — defined outside the crate
+///
+/// Returns `{symbol, from_file, definition, candidates, ambiguous}`, where
+/// `definition` is the best candidate (or null) and `ambiguous` is true when
+/// more than one candidate shares the best rank.
+#[pg_extern]
+fn definition(symbol: &str, from_file: pgrx::Uuid) -> pgrx::JsonB {
+    use crate::parser::import_linker::{file_module, Symbol, SymbolTable, DEFINING_KINDS};
+
+    let file_id = from_file.to_string();
+    let file = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object('kind', n.kind, 'content', n.content, 'parent_kind', p.kind,
+                                   'parent_id', p.id)
+         FROM kerai.nodes n LEFT JOIN kerai.nodes p ON p.id = n.parent_id
+         WHERE n.id = '{}'::uuid",
+        file_id,
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| error!("Node {} not found", file_id));
+    if file["kind"] != "file" {
+        error!(
+            "Node {} is kind '{}', expected 'file'",
+            file_id,
+            file["kind"].as_str().unwrap_or_default(),
+        );
+    }
+
+    // Files of a crate resolve against the whole crate; standalone files alone
+    let root = match file["parent_kind"].as_str() {
+        Some("crate") => file["parent_id"].as_str().unwrap_or(&file_id).to_string(),
+        _ => file_id.clone(),
+    };
+    let table = SymbolTable::load(&root);
+    let module = file_module(file["content"].as_str().unwrap_or_default());
+    let segments: Vec<String> = symbol.split("::").map(str::to_string).collect();
+    let name = segments.last().cloned().unwrap_or_default();
+
+    let mut ranked: Vec<(&Symbol, &str, i32)> = Vec::new();
+    if segments.len() == 1 {
+        for s in table.lookup(&module, &name) {
+            if s.file_id == file_id {
+                ranked.push((s, "local", 1));
+            }
+        }
+        let uses = table
+            .symbols
+            .iter()
+            .filter(|s| s.kind == "use" && s.file_id == file_id && s.module == module);
+        for use_symbol in uses {
+            for (import, target) in table.resolve_use(use_symbol) {
+                if import.glob {
+                    let mut target_module = target.module.clone();
+                    target_module.push(target.name.clone());
+                    for s in table.lookup(&target_module, &name) {
+                        ranked.push((s, "glob_import", 3));
+                    }
+                } else if import.binding == name {
+                    ranked.push((target, "import", 2));
+                }
+            }
+        }
+    } else {
+        for s in table.resolve_path(&segments, &module) {
+            ranked.push((s, "path", 4));
+        }
+    }
+    let mut in_crate: Vec<&Symbol> = table
+        .symbols
+        .iter()
+        .filter(|s| s.name == name && DEFINING_KINDS.contains(&s.kind.as_str()))
+        .collect();
+    in_crate.sort_by_key(|s| (s.module.len(), s.file.clone()));
+    ranked.extend(in_crate.into_iter().map(|s| (s, "crate", 5)));
+    // Stable, so discovery order still breaks ties within a rank; dedup
+    // below then keeps each symbol's best reason
+    ranked.sort_by_key(|(_, _, rank)| *rank);
+
+    let mut seen = HashSet::new();
+    let mut candidates: Vec<serde_json::Value> = ranked
+        .into_iter()
+        .filter(|(s, _, _)| seen.insert(s.id.clone()))
+        .map(|(s, reason, rank)| {
+            json!({
+                "id": s.id,
+                "kind": s.kind,
+                "content": s.name,
+                "path": s.path,
+                "file_id": s.file_id,
+                "file": s.file,
+                "module": s.module.join("::"),
+                "reason": reason,
+                "rank": rank,
+            })
+        })
+        .collect();
+
+    // Definitions outside this crate, in case the symbol comes from elsewhere
+    let kinds = DEFINING_KINDS
+        .iter()
+        .map(|k| format!("'{k}'"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let external = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', id, 'kind', kind, 'content', content, 'path', path::text
+        ) ORDER BY path::text), '[]'::jsonb)
+        FROM (SELECT * FROM kerai.nodes
+              WHERE content = {} AND kind IN ({}) ORDER BY path::text LIMIT 50) n",
+        sql_text(&name),
+        kinds,
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| json!([]));
+    for mut row in external.as_array().cloned().unwrap_or_default() {
+        let id = row["id"].as_str().unwrap_or_default().to_string();
+        if seen.insert(id) {
+            row["reason"] = json!("external");
+            row["rank"] = json!(6);
+            candidates.push(row);
+        }
+    }
+
+    let best_rank = candidates.first().map(|c| c["rank"].clone());
+    let ambiguous = candidates
+        .iter()
+        .filter(|c| Some(&c["rank"]) == best_rank.as_ref())
+        .count()
+        > 1;
+
+    pgrx::JsonB(json!({
+        "symbol": symbol,
+        "from_file": file_id,
+        "definition": candidates.first(),
+        "candidates": candidates,
+        "ambiguous": ambiguous,
     }))
 }
 
@@ -168,6 +550,135 @@ fn tree(path_pattern: Option<&str>) -> pgrx::JsonB {
         .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])))
 }
 
+/// Outline a subtree as nested JSON, pruned by depth and filtered by kind.
+///
+/// - No path: start from top-level nodes. Otherwise start from the topmost nodes
+///   whose path is under `path_pattern`, or matches it when it has lquery
+///   wildcards (`*`, `|`, `!`) as in `tree`.
+/// - `max_depth` counts parent/child levels below those roots (0 = roots only).
+/// - `kinds` keeps only matching nodes; a kept node whose parent was filtered out
+///   is attached to its nearest kept ancestor.
+///
+/// Each node includes its unfiltered `child_count` and nested `children`.
+#[pg_extern]
+fn tree_limited(
+    path_pattern: Option<&str>,
+    max_depth: Option<i32>,
+    kinds: Option<Vec<String>>,
+) -> pgrx::JsonB {
+    let root_clause = match path_pattern {
+        None => "n.parent_id IS NULL".to_string(),
+        Some(pattern) => {
+            let escaped = sql_escape(pattern);
+            // Same lquery detection as `tree`
+            let has_lquery = pattern.contains('*') || pattern.contains('|') || pattern.contains('!');
+            let matches = if has_lquery {
+                format!("~ '{}'::lquery", escaped)
+            } else {
+                format!("<@ '{}'::ltree", escaped)
+            };
+            format!(
+                "n.path {0}
+                 AND NOT EXISTS (
+                     SELECT 1 FROM kerai.nodes p
+                     WHERE p.id = n.parent_id AND p.path {0}
+                 )",
+                matches,
+            )
+        }
+    };
+    let depth_clause = match max_depth {
+        Some(d) => format!("WHERE w.depth < {}", d.max(0)),
+        None => String::new(),
+    };
+
+    let sql = format!(
+        "WITH RECURSIVE walk AS (
+            SELECT n.id, n.parent_id, n.kind, n.content, n.path, 0 AS depth,
+                   ARRAY[n.position] AS sort_key
+            FROM kerai.nodes n
+            WHERE {}
+            UNION ALL
+            SELECT c.id, c.parent_id, c.kind, c.content, c.path, w.depth + 1,
+                   w.sort_key || c.position
+            FROM kerai.nodes c
+            JOIN walk w ON c.parent_id = w.id
+            {}
+        )
+        SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', w.id,
+            'parent_id', w.parent_id,
+            'kind', w.kind,
+            'content', w.content,
+            'path', w.path::text,
+            'child_count', (SELECT count(*) FROM kerai.nodes c WHERE c.parent_id = w.id)
+        ) ORDER BY w.sort_key, w.path::text), '[]'::jsonb)
+        FROM walk w",
+        root_clause, depth_clause,
+    );
+
+    let rows = Spi::get_one::<pgrx::JsonB>(&sql)
+        .unwrap()
+        .unwrap_or_else(|| pgrx::JsonB(json!([])));
+    let rows = rows.0.as_array().cloned().unwrap_or_default();
+
+    // Rows arrive in depth-first order, so every parent is seen before its children.
+    // `anchor` maps each visited node to its nearest kept ancestor-or-self.
+    let mut anchor: HashMap<String, Option<String>> = HashMap::new();
+    let mut children: HashMap<Option<String>, Vec<String>> = HashMap::new();
+    let mut nodes: HashMap<String, serde_json::Value> = HashMap::new();
+
+    for row in rows {
+        let id = row["id"].as_str().unwrap_or("").to_string();
+        let parent = row["parent_id"]
+            .as_str()
+            .and_then(|p| anchor.get(p).cloned())
+            .flatten();
+        let keep = match &kinds {
+            Some(k) => k.iter().any(|kind| row["kind"].as_str() == Some(kind.as_str())),
+            None => true,
+        };
+
+        if keep {
+            children.entry(parent).or_default().push(id.clone());
+            anchor.insert(id.clone(), Some(id.clone()));
+            nodes.insert(
+                id,
+                json!({
+                    "id": row["id"],
+                    "kind": row["kind"],
+                    "content": row["content"],
+                    "path": row["path"],
+                    "child_count": row["child_count"],
+                }),
+            );
+        } else {
+            anchor.insert(id, parent);
+        }
+    }
+
+    pgrx::JsonB(serde_json::Value::Array(nest_outline(None, &mut nodes, &children)))
+}
+
+/// Assemble the nested outline for `tree_limited` from its flat parent map.
+fn nest_outline(
+    parent: Option<String>,
+    nodes: &mut HashMap<String, serde_json::Value>,
+    children: &HashMap<Option<String>, Vec<String>>,
+) -> Vec<serde_json::Value> {
+    let Some(ids) = children.get(&parent) else {
+        return Vec::new();
+    };
+    ids.iter()
+        .filter_map(|id| {
+            let mut node = nodes.remove(id)?;
+            node["children"] =
+                serde_json::Value::Array(nest_outline(Some(id.clone()), nodes, children));
+            Some(node)
+        })
+        .collect()
+}
+
 /// Get direct children of a node, ordered by position.
 ///
 /// Each child includes its own `child_count`.
@@ -225,14 +736,449 @@ fn ancestors(node_id: pgrx::Uuid) -> pgrx::JsonB {
         .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])))
 }
 
+/// Find edges by relation and metadata containment (`metadata @> filter`).
+///
+/// Either argument may be NULL to skip that condition, e.g.
+/// `edges_where('documents', '{"placement": "above"}')`.
+///
+/// Returns JSON array of `{id, relation, metadata, source: {id, kind, content, path},
+/// target: {id, kind, content, path}}`.
+#[pg_extern]
+fn edges_where(relation: Option<&str>, meta_filter: Option<pgrx::JsonB>) -> pgrx::JsonB {
+    let mut conditions = Vec::new();
+    if let Some(rel) = relation {
+        conditions.push(format!("e.relation = '{}'", sql_escape(rel)));
+    }
+    if let Some(filter) = meta_filter {
+        conditions.push(format!(
+            "e.metadata @> '{}'::jsonb",
+            sql_escape(&filter.0.to_string()),
+        ));
+    }
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+
+    let sql = format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', e.id,
+            'relation', e.relation,
+            'metadata', e.metadata,
+            'source', jsonb_build_object(
+                'id', s.id, 'kind', s.kind, 'content', s.content, 'path', s.path::text
+            ),
+            'target', jsonb_build_object(
+                'id', t.id, 'kind', t.kind, 'content', t.content, 'path', t.path::text
+            )
+        ) ORDER BY e.relation, s.path::text, s.position), '[]'::jsonb)
+        FROM kerai.edges e
+        JOIN kerai.nodes s ON s.id = e.source_id
+        JOIN kerai.nodes t ON t.id = e.target_id
+        {}",
+        where_clause,
+    );
+
+    Spi::get_one::<pgrx::JsonB>(&sql)
+        .unwrap()
+        .unwrap_or_else(|| pgrx::JsonB(json!([])))
+}
+
+/// Immediate neighbors of a node across edges, the one-hop building block
+/// for custom traversals.
+///
+/// `relations` limits the edge relations followed (NULL or empty = any);
+/// `direction` is `out` (node is the source), `in` (node is the target), or
+/// `both` (default).
+///
+/// Returns JSON array of `{id, kind, content, path, relation, direction,
+/// edge_id, edge_metadata}`, one entry per connecting edge.
+#[pg_extern]
+fn neighbors(
+    node_id: pgrx::Uuid,
+    relations: default!(Option<Vec<String>>, "NULL"),
+    direction: default!(&str, "'both'"),
+) -> pgrx::JsonB {
+    neighbor_rows(&node_id.to_string(), &relations.unwrap_or_default(), direction)
+}
+
+/// Shared body of `neighbors`, also used per step by `traverse`.
+fn neighbor_rows(node_id: &str, relations: &[String], direction: &str) -> pgrx::JsonB {
+    let (outgoing, incoming) = match direction {
+        "out" => (true, false),
+        "in" => (false, true),
+        "both" => (true, true),
+        other => error!("Invalid direction '{}'. Must be one of: in, out, both", other),
+    };
+
+    let relation_clause = if relations.is_empty() {
+        String::new()
+    } else {
+        let list: Vec<String> = relations.iter().map(|r| sql_text(r)).collect();
+        format!("AND e.relation IN ({})", list.join(", "))
+    };
+
+    let mut arms = Vec::new();
+    if outgoing {
+        arms.push(format!(
+            "SELECT e.id AS edge_id, e.relation, e.metadata, e.target_id AS neighbor, 'out' AS direction
+             FROM kerai.edges e WHERE e.source_id = '{node_id}'::uuid {relation_clause}"
+        ));
+    }
+    if incoming {
+        arms.push(format!(
+            "SELECT e.id AS edge_id, e.relation, e.metadata, e.source_id AS neighbor, 'in' AS direction
+             FROM kerai.edges e WHERE e.target_id = '{node_id}'::uuid {relation_clause}"
+        ));
+    }
+
+    let sql = format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', n.id,
+            'kind', n.kind,
+            'content', n.content,
+            'path', n.path::text,
+            'relation', h.relation,
+            'direction', h.direction,
+            'edge_id', h.edge_id,
+            'edge_metadata', h.metadata
+        ) ORDER BY h.direction DESC, h.relation, n.path::text, n.position), '[]'::jsonb)
+        FROM ({}) h
+        JOIN kerai.nodes n ON n.id = h.neighbor",
+        arms.join(" UNION ALL "),
+    );
+
+    Spi::get_one::<pgrx::JsonB>(&sql)
+        .unwrap()
+        .unwrap_or_else(|| pgrx::JsonB(json!([])))
+}
+
+/// Wall-clock budget for traversals that can blow up on pathological graphs.
+///
+/// `statement_timeout` only applies to top-level statements, so multi-step
+/// traversals check the budget between steps instead and stop early with a
+/// partial result. `max_ms` falls back to the `config/query_max_ms`
+/// preference (default 5000).
+pub(crate) struct QueryBudget {
+    started: Instant,
+    pub(crate) max_ms: u64,
+}
+
+impl QueryBudget {
+    const DEFAULT_MAX_MS: u64 = 5_000;
+
+    pub(crate) fn load(max_ms: Option<i32>) -> Self {
+        let max_ms = match max_ms {
+            Some(ms) => ms.max(0) as u64,
+            None => Spi::get_one::<String>("SELECT kerai.get_preference('config', 'query_max_ms')")
+                .ok()
+                .flatten()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(Self::DEFAULT_MAX_MS),
+        };
+        Self {
+            started: Instant::now(),
+            max_ms,
+        }
+    }
+
+    pub(crate) fn exceeded(&self) -> bool {
+        self.started.elapsed() >= Duration::from_millis(self.max_ms)
+    }
+
+    /// Wrap `results` under `key` with `truncated`, `status` (`ok` or
+    /// `query_budget_exceeded`), `elapsed_ms`, and `max_ms`.
+    pub(crate) fn finish(
+        &self,
+        key: &str,
+        results: serde_json::Value,
+        truncated: bool,
+    ) -> pgrx::JsonB {
+        pgrx::JsonB(json!({
+            key: results,
+            "truncated": truncated,
+            "status": if truncated { "query_budget_exceeded" } else { "ok" },
+            "elapsed_ms": self.started.elapsed().as_millis() as u64,
+            "max_ms": self.max_ms,
+        }))
+    }
+}
+
+/// Nodes reachable from `start_id` by following outgoing `relation` edges,
+/// e.g. everything a function transitively calls over `calls`.
+///
+/// A single recursive CTE over `kerai.edges`, at most `max_depth` hops deep
+/// (default 10). A path never revisits a node, so cycles such as mutual or
+/// self recursion end instead of looping. The start node itself isn't listed.
+///
+/// Returns a JSON array of `{id, kind, content, path, depth, edge_path}`, one
+/// per reachable node at its shortest depth, ordered by depth. `edge_path` is
+/// the edge ids from `start_id` to the node.
+#[pg_extern]
+fn reachable(start_id: pgrx::Uuid, relation: &str, max_depth: default!(i32, 10)) -> pgrx::JsonB {
+    let sql = format!(
+        "WITH RECURSIVE walk(node_id, depth, edge_path, node_path) AS (
+            SELECT e.target_id, 1, ARRAY[e.id], ARRAY['{start}'::uuid, e.target_id]
+            FROM kerai.edges e
+            WHERE e.source_id = '{start}'::uuid AND e.relation = {relation}
+              AND e.target_id <> '{start}'::uuid
+          UNION ALL
+            SELECT e.target_id, w.depth + 1, w.edge_path || e.id, w.node_path || e.target_id
+            FROM walk w
+            JOIN kerai.edges e ON e.source_id = w.node_id AND e.relation = {relation}
+            WHERE w.depth < {max_depth} AND e.target_id <> ALL(w.node_path)
+        ),
+        shortest AS (
+            SELECT DISTINCT ON (node_id) node_id, depth, edge_path
+            FROM walk
+            ORDER BY node_id, depth, edge_path
+        )
+        SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', n.id,
+            'kind', n.kind,
+            'content', n.content,
+            'path', n.path::text,
+            'depth', s.depth,
+            'edge_path', to_jsonb(s.edge_path)
+        ) ORDER BY s.depth, n.content, n.id), '[]'::jsonb)
+        FROM shortest s
+        JOIN kerai.nodes n ON n.id = s.node_id",
+        start = start_id,
+        relation = sql_text(relation),
+        max_depth = max_depth.max(1),
+    );
+
+    Spi::get_one::<pgrx::JsonB>(&sql)
+        .unwrap()
+        .unwrap_or_else(|| pgrx::JsonB(json!([])))
+}
+
+/// Every simple cycle in the directed graph of `relation` edges, e.g.
+/// mutually recursive functions over `calls`.
+///
+/// A recursive CTE walks paths that only visit nodes with a larger id than
+/// their first node, so each cycle is found once, from its smallest id,
+/// rather than once per rotation. Cycles longer than `max_depth` edges
+/// (default 10) are not followed. At most `max_cycles` (default 100) are
+/// returned; the walk stops as soon as that many are found. The query runs
+/// under a `QueryBudget` of `max_ms`, as for `traverse`; if it runs out no
+/// cycles are returned and `status` is `query_budget_exceeded`.
+///
+/// Returns `{relation, cycles, truncated, status, elapsed_ms, max_ms}` where
+/// each cycle is an array of node ids starting from its smallest id, in edge
+/// order.
+#[pg_extern]
+fn find_cycles(
+    relation: &str,
+    max_cycles: default!(i32, 100),
+    max_depth: default!(i32, 10),
+    max_ms: default!(Option<i32>, "NULL"),
+) -> pgrx::JsonB {
+    let budget = QueryBudget::load(max_ms);
+    let max_cycles = max_cycles.max(1) as usize;
+    let sql = format!(
+        "WITH RECURSIVE walk(start_id, node_id, node_path, closed, depth) AS (
+            SELECT e.source_id, e.target_id, ARRAY[e.source_id], e.target_id = e.source_id, 1
+            FROM kerai.edges e
+            WHERE e.relation = {relation} AND e.target_id >= e.source_id
+          UNION ALL
+            SELECT w.start_id, e.target_id, w.node_path || w.node_id, e.target_id = w.start_id,
+                   w.depth + 1
+            FROM walk w
+            JOIN kerai.edges e ON e.source_id = w.node_id AND e.relation = {relation}
+            WHERE NOT w.closed
+              AND w.depth < {max_depth}
+              AND (e.target_id = w.start_id
+                   OR (e.target_id > w.start_id
+                       AND e.target_id <> ALL(w.node_path || w.node_id)))
+        )
+        SELECT COALESCE(jsonb_agg(to_jsonb(node_path)), '[]'::jsonb)
+        FROM (SELECT node_path FROM walk WHERE closed LIMIT {}) found",
+        max_cycles + 1,
+        relation = sql_text(relation),
+        max_depth = max_depth.max(1),
+    );
+
+    let found =
+        with_statement_timeout(budget.max_ms, || Spi::get_one::<pgrx::JsonB>(&sql).unwrap());
+    let timed_out = found.is_none();
+    let mut cycles = found.flatten().map(|j| j.0).unwrap_or_else(|| json!([]));
+    let over_limit = cycles.as_array().is_some_and(|c| c.len() > max_cycles);
+    if let Some(list) = cycles.as_array_mut() {
+        list.truncate(max_cycles);
+    }
+
+    let mut result = budget.finish("cycles", cycles, timed_out).0;
+    result["relation"] = json!(relation);
+    result["truncated"] = json!(timed_out || over_limit);
+    pgrx::JsonB(result)
+}
+
+/// Breadth-first traversal from a node along edges, built on `neighbors`.
+///
+/// `relations` and `direction` are as for `neighbors`; `max_depth` bounds
+/// the hop count (NULL = unbounded). The walk runs under a `QueryBudget`
+/// of `max_ms` (0 = no expansion at all); when it runs out, the nodes
+/// reached so far are returned with `truncated: true`.
+///
+/// Returns `{nodes, truncated, status, elapsed_ms, max_ms}` where each node
+/// is `{id, kind, content, path, depth, relation, direction, from}` and the
+/// start node has depth 0.
+#[pg_extern]
+fn traverse(
+    node_id: pgrx::Uuid,
+    relations: default!(Option<Vec<String>>, "NULL"),
+    direction: default!(&str, "'both'"),
+    max_depth: default!(Option<i32>, "NULL"),
+    max_ms: default!(Option<i32>, "NULL"),
+) -> pgrx::JsonB {
+    if !matches!(direction, "in" | "out" | "both") {
+        error!("Invalid direction '{}'. Must be one of: in, out, both", direction);
+    }
+    let budget = QueryBudget::load(max_ms);
+
+    let start = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'id', n.id, 'kind', n.kind, 'content', n.content, 'path', n.path::text
+        ) FROM kerai.nodes n WHERE n.id = '{node_id}'::uuid"
+    ))
+    .unwrap()
+    .unwrap_or_else(|| error!("Node not found: {}", node_id))
+    .0;
+
+    let mut visited: HashSet<String> = HashSet::new();
+    visited.insert(node_id.to_string());
+    let mut nodes = vec![json!({
+        "id": start["id"],
+        "kind": start["kind"],
+        "content": start["content"],
+        "path": start["path"],
+        "depth": 0,
+        "relation": null,
+        "direction": null,
+        "from": null,
+    })];
+
+    let relations = relations.unwrap_or_default();
+    let mut frontier: VecDeque<(String, i32)> = VecDeque::from([(node_id.to_string(), 0)]);
+    let mut truncated = false;
+
+    while let Some((id, depth)) = frontier.pop_front() {
+        if max_depth.is_some_and(|d| depth >= d) {
+            continue;
+        }
+        if budget.exceeded() {
+            truncated = true;
+            break;
+        }
+        let hops = neighbor_rows(&id, &relations, direction).0;
+        for hop in hops.as_array().into_iter().flatten() {
+            let Some(next) = hop["id"].as_str() else {
+                continue;
+            };
+            if !visited.insert(next.to_string()) {
+                continue;
+            }
+            nodes.push(json!({
+                "id": hop["id"],
+                "kind": hop["kind"],
+                "content": hop["content"],
+                "path": hop["path"],
+                "depth": depth + 1,
+                "relation": hop["relation"],
+                "direction": hop["direction"],
+                "from": &id,
+            }));
+            frontier.push_back((next.to_string(), depth + 1));
+        }
+    }
+
+    budget.finish("nodes", serde_json::Value::Array(nodes), truncated)
+}
+
+/// Functions reachable from `node_id` along `calls` edges, hottest first.
+///
+/// Each edge's `count` is how many call sites it stands for; `weight` is the
+/// product of counts from the start, so callees reached through repeated
+/// calls rank above one-off ones. `max_depth` bounds the hop count and the
+/// walk runs under a `QueryBudget` of `max_ms`, as for `traverse`.
+///
+/// Returns `{calls, truncated, status, elapsed_ms, max_ms}` where each call
+/// is `{id, content, path, depth, from, count, weight}`.
+#[pg_extern]
+fn call_graph(
+    node_id: pgrx::Uuid,
+    max_depth: default!(i32, 3),
+    max_ms: default!(Option<i32>, "NULL"),
+) -> pgrx::JsonB {
+    let budget = QueryBudget::load(max_ms);
+    let exists = Spi::get_one::<bool>(&format!(
+        "SELECT EXISTS(SELECT 1 FROM kerai.nodes WHERE id = '{node_id}'::uuid)"
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if !exists {
+        error!("Node not found: {}", node_id);
+    }
+
+    let relations = vec!["calls".to_string()];
+    let mut visited: HashSet<String> = HashSet::from([node_id.to_string()]);
+    let mut frontier: VecDeque<(String, i32, i64)> = VecDeque::from([(node_id.to_string(), 0, 1)]);
+    let mut calls = Vec::new();
+    let mut truncated = false;
+
+    while let Some((id, depth, weight)) = frontier.pop_front() {
+        if depth >= max_depth {
+            continue;
+        }
+        if budget.exceeded() {
+            truncated = true;
+            break;
+        }
+        let hops = neighbor_rows(&id, &relations, "out").0;
+        for hop in hops.as_array().into_iter().flatten() {
+            let Some(next) = hop["id"].as_str() else {
+                continue;
+            };
+            if !visited.insert(next.to_string()) {
+                continue;
+            }
+            let count = hop["edge_metadata"]["count"].as_i64().unwrap_or(1);
+            calls.push(json!({
+                "id": hop["id"],
+                "content": hop["content"],
+                "path": hop["path"],
+                "depth": depth + 1,
+                "from": &id,
+                "count": count,
+                "weight": weight * count,
+            }));
+            frontier.push_back((next.to_string(), depth + 1, weight * count));
+        }
+    }
+
+    calls.sort_by_key(|c| std::cmp::Reverse(c["weight"].as_i64().unwrap_or(0)));
+    budget.finish("calls", serde_json::Value::Array(calls), truncated)
+}
+
 /// Full-text search using PostgreSQL tsvector/tsquery with ranking.
 ///
 /// Unlike `find` which uses ILIKE pattern matching, `search` uses proper
 /// FTS with `plainto_tsquery` and `ts_rank` for relevance-ranked results.
 ///
-/// Returns JSON array of `{id, kind, content, path, rank, metadata}`.
+/// Returns JSON array of `{id, kind, content, path, rank, snippet, metadata}`,
+/// highest `rank` first. `snippet` is the `ts_headline` excerpt of `content`
+/// around the matched words, which are wrapped in `<b>`/`</b>`.
+/// With `highlight`, each result also carries `highlights: [{start, end, text}]`
+/// for the words `ts_headline` matched in `content` (character offsets).
 #[pg_extern]
-fn search(query: &str, kind_filter: Option<&str>, limit: Option<i32>) -> pgrx::JsonB {
+fn search(
+    query: &str,
+    kind_filter: Option<&str>,
+    limit: Option<i32>,
+    highlight: default!(bool, false),
+) -> pgrx::JsonB {
     let limit_val = limit.unwrap_or(50).max(1).min(1000);
     let escaped_query = sql_escape(query);
 
@@ -241,6 +1187,17 @@ fn search(query: &str, kind_filter: Option<&str>, limit: Option<i32>) -> pgrx::J
         None => String::new(),
     };
 
+    // Mark the whole content with control characters so offsets can be recovered
+    let headline_col = if highlight {
+        format!(
+            "|| jsonb_build_object('{}', ts_headline('english', COALESCE(n.content, ''), q.query, \
+             'StartSel=' || chr({}) || ', StopSel=' || chr({}) || ', HighlightAll=true'))",
+            HEADLINE_KEY, HL_START as u32, HL_STOP as u32,
+        )
+    } else {
+        String::new()
+    };
+
     let sql = format!(
         "SELECT COALESCE(jsonb_agg(r ORDER BY rank DESC), '[]'::jsonb) FROM (
             SELECT jsonb_build_object(
@@ -248,22 +1205,166 @@ fn search(query: &str, kind_filter: Option<&str>, limit: Option<i32>) -> pgrx::J
                 'kind', n.kind,
                 'content', n.content,
                 'path', n.path::text,
-                'rank', ts_rank(to_tsvector('english', COALESCE(n.content, '')), q.query),
+                'rank', ts_rank(n.content_tsv, q.query),
+                'snippet', ts_headline('english', COALESCE(n.content, ''), q.query,
+                                       'MaxWords=20, MinWords=5'),
                 'metadata', n.metadata
-            ) AS r,
-            ts_rank(to_tsvector('english', COALESCE(n.content, '')), q.query) AS rank
+            ) {} AS r,
+            ts_rank(n.content_tsv, q.query) AS rank
             FROM kerai.nodes n,
                  plainto_tsquery('english', '{}') q(query)
-            WHERE to_tsvector('english', COALESCE(n.content, '')) @@ q.query {}
+            WHERE n.content_tsv @@ q.query {}
             ORDER BY rank DESC
             LIMIT {}
         ) sub",
-        escaped_query, kind_clause, limit_val,
+        headline_col, escaped_query, kind_clause, limit_val,
     );
 
-    Spi::get_one::<pgrx::JsonB>(&sql)
+    let mut result = Spi::get_one::<pgrx::JsonB>(&sql)
         .unwrap()
-        .unwrap_or_else(|| pgrx::JsonB(json!([])))
+        .unwrap_or_else(|| pgrx::JsonB(json!([])));
+
+    if highlight {
+        for row in result.0.as_array_mut().into_iter().flatten() {
+            let marked = row
+                .as_object_mut()
+                .and_then(|o| o.remove(HEADLINE_KEY))
+                .and_then(|v| v.as_str().map(String::from))
+                .unwrap_or_default();
+            row["highlights"] = json!(marked_highlights(&marked));
+        }
+    }
+    result
+}
+
+/// Recompute the full-text vectors of nodes under `scope` (an ltree path;
+/// NULL for every node), including pathless descendants such as comments.
+///
+/// `content_tsv` is a generated column, so inserts and updates keep it current
+/// on their own; this is for repairing rows after a text search configuration
+/// change or a bulk load that bypassed normal writes.
+///
+/// Returns `{scope, reindexed}`.
+#[pg_extern]
+fn reindex_fts(scope: default!(Option<&str>, "NULL")) -> pgrx::JsonB {
+    // Rewriting a column recomputes stored generated columns on the row
+    let sql = match scope {
+        Some(s) => format!(
+            "WITH RECURSIVE subtree AS (
+                SELECT id FROM kerai.nodes WHERE path <@ {}
+                UNION
+                SELECT n.id FROM kerai.nodes n JOIN subtree t ON n.parent_id = t.id
+            ),
+            touched AS (
+                UPDATE kerai.nodes SET content = content
+                WHERE id IN (SELECT id FROM subtree)
+                RETURNING 1
+            )
+            SELECT count(*) FROM touched",
+            sql_ltree(s),
+        ),
+        None => "WITH touched AS (
+                UPDATE kerai.nodes SET content = content RETURNING 1
+            )
+            SELECT count(*) FROM touched"
+            .to_string(),
+    };
+
+    let reindexed = Spi::get_one::<i64>(&sql).unwrap().unwrap_or(0);
+    pgrx::JsonB(json!({
+        "scope": scope,
+        "reindexed": reindexed,
+    }))
+}
+
+/// Temporary result key holding the marked-up `ts_headline` output.
+const HEADLINE_KEY: &str = "_headline";
+/// Selection markers passed to `ts_headline`; control characters never
+/// appear in indexed source text.
+const HL_START: char = '\u{2}';
+const HL_STOP: char = '\u{3}';
+
+/// Recover `{start, end, text}` spans (character offsets into the unmarked
+/// text) from a `ts_headline` result delimited by `HL_START`/`HL_STOP`.
+fn marked_highlights(marked: &str) -> Vec<serde_json::Value> {
+    let mut spans = Vec::new();
+    let mut offset = 0usize;
+    let mut open: Option<(usize, String)> = None;
+    for ch in marked.chars() {
+        match ch {
+            HL_START => open = Some((offset, String::new())),
+            HL_STOP => {
+                if let Some((start, text)) = open.take() {
+                    spans.push(json!({"start": start, "end": offset, "text": text}));
+                }
+            }
+            _ => {
+                if let Some((_, ref mut text)) = open {
+                    text.push(ch);
+                }
+                offset += 1;
+            }
+        }
+    }
+    spans
+}
+
+/// Split an ILIKE pattern into its literal segments (between `%` wildcards),
+/// each compiled as a case-insensitive regex with `_` matching any character.
+fn like_segments(pattern: &str) -> Vec<regex::Regex> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut chars = pattern.chars();
+    let mut flush = |current: &mut String| {
+        if !current.is_empty() {
+            if let Ok(re) = regex::Regex::new(&format!("(?i){}", current)) {
+                segments.push(re);
+            }
+            current.clear();
+        }
+    };
+    while let Some(ch) = chars.next() {
+        match ch {
+            '%' => flush(&mut current),
+            '_' => current.push('.'),
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    current.push_str(&regex::escape(&next.to_string()));
+                }
+            }
+            _ => current.push_str(&regex::escape(&ch.to_string())),
+        }
+    }
+    flush(&mut current);
+    segments
+}
+
+/// Non-overlapping matches of the pattern's literal segments in `content`,
+/// as `{start, end, text}` character offsets in order of appearance.
+fn like_highlights(content: &str, segments: &[regex::Regex]) -> Vec<serde_json::Value> {
+    let mut ranges: Vec<(usize, usize)> = segments
+        .iter()
+        .flat_map(|re| re.find_iter(content).map(|m| (m.start(), m.end())))
+        .filter(|(s, e)| e > s)
+        .collect();
+    ranges.sort();
+
+    let mut spans = Vec::new();
+    let mut last_end = 0usize;
+    for (start, end) in ranges {
+        if start < last_end {
+            continue;
+        }
+        let char_start = content[..start].chars().count();
+        let text = &content[start..end];
+        spans.push(json!({
+            "start": char_start,
+            "end": char_start + text.chars().count(),
+            "text": text,
+        }));
+        last_end = end;
+    }
+    spans
 }
 
 /// Context-aware search combining FTS with perspective-weighted ranking.
@@ -319,9 +1420,9 @@ fn context_search(
 
     // When no agent join, reference pw columns directly as NULLs
     let combined_expr = if agent_join.is_empty() {
-        "ts_rank(to_tsvector('english', COALESCE(n.content, '')), q.query) AS combined_score"
+        "ts_rank(n.content_tsv, q.query) AS combined_score"
     } else {
-        "ts_rank(to_tsvector('english', COALESCE(n.content, '')), q.query) * (1.0 + COALESCE(pw.avg_weight, 0.0)) AS combined_score"
+        "ts_rank(n.content_tsv, q.query) * (1.0 + COALESCE(pw.avg_weight, 0.0)) AS combined_score"
     };
 
     let sql = format!(
@@ -330,7 +1431,7 @@ fn context_search(
             'kind', n.kind,
             'content', n.content,
             'path', n.path::text,
-            'fts_rank', ts_rank(to_tsvector('english', COALESCE(n.content, '')), q.query),
+            'fts_rank', ts_rank(n.content_tsv, q.query),
             'perspective_weight', sub.perspective_weight,
             'combined_score', sub.combined_score,
             'agents', sub.agents
@@ -342,7 +1443,7 @@ fn context_search(
             FROM kerai.nodes n,
                  plainto_tsquery('english', '{escaped_query}') q(query)
             {agent_join}
-            WHERE to_tsvector('english', COALESCE(n.content, '')) @@ q.query
+            WHERE n.content_tsv @@ q.query
             ORDER BY combined_score DESC
             LIMIT {limit_val}
         ) sub
@@ -354,3 +1455,430 @@ fn context_search(
         .unwrap()
         .unwrap_or_else(|| pgrx::JsonB(json!([])))
 }
+
+/// Default `min_lines` for `find_duplicates`; shorter matches are mostly
+/// one-line accessors and trivial impls.
+const DUPLICATE_MIN_LINES: i32 = 5;
+
+/// Find groups of nodes with identical (whitespace-normalized) source text.
+///
+/// Hashes `metadata.source` (falling back to `content`) and reports groups of
+/// nodes sharing a hash across two or more files, most frequent first.
+/// Nodes inside another reported node are left out, so a duplicated impl is
+/// one group rather than one per method. Line counts come from
+/// `start_line`/`end_line` metadata when present, otherwise the text.
+///
+/// Returns JSON array of `{hash, kind, lines, count, nodes: [{id, content, path, file}]}`.
+#[pg_extern]
+fn find_duplicates(kind: Option<&str>, min_lines: Option<i32>) -> pgrx::JsonB {
+    let min_lines_val = min_lines.unwrap_or(DUPLICATE_MIN_LINES).max(1);
+
+    let kind_clause = match kind {
+        Some(k) => format!("AND n.kind = '{}'", sql_escape(k)),
+        None => String::new(),
+    };
+
+    let sql = format!(
+        "WITH RECURSIVE cand AS (
+            SELECT n.id, n.kind, n.content, n.path,
+                   md5(regexp_replace(btrim(COALESCE(n.metadata->>'source', n.content)), '\\s+', ' ', 'g')) AS h,
+                   COALESCE(
+                       (n.metadata->>'end_line')::int - (n.metadata->>'start_line')::int + 1,
+                       array_length(string_to_array(COALESCE(n.metadata->>'source', n.content), E'\\n'), 1)
+                   ) AS lines
+            FROM kerai.nodes n
+            WHERE COALESCE(n.metadata->>'source', n.content) IS NOT NULL
+              AND n.kind NOT IN ('file', 'document', 'crate') {kind_clause}
+        ),
+        dups AS (
+            SELECT * FROM cand
+            WHERE lines >= {min_lines_val}
+              AND h IN (
+                  SELECT h FROM cand WHERE lines >= {min_lines_val}
+                  GROUP BY h HAVING count(*) >= 2
+              )
+        ),
+        up AS (
+            SELECT d.id AS node_id, n.id, n.kind, n.content, n.parent_id, 0 AS depth
+            FROM dups d JOIN kerai.nodes n ON n.id = d.id
+            UNION ALL
+            SELECT up.node_id, p.id, p.kind, p.content, p.parent_id, up.depth + 1
+            FROM up JOIN kerai.nodes p ON p.id = up.parent_id
+            WHERE up.kind NOT IN ('file', 'document')
+        ),
+        files AS (
+            SELECT node_id, content AS file FROM up WHERE kind IN ('file', 'document')
+        ),
+        nested AS (
+            SELECT DISTINCT up.node_id FROM up
+            JOIN dups d ON d.id = up.id
+            WHERE up.depth > 0
+        ),
+        groups AS (
+            SELECT d.h, min(d.kind) AS kind, max(d.lines) AS lines, count(*) AS cnt,
+                   jsonb_agg(jsonb_build_object(
+                       'id', d.id,
+                       'content', d.content,
+                       'path', d.path::text,
+                       'file', f.file
+                   ) ORDER BY f.file, d.path::text) AS nodes
+            FROM dups d
+            LEFT JOIN files f ON f.node_id = d.id
+            WHERE d.id NOT IN (SELECT node_id FROM nested)
+            GROUP BY d.h
+            HAVING count(*) >= 2 AND count(DISTINCT f.file) >= 2
+        )
+        SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'hash', h,
+            'kind', kind,
+            'lines', lines,
+            'count', cnt,
+            'nodes', nodes
+        ) ORDER BY cnt DESC, lines DESC, h), '[]'::jsonb)
+        FROM groups",
+    );
+
+    Spi::get_one::<pgrx::JsonB>(&sql)
+        .unwrap()
+        .unwrap_or_else(|| pgrx::JsonB(json!([])))
+}
+
+/// List extracted doctests with their host item, optionally under a path scope.
+///
+/// Returns JSON array of `{id, code, attributes, line_count, path, host_id, host_kind, host_name}`.
+#[pg_extern]
+fn list_doctests(scope: Option<&str>) -> pgrx::JsonB {
+    let scope_clause = match scope {
+        Some(s) => format!("AND d.path <@ '{}'::ltree", sql_escape(s)),
+        None => String::new(),
+    };
+
+    let sql = format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', d.id,
+            'code', d.content,
+            'attributes', d.metadata->'attributes',
+            'line_count', d.metadata->'line_count',
+            'path', d.path::text,
+            'host_id', h.id,
+            'host_kind', h.kind,
+            'host_name', h.content
+        ) ORDER BY d.path::text, d.position), '[]'::jsonb)
+        FROM kerai.nodes d
+        LEFT JOIN kerai.nodes h ON h.id = d.parent_id
+        WHERE d.kind = 'doctest' {}",
+        scope_clause,
+    );
+
+    Spi::get_one::<pgrx::JsonB>(&sql)
+        .unwrap()
+        .unwrap_or_else(|| pgrx::JsonB(json!([])))
+}
+
+/// Default node cap for `export_dot`; larger graphs are truncated with a warning.
+const DOT_MAX_NODES: i32 = 500;
+
+/// Longest node label `export_dot` emits before truncating with an ellipsis.
+const DOT_LABEL_CHARS: usize = 40;
+
+/// Export nodes and edges under a scope as a Graphviz DOT digraph.
+///
+/// With `relations`, only edges of those relations are drawn and only their
+/// endpoints become nodes; without, every node under the scope is drawn along
+/// with all edges between them. Both edge endpoints must lie under the scope.
+/// At most `max_nodes` nodes (default 500) are emitted, in path order.
+///
+/// `kerai postgres query "SELECT kerai.export_dot('pkg.auth', ARRAY['calls'])" | dot -Tsvg`
+#[pg_extern]
+fn export_dot(
+    scope: &str,
+    relations: default!(Option<Vec<String>>, "NULL"),
+    max_nodes: default!(Option<i32>, "NULL"),
+) -> String {
+    let cap = max_nodes.unwrap_or(DOT_MAX_NODES);
+    if cap <= 0 {
+        error!("max_nodes must be positive, got {}", cap);
+    }
+    let scope_lit = sql_ltree(scope);
+
+    let relations: Vec<String> = relations.unwrap_or_default();
+    let relation_clause = if relations.is_empty() {
+        String::new()
+    } else {
+        let list: Vec<String> = relations.iter().map(|r| sql_text(r)).collect();
+        format!("AND e.relation IN ({})", list.join(", "))
+    };
+    let edges_cte = format!(
+        "sel_edges AS (
+            SELECT e.source_id, e.target_id, e.relation
+            FROM kerai.edges e
+            JOIN kerai.nodes s ON s.id = e.source_id
+            JOIN kerai.nodes t ON t.id = e.target_id
+            WHERE s.path <@ {scope} AND t.path <@ {scope} {rel}
+        )",
+        scope = scope_lit,
+        rel = relation_clause,
+    );
+    let candidates = if relations.is_empty() {
+        format!("SELECT n.* FROM kerai.nodes n WHERE n.path <@ {}", scope_lit)
+    } else {
+        "SELECT n.* FROM kerai.nodes n WHERE n.id IN (
+            SELECT source_id FROM sel_edges UNION SELECT target_id FROM sel_edges
+        )"
+        .to_string()
+    };
+
+    let total = Spi::get_one::<i64>(&format!(
+        "WITH {}, candidates AS ({}) SELECT count(*) FROM candidates",
+        edges_cte, candidates,
+    ))
+    .unwrap()
+    .unwrap_or(0);
+    if total > cap as i64 {
+        warning!(
+            "export_dot: {} nodes under '{}' exceed max_nodes {}; graph truncated",
+            total,
+            scope,
+            cap
+        );
+    }
+
+    let nodes = Spi::get_one::<pgrx::JsonB>(&format!(
+        "WITH {}, candidates AS ({}),
+         kept AS (SELECT * FROM candidates ORDER BY path, position LIMIT {})
+         SELECT COALESCE(jsonb_agg(jsonb_build_object(
+             'id', id, 'kind', kind, 'content', content
+         ) ORDER BY path, position), '[]'::jsonb)
+         FROM kept",
+        edges_cte, candidates, cap,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(json!([])));
+
+    // Only edges between kept nodes, so a truncated graph doesn't load the whole scope's edges
+    let kept: Vec<&str> = nodes
+        .0
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|n| n["id"].as_str())
+        .collect();
+    let kept_lit = format!("'{{{}}}'::uuid[]", kept.join(","));
+    let edges = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+             'source', e.source_id, 'target', e.target_id, 'relation', e.relation
+         ) ORDER BY e.relation, e.source_id, e.target_id), '[]'::jsonb)
+         FROM kerai.edges e
+         WHERE e.source_id = ANY({kept}) AND e.target_id = ANY({kept}) {rel}",
+        kept = kept_lit,
+        rel = relation_clause,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(json!([])));
+
+    render_dot(nodes.0.as_array(), edges.0.as_array())
+}
+
+/// Serialize node and edge rows to DOT, dropping edges whose endpoints were capped out.
+fn render_dot(
+    nodes: Option<&Vec<serde_json::Value>>,
+    edges: Option<&Vec<serde_json::Value>>,
+) -> String {
+    let mut out = String::from(
+        "digraph kerai {\n  rankdir=LR;\n  node [shape=box, fontname=\"monospace\"];\n",
+    );
+    let mut kept = HashSet::new();
+
+    for node in nodes.into_iter().flatten() {
+        let id = node["id"].as_str().unwrap_or_default();
+        let kind = node["kind"].as_str().unwrap_or_default();
+        let label = match node["content"].as_str() {
+            Some(content) if !content.is_empty() => {
+                format!("{}: {}", kind, truncate_label(content))
+            }
+            _ => kind.to_string(),
+        };
+        out.push_str(&format!("  \"{}\" [label=\"{}\"];\n", id, dot_escape(&label)));
+        kept.insert(id.to_string());
+    }
+
+    for edge in edges.into_iter().flatten() {
+        let source = edge["source"].as_str().unwrap_or_default();
+        let target = edge["target"].as_str().unwrap_or_default();
+        if !kept.contains(source) || !kept.contains(target) {
+            continue;
+        }
+        let relation = edge["relation"].as_str().unwrap_or_default();
+        out.push_str(&format!(
+            "  \"{}\" -> \"{}\" [label=\"{}\"];\n",
+            source,
+            target,
+            dot_escape(relation),
+        ));
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// First line of `content`, cut to `DOT_LABEL_CHARS` characters.
+fn truncate_label(content: &str) -> String {
+    let line = content.lines().next().unwrap_or_default();
+    if line.chars().count() > DOT_LABEL_CHARS || content.contains('\n') {
+        let cut: String = line.chars().take(DOT_LABEL_CHARS).collect();
+        format!("{}…", cut)
+    } else {
+        line.to_string()
+    }
+}
+
+/// Escape a string for a double-quoted DOT ID.
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Show the plan for a read-only kerai query and flag sequential scans on
+/// kerai tables, noting which filtered columns have no index to use.
+///
+/// Only single `SELECT` (or `WITH ... SELECT`) statements are accepted. The
+/// plan is produced in a subtransaction with `transaction_read_only` on, which
+/// is always rolled back. With `analyze` the query is actually run
+/// (`EXPLAIN ANALYZE`) so the plan carries real row counts and timings; a
+/// write it attempts (say, through a function call) fails instead of landing.
+///
+/// Returns `{plan, analyze, total_cost, execution_time_ms, seq_scans}`, where
+/// each seq scan is `{relation, alias, estimated_rows, filter, columns,
+/// unindexed_columns, advice}`.
+#[pg_extern]
+fn explain(sql: &str, analyze: default!(bool, false)) -> pgrx::JsonB {
+    let statement = read_only_statement(sql);
+    let options = if analyze { "FORMAT JSON, ANALYZE" } else { "FORMAT JSON" };
+    let output = subxact::rolled_back_subtransaction(|| {
+        Spi::run("SET LOCAL transaction_read_only = on").unwrap();
+        Spi::get_one::<pgrx::Json>(&format!("EXPLAIN ({}) {}", options, statement))
+    })
+    .unwrap_or_else(|e| error!("Failed to explain query: {}", subxact::caught_message(e)))
+    .unwrap_or_else(|e| error!("Failed to explain query: {}", e))
+    .unwrap_or_else(|| error!("EXPLAIN returned no plan"))
+    .0;
+    let explained = output.get(0).cloned().unwrap_or(serde_json::Value::Null);
+
+    let mut scans = Vec::new();
+    collect_seq_scans(&explained["Plan"], &mut scans);
+    let mut indexed: HashMap<String, Option<HashSet<String>>> = HashMap::new();
+    let seq_scans: Vec<serde_json::Value> = scans
+        .into_iter()
+        .filter_map(|scan| {
+            let relation = scan["Relation Name"].as_str()?.to_string();
+            let leading = indexed
+                .entry(relation.clone())
+                .or_insert_with(|| indexed_columns(&relation))
+                .as_ref()?;
+            let filter = scan["Filter"].as_str();
+            let columns = filter.map(filter_columns).unwrap_or_default();
+            let unindexed: Vec<&String> = columns.iter().filter(|c| !leading.contains(*c)).collect();
+            let advice = match (filter, unindexed.first()) {
+                (None, _) => format!("Reads every row of kerai.{}", relation),
+                (Some(_), Some(column)) => format!(
+                    "No index leads with {}; consider CREATE INDEX ON kerai.{} ({})",
+                    unindexed.iter().map(|c| c.as_str()).collect::<Vec<_>>().join(", "),
+                    relation,
+                    column,
+                ),
+                (Some(_), None) => format!(
+                    "Filtered columns are indexed but scanned sequentially; kerai.{} may be \
+                     small or its statistics stale (ANALYZE kerai.{})",
+                    relation, relation,
+                ),
+            };
+            Some(json!({
+                "relation": relation,
+                "alias": scan["Alias"],
+                "estimated_rows": scan["Plan Rows"],
+                "filter": filter,
+                "columns": columns,
+                "unindexed_columns": unindexed,
+                "advice": advice,
+            }))
+        })
+        .collect();
+
+    pgrx::JsonB(json!({
+        "plan": explained["Plan"],
+        "analyze": analyze,
+        "total_cost": explained["Plan"]["Total Cost"],
+        "execution_time_ms": explained.get("Execution Time"),
+        "seq_scans": seq_scans,
+    }))
+}
+
+/// Validate that `sql` is a single read-only SELECT and return it without a
+/// trailing semicolon. Keywords inside string literals are ignored.
+fn read_only_statement(sql: &str) -> &str {
+    let statement = sql.trim().trim_end_matches(';').trim_end();
+    let literals = regex::Regex::new(r"'(?:[^']|'')*'").unwrap();
+    let code = literals.replace_all(statement, "''").to_ascii_lowercase();
+
+    if code.contains(';') {
+        error!("explain accepts a single statement");
+    }
+    let first = code.split_whitespace().next().unwrap_or("");
+    if first != "select" && first != "with" {
+        error!("explain only accepts read-only SELECT statements, got '{}'", first);
+    }
+    let writes = regex::Regex::new(r"\b(insert|update|delete|merge|into|truncate)\b").unwrap();
+    if let Some(m) = writes.find(&code) {
+        error!("explain only accepts read-only SELECT statements; found '{}'", m.as_str());
+    }
+    statement
+}
+
+/// Seq Scan nodes anywhere in a plan tree.
+fn collect_seq_scans(plan: &serde_json::Value, out: &mut Vec<serde_json::Value>) {
+    if plan["Node Type"] == "Seq Scan" {
+        out.push(plan.clone());
+    }
+    for child in plan["Plans"].as_array().into_iter().flatten() {
+        collect_seq_scans(child, out);
+    }
+}
+
+/// Leading columns of the indexes on a kerai table, or None when the
+/// relation is not a kerai table.
+fn indexed_columns(relation: &str) -> Option<HashSet<String>> {
+    let row = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT CASE WHEN to_regclass('kerai.' || quote_ident({rel})) IS NULL THEN NULL
+                ELSE (SELECT COALESCE(jsonb_agg(DISTINCT a.attname), '[]'::jsonb)
+                      FROM pg_index i
+                      JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = i.indkey[0]
+                      WHERE i.indrelid = to_regclass('kerai.' || quote_ident({rel})))
+                END",
+        rel = sql_text(relation),
+    ))
+    .unwrap_or(None)?;
+    Some(
+        row.0
+            .as_array()?
+            .iter()
+            .filter_map(|c| c.as_str().map(str::to_string))
+            .collect(),
+    )
+}
+
+/// Columns compared in a plan filter such as `((kind = 'fn'::text) AND (parent_id IS NULL))`.
+fn filter_columns(filter: &str) -> Vec<String> {
+    let literals = regex::Regex::new(r"'(?:[^']|'')*'").unwrap();
+    let filter = literals.replace_all(filter, "''");
+    let comparison = regex::Regex::new(
+        r"\b([a-z_][a-z0-9_]*)\)?(?:::[a-z ]+)?\s+(?:=|<>|<=|>=|<|>|!?~~\*?|!?~\*?|<@|@>|@@|&&|IS\b|IN\b)",
+    )
+    .unwrap();
+    let mut columns: Vec<String> = Vec::new();
+    for cap in comparison.captures_iter(&filter) {
+        let column = cap[1].to_string();
+        if !columns.contains(&column) {
+            columns.push(column);
+        }
+    }
+    columns
+}
@@ -2,7 +2,10 @@
 use pgrx::prelude::*;
 
 use crate::parser::kinds::Kind;
+use super::cfg_filter::CfgSet;
+use super::comment_reflow;
 use super::import_sorter::{self, ImportEntry};
+use super::renderer::{self, RenderContext};
 
 /// Options controlling reconstruction intelligence features.
 #[derive(Debug, Clone)]
@@ -10,6 +13,13 @@ pub struct AssemblyOptions {
     pub sort_imports: bool,
     pub order_derives: bool,
     pub suggestions: bool,
+    /// When set, items whose cfg predicate is false under this set are omitted.
+    pub cfg: Option<CfgSet>,
+    /// When set, regular comments are rewrapped to this many columns.
+    pub reflow_comments: Option<usize>,
+    /// Separate std / external / crate import groups with blank lines and
+    /// collapse repeated blank lines (applied after formatting).
+    pub group_imports: bool,
 }
 
 impl Default for AssemblyOptions {
@@ -18,6 +28,9 @@ impl Default for AssemblyOptions {
             sort_imports: true,
             order_derives: true,
             suggestions: false,
+            cfg: None,
+            reflow_comments: None,
+            group_imports: false,
         }
     }
 }
@@ -58,7 +71,10 @@ pub fn assemble_file_with_options(file_node_id: &str, options: &AssemblyOptions)
     };
 
     // Collect all direct children ordered by position
-    let items = query_child_items(file_node_id);
+    let mut items = query_child_items(file_node_id);
+    if let Some(ref cfg) = options.cfg {
+        strip_disabled_items(&mut items, cfg);
+    }
 
     // Collect IDs of comment nodes that appear as direct children
     let comment_str = Kind::Comment.as_str();
@@ -72,57 +88,26 @@ pub fn assemble_file_with_options(file_node_id: &str, options: &AssemblyOptions)
         .collect();
 
     if sort_imports {
-        // Partition items into: use items, comments-above-use items, and everything else
         emit_sorted_imports(&items, use_str, comment_str, comment_block_str, &mut parts);
+    }
 
-        // Emit remaining non-use items
-        for item in &items {
-            if item.kind == use_str {
-                continue; // already emitted
-            }
-            if is_comment_kind(&item.kind, comment_str, comment_block_str) {
-                if item.consumed_by_import_sort {
-                    continue;
-                }
-                let placement = item.placement.as_deref().unwrap_or("above");
-                if placement == "trailing" {
-                    continue;
-                }
-                if let Some(ref content) = item.content {
-                    emit_comment(&mut parts, content, item.style.as_deref().unwrap_or("line"));
-                }
-                continue;
-            }
-
-            // Emit suggestions above this item
-            emit_suggestions_for_item(&mut parts, &item.id, &suggestion_map);
-            emit_item(&mut parts, item, &direct_comment_ids);
-        }
-    } else {
-        // No import sorting — emit everything in position order
-        for item in &items {
-            if is_comment_kind(&item.kind, comment_str, comment_block_str) {
-                let placement = item.placement.as_deref().unwrap_or("above");
-                if placement == "trailing" {
-                    continue;
-                }
-                if let Some(ref content) = item.content {
-                    emit_comment(&mut parts, content, item.style.as_deref().unwrap_or("line"));
-                }
-                continue;
-            }
-
-            // Emit suggestions above this item
-            emit_suggestions_for_item(&mut parts, &item.id, &suggestion_map);
-            emit_item(&mut parts, item, &direct_comment_ids);
-        }
+    // Emit each item through the renderer registered for its kind
+    let mut ctx = RenderContext {
+        parts: &mut parts,
+        options,
+        sort_imports,
+        direct_comment_ids: &direct_comment_ids,
+        suggestions: &suggestion_map,
+    };
+    for item in &items {
+        renderer::renderer_for(&item.kind).render(item, &mut ctx);
     }
 
     parts.join("\n")
 }
 
 /// A suggestion to emit as a // kerai: comment.
-struct SuggestionForEmit {
+pub(super) struct SuggestionForEmit {
     message: String,
     rule_id: String,
 }
@@ -176,7 +161,7 @@ fn query_suggestions(
 }
 
 /// Emit `// kerai:` suggestion comments for a target item.
-fn emit_suggestions_for_item(
+pub(super) fn emit_suggestions_for_item(
     parts: &mut Vec<String>,
     item_id: &str,
     suggestion_map: &std::collections::HashMap<String, Vec<SuggestionForEmit>>,
@@ -242,12 +227,23 @@ fn emit_sorted_imports(
 
 }
 
+/// Drop items whose recorded cfg predicate is false, and strip disabled
+/// nested members from the source of the items that remain.
+fn strip_disabled_items(items: &mut Vec<ChildItem>, cfg: &CfgSet) {
+    items.retain(|item| item.cfg_expr.as_ref().is_none_or(|expr| cfg.eval(expr)));
+    for item in items.iter_mut() {
+        if let Some(ref source) = item.source {
+            item.source = Some(cfg.strip_source(source));
+        }
+    }
+}
+
 fn is_comment_kind(kind: &str, comment_str: &str, comment_block_str: &str) -> bool {
     kind == comment_str || kind == comment_block_str
 }
 
 /// Emit a single non-comment, non-use item.
-fn emit_item(
+pub(super) fn emit_item(
     parts: &mut Vec<String>,
     item: &ChildItem,
     direct_comment_ids: &std::collections::HashSet<String>,
@@ -296,6 +292,33 @@ fn emit_item(
     }
 }
 
+/// Emit a standalone comment node, rewrapping it first when `reflow` is set.
+/// Only line comments are reflowed; they are re-indented to their original column.
+pub(super) fn emit_comment_item(parts: &mut Vec<String>, item: &ChildItem, reflow: Option<usize>) {
+    let Some(ref content) = item.content else {
+        return;
+    };
+    let style = item.style.as_deref().unwrap_or("line");
+    let width = match reflow {
+        Some(w) if style == "line" => w,
+        _ => {
+            emit_comment(parts, content, style);
+            return;
+        }
+    };
+
+    let indent = item.col.saturating_sub(1);
+    let reflowed = comment_reflow::reflow(content, width, indent);
+    let start = parts.len();
+    emit_comment(parts, &reflowed, style);
+    if indent > 0 {
+        let pad = " ".repeat(indent);
+        for line in &mut parts[start..] {
+            line.insert_str(0, &pad);
+        }
+    }
+}
+
 /// Emit a comment (line or block style) into the parts list.
 fn emit_comment(parts: &mut Vec<String>, content: &str, style: &str) {
     if style == "block" {
@@ -357,15 +380,33 @@ pub fn query_file_flags(file_node_id: &str) -> FileFlags {
     flags
 }
 
-struct ChildItem {
-    id: String,
-    kind: String,
-    content: Option<String>,
-    source: Option<String>,
-    placement: Option<String>,
-    style: Option<String>,
+#[derive(Clone)]
+pub(super) struct ChildItem {
+    pub id: String,
+    pub kind: String,
+    pub content: Option<String>,
+    pub source: Option<String>,
+    pub placement: Option<String>,
+    pub style: Option<String>,
+    /// 1-based source column of a comment node (0 when unknown).
+    pub col: usize,
+    pub cfg_expr: Option<serde_json::Value>,
+    /// Signature qualifiers recorded on a fn node.
+    pub fn_qualifiers: Option<FnQualifiers>,
+    /// Stored `generics` and `where` metadata, when recorded.
+    pub generics: Option<serde_json::Value>,
+    pub where_preds: Option<serde_json::Value>,
     /// Set to true when this comment was above a use item and was consumed by import sorting.
-    consumed_by_import_sort: bool,
+    pub consumed_by_import_sort: bool,
+}
+
+/// `const`/`async`/`unsafe`/`extern` qualifiers from fn node metadata.
+#[derive(Clone)]
+pub(super) struct FnQualifiers {
+    pub is_async: bool,
+    pub is_const: bool,
+    pub is_unsafe: bool,
+    pub abi: Option<String>,
 }
 
 fn query_child_items(file_node_id: &str) -> Vec<ChildItem> {
@@ -377,10 +418,18 @@ fn query_child_items(file_node_id: &str) -> Vec<ChildItem> {
             "SELECT id::text, kind, content, \
              metadata->>'source' AS source_text, \
              metadata->>'placement' AS placement, \
-             metadata->>'style' AS style \
+             metadata->>'style' AS style, \
+             (metadata->>'col')::int AS col, \
+             metadata->'cfg_expr' AS cfg_expr, \
+             (metadata->>'is_async')::boolean AS is_async, \
+             (metadata->>'is_const')::boolean AS is_const, \
+             (metadata->>'is_unsafe')::boolean AS is_unsafe, \
+             metadata->>'abi' AS abi, \
+             metadata->'generics' AS generics, \
+             metadata->'where' AS where_preds \
              FROM kerai.nodes \
              WHERE parent_id = '{}'::uuid \
-             AND kind NOT IN ('doc_comment', 'attribute', 'suggestion') \
+             AND kind NOT IN ('doc_comment', 'doctest', 'attribute', 'suggestion') \
              ORDER BY position ASC",
             file_node_id.replace('\'', "''")
         );
@@ -398,9 +447,37 @@ fn query_child_items(file_node_id: &str) -> Vec<ChildItem> {
             let source: Option<String> = row.get_by_name::<String, _>("source_text").unwrap();
             let placement: Option<String> = row.get_by_name::<String, _>("placement").unwrap();
             let style: Option<String> = row.get_by_name::<String, _>("style").unwrap();
+            let col = row
+                .get_by_name::<i32, _>("col")
+                .unwrap()
+                .unwrap_or(0)
+                .max(0) as usize;
+            let cfg_expr = row
+                .get_by_name::<pgrx::JsonB, _>("cfg_expr")
+                .unwrap()
+                .map(|j| j.0);
+            // Nodes parsed before qualifiers were recorded have none
+            let fn_qualifiers = row
+                .get_by_name::<bool, _>("is_async")
+                .unwrap()
+                .map(|is_async| FnQualifiers {
+                    is_async,
+                    is_const: row.get_by_name::<bool, _>("is_const").unwrap().unwrap_or(false),
+                    is_unsafe: row.get_by_name::<bool, _>("is_unsafe").unwrap().unwrap_or(false),
+                    abi: row.get_by_name::<String, _>("abi").unwrap(),
+                });
+            let generics = row
+                .get_by_name::<pgrx::JsonB, _>("generics")
+                .unwrap()
+                .map(|j| j.0);
+            let where_preds = row
+                .get_by_name::<pgrx::JsonB, _>("where_preds")
+                .unwrap()
+                .map(|j| j.0);
 
             items.push(ChildItem {
-                id, kind, content, source, placement, style,
+                id, kind, content, source, placement, style, col, cfg_expr, fn_qualifiers,
+                generics, where_preds,
                 consumed_by_import_sort: false,
             });
         }
@@ -54,7 +54,7 @@ fn reconstruct_c_file(file_node_id: pgrx::Uuid) -> String {
 }
 
 /// Internal: assemble C source from child nodes.
-fn assemble_c_file(file_node_id: &str) -> String {
+pub(crate) fn assemble_c_file(file_node_id: &str) -> String {
     let items = query_child_items(file_node_id);
     let mut parts: Vec<String> = Vec::new();
 
@@ -0,0 +1,181 @@
+/// Evaluate `#[cfg(...)]` predicates against a caller-supplied configuration
+/// and strip disabled items during reconstruction.
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+
+use crate::parser::ast_walker::item_attrs;
+use crate::parser::metadata::cfg_expr;
+
+/// Active cfg options, e.g. `{"feature": ["x"], "test": true, "unix": true}`.
+///
+/// Name-only options (`test`, `unix`) are set with `true`; key-value options
+/// (`feature`, `target_os`) take a string or an array of strings. Anything not
+/// listed is unset, as it would be for rustc.
+#[derive(Debug, Clone, Default)]
+pub struct CfgSet {
+    names: HashSet<String>,
+    values: HashMap<String, Vec<String>>,
+}
+
+impl CfgSet {
+    pub fn from_json(val: &Value) -> Self {
+        let mut set = CfgSet::default();
+        let Some(obj) = val.as_object() else {
+            return set;
+        };
+        for (key, v) in obj {
+            match v {
+                Value::Bool(true) => {
+                    set.names.insert(key.clone());
+                }
+                Value::String(s) => {
+                    set.values.entry(key.clone()).or_default().push(s.clone());
+                }
+                Value::Array(arr) => {
+                    let entry = set.values.entry(key.clone()).or_default();
+                    entry.extend(arr.iter().filter_map(|x| x.as_str()).map(String::from));
+                }
+                _ => {}
+            }
+        }
+        set
+    }
+
+    /// Evaluate a predicate tree as produced by `parser::metadata::cfg_expr`.
+    /// Unrecognised shapes evaluate true so malformed metadata never drops code.
+    pub fn eval(&self, expr: &Value) -> bool {
+        if let Some(all) = expr.get("all").and_then(|v| v.as_array()) {
+            return all.iter().all(|e| self.eval(e));
+        }
+        if let Some(any) = expr.get("any").and_then(|v| v.as_array()) {
+            return any.iter().any(|e| self.eval(e));
+        }
+        if let Some(inner) = expr.get("not") {
+            return !self.eval(inner);
+        }
+        let Some(key) = expr.get("key").and_then(|v| v.as_str()) else {
+            return true;
+        };
+        match expr.get("value").and_then(|v| v.as_str()) {
+            Some(value) => self
+                .values
+                .get(key)
+                .is_some_and(|vals| vals.iter().any(|v| v == value)),
+            None => self.names.contains(key),
+        }
+    }
+
+    /// Whether every `#[cfg]` attribute in the list is enabled.
+    fn attrs_enabled(&self, attrs: &[syn::Attribute]) -> bool {
+        attrs
+            .iter()
+            .filter_map(cfg_expr)
+            .all(|expr| self.eval(&expr))
+    }
+
+    /// Remove disabled nested items (module items, impl/trait members,
+    /// fields, variants) from an item's stored source. The source is returned
+    /// verbatim unless a `#[cfg]` evaluated false and something was removed;
+    /// source that does not parse as an item is also returned unchanged.
+    pub fn strip_source(&self, source: &str) -> String {
+        if !source.contains("cfg") {
+            return source.to_string();
+        }
+        match syn::parse_str::<syn::Item>(source) {
+            Ok(mut item) => {
+                if self.strip_item(&mut item) {
+                    quote::quote!(#item).to_string()
+                } else {
+                    source.to_string()
+                }
+            }
+            Err(_) => source.to_string(),
+        }
+    }
+
+    /// Strip disabled members from `item`, returning whether anything was removed.
+    fn strip_item(&self, item: &mut syn::Item) -> bool {
+        match item {
+            syn::Item::Mod(m) => {
+                let Some((_, ref mut items)) = m.content else {
+                    return false;
+                };
+                let before = items.len();
+                items.retain(|i| item_attrs(i).is_none_or(|attrs| self.attrs_enabled(attrs)));
+                let mut removed = items.len() != before;
+                for i in items.iter_mut() {
+                    removed |= self.strip_item(i);
+                }
+                removed
+            }
+            syn::Item::Impl(i) => {
+                let before = i.items.len();
+                i.items.retain(|member| match member {
+                    syn::ImplItem::Fn(f) => self.attrs_enabled(&f.attrs),
+                    syn::ImplItem::Const(c) => self.attrs_enabled(&c.attrs),
+                    syn::ImplItem::Type(t) => self.attrs_enabled(&t.attrs),
+                    syn::ImplItem::Macro(m) => self.attrs_enabled(&m.attrs),
+                    _ => true,
+                });
+                i.items.len() != before
+            }
+            syn::Item::Trait(t) => {
+                let before = t.items.len();
+                t.items.retain(|member| match member {
+                    syn::TraitItem::Fn(f) => self.attrs_enabled(&f.attrs),
+                    syn::TraitItem::Const(c) => self.attrs_enabled(&c.attrs),
+                    syn::TraitItem::Type(t) => self.attrs_enabled(&t.attrs),
+                    syn::TraitItem::Macro(m) => self.attrs_enabled(&m.attrs),
+                    _ => true,
+                });
+                t.items.len() != before
+            }
+            syn::Item::Struct(s) => self.strip_fields(&mut s.fields),
+            syn::Item::Union(u) => {
+                let before = u.fields.named.len();
+                u.fields.named = std::mem::take(&mut u.fields.named)
+                    .into_iter()
+                    .filter(|f| self.attrs_enabled(&f.attrs))
+                    .collect();
+                u.fields.named.len() != before
+            }
+            syn::Item::Enum(e) => {
+                let before = e.variants.len();
+                let mut removed = false;
+                e.variants = std::mem::take(&mut e.variants)
+                    .into_iter()
+                    .filter(|v| self.attrs_enabled(&v.attrs))
+                    .map(|mut v| {
+                        removed |= self.strip_fields(&mut v.fields);
+                        v
+                    })
+                    .collect();
+                removed || e.variants.len() != before
+            }
+            _ => false,
+        }
+    }
+
+    fn strip_fields(&self, fields: &mut syn::Fields) -> bool {
+        match fields {
+            syn::Fields::Named(named) => {
+                let before = named.named.len();
+                named.named = std::mem::take(&mut named.named)
+                    .into_iter()
+                    .filter(|f| self.attrs_enabled(&f.attrs))
+                    .collect();
+                named.named.len() != before
+            }
+            syn::Fields::Unnamed(unnamed) => {
+                let before = unnamed.unnamed.len();
+                unnamed.unnamed = std::mem::take(&mut unnamed.unnamed)
+                    .into_iter()
+                    .filter(|f| self.attrs_enabled(&f.attrs))
+                    .collect();
+                unnamed.unnamed.len() != before
+            }
+            syn::Fields::Unit => false,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+/// Rewrap regular comment text to a target width during reconstruction.
+///
+/// Only prose is rewrapped: paragraphs (runs of prose lines) are greedily
+/// refilled. Lines that are indented, start a list item (`- `, `* `, `1. `),
+/// look like commented-out code, or are `kerai:` directives pass through
+/// untouched and end the paragraph. Words longer than the available width are
+/// never split.
+///
+/// Reflow comment `content` so each emitted line (including `indent` columns
+/// and the `// ` marker) fits within `width`.
+pub fn reflow(content: &str, width: usize, indent: usize) -> String {
+    // "// " marker is three columns; never wrap narrower than a single word
+    let avail = width.saturating_sub(indent + 3).max(1);
+
+    let mut out: Vec<String> = Vec::new();
+    let mut para: Vec<&str> = Vec::new();
+
+    for line in content.split('\n') {
+        if line.trim().is_empty() {
+            flush(&mut out, &mut para, avail);
+            out.push(String::new());
+            continue;
+        }
+        if !is_prose(line) {
+            flush(&mut out, &mut para, avail);
+            out.push(line.to_string());
+            continue;
+        }
+        para.extend(line.split_whitespace());
+    }
+    flush(&mut out, &mut para, avail);
+
+    out.join("\n")
+}
+
+/// Whether a comment line is plain prose that may be rewrapped.
+fn is_prose(line: &str) -> bool {
+    if line.starts_with(char::is_whitespace) {
+        return false;
+    }
+    let line = line.trim_end();
+    !(line.starts_with("kerai:") || is_list_item(line) || looks_like_code(line))
+}
+
+/// Emit the pending paragraph, if any, and reset it.
+fn flush(out: &mut Vec<String>, para: &mut Vec<&str>, avail: usize) {
+    fill(out, para, avail);
+    para.clear();
+}
+
+/// Greedily fill `words` into lines of at most `avail` columns.
+fn fill(out: &mut Vec<String>, words: &[&str], avail: usize) {
+    let mut line = String::new();
+
+    for word in words {
+        if !line.is_empty() && line.len() + 1 + word.len() > avail {
+            out.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        out.push(line);
+    }
+}
+
+/// Whether a line starts with a list marker (`- `, `* `, `+ `, `12. `, `3) `)
+/// or is a bare `-`/`*` bullet.
+fn is_list_item(line: &str) -> bool {
+    if ["-", "*", "+"].contains(&line) || ["- ", "* ", "+ "].iter().any(|b| line.starts_with(b)) {
+        return true;
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    digits > 0 && (line[digits..].starts_with(". ") || line[digits..].starts_with(") "))
+}
+
+/// Heuristic for commented-out code: statement or block punctuation at the
+/// end, attributes or nested comments at the start, or path and arrow tokens.
+fn looks_like_code(line: &str) -> bool {
+    line.ends_with([';', '{', '}'])
+        || ["}", "#[", "#!", "//", "/*"]
+            .iter()
+            .any(|p| line.starts_with(p))
+        || ["::", "=>", "->", "();", " = "]
+            .iter()
+            .any(|t| line.contains(t))
+}
@@ -0,0 +1,94 @@
+/// Rebuild generic parameter lists and where clauses from node metadata.
+///
+/// The inverse of `parser::metadata::insert_generics`: each stored param or
+/// predicate is rendered back to source and re-parsed with syn.
+use serde_json::Value;
+
+/// Apply stored `generics` and `where` metadata over `current`. A missing key
+/// keeps what `current` already has, since nodes parsed before the key was
+/// recorded don't carry it. Returns `None` when the metadata doesn't parse.
+pub(super) fn generics_from_metadata(
+    params: Option<&Value>,
+    preds: Option<&Value>,
+    current: &syn::Generics,
+) -> Option<syn::Generics> {
+    let mut generics = current.clone();
+
+    if let Some(params) = params {
+        let rendered = params
+            .as_array()?
+            .iter()
+            .map(param_source)
+            .collect::<Option<Vec<_>>>()?;
+        let parsed: syn::Generics = if rendered.is_empty() {
+            syn::Generics::default()
+        } else {
+            syn::parse_str(&format!("<{}>", rendered.join(", "))).ok()?
+        };
+        generics.lt_token = parsed.lt_token;
+        generics.params = parsed.params;
+        generics.gt_token = parsed.gt_token;
+    }
+
+    if let Some(preds) = preds {
+        let rendered = preds
+            .as_array()?
+            .iter()
+            .map(predicate_source)
+            .collect::<Option<Vec<_>>>()?;
+        generics.where_clause = if rendered.is_empty() {
+            None
+        } else {
+            Some(syn::parse_str(&format!("where {}", rendered.join(", "))).ok()?)
+        };
+    }
+
+    Some(generics)
+}
+
+/// Source for one generic param. Older nodes stored each param as a string.
+fn param_source(param: &Value) -> Option<String> {
+    if let Some(raw) = param.as_str() {
+        return Some(raw.to_string());
+    }
+    let name = param.get("name")?.as_str()?;
+    let mut out = match param.get("kind")?.as_str()? {
+        "const" => format!("const {}: {}", name, param.get("ty")?.as_str()?),
+        _ => format!("{}{}", name, bounds_source(param)),
+    };
+    if let Some(default) = param.get("default").and_then(|d| d.as_str()) {
+        out.push_str(" = ");
+        out.push_str(default);
+    }
+    Some(out)
+}
+
+/// Source for one where-clause predicate.
+fn predicate_source(pred: &Value) -> Option<String> {
+    if let Some(raw) = pred.get("predicate").and_then(|p| p.as_str()) {
+        return Some(raw.to_string());
+    }
+    let bounded = pred.get("bounded")?.as_str()?;
+    let binder = match pred.get("for").and_then(|f| f.as_array()) {
+        Some(lifetimes) => {
+            let names: Vec<&str> = lifetimes.iter().filter_map(|l| l.as_str()).collect();
+            format!("for<{}> ", names.join(", "))
+        }
+        None => String::new(),
+    };
+    Some(format!("{}{}{}", binder, bounded, bounds_source(pred)))
+}
+
+/// `: A + B` for a non-empty `bounds` list, otherwise empty.
+fn bounds_source(value: &Value) -> String {
+    let bounds: Vec<&str> = value
+        .get("bounds")
+        .and_then(|b| b.as_array())
+        .map(|b| b.iter().filter_map(|x| x.as_str()).collect())
+        .unwrap_or_default();
+    if bounds.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", bounds.join(" + "))
+    }
+}
@@ -161,6 +161,167 @@ pub fn format_sorted_imports(entries: &[ImportEntry]) -> Vec<String> {
     lines
 }
 
+/// Separate top-level `use` groups with a single blank line and collapse
+/// runs of blank lines elsewhere, approximating rustfmt's
+/// `group_imports = "StdExternalCrate"` layout on formatted source.
+///
+/// Works line-by-line on already formatted output: blank lines between two
+/// imports of the same group are dropped, a blank line is inserted where the
+/// group changes, and blank lines inside string literals are left alone.
+/// Comments between two imports stay with the import below them, so a group
+/// change puts the blank line above the comment.
+pub fn group_import_blocks(source: &str) -> String {
+    let mut out: Vec<&str> = Vec::new();
+    // Group of the previous top-level use statement, while still adjacent to it
+    let mut last_use: Option<ImportGroup> = None;
+    let mut pending_blank = false;
+    let mut in_use = false;
+    let mut lexer = LiteralState::default();
+    // Comment lines after a use statement, held until the next line shows
+    // whether they precede another import
+    let mut held: Vec<&str> = Vec::new();
+    let mut blank_before_held = false;
+
+    for line in source.lines() {
+        if lexer.in_string() {
+            out.push(line);
+            lexer.scan(line);
+            continue;
+        }
+        if in_use {
+            out.push(line);
+            in_use = !line.trim_end().ends_with(';');
+            continue;
+        }
+        if line.trim().is_empty() {
+            pending_blank = true;
+            continue;
+        }
+        if last_use.is_some() && line.trim_start().starts_with("//") {
+            if held.is_empty() {
+                blank_before_held = pending_blank;
+            } else if pending_blank {
+                held.push("");
+            }
+            pending_blank = false;
+            held.push(line);
+            continue;
+        }
+
+        let group = is_top_level_use(line).then(|| classify_import(line));
+        match (last_use, group) {
+            (Some(prev), Some(cur)) if prev == cur => out.append(&mut held),
+            (Some(_), Some(_)) => {
+                out.push("");
+                out.append(&mut held);
+            }
+            _ => {
+                if !held.is_empty() {
+                    if blank_before_held {
+                        out.push("");
+                    }
+                    out.append(&mut held);
+                }
+                if pending_blank && !out.is_empty() {
+                    out.push("");
+                }
+            }
+        }
+        pending_blank = false;
+        last_use = group;
+
+        out.push(line);
+        if group.is_some() {
+            in_use = !line.trim_end().ends_with(';');
+        } else {
+            lexer.scan(line);
+        }
+    }
+    if !held.is_empty() {
+        if blank_before_held {
+            out.push("");
+        }
+        out.append(&mut held);
+    }
+
+    let mut result = out.join("\n");
+    if source.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+/// Whether a line starts a module-level `use` item (optionally `pub`).
+fn is_top_level_use(line: &str) -> bool {
+    !line.starts_with(char::is_whitespace) && strip_visibility(line).starts_with("use ")
+}
+
+/// Minimal lexer state for tracking multi-line string literals across lines.
+#[derive(Default)]
+struct LiteralState {
+    /// `Some(n)` while inside a string literal; `n` is the raw-string hash
+    /// count, or `None` inside a regular (escaped) string.
+    open: Option<Option<usize>>,
+}
+
+impl LiteralState {
+    fn in_string(&self) -> bool {
+        self.open.is_some()
+    }
+
+    /// Advance through `line`, updating whether a string literal is still open.
+    fn scan(&mut self, line: &str) {
+        let bytes = line.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            match self.open {
+                Some(None) => match bytes[i] {
+                    b'\\' => i += 1,
+                    b'"' => self.open = None,
+                    _ => {}
+                },
+                Some(Some(hashes)) => {
+                    if bytes[i] == b'"'
+                        && bytes[i + 1..].iter().take(hashes).filter(|&&b| b == b'#').count() == hashes
+                    {
+                        self.open = None;
+                        i += hashes;
+                    }
+                }
+                None => match bytes[i] {
+                    b'/' if bytes.get(i + 1) == Some(&b'/') => return,
+                    b'"' => self.open = Some(None),
+                    b'r' if !prev_is_ident(bytes, i) => {
+                        let hashes = bytes[i + 1..].iter().take_while(|&&b| b == b'#').count();
+                        if bytes.get(i + 1 + hashes) == Some(&b'"') {
+                            self.open = Some(Some(hashes));
+                            i += 1 + hashes;
+                        }
+                    }
+                    b'\'' => {
+                        // Skip char literals ('x', '\n', '"'); lifetimes fall through
+                        if bytes.get(i + 1) == Some(&b'\\') {
+                            if let Some(end) = bytes[i + 2..].iter().position(|&b| b == b'\'') {
+                                i += 2 + end;
+                            }
+                        } else if let Some(len) = line[i + 1..].chars().next().map(char::len_utf8) {
+                            if bytes.get(i + 1 + len) == Some(&b'\'') {
+                                i += 1 + len;
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+            }
+            i += 1;
+        }
+    }
+}
+
+fn prev_is_ident(bytes: &[u8], i: usize) -> bool {
+    i > 0 && (bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -288,4 +449,46 @@ mod tests {
             "use crate::sql;",
         ]);
     }
+
+    #[test]
+    fn test_group_import_blocks() {
+        let src = "use std::io;\nuse serde::Deserialize;\nuse serde::Serialize;\n\n\nuse crate::sql;\nfn a() {}\n\n\n\nfn b() {}\n";
+        assert_eq!(
+            group_import_blocks(src),
+            "use std::io;\n\nuse serde::Deserialize;\nuse serde::Serialize;\n\nuse crate::sql;\nfn a() {}\n\nfn b() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_group_import_blocks_multiline_use() {
+        let src = "use std::{\n    fs,\n    io,\n};\nuse crate::sql;\n";
+        assert_eq!(
+            group_import_blocks(src),
+            "use std::{\n    fs,\n    io,\n};\n\nuse crate::sql;\n"
+        );
+    }
+
+    #[test]
+    fn test_group_import_blocks_keeps_string_blanks() {
+        let src = "const S: &str = \"a\n\n\nb\";\nconst R: &str = r#\"x \" \n\n\ny\"#;\nconst C: char = '\"';\n\n\nfn f() {}\n";
+        assert_eq!(
+            group_import_blocks(src),
+            "const S: &str = \"a\n\n\nb\";\nconst R: &str = r#\"x \" \n\n\ny\"#;\nconst C: char = '\"';\n\nfn f() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_group_import_blocks_separator_before_comment() {
+        let src = "use std::io;\n// for config\nuse serde::Deserialize;\n// same group\nuse serde::Serialize;\n// trailing\n\nfn f() {}\n";
+        assert_eq!(
+            group_import_blocks(src),
+            "use std::io;\n\n// for config\nuse serde::Deserialize;\n// same group\nuse serde::Serialize;\n// trailing\n\nfn f() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_group_import_blocks_nested_use_untouched() {
+        let src = "mod m {\n    use crate::a;\n    use std::io;\n}\n";
+        assert_eq!(group_import_blocks(src), src);
+    }
 }
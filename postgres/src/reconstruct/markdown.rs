@@ -33,8 +33,17 @@ fn reconstruct_markdown(document_node_id: pgrx::Uuid) -> String {
         );
     }
 
+    assemble_markdown(&id_str)
+}
+
+/// Internal: render a document node's children as CommonMark.
+pub(crate) fn assemble_markdown(document_node_id: &str) -> String {
     let mut output = String::new();
-    reconstruct_children(&id_str, &mut output, 0);
+    let children = query_children(document_node_id);
+
+    for child in &children {
+        emit_node(child, &mut output, 0);
+    }
     output.trim_end().to_string()
 }
 
@@ -150,6 +159,11 @@ fn emit_node(node: &MdNode, output: &mut String, depth: usize) {
             output.push_str(&format!("![{}]({})\n\n", alt, url));
         }
 
+        kinds::FRONTMATTER => {
+            output.push_str(node.content.as_deref().unwrap_or(""));
+            output.push_str("\n\n");
+        }
+
         kinds::HTML_BLOCK => {
             let content = node.content.as_deref().unwrap_or("");
             output.push_str(content);
@@ -3,14 +3,22 @@ use pgrx::prelude::*;
 use serde_json::json;
 
 mod assembler;
+mod cfg_filter;
+mod comment_reflow;
 mod derive_orderer;
 mod formatter;
+mod generics;
 mod go;
 mod c;
 mod import_sorter;
 mod markdown;
+mod python;
+mod renderer;
 
 use assembler::{AssemblyOptions, query_file_flags};
+use cfg_filter::CfgSet;
+use crate::sql::sql_uuid;
+use crate::subxact;
 
 /// Parse reconstruction options from a JSONB parameter.
 fn parse_options(options: Option<pgrx::JsonB>) -> AssemblyOptions {
@@ -25,6 +33,18 @@ fn parse_options(options: Option<pgrx::JsonB>) -> AssemblyOptions {
         if let Some(v) = val.get("suggestions").and_then(|v| v.as_bool()) {
             opts.suggestions = v;
         }
+        if val.get("strip_disabled_cfg").and_then(|v| v.as_bool()) == Some(true) {
+            let cfg = val.get("cfg").map(CfgSet::from_json).unwrap_or_default();
+            opts.cfg = Some(cfg);
+        }
+        if let Some(v) = val.get("group_imports").and_then(|v| v.as_bool()) {
+            opts.group_imports = v;
+        }
+        if let Some(width) = val.get("reflow_comments").and_then(|v| v.as_u64()) {
+            if width > 0 {
+                opts.reflow_comments = Some(width as usize);
+            }
+        }
     }
     opts
 }
@@ -38,10 +58,16 @@ fn reconstruct_file(file_node_id: pgrx::Uuid) -> String {
 
 /// Reconstruct a Rust source file with explicit options.
 ///
-/// Options JSON keys (all boolean, default true):
+/// Options JSON keys (boolean, default true unless noted):
 /// - sort_imports: canonical import ordering (std → external → crate)
 /// - order_derives: alphabetical #[derive(...)] normalization
-/// - suggestions: emit // kerai: advisory comments
+/// - suggestions: emit // kerai: advisory comments (default false)
+/// - strip_disabled_cfg: omit items whose #[cfg] is false under `cfg` (default false)
+/// - cfg: active options, e.g. `{"feature": ["x"], "test": true}`
+/// - reflow_comments: rewrap regular comments to this width (number; default off).
+///   Doc comments and `// kerai:` lines are left as-is
+/// - group_imports: blank line between std / external / crate import groups,
+///   other blank-line runs collapsed to one, like rustfmt (default false)
 #[pg_extern]
 fn reconstruct_file_with_options(
     file_node_id: pgrx::Uuid,
@@ -51,32 +77,95 @@ fn reconstruct_file_with_options(
     let opts = parse_options(options);
 
     // Validate that the node exists and is a file node
-    let kind = Spi::get_one::<String>(&format!(
-        "SELECT kind FROM kerai.nodes WHERE id = '{}'::uuid",
-        id_str.replace('\'', "''")
-    ))
-    .expect("Failed to query node")
-    .unwrap_or_else(|| pgrx::error!("Node not found: {}", id_str));
+    let node = describe_node(&id_str);
+    let kind = node["kind"].as_str().unwrap_or_default();
 
     if kind != "file" {
+        let file = match node["file"].as_object() {
+            Some(f) => format!(
+                "; it belongs to file '{}' ({})",
+                f["content"].as_str().unwrap_or_default(),
+                f["id"].as_str().unwrap_or_default()
+            ),
+            None => "; no enclosing file node".to_string(),
+        };
         pgrx::error!(
-            "Node {} is kind '{}', expected 'file'",
+            "Node {} ('{}', path {}) is kind '{}', expected 'file'{}",
             id_str,
-            kind
+            node["content"].as_str().unwrap_or_default(),
+            node["path"].as_str().unwrap_or("none"),
+            kind,
+            file
         );
     }
 
-    let flags = query_file_flags(&id_str);
-    let raw = assembler::assemble_file_with_options(&id_str, &opts);
+    let filename = node["content"].as_str().unwrap_or_default().to_string();
+    pgrx::PgTryBuilder::new(|| render_rust_file(&id_str, &opts))
+        .catch_others(|e| {
+            pgrx::error!(
+                "Failed to reconstruct file '{}' ({}): {}",
+                filename,
+                id_str,
+                subxact::caught_message(e)
+            )
+        })
+        .execute()
+}
+
+/// Look up a node's kind, content, path, and nearest enclosing file for
+/// reconstruction errors. Errors with the node id when it can't be found.
+fn describe_node(id: &str) -> serde_json::Value {
+    let query = format!(
+        "SELECT (
+            SELECT jsonb_build_object(
+                'kind', n.kind,
+                'content', n.content,
+                'path', n.path::text,
+                'file', (
+                    WITH RECURSIVE up AS (
+                        SELECT p.id, p.kind, p.content, p.parent_id, 1 AS depth
+                        FROM kerai.nodes p WHERE p.id = n.parent_id
+                        UNION ALL
+                        SELECT p.id, p.kind, p.content, p.parent_id, up.depth + 1
+                        FROM kerai.nodes p JOIN up ON p.id = up.parent_id
+                        WHERE up.kind <> 'file'
+                    )
+                    SELECT jsonb_build_object('id', up.id, 'content', up.content)
+                    FROM up WHERE up.kind = 'file'
+                    ORDER BY up.depth LIMIT 1
+                )
+            )
+            FROM kerai.nodes n WHERE n.id = {}
+        )",
+        sql_uuid(id)
+    );
+    match Spi::get_one::<pgrx::JsonB>(&query) {
+        Ok(Some(node)) => node.0,
+        Ok(None) => pgrx::error!("Failed to query node {}: no node with this id exists", id),
+        Err(e) => pgrx::error!("Failed to query node {}: {}", id, e),
+    }
+}
+
+/// Assemble, format, and derive-order a Rust file node.
+fn render_rust_file(file_id: &str, opts: &AssemblyOptions) -> String {
+    let flags = query_file_flags(file_id);
+    let raw = assembler::assemble_file_with_options(file_id, opts);
     let formatted = formatter::format_source(&raw);
 
     // Apply derive ordering after formatting (quote::ToTokens uses spaced syntax
     // that doesn't match #[derive(...)], so we must order after prettyplease normalizes)
     let order = opts.order_derives && !flags.skip_order_derives && !flags.skip_all;
-    if order {
+    let ordered = if order {
         derive_orderer::order_derives(&formatted)
     } else {
         formatted
+    };
+
+    // Group separators must be added after prettyplease, which drops blank lines
+    if opts.group_imports && !flags.skip_all {
+        import_sorter::group_import_blocks(&ordered)
+    } else {
+        ordered
     }
 }
 
@@ -119,18 +208,135 @@ fn reconstruct_crate_with_options(
             let file_id: String = row.get_by_name::<String, _>("id").unwrap().unwrap_or_default();
             let filename: String = row.get_by_name::<String, _>("content").unwrap().unwrap_or_default();
 
-            let file_flags = query_file_flags(&file_id);
-            let raw = assembler::assemble_file_with_options(&file_id, &opts);
-            let formatted = formatter::format_source(&raw);
-            let order = opts.order_derives && !file_flags.skip_order_derives && !file_flags.skip_all;
-            let final_source = if order {
-                derive_orderer::order_derives(&formatted)
-            } else {
-                formatted
-            };
-            files.insert(filename, json!(final_source));
+            files.insert(filename, json!(render_rust_file(&file_id, &opts)));
         }
     });
 
     pgrx::JsonB(serde_json::Value::Object(files))
 }
+
+/// A reconstructable file node discovered under a tree root.
+struct TreeFile {
+    id: String,
+    kind: String,
+    language: Option<String>,
+    path: String,
+    metadata: serde_json::Value,
+}
+
+/// Reconstruct every file under a directory, crate, or repository subtree.
+///
+/// Each file node is dispatched to its language reconstructor (Rust, Go, C,
+/// markdown; opaque text files are emitted verbatim). Files that fail are
+/// reported in `failed` without aborting the rest.
+///
+/// Returns `{files: {path: source}, failed: [{node_id, path, language, error}],
+/// file_count, failed_count}`.
+#[pg_extern]
+fn reconstruct_tree(root: pgrx::Uuid, options: Option<pgrx::JsonB>) -> pgrx::JsonB {
+    let root_str = root.to_string();
+    let opts = parse_options(options);
+
+    let exists = Spi::get_one::<bool>(&format!(
+        "SELECT EXISTS(SELECT 1 FROM kerai.nodes WHERE id = {})",
+        sql_uuid(&root_str)
+    ))
+    .unwrap_or(Some(false))
+    .unwrap_or(false);
+    if !exists {
+        pgrx::error!("Node not found: {}", root_str);
+    }
+
+    // Walk containers only; stop descending once a file-level node is reached
+    let query = format!(
+        "WITH RECURSIVE tree AS (
+            SELECT id, kind, language, content, metadata, 0 AS depth
+            FROM kerai.nodes WHERE id = {root}
+            UNION ALL
+            SELECT n.id, n.kind, n.language, n.content, n.metadata, t.depth + 1
+            FROM kerai.nodes n
+            JOIN tree t ON n.parent_id = t.id
+            WHERE t.kind NOT IN ({file_kinds})
+        )
+        SELECT id::text, kind, language, content, metadata
+        FROM tree
+        WHERE kind IN ({file_kinds})
+        ORDER BY depth, content",
+        root = sql_uuid(&root_str),
+        file_kinds = "'file', 'document', 'repo_opaque_text', 'repo_opaque_binary'",
+    );
+
+    let mut tree_files: Vec<TreeFile> = Vec::new();
+    Spi::connect(|client| {
+        let result = client.select(&query, None, &[]).unwrap();
+        for row in result {
+            let id: String = row.get_by_name::<String, _>("id").unwrap().unwrap_or_default();
+            let kind: String = row.get_by_name::<String, _>("kind").unwrap().unwrap_or_default();
+            let language: Option<String> = row.get_by_name::<String, _>("language").unwrap();
+            let content: String = row.get_by_name::<String, _>("content").unwrap().unwrap_or_default();
+            let metadata = row
+                .get_by_name::<pgrx::JsonB, _>("metadata")
+                .unwrap()
+                .map(|j| j.0)
+                .unwrap_or(json!({}));
+            let path = metadata
+                .get("path")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .unwrap_or(content);
+            tree_files.push(TreeFile { id, kind, language, path, metadata });
+        }
+    });
+
+    let mut files = serde_json::Map::new();
+    let mut failed: Vec<serde_json::Value> = Vec::new();
+
+    for file in &tree_files {
+        // In a subtransaction, so one file's ERROR doesn't abort the export
+        let outcome = subxact::try_subtransaction(|| render_tree_file(file, &opts))
+            .unwrap_or_else(|e| Err(subxact::caught_message(e)));
+        match outcome {
+            Ok(source) => {
+                files.insert(file.path.clone(), json!(source));
+            }
+            Err(error) => failed.push(json!({
+                "node_id": file.id,
+                "path": file.path,
+                "language": file.language,
+                "error": error,
+            })),
+        }
+    }
+
+    pgrx::JsonB(json!({
+        "file_count": files.len(),
+        "failed_count": failed.len(),
+        "files": files,
+        "failed": failed,
+    }))
+}
+
+/// Dispatch a single tree file to the reconstructor for its kind and language.
+fn render_tree_file(file: &TreeFile, opts: &AssemblyOptions) -> Result<String, String> {
+    match (file.kind.as_str(), file.language.as_deref()) {
+        ("file", None | Some("rust")) => Ok(render_rust_file(&file.id, opts)),
+        ("file", Some("go")) => Ok(go::assemble_go_file(&file.id)),
+        ("file", Some("c")) => Ok(c::assemble_c_file(&file.id)),
+        ("document", _) => Ok(markdown::assemble_markdown(&file.id)),
+        ("repo_opaque_text", _) => {
+            if file.metadata.get("truncated").and_then(|v| v.as_bool()) == Some(true) {
+                return Err("Opaque text was truncated at ingest".to_string());
+            }
+            file.metadata
+                .get("source")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .ok_or_else(|| "Opaque text has no stored source".to_string())
+        }
+        ("repo_opaque_binary", _) => Err("Binary content is not stored".to_string()),
+        (_, lang) => Err(format!(
+            "No reconstructor for language '{}'",
+            lang.unwrap_or("unknown")
+        )),
+    }
+}
@@ -0,0 +1,337 @@
+/// Reconstruct Python source files.
+///
+/// A structurally stored Python file is a `file` node (language `python`)
+/// whose descendants are `py_*` nodes, ordered by `position` under their
+/// parent:
+///
+/// - `py_import` and `py_statement`: `content` is the statement's source, with
+///   continuation lines indented relative to the statement itself.
+/// - `py_class`: `content` is the name, `metadata.bases` the base list.
+/// - `py_function`: `content` is the name, `metadata.params` the parameter
+///   list, `metadata.returns` the return annotation and `metadata.is_async`
+///   whether it is `async def`. Methods and nested definitions are children.
+/// - `py_decorator`: a child of the class or function it decorates, with the
+///   expression after `@` as `content`.
+/// - `py_docstring` and `py_comment`: linked to the file, class or function
+///   they describe by a `documents` edge. Unattached `py_comment` children are
+///   emitted in place.
+///
+/// No parser in this crate produces `py_*` nodes yet: repo ingestion and
+/// `parse_auto` store `.py` files as one `repo_opaque_text` node carrying the
+/// full source, which is returned as stored rather than rebuilt.
+use std::collections::{HashMap, HashSet};
+
+use pgrx::prelude::*;
+
+use crate::repo::kinds::REPO_OPAQUE_TEXT;
+use crate::sql::sql_uuid;
+
+const PY_IMPORT: &str = "py_import";
+const PY_CLASS: &str = "py_class";
+const PY_FUNCTION: &str = "py_function";
+const PY_DECORATOR: &str = "py_decorator";
+const PY_DOCSTRING: &str = "py_docstring";
+const PY_COMMENT: &str = "py_comment";
+
+const INDENT: &str = "    ";
+
+/// Reconstruct a Python source file.
+///
+/// Takes the UUID of a Python file's node and returns its source. Files cut
+/// at the opaque-text size cap can't be reconstructed and raise an error.
+#[pg_extern]
+fn reconstruct_python_file(file_node_id: pgrx::Uuid) -> String {
+    let id_str = file_node_id.to_string();
+
+    let node = match Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'kind', kind,
+            'language', language,
+            'name', content,
+            'metadata', metadata
+        ) FROM kerai.nodes WHERE id = {}",
+        sql_uuid(&id_str),
+    )) {
+        Ok(Some(n)) => n.0,
+        Ok(None) => pgrx::error!("Failed to query node {}: no node with this id exists", id_str),
+        Err(e) => pgrx::error!("Failed to query node {}: {}", id_str, e),
+    };
+
+    let kind = node["kind"].as_str().unwrap_or_default();
+    if kind != "file" && kind != REPO_OPAQUE_TEXT {
+        pgrx::error!(
+            "Node {} is kind '{}', expected 'file' (Python files are stored as '{}')",
+            id_str,
+            kind,
+            REPO_OPAQUE_TEXT
+        );
+    }
+
+    let language = node["language"].as_str().unwrap_or_default();
+    if language != "python" {
+        pgrx::error!(
+            "Node {} has language '{}', expected 'python'",
+            id_str,
+            language
+        );
+    }
+
+    if kind == "file" {
+        return assemble_python_file(&id_str);
+    }
+
+    let name = node["name"].as_str().unwrap_or_default();
+    let meta = &node["metadata"];
+    let Some(source) = meta["source"].as_str() else {
+        pgrx::error!("Python file '{}' ({}) has no stored source", name, id_str);
+    };
+    if meta["truncated"].as_bool().unwrap_or(false) {
+        pgrx::error!(
+            "Python file '{}' ({}) was truncated when stored; only the first {} bytes are kept",
+            name,
+            id_str,
+            source.len()
+        );
+    }
+
+    source.to_string()
+}
+
+/// A `py_*` node below the file.
+struct PyNode {
+    id: String,
+    kind: String,
+    content: String,
+    metadata: serde_json::Value,
+}
+
+/// The file's node tree and the docstrings and comments attached to it.
+struct PyTree {
+    children: HashMap<String, Vec<PyNode>>,
+    docstrings: HashMap<String, String>,
+    comments: HashMap<String, Vec<String>>,
+    /// Comment nodes emitted above their target rather than in place
+    attached: HashSet<String>,
+}
+
+/// Internal: assemble Python source from the `py_*` nodes under a file.
+pub(crate) fn assemble_python_file(file_node_id: &str) -> String {
+    let tree = load_tree(file_node_id);
+    let mut lines = Vec::new();
+    emit_body(&tree, file_node_id, 0, &mut lines);
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+/// Load every descendant of the file plus the `documents` edges onto them.
+fn load_tree(file_node_id: &str) -> PyTree {
+    let rows = Spi::get_one::<pgrx::JsonB>(&format!(
+        "WITH RECURSIVE tree AS (
+            SELECT id, parent_id, kind, content, position, metadata
+            FROM kerai.nodes WHERE parent_id = {0}
+            UNION ALL
+            SELECT n.id, n.parent_id, n.kind, n.content, n.position, n.metadata
+            FROM kerai.nodes n JOIN tree t ON n.parent_id = t.id
+        )
+        SELECT jsonb_build_object(
+            'nodes', COALESCE((
+                SELECT jsonb_agg(jsonb_build_object(
+                    'id', id, 'parent_id', parent_id, 'kind', kind,
+                    'content', COALESCE(content, ''), 'metadata', metadata
+                ) ORDER BY parent_id, position, id)
+                FROM tree
+            ), '[]'::jsonb),
+            'docs', COALESCE((
+                SELECT jsonb_agg(jsonb_build_object(
+                    'id', d.id, 'target_id', e.target_id, 'kind', d.kind,
+                    'content', COALESCE(d.content, '')
+                ) ORDER BY e.target_id, d.position, d.id)
+                FROM kerai.edges e
+                JOIN kerai.nodes d ON d.id = e.source_id
+                WHERE e.relation = 'documents'
+                  AND d.kind IN ('{1}', '{2}')
+                  AND (e.target_id = {0} OR e.target_id IN (SELECT id FROM tree))
+            ), '[]'::jsonb)
+        )",
+        sql_uuid(file_node_id),
+        PY_DOCSTRING,
+        PY_COMMENT,
+    ))
+    .unwrap_or_else(|e| pgrx::error!("Failed to load Python nodes under {}: {}", file_node_id, e))
+    .map(|j| j.0)
+    .unwrap_or_default();
+
+    let mut tree = PyTree {
+        children: HashMap::new(),
+        docstrings: HashMap::new(),
+        comments: HashMap::new(),
+        attached: HashSet::new(),
+    };
+
+    for doc in rows["docs"].as_array().into_iter().flatten() {
+        let target = doc["target_id"].as_str().unwrap_or_default().to_string();
+        let content = doc["content"].as_str().unwrap_or_default().to_string();
+        if doc["kind"] == PY_DOCSTRING {
+            tree.docstrings.insert(target, content);
+        } else {
+            tree.attached
+                .insert(doc["id"].as_str().unwrap_or_default().to_string());
+            tree.comments.entry(target).or_default().push(content);
+        }
+    }
+
+    for row in rows["nodes"].as_array().into_iter().flatten() {
+        let parent = row["parent_id"].as_str().unwrap_or_default().to_string();
+        tree.children.entry(parent).or_default().push(PyNode {
+            id: row["id"].as_str().unwrap_or_default().to_string(),
+            kind: row["kind"].as_str().unwrap_or_default().to_string(),
+            content: row["content"].as_str().unwrap_or_default().to_string(),
+            metadata: row["metadata"].clone(),
+        });
+    }
+
+    tree
+}
+
+/// Emit the body of `owner` (the file, a class or a function) at `depth`:
+/// its docstring, then its statements and definitions in order. Returns
+/// false if nothing was emitted.
+fn emit_body(tree: &PyTree, owner: &str, depth: usize, lines: &mut Vec<String>) -> bool {
+    let indent = INDENT.repeat(depth);
+    let mut previous: Option<&str> = None;
+
+    // Comments on a class or function are emitted above it by the caller
+    if depth == 0 {
+        for comment in tree.comments.get(owner).into_iter().flatten() {
+            push_comment(lines, &indent, comment);
+            previous = Some(PY_COMMENT);
+        }
+    }
+
+    if let Some(doc) = tree.docstrings.get(owner) {
+        push_block(lines, &indent, &render_docstring(doc));
+        previous = Some(PY_DOCSTRING);
+    }
+
+    let items = tree
+        .children
+        .get(owner)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for item in items {
+        let kind = item.kind.as_str();
+        if kind == PY_DECORATOR || kind == PY_DOCSTRING || tree.attached.contains(&item.id) {
+            continue;
+        }
+
+        if let Some(prev) = previous {
+            let blank = blank_lines_between(prev, kind, depth);
+            lines.resize(lines.len() + blank, String::new());
+        }
+        previous = Some(kind);
+
+        for comment in tree.comments.get(&item.id).into_iter().flatten() {
+            push_comment(lines, &indent, comment);
+        }
+
+        match kind {
+            PY_CLASS => {
+                emit_decorators(tree, &item.id, &indent, lines);
+                let bases = item.metadata["bases"].as_str().unwrap_or_default();
+                if bases.is_empty() {
+                    lines.push(format!("{}class {}:", indent, item.content));
+                } else {
+                    lines.push(format!("{}class {}({}):", indent, item.content, bases));
+                }
+                emit_suite(tree, &item.id, depth + 1, lines);
+            }
+            PY_FUNCTION => {
+                emit_decorators(tree, &item.id, &indent, lines);
+                let meta = &item.metadata;
+                let keyword = if meta["is_async"].as_bool().unwrap_or(false) {
+                    "async def"
+                } else {
+                    "def"
+                };
+                let params = meta["params"].as_str().unwrap_or_default();
+                let returns = match meta["returns"].as_str() {
+                    Some(r) if !r.is_empty() => format!(" -> {}", r),
+                    _ => String::new(),
+                };
+                lines.push(format!(
+                    "{}{} {}({}){}:",
+                    indent, keyword, item.content, params, returns
+                ));
+                emit_suite(tree, &item.id, depth + 1, lines);
+            }
+            PY_COMMENT => push_comment(lines, &indent, &item.content),
+            _ => push_block(lines, &indent, &item.content),
+        }
+    }
+
+    previous.is_some()
+}
+
+/// Emit the indented body of a class or function, or `pass` if it is empty.
+fn emit_suite(tree: &PyTree, owner: &str, depth: usize, lines: &mut Vec<String>) {
+    if !emit_body(tree, owner, depth, lines) {
+        lines.push(format!("{}pass", INDENT.repeat(depth)));
+    }
+}
+
+/// Emit the decorators of a class or function, in their stored order.
+fn emit_decorators(tree: &PyTree, owner: &str, indent: &str, lines: &mut Vec<String>) {
+    let decorators = tree.children.get(owner).into_iter().flatten();
+    for decorator in decorators.filter(|n| n.kind == PY_DECORATOR) {
+        lines.push(format!("{}@{}", indent, decorator.content));
+    }
+}
+
+/// Blank lines between two consecutive items of a body (PEP 8): two around
+/// top-level definitions, one around nested ones and after a docstring.
+fn blank_lines_between(prev: &str, next: &str, depth: usize) -> usize {
+    let is_def = |k: &str| k == PY_CLASS || k == PY_FUNCTION;
+    if is_def(prev) || is_def(next) {
+        if depth == 0 {
+            2
+        } else {
+            1
+        }
+    } else if prev == PY_DOCSTRING || (prev != next && (prev == PY_IMPORT || next == PY_IMPORT)) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Triple-quoted docstring text; multi-line docstrings close on their own line.
+fn render_docstring(text: &str) -> String {
+    if text.contains('\n') {
+        format!("\"\"\"{}\n\"\"\"", text)
+    } else {
+        format!("\"\"\"{}\"\"\"", text)
+    }
+}
+
+/// Push a `#` comment, one line per line of text.
+fn push_comment(lines: &mut Vec<String>, indent: &str, text: &str) {
+    for line in text.lines() {
+        if line.is_empty() {
+            lines.push(format!("{}#", indent));
+        } else {
+            lines.push(format!("{}# {}", indent, line));
+        }
+    }
+}
+
+/// Push a block of source, indenting every non-empty line.
+fn push_block(lines: &mut Vec<String>, indent: &str, text: &str) {
+    for line in text.lines() {
+        if line.is_empty() {
+            lines.push(String::new());
+        } else {
+            lines.push(format!("{}{}", indent, line));
+        }
+    }
+}
@@ -0,0 +1,186 @@
+/// Per-kind renderers — how each child item of a file node is emitted.
+///
+/// The assembler walks a file's children in position order and hands each
+/// one to the renderer registered for its kind. Kinds without a registered
+/// renderer fall back to `ItemRenderer`, which emits stored source verbatim.
+/// To reconstruct a new kind, implement `KindRenderer` and add it to
+/// `registry()`.
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use crate::parser::kinds::Kind;
+use super::assembler::{
+    emit_comment_item, emit_item, emit_suggestions_for_item, AssemblyOptions, ChildItem,
+    FnQualifiers, SuggestionForEmit,
+};
+use super::generics;
+
+/// State shared by all renderers while a single file is assembled.
+pub(super) struct RenderContext<'a> {
+    pub parts: &'a mut Vec<String>,
+    pub options: &'a AssemblyOptions,
+    /// Whether imports were already emitted as a sorted block.
+    pub sort_imports: bool,
+    pub direct_comment_ids: &'a HashSet<String>,
+    pub suggestions: &'a HashMap<String, Vec<SuggestionForEmit>>,
+}
+
+/// Emits the source lines for one child item of a file node.
+pub(super) trait KindRenderer: Sync {
+    fn render(&self, item: &ChildItem, ctx: &mut RenderContext);
+}
+
+/// Default renderer: suggestions, then the item's stored source (or its
+/// content with outer doc comments when no source was recorded).
+struct ItemRenderer;
+
+impl KindRenderer for ItemRenderer {
+    fn render(&self, item: &ChildItem, ctx: &mut RenderContext) {
+        emit_suggestions_for_item(ctx.parts, &item.id, ctx.suggestions);
+        emit_item(ctx.parts, item, ctx.direct_comment_ids);
+    }
+}
+
+/// `use` items: skipped when the sorted import block already covered them.
+struct UseRenderer;
+
+impl KindRenderer for UseRenderer {
+    fn render(&self, item: &ChildItem, ctx: &mut RenderContext) {
+        if !ctx.sort_imports {
+            ItemRenderer.render(item, ctx);
+        }
+    }
+}
+
+/// Items with a signature (fns, structs, enums, traits, impls): generic
+/// params, where clauses, and fn `const`/`async`/`unsafe`/`extern`
+/// qualifiers are taken from node metadata, so they survive even when the
+/// stored source has drifted from it.
+struct SignatureRenderer;
+
+impl KindRenderer for SignatureRenderer {
+    fn render(&self, item: &ChildItem, ctx: &mut RenderContext) {
+        match rewrite_signature(item) {
+            Some(source) => {
+                let item = ChildItem {
+                    source: Some(source),
+                    ..item.clone()
+                };
+                ItemRenderer.render(&item, ctx);
+            }
+            None => ItemRenderer.render(item, ctx),
+        }
+    }
+}
+
+/// Re-emit an item's source with its signature set from metadata. Returns
+/// `None` when the source doesn't parse or already matches.
+fn rewrite_signature(item: &ChildItem) -> Option<String> {
+    let source = item.source.as_deref()?;
+    let mut parsed: syn::Item = syn::parse_str(source).ok()?;
+    let mut changed = false;
+
+    let target = match &mut parsed {
+        syn::Item::Fn(f) => Some(&mut f.sig.generics),
+        syn::Item::Struct(s) => Some(&mut s.generics),
+        syn::Item::Enum(e) => Some(&mut e.generics),
+        syn::Item::Trait(t) => Some(&mut t.generics),
+        syn::Item::Impl(i) => Some(&mut i.generics),
+        _ => None,
+    };
+    if let Some(target) = target {
+        let stored = generics::generics_from_metadata(
+            item.generics.as_ref(),
+            item.where_preds.as_ref(),
+            target,
+        );
+        if let Some(stored) = stored {
+            if generics_tokens(target) != generics_tokens(&stored) {
+                *target = stored;
+                changed = true;
+            }
+        }
+    }
+
+    if let (syn::Item::Fn(f), Some(q)) = (&mut parsed, &item.fn_qualifiers) {
+        changed |= apply_fn_qualifiers(&mut f.sig, q);
+    }
+
+    changed.then(|| quote::quote!(#parsed).to_string())
+}
+
+/// Generic params plus where clause, which `Generics` doesn't print itself.
+fn generics_tokens(generics: &syn::Generics) -> String {
+    let where_clause = &generics.where_clause;
+    quote::quote!(#generics #where_clause).to_string()
+}
+
+/// Set a fn signature's qualifiers from `q`, returning whether any changed.
+/// Rust fixes their order as `const async unsafe extern "abi" fn`, which
+/// quoting the syn signature reproduces.
+fn apply_fn_qualifiers(sig: &mut syn::Signature, q: &FnQualifiers) -> bool {
+    let current_abi = sig.abi.as_ref().map(|a| {
+        a.name
+            .as_ref()
+            .map(|n| n.value())
+            .unwrap_or_else(|| "C".into())
+    });
+    if sig.asyncness.is_some() == q.is_async
+        && sig.constness.is_some() == q.is_const
+        && sig.unsafety.is_some() == q.is_unsafe
+        && current_abi == q.abi
+    {
+        return false;
+    }
+
+    sig.constness = q.is_const.then(Default::default);
+    sig.asyncness = q.is_async.then(Default::default);
+    sig.unsafety = q.is_unsafe.then(Default::default);
+    sig.abi = q.abi.as_ref().map(|name| syn::Abi {
+        extern_token: Default::default(),
+        name: Some(syn::LitStr::new(name, proc_macro2::Span::call_site())),
+    });
+    true
+}
+
+/// Standalone line and block comments. Trailing comments are attached to
+/// their item by `emit_item`, and comments above imports are dropped when
+/// the imports are reordered.
+struct CommentRenderer;
+
+impl KindRenderer for CommentRenderer {
+    fn render(&self, item: &ChildItem, ctx: &mut RenderContext) {
+        if ctx.sort_imports && item.consumed_by_import_sort {
+            return;
+        }
+        if item.placement.as_deref().unwrap_or("above") == "trailing" {
+            return;
+        }
+        emit_comment_item(ctx.parts, item, ctx.options.reflow_comments);
+    }
+}
+
+/// Renderers keyed by node kind.
+fn registry() -> &'static HashMap<Kind, &'static dyn KindRenderer> {
+    static REGISTRY: OnceLock<HashMap<Kind, &'static dyn KindRenderer>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut map: HashMap<Kind, &'static dyn KindRenderer> = HashMap::new();
+        map.insert(Kind::Fn, &SignatureRenderer);
+        map.insert(Kind::Struct, &SignatureRenderer);
+        map.insert(Kind::Enum, &SignatureRenderer);
+        map.insert(Kind::Trait, &SignatureRenderer);
+        map.insert(Kind::Impl, &SignatureRenderer);
+        map.insert(Kind::Use, &UseRenderer);
+        map.insert(Kind::Comment, &CommentRenderer);
+        map.insert(Kind::CommentBlock, &CommentRenderer);
+        map
+    })
+}
+
+/// Look up the renderer for a stored kind string.
+pub(super) fn renderer_for(kind: &str) -> &'static dyn KindRenderer {
+    kind.parse::<Kind>()
+        .ok()
+        .and_then(|k| registry().get(&k).copied())
+        .unwrap_or(&ItemRenderer)
+}
@@ -8,3 +8,9 @@ pub const REPO_TAG: &str = "repo_tag";
 pub const REPO_BRANCH: &str = "repo_branch";
 pub const REPO_OPAQUE_TEXT: &str = "repo_opaque_text";
 pub const REPO_OPAQUE_BINARY: &str = "repo_opaque_binary";
+
+/// All repository kinds, for kind validation and `kerai.list_kinds()`.
+pub const ALL: &[&str] = &[
+    REPO_REPOSITORY, REPO_COMMIT, REPO_DIRECTORY, REPO_TAG,
+    REPO_BRANCH, REPO_OPAQUE_TEXT, REPO_OPAQUE_BINARY,
+];
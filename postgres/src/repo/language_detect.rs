@@ -47,18 +47,38 @@ pub fn classify(filename: &str, content_sample: Option<&[u8]>) -> LanguageClass
     let ext = match basename.rsplit_once('.') {
         Some((_, ext)) => ext.to_lowercase(),
         None => {
-            // No extension — check content for binary
+            // No extension — check content for binary, then sniff it
             return if is_binary(content_sample) {
                 LanguageClass::Binary
             } else {
-                LanguageClass::OpaqueText("text".to_string())
+                classify_sniffed(content_sample)
+                    .unwrap_or_else(|| LanguageClass::OpaqueText("text".to_string()))
             };
         }
     };
 
+    if AMBIGUOUS_EXTENSIONS.contains(&ext.as_str()) && !is_binary(content_sample) {
+        if let Some(class) = classify_sniffed(content_sample) {
+            return class;
+        }
+    }
+
     classify_extension(&ext, content_sample)
 }
 
+/// Classify a text sample by shebang or content, if either is recognised.
+fn classify_sniffed(content_sample: Option<&[u8]>) -> Option<LanguageClass> {
+    let text = String::from_utf8_lossy(content_sample?);
+    let (lang, _) = sniff_language(&text)?;
+    Some(match lang.as_str() {
+        "rust" => LanguageClass::Parseable(ParseableLanguage::Rust),
+        "go" => LanguageClass::Parseable(ParseableLanguage::Go),
+        "c" => LanguageClass::Parseable(ParseableLanguage::C),
+        "markdown" => LanguageClass::Parseable(ParseableLanguage::Markdown),
+        _ => LanguageClass::OpaqueText(lang),
+    })
+}
+
 /// Special filenames that don't rely on extension.
 fn classify_special_filename(basename: &str) -> Option<LanguageClass> {
     match basename {
@@ -188,6 +208,107 @@ fn classify_extension(ext: &str, content_sample: Option<&[u8]>) -> LanguageClass
     }
 }
 
+/// Extensions that say little about a file's language (`notes.txt` may well be
+/// a shell script), so content sniffing takes precedence when it finds a match.
+pub const AMBIGUOUS_EXTENSIONS: &[&str] = &["txt", "text", "in", "cgi"];
+
+/// How a language was chosen for a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectedBy {
+    Shebang,
+    Content,
+}
+
+impl DetectedBy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Shebang => "shebang",
+            Self::Content => "content",
+        }
+    }
+}
+
+/// Pick a language from file content alone: a `#!` interpreter line first,
+/// then a few unambiguous source patterns. Returns `None` if nothing matches.
+pub fn sniff_language(content: &str) -> Option<(String, DetectedBy)> {
+    let first_line = content.lines().next().unwrap_or("");
+    if let Some(lang) = shebang_language(first_line) {
+        return Some((lang.to_string(), DetectedBy::Shebang));
+    }
+    content_language(content).map(|lang| (lang.to_string(), DetectedBy::Content))
+}
+
+/// Map a `#!` line to a language by its interpreter, looking through
+/// `/usr/bin/env` (and its `-S` flag) to the real command.
+pub fn shebang_language(line: &str) -> Option<&'static str> {
+    let rest = line.strip_prefix("#!")?.trim();
+    let mut words = rest.split_whitespace();
+    let mut interp = words.next()?.rsplit('/').next()?;
+    if interp == "env" {
+        interp = words.find(|w| !w.starts_with('-'))?;
+    }
+    // python3.11 → python, ruby2.7 → ruby
+    let base = interp.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+    let lang = match base {
+        "sh" | "bash" | "zsh" | "ksh" | "dash" | "ash" => "shell",
+        "fish" => "fish",
+        "python" | "pypy" => "python",
+        "ruby" => "ruby",
+        "perl" => "perl",
+        "node" | "nodejs" | "deno" | "bun" => "javascript",
+        "ts-node" => "typescript",
+        "php" => "php",
+        "lua" | "luajit" => "lua",
+        "Rscript" => "r",
+        "julia" => "julia",
+        "elixir" => "elixir",
+        "escript" => "erlang",
+        "pwsh" | "powershell" => "powershell",
+        "make" => "make",
+        "awk" | "gawk" | "mawk" => "awk",
+        "tclsh" | "wish" => "tcl",
+        "run-cargo-script" | "rust-script" => "rust",
+        _ => return None,
+    };
+    Some(lang)
+}
+
+/// Recognise a handful of parseable languages by their opening lines.
+/// Deliberately conservative: a wrong guess routes text to a parser that
+/// will reject it, so only strong signals count.
+fn content_language(content: &str) -> Option<&'static str> {
+    let lines: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .take(50)
+        .collect();
+    let first = lines.first()?;
+
+    if lines.iter().any(|l| l.starts_with("package ") && !l.ends_with(';'))
+        && lines.iter().any(|l| l.starts_with("func ") || l.starts_with("import "))
+    {
+        return Some("go");
+    }
+    if lines.iter().any(|l| l.starts_with("#include <") || l.starts_with("#include \""))
+        && !lines.iter().any(|l| l.contains("std::") || l.starts_with("class ") || l.starts_with("namespace "))
+    {
+        return Some("c");
+    }
+    if lines.iter().any(|l| l.starts_with("use std::") || l.starts_with("fn main()") || l.starts_with("pub fn "))
+    {
+        return Some("rust");
+    }
+    if first.starts_with("\\documentclass") || first.starts_with("\\begin{document}") {
+        return Some("latex");
+    }
+    if first.starts_with("# ") && lines.iter().skip(1).any(|l| l.starts_with("## ") || l.starts_with("- ")) {
+        return Some("markdown");
+    }
+    None
+}
+
 /// Check if content is likely binary by looking for null bytes in the sample.
 /// This mirrors git's own heuristic.
 fn is_binary(sample: Option<&[u8]>) -> bool {
@@ -341,6 +462,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_shebang_detection() {
+        assert_eq!(shebang_language("#!/usr/bin/env python3"), Some("python"));
+        assert_eq!(shebang_language("#!/bin/bash -e"), Some("shell"));
+        assert_eq!(shebang_language("#!/usr/bin/env -S node --harmony"), Some("javascript"));
+        assert_eq!(shebang_language("#!/usr/bin/perl -w"), Some("perl"));
+        assert_eq!(shebang_language("#!/opt/unknown"), None);
+        assert_eq!(shebang_language("# not a shebang"), None);
+
+        assert_eq!(
+            classify("bin/deploy", Some(b"#!/bin/sh\necho hi\n")),
+            LanguageClass::OpaqueText("shell".to_string())
+        );
+        assert_eq!(
+            classify("notes.txt", Some(b"#!/usr/bin/env python\nprint(1)\n")),
+            LanguageClass::OpaqueText("python".to_string())
+        );
+    }
+
+    #[test]
+    fn test_content_heuristics() {
+        assert_eq!(
+            classify("main", Some(b"package main\n\nfunc main() {}\n")),
+            LanguageClass::Parseable(ParseableLanguage::Go)
+        );
+        assert_eq!(
+            classify("snippet.txt", Some(b"#include <stdio.h>\nint main(void) { return 0; }\n")),
+            LanguageClass::Parseable(ParseableLanguage::C)
+        );
+        // Plain prose stays opaque text
+        assert_eq!(
+            classify("notes.txt", Some(b"Remember to buy milk.\n")),
+            LanguageClass::OpaqueText("text".to_string())
+        );
+        // An explicit extension wins over content
+        assert_eq!(
+            classify("tool.py", Some(b"#!/bin/sh\n")),
+            LanguageClass::OpaqueText("python".to_string())
+        );
+    }
+
     #[test]
     fn test_path_with_directories() {
         assert_eq!(
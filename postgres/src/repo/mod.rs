@@ -13,8 +13,8 @@ mod census;
 mod cloner;
 mod commit_walker;
 pub mod kinds;
-mod language_detect;
-mod tree_walker;
+pub(crate) mod language_detect;
+pub(crate) mod tree_walker;
 
 /// Get the self instance ID from the database.
 fn get_self_instance_id() -> String {
@@ -11,7 +11,7 @@ use super::kinds;
 use super::language_detect::{classify, LanguageClass, ParseableLanguage};
 
 /// Maximum size for storing opaque text source in metadata.
-const OPAQUE_TEXT_MAX: usize = 100 * 1024; // 100 KB
+pub(crate) const OPAQUE_TEXT_MAX: usize = 100 * 1024; // 100 KB
 
 /// Files larger than this are treated as binary regardless of extension.
 const TEXT_SIZE_LIMIT: usize = 1024 * 1024; // 1 MB
@@ -22,6 +22,8 @@ CREATE TABLE kerai.instances (
     endpoint        TEXT,
     description     TEXT,
     is_self         BOOLEAN NOT NULL DEFAULT false,
+    trust_level     TEXT NOT NULL DEFAULT 'full'
+                    CHECK (trust_level IN ('full', 'read_only', 'untrusted')),
     last_seen       TIMESTAMPTZ,
     metadata        JSONB DEFAULT '{}'::jsonb,
     created_at      TIMESTAMPTZ NOT NULL DEFAULT now()
@@ -51,7 +53,8 @@ CREATE TABLE kerai.nodes (
     position    INTEGER NOT NULL DEFAULT 0,
     path        ltree,
     metadata    JSONB DEFAULT '{}'::jsonb,
-    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    content_tsv tsvector GENERATED ALWAYS AS (to_tsvector('english', COALESCE(content, ''))) STORED
 );
 
 CREATE INDEX idx_nodes_instance ON kerai.nodes (instance_id);
@@ -60,8 +63,19 @@ CREATE INDEX idx_nodes_parent ON kerai.nodes (parent_id);
 CREATE INDEX idx_nodes_path ON kerai.nodes USING gist (path);
 CREATE INDEX idx_nodes_language ON kerai.nodes (language) WHERE language IS NOT NULL;
 CREATE INDEX idx_nodes_parent_position ON kerai.nodes (parent_id, position);
-CREATE INDEX idx_nodes_content_fts ON kerai.nodes
-    USING gin (to_tsvector('english', COALESCE(content, '')));
+CREATE INDEX idx_nodes_content_fts ON kerai.nodes USING gin (content_tsv);
+CREATE INDEX idx_nodes_metadata ON kerai.nodes USING gin (metadata jsonb_path_ops);
+-- Name lookups when linking edges; hash, as content can outgrow a btree entry
+CREATE INDEX idx_nodes_content ON kerai.nodes USING hash (content);
+-- Callers by callee name, for linking calls edges
+CREATE INDEX idx_nodes_calls ON kerai.nodes USING gin ((metadata->'calls'))
+    WHERE metadata ? 'calls';
+-- Impls by the trait and type they name, for linking implements/for edges
+CREATE INDEX idx_nodes_impl_trait ON kerai.nodes USING hash ((metadata->>'trait_name'))
+    WHERE kind = 'impl';
+CREATE INDEX idx_nodes_impl_type ON kerai.nodes
+    USING hash ((COALESCE(metadata->>'type_name', metadata->>'self_name')))
+    WHERE kind = 'impl';
 "#,
     name = "table_nodes",
     requires = ["table_instances"]
@@ -82,6 +96,7 @@ CREATE TABLE kerai.edges (
 CREATE INDEX idx_edges_source ON kerai.edges (source_id);
 CREATE INDEX idx_edges_target ON kerai.edges (target_id);
 CREATE INDEX idx_edges_relation ON kerai.edges (relation);
+CREATE INDEX idx_edges_metadata ON kerai.edges USING gin (metadata jsonb_path_ops);
 CREATE UNIQUE INDEX idx_edges_unique_rel
     ON kerai.edges (source_id, target_id, relation);
 "#,
@@ -284,18 +299,20 @@ CREATE TABLE kerai.perspectives (
     weight      DOUBLE PRECISION NOT NULL DEFAULT 0,
     context_id  UUID REFERENCES kerai.nodes(id),
     reasoning   TEXT,
+    instance_id UUID REFERENCES kerai.instances(id),
     created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
     updated_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
     UNIQUE(agent_id, node_id, context_id)
 );
 
 CREATE INDEX idx_perspectives_agent ON kerai.perspectives(agent_id);
+CREATE INDEX idx_perspectives_instance ON kerai.perspectives(instance_id);
 CREATE INDEX idx_perspectives_node ON kerai.perspectives(node_id);
 CREATE INDEX idx_perspectives_context ON kerai.perspectives(context_id) WHERE context_id IS NOT NULL;
 CREATE INDEX idx_perspectives_weight ON kerai.perspectives(weight);
 "#,
     name = "table_perspectives",
-    requires = ["table_agents", "table_nodes"]
+    requires = ["table_instances", "table_agents", "table_nodes"]
 );
 
 // Table: associations — weighted relationships between nodes from an agent's view
@@ -412,6 +429,144 @@ CREATE INDEX idx_bids_bidder ON kerai.bids(bidder_wallet);
     requires = ["table_auctions", "table_wallets"]
 );
 
+// Alter auctions — English (ascending) auctions: bids climb by min_increment
+// until ends_at, and the high bid wins if it meets reserve_price
+extension_sql!(
+    r#"
+ALTER TABLE kerai.auctions ADD COLUMN reserve_price BIGINT;
+ALTER TABLE kerai.auctions ADD COLUMN min_increment BIGINT;
+ALTER TABLE kerai.auctions ADD COLUMN ends_at TIMESTAMPTZ;
+ALTER TABLE kerai.auctions ADD CONSTRAINT auctions_auction_type_check
+    CHECK (auction_type IN ('dutch', 'english', 'sealed'));
+"#,
+    name = "alter_auctions_english",
+    requires = ["table_auctions"]
+);
+
+// Sealed-bid (Vickrey) auctions: bidders commit sha256(amount || salt) before
+// ends_at, reveal before reveal_ends_at, and the winner pays the second price
+extension_sql!(
+    r#"
+ALTER TABLE kerai.auctions ADD COLUMN reveal_ends_at TIMESTAMPTZ;
+
+CREATE TABLE kerai.sealed_bids (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    auction_id      UUID NOT NULL REFERENCES kerai.auctions(id),
+    bidder_wallet   UUID NOT NULL REFERENCES kerai.wallets(id),
+    commitment      BYTEA NOT NULL,
+    amount          BIGINT,
+    status          TEXT NOT NULL DEFAULT 'committed'
+                    CHECK (status IN ('committed', 'revealed', 'forfeited')),
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    revealed_at     TIMESTAMPTZ,
+    UNIQUE (auction_id, bidder_wallet)
+);
+
+CREATE INDEX idx_sealed_bids_auction ON kerai.sealed_bids(auction_id);
+"#,
+    name = "table_sealed_bids",
+    requires = ["alter_auctions_english", "table_wallets"]
+);
+
+// Alter bids — withdrawal while the auction is active
+extension_sql!(
+    r#"
+ALTER TABLE kerai.bids ADD COLUMN status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active', 'withdrawn'));
+ALTER TABLE kerai.bids ADD COLUMN withdrawn_at TIMESTAMPTZ;
+"#,
+    name = "alter_bids_withdrawal",
+    requires = ["table_bids"]
+);
+
+// Alter auctions — anti-snipe: an English bid within extension_seconds of
+// ends_at pushes ends_at back by extension_seconds (0 disables)
+extension_sql!(
+    r#"
+ALTER TABLE kerai.auctions ADD COLUMN extension_seconds INTEGER NOT NULL DEFAULT 0
+    CHECK (extension_seconds >= 0);
+"#,
+    name = "alter_auctions_extension",
+    requires = ["alter_auctions_english"]
+);
+
+// Table: royalties — per-attestation revenue splits paid out on settlement.
+// Shares are in basis points; the seller keeps whatever is not allocated.
+extension_sql!(
+    r#"
+CREATE TABLE kerai.royalties (
+    attestation_id  UUID NOT NULL REFERENCES kerai.attestations(id),
+    wallet_id       UUID NOT NULL REFERENCES kerai.wallets(id),
+    basis_points    INTEGER NOT NULL CHECK (basis_points > 0 AND basis_points <= 10000),
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (attestation_id, wallet_id)
+);
+"#,
+    name = "table_royalties",
+    requires = ["table_attestations", "table_wallets"]
+);
+
+// Alter agents — the instance an agent was imported from, so only that
+// instance's signed bundles can update it (NULL: the agent is local)
+extension_sql!(
+    r#"
+ALTER TABLE kerai.agents ADD COLUMN origin_instance_id UUID REFERENCES kerai.instances(id);
+"#,
+    name = "alter_agents_origin",
+    requires = ["table_agents", "table_instances"]
+);
+
+// Table: agent_activity — actions agents take on their own behalf, such as
+// spending from their linked wallet with agent_transfer
+extension_sql!(
+    r#"
+CREATE TABLE kerai.agent_activity (
+    id          UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    agent_id    UUID NOT NULL REFERENCES kerai.agents(id),
+    action      TEXT NOT NULL,
+    details     JSONB NOT NULL DEFAULT '{}'::jsonb,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_agent_activity_agent ON kerai.agent_activity (agent_id, created_at);
+"#,
+    name = "table_agent_activity",
+    requires = ["table_agents"]
+);
+
+// Alter bounties — the reward is held in escrow from creation until the
+// bounty is paid (released to the claimer) or expires (refunded to the poster)
+extension_sql!(
+    r#"
+ALTER TABLE kerai.bounties ADD COLUMN escrow_id UUID REFERENCES kerai.escrows(id);
+"#,
+    name = "alter_bounties_escrow",
+    requires = ["table_bounties", "table_escrows"]
+);
+
+// Table: bounty_claims — claim history. A claim is 'active' until the bounty
+// is paid ('settled') or the claim lapses and is released back to 'open'
+extension_sql!(
+    r#"
+ALTER TABLE kerai.bounties ADD COLUMN claim_expires_at TIMESTAMPTZ;
+
+CREATE TABLE kerai.bounty_claims (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    bounty_id       UUID NOT NULL REFERENCES kerai.bounties(id),
+    claimer_wallet  UUID NOT NULL REFERENCES kerai.wallets(id),
+    status          TEXT NOT NULL DEFAULT 'active'
+                    CHECK (status IN ('active', 'released', 'settled')),
+    claimed_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at      TIMESTAMPTZ NOT NULL,
+    ended_at        TIMESTAMPTZ
+);
+
+CREATE INDEX idx_bounty_claims_bounty ON kerai.bounty_claims (bounty_id, claimed_at);
+"#,
+    name = "table_bounty_claims",
+    requires = ["table_bounties", "table_wallets"]
+);
+
 // Alter challenges — add auction_id for marketplace integration
 extension_sql!(
     r#"
@@ -449,6 +604,44 @@ CREATE INDEX idx_tasks_swarm ON kerai.tasks (swarm_id) WHERE swarm_id IS NOT NUL
     requires = ["table_nodes", "table_agents"]
 );
 
+// Task completion: winning agent and an optional reward paid on completion
+extension_sql!(
+    r#"
+ALTER TABLE kerai.tasks ADD COLUMN IF NOT EXISTS reward BIGINT CHECK (reward > 0);  -- nKoi
+ALTER TABLE kerai.tasks ADD COLUMN IF NOT EXISTS winner_agent_id UUID REFERENCES kerai.agents(id);
+ALTER TABLE kerai.tasks ADD COLUMN IF NOT EXISTS completed_at TIMESTAMPTZ;
+"#,
+    name = "alter_tasks_completion",
+    requires = ["table_tasks", "table_agents"]
+);
+
+// Table: task_dependencies — a task cannot launch until every task it
+// depends on has succeeded (status 'succeeded' or 'completed')
+extension_sql!(
+    r#"
+CREATE TABLE kerai.task_dependencies (
+    task_id         UUID NOT NULL REFERENCES kerai.tasks(id) ON DELETE CASCADE,
+    depends_on      UUID NOT NULL REFERENCES kerai.tasks(id) ON DELETE CASCADE,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (task_id, depends_on),
+    CHECK (task_id <> depends_on)
+);
+
+CREATE INDEX idx_task_dependencies_depends_on ON kerai.task_dependencies (depends_on);
+"#,
+    name = "table_task_dependencies",
+    requires = ["table_tasks"]
+);
+
+// Alter tasks — launch time, so budget_seconds can be measured
+extension_sql!(
+    r#"
+ALTER TABLE kerai.tasks ADD COLUMN launched_at TIMESTAMPTZ;
+"#,
+    name = "alter_tasks_launched_at",
+    requires = ["table_tasks"]
+);
+
 // Table: test_results — UNLOGGED for write performance
 extension_sql!(
     r#"
@@ -473,6 +666,24 @@ CREATE INDEX idx_test_results_task_created ON kerai.test_results (task_id, creat
     requires = ["table_tasks", "table_agents"]
 );
 
+// Table: swarm_agents — agents working a task's swarm, and whether autoscaling
+// has retired them from it
+extension_sql!(
+    r#"
+CREATE TABLE kerai.swarm_agents (
+    task_id     UUID NOT NULL REFERENCES kerai.tasks(id),
+    agent_id    UUID NOT NULL REFERENCES kerai.agents(id),
+    status      TEXT NOT NULL DEFAULT 'active',  -- 'active' or 'stopped'
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (task_id, agent_id)
+);
+
+CREATE INDEX idx_swarm_agents_agent ON kerai.swarm_agents (agent_id);
+"#,
+    name = "table_swarm_agents",
+    requires = ["table_tasks", "table_agents"]
+);
+
 // Table: bounties — task bounties funded by wallets
 extension_sql!(
     r#"
@@ -485,6 +696,8 @@ CREATE TABLE kerai.bounties (
     reward          BIGINT NOT NULL CHECK (reward > 0),
     status          TEXT NOT NULL DEFAULT 'open',
     claimed_by      UUID REFERENCES kerai.wallets(id),
+    reserved_by     UUID REFERENCES kerai.wallets(id),
+    reserved_until  TIMESTAMPTZ,
     verified_at     TIMESTAMPTZ,
     created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
     expires_at      TIMESTAMPTZ
@@ -499,6 +712,28 @@ CREATE INDEX idx_bounties_reward ON kerai.bounties (reward);
     requires = ["table_wallets"]
 );
 
+// Table: escrows — funds held in the escrow wallet until released or refunded
+extension_sql!(
+    r#"
+CREATE TABLE kerai.escrows (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    from_wallet     UUID NOT NULL REFERENCES kerai.wallets(id),
+    amount          BIGINT NOT NULL CHECK (amount > 0),  -- nKoi
+    reason          TEXT NOT NULL,
+    status          TEXT NOT NULL DEFAULT 'held'
+                    CHECK (status IN ('held', 'released', 'refunded')),
+    released_to     UUID REFERENCES kerai.wallets(id),
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    settled_at      TIMESTAMPTZ
+);
+
+CREATE INDEX idx_escrows_from ON kerai.escrows (from_wallet);
+CREATE INDEX idx_escrows_status ON kerai.escrows (status);
+"#,
+    name = "table_escrows",
+    requires = ["table_wallets"]
+);
+
 // Table: operations — CRDT operation log (stub for Plan 04)
 extension_sql!(
     r#"
@@ -512,10 +747,13 @@ CREATE TABLE kerai.operations (
     author_seq  BIGINT NOT NULL,
     payload     JSONB NOT NULL DEFAULT '{}'::jsonb,
     signature   BYTEA,
+    target_path ltree,  -- path of the affected node; NULL for ops without one
+    applied     BOOLEAN NOT NULL DEFAULT true,  -- false while recorded from a read_only peer
     created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
 );
 
 CREATE INDEX idx_operations_instance ON kerai.operations (instance_id);
+CREATE INDEX idx_operations_target_path ON kerai.operations USING gist (target_path);
 CREATE INDEX idx_operations_node ON kerai.operations (node_id) WHERE node_id IS NOT NULL;
 CREATE INDEX idx_operations_author ON kerai.operations (author);
 CREATE INDEX idx_operations_lamport ON kerai.operations (lamport_ts);
@@ -538,6 +776,22 @@ CREATE TABLE kerai.version_vector (
     requires = ["schema_bootstrap"]
 );
 
+// Table: pending_ops — peer ops held back by ingest_ops until their
+// author's previous op has been applied
+extension_sql!(
+    r#"
+CREATE TABLE kerai.pending_ops (
+    author      TEXT NOT NULL,
+    author_seq  BIGINT NOT NULL,
+    op          JSONB NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (author, author_seq)
+);
+"#,
+    name = "table_pending_ops",
+    requires = ["schema_bootstrap"]
+);
+
 // Table: reward_schedule — configurable emission rates per work type
 extension_sql!(
     r#"
@@ -553,6 +807,21 @@ CREATE TABLE kerai.reward_schedule (
     requires = ["schema_bootstrap"]
 );
 
+// Table: reward_scope_multipliers — per-subtree scaling of reward_schedule amounts
+extension_sql!(
+    r#"
+CREATE TABLE kerai.reward_scope_multipliers (
+    scope       ltree PRIMARY KEY,
+    multiplier  DOUBLE PRECISION NOT NULL CHECK (multiplier > 0),
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_reward_scope_multipliers_scope ON kerai.reward_scope_multipliers USING gist (scope);
+"#,
+    name = "table_reward_scope_multipliers",
+    requires = ["schema_bootstrap"]
+);
+
 // Table: reward_log — audit trail for auto-mints
 extension_sql!(
     r#"
@@ -569,6 +838,22 @@ CREATE TABLE kerai.reward_log (
     requires = ["table_wallets"]
 );
 
+// Table: mining_schedule — single row driving the background mining worker
+extension_sql!(
+    r#"
+CREATE TABLE kerai.mining_schedule (
+    id               BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    interval_seconds INTEGER NOT NULL DEFAULT 0 CHECK (interval_seconds >= 0),  -- 0 = disabled
+    last_run_at      TIMESTAMPTZ,
+    last_result      JSONB
+);
+
+INSERT INTO kerai.mining_schedule DEFAULT VALUES;
+"#,
+    name = "table_mining_schedule",
+    requires = ["table_reward_log"]
+);
+
 // Table: repositories — ingested git repositories
 extension_sql!(
     r#"
@@ -610,6 +895,35 @@ INSERT INTO kerai.reward_schedule (work_type, reward) VALUES
     requires = ["table_reward_schedule"]
 );
 
+// Alter reward_schedule: halve each reward every halving_interval mints of
+// its work type (NULL never halves)
+extension_sql!(
+    r#"
+ALTER TABLE kerai.reward_schedule ADD COLUMN halving_interval BIGINT
+    CHECK (halving_interval IS NULL OR halving_interval > 0);
+
+UPDATE kerai.reward_schedule SET halving_interval = 100000;
+"#,
+    name = "alter_reward_schedule_halving",
+    requires = ["seed_reward_schedule"]
+);
+
+// Alter reward_schedule/reward_log: at most rate_limit mints of a work type per
+// rate_window for this instance (NULL is unlimited)
+extension_sql!(
+    r#"
+ALTER TABLE kerai.reward_schedule
+    ADD COLUMN rate_limit INTEGER CHECK (rate_limit IS NULL OR rate_limit > 0),
+    ADD COLUMN rate_window INTERVAL CHECK (rate_window IS NULL OR rate_window > interval '0');
+
+ALTER TABLE kerai.reward_log ADD COLUMN instance_id UUID REFERENCES kerai.instances(id);
+
+CREATE INDEX idx_reward_log_rate ON kerai.reward_log (instance_id, work_type, created_at);
+"#,
+    name = "alter_reward_rate_limit",
+    requires = ["table_reward_schedule", "table_reward_log", "table_instances"]
+);
+
 // Alter wallets: add nonce column for replay protection
 extension_sql!(
     r#"
@@ -619,6 +933,43 @@ ALTER TABLE kerai.wallets ADD COLUMN nonce BIGINT NOT NULL DEFAULT 0;
     requires = ["table_wallets"]
 );
 
+// Alter wallets: m-of-n approval. A wallet's signers are its own key plus any
+// in wallet_signers; transfers out need required_signers of them.
+extension_sql!(
+    r#"
+ALTER TABLE kerai.wallets ADD COLUMN required_signers INTEGER NOT NULL DEFAULT 1
+    CHECK (required_signers >= 1);
+
+CREATE TABLE kerai.wallet_signers (
+    wallet_id       UUID NOT NULL REFERENCES kerai.wallets(id) ON DELETE CASCADE,
+    public_key      BYTEA NOT NULL,
+    key_fingerprint TEXT NOT NULL,
+    added_at        TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (wallet_id, key_fingerprint)
+);
+"#,
+    name = "alter_wallets_multisig",
+    requires = ["table_wallets"]
+);
+
+// Table: nonce_events — rejected transfer nonces and admin resets, for recovery diagnostics
+extension_sql!(
+    r#"
+CREATE TABLE kerai.nonce_events (
+    id          UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    wallet_id   UUID NOT NULL REFERENCES kerai.wallets(id),
+    event       TEXT NOT NULL CHECK (event IN ('rejected', 'reset')),
+    expected    BIGINT NOT NULL,  -- nonce the wallet would have accepted
+    nonce       BIGINT NOT NULL,  -- attempted nonce, or new nonce for resets
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_nonce_events_wallet ON kerai.nonce_events (wallet_id, created_at);
+"#,
+    name = "table_nonce_events",
+    requires = ["alter_wallets_nonce"]
+);
+
 // Table: preferences — per-instance key/value settings
 extension_sql!(
     r#"
@@ -640,6 +991,33 @@ CREATE INDEX idx_preferences_category ON kerai.preferences (category);
     requires = ["table_instances"]
 );
 
+// Trigger: reject unknown kinds when config/strict_kinds = 'true'. The body
+// calls kerai.is_known_kind only when it fires, so the function needn't exist yet
+extension_sql!(
+    r#"
+CREATE FUNCTION kerai.check_node_kind() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM kerai.preferences p
+        JOIN kerai.instances i ON i.id = p.instance_id
+        WHERE i.is_self = true
+          AND p.category = 'config' AND p.key = 'strict_kinds' AND p.value = 'true'
+    ) AND NOT kerai.is_known_kind(NEW.kind) THEN
+        RAISE EXCEPTION 'Unknown node kind: ''%''', NEW.kind;
+    END IF;
+    RETURN NEW;
+END;
+$$;
+
+CREATE TRIGGER trg_nodes_check_kind
+    BEFORE INSERT OR UPDATE OF kind ON kerai.nodes
+    FOR EACH ROW EXECUTE FUNCTION kerai.check_node_kind();
+"#,
+    name = "trigger_nodes_check_kind",
+    requires = ["table_nodes", "table_preferences"]
+);
+
 // Table: model_vocab — node UUID ↔ dense integer index per model
 extension_sql!(
     r#"
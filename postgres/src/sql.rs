@@ -49,3 +49,16 @@ pub fn sql_jsonb(val: &serde_json::Value) -> String {
 pub fn sql_ltree(path: &str) -> String {
     format!("'{}'::ltree", sql_escape(path))
 }
+
+/// Whether `path` is a valid ltree: dot-separated, non-empty labels of
+/// letters, digits, `_` and `-`.
+pub fn is_ltree_path(path: &str) -> bool {
+    !path.is_empty()
+        && path.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 1000
+                && label
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        })
+}
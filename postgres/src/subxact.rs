@@ -0,0 +1,71 @@
+/// Internal subtransactions around code that may raise a Postgres ERROR.
+///
+/// Catching an ERROR with `PgTryBuilder` alone leaves the surrounding
+/// transaction aborted, so the next SPI call fails. These helpers run the
+/// guarded code in a subtransaction (as PL/pgSQL's `EXCEPTION` blocks do) and
+/// roll it back on error, leaving the caller's transaction usable.
+use std::panic::AssertUnwindSafe;
+
+use pgrx::pg_sys;
+use pgrx::pg_sys::panic::CaughtError;
+
+/// Run `f` in a subtransaction that is committed when it returns and rolled
+/// back when it raises. Returns the caught error instead of propagating it.
+pub fn try_subtransaction<T>(f: impl FnOnce() -> T) -> Result<T, CaughtError> {
+    run(f, true)
+}
+
+/// Run `f` in a subtransaction that is always rolled back, discarding any
+/// writes it made. Errors are caught and returned as for
+/// [`try_subtransaction`].
+pub fn rolled_back_subtransaction<T>(f: impl FnOnce() -> T) -> Result<T, CaughtError> {
+    run(f, false)
+}
+
+fn run<T>(f: impl FnOnce() -> T, commit: bool) -> Result<T, CaughtError> {
+    let (context, owner) = unsafe { (pg_sys::CurrentMemoryContext, pg_sys::CurrentResourceOwner) };
+    // Put the caller's memory context and resource owner back after the
+    // subtransaction ends, whichever way it ends
+    let restore = move || unsafe {
+        pg_sys::CurrentMemoryContext = context;
+        pg_sys::CurrentResourceOwner = owner;
+    };
+
+    unsafe {
+        pg_sys::BeginInternalSubTransaction(std::ptr::null());
+        pg_sys::CurrentMemoryContext = context;
+    }
+
+    let f = AssertUnwindSafe(f);
+    pgrx::PgTryBuilder::new(move || {
+        let value = f();
+        unsafe {
+            if commit {
+                pg_sys::ReleaseCurrentSubTransaction();
+            } else {
+                pg_sys::RollbackAndReleaseCurrentSubTransaction();
+            }
+        }
+        restore();
+        Ok(value)
+    })
+    .catch_others(move |err| {
+        unsafe {
+            pg_sys::CurrentMemoryContext = context;
+            pg_sys::RollbackAndReleaseCurrentSubTransaction();
+        }
+        restore();
+        Err(err)
+    })
+    .execute()
+}
+
+/// The message of a caught error, for re-raising it with more context.
+pub fn caught_message(err: CaughtError) -> String {
+    match err {
+        CaughtError::PostgresError(report) | CaughtError::ErrorReport(report) => {
+            report.message().to_string()
+        }
+        CaughtError::RustPanic { ereport, .. } => ereport.message().to_string(),
+    }
+}
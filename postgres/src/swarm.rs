@@ -4,6 +4,7 @@ use pgrx::prelude::*;
 use crate::sql::sql_escape;
 
 /// Launch a swarm for a task. Creates a swarm agent, links it to the task, sets status='running'.
+/// Every task it depends on (see `add_task_dependency`) must have succeeded.
 #[pg_extern]
 fn launch_swarm(
     task_id: pgrx::Uuid,
@@ -24,6 +25,13 @@ fn launch_swarm(
         Some(s) => error!("Task must be 'pending' to launch swarm, currently '{}'", s),
     }
 
+    if let Some((dep_id, dep_status)) = crate::tasks::unmet_dependencies(task_id).first() {
+        error!(
+            "Cannot launch task {}: unmet dependency {} (currently '{}')",
+            task_id, dep_id, dep_status
+        );
+    }
+
     // Create swarm agent with name derived from task_id
     let task_short = &task_id.to_string()[..8];
     let swarm_name = format!("swarm-{}", task_short);
@@ -57,6 +65,7 @@ fn launch_swarm(
              agent_kind = '{}',
              agent_model = {},
              agent_count = {},
+             launched_at = now(),
              updated_at = now()
          WHERE id = '{}'::uuid",
         sql_escape(&swarm_id),
@@ -105,7 +114,9 @@ fn stop_swarm(task_id: pgrx::Uuid) -> pgrx::JsonB {
     }))
 }
 
-/// Record a test result for a task from a named agent.
+/// Record a test result for a task from a named agent. Its `ops_count`
+/// counts against the task's `budget_ops`; a running task that has used up
+/// its budget is stopped (`task_stopped` in the result).
 #[pg_extern]
 fn record_test_result(
     task_id: pgrx::Uuid,
@@ -166,6 +177,8 @@ fn record_test_result(
     .unwrap()
     .unwrap();
 
+    let task_stopped = crate::tasks::stop_if_over_budget(task_id);
+
     pgrx::JsonB(serde_json::json!({
         "id": result_id,
         "task_id": task_id.to_string(),
@@ -173,6 +186,7 @@ fn record_test_result(
         "passed": passed,
         "duration_ms": duration_ms,
         "ops_count": ops_count,
+        "task_stopped": task_stopped,
     }))
 }
 
@@ -230,7 +244,171 @@ fn swarm_progress(task_id: pgrx::Uuid) -> pgrx::JsonB {
     json
 }
 
-/// Task overview with test result counts. NULL task_id = all tasks.
+/// Complete a task once its success criterion is met: at least `quorum`
+/// agents whose latest result passed. The winner is the agent whose passing
+/// latest result came first (fastest run breaks ties). If the task has a
+/// `reward`, it is transferred from the self instance wallet to the winner's
+/// wallet; a winner without a wallet, or an underfunded instance wallet,
+/// leaves the reward unpaid but the task still completes.
+///
+/// Returns `{task_id, status, completed, passing_agents, quorum, winner, reward}`.
+/// Tasks that are already completed report their recorded winner.
+#[pg_extern]
+fn evaluate_task(task_id: pgrx::Uuid, quorum: default!(i32, 1)) -> pgrx::JsonB {
+    if quorum < 1 {
+        error!("quorum must be at least 1");
+    }
+
+    let task = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object('status', t.status, 'reward', t.reward, 'winner', w.name)
+         FROM kerai.tasks t LEFT JOIN kerai.agents w ON t.winner_agent_id = w.id
+         WHERE t.id = '{}'::uuid",
+        task_id,
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Task not found: {}", task_id))
+    .0;
+
+    let status = task["status"].as_str().unwrap_or("");
+    if status == "completed" {
+        return pgrx::JsonB(serde_json::json!({
+            "task_id": task_id.to_string(),
+            "status": status,
+            "completed": true,
+            "winner": task["winner"],
+        }));
+    }
+    if status != "pending" && status != "running" {
+        error!("Task must be 'pending' or 'running' to evaluate, currently '{}'", status);
+    }
+
+    // Each agent's latest result; passing ones in the order they passed
+    let passing = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'agent_id', l.agent_id, 'agent_name', a.name, 'wallet_id', a.wallet_id
+         ) ORDER BY l.created_at, l.duration_ms NULLS LAST), '[]'::jsonb)
+         FROM (
+            SELECT DISTINCT ON (agent_id) agent_id, passed, created_at, duration_ms
+            FROM kerai.test_results
+            WHERE task_id = '{}'::uuid
+            ORDER BY agent_id, created_at DESC
+         ) l
+         JOIN kerai.agents a ON a.id = l.agent_id
+         WHERE l.passed",
+        task_id,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])))
+    .0;
+    let passing = passing.as_array().cloned().unwrap_or_default();
+    let passing_names: Vec<&serde_json::Value> =
+        passing.iter().map(|p| &p["agent_name"]).collect();
+
+    if passing.len() < quorum as usize {
+        return pgrx::JsonB(serde_json::json!({
+            "task_id": task_id.to_string(),
+            "status": status,
+            "completed": false,
+            "passing_agents": passing_names,
+            "quorum": quorum,
+        }));
+    }
+
+    // Only the evaluation whose update flips the status pays the reward; a
+    // concurrent one that lost the race reports the recorded winner instead
+    let winner = &passing[0];
+    let winner_id = winner["agent_id"].as_str().unwrap_or_default();
+    let completed = Spi::get_one::<pgrx::Uuid>(&format!(
+        "UPDATE kerai.tasks
+         SET status = 'completed', winner_agent_id = '{}'::uuid,
+             completed_at = now(), updated_at = now()
+         WHERE id = '{}'::uuid AND status IN ('pending', 'running')
+         RETURNING id",
+        sql_escape(winner_id),
+        task_id,
+    ))
+    .unwrap_or(None);
+    if completed.is_none() {
+        let recorded = Spi::get_one::<pgrx::JsonB>(&format!(
+            "SELECT jsonb_build_object('status', t.status, 'winner', w.name)
+             FROM kerai.tasks t LEFT JOIN kerai.agents w ON t.winner_agent_id = w.id
+             WHERE t.id = '{}'::uuid",
+            task_id,
+        ))
+        .unwrap()
+        .unwrap()
+        .0;
+        return pgrx::JsonB(serde_json::json!({
+            "task_id": task_id.to_string(),
+            "status": recorded["status"],
+            "completed": recorded["status"] == "completed",
+            "winner": recorded["winner"],
+        }));
+    }
+
+    let reward = task["reward"].as_i64().map(|amount| {
+        pay_task_reward(&task_id.to_string(), amount, winner["wallet_id"].as_str())
+    });
+
+    pgrx::JsonB(serde_json::json!({
+        "task_id": task_id.to_string(),
+        "status": "completed",
+        "completed": true,
+        "passing_agents": passing_names,
+        "quorum": quorum,
+        "winner": winner["agent_name"],
+        "reward": reward,
+    }))
+}
+
+/// Transfer a task's reward from the self instance wallet to the winner.
+/// Reports why the reward went unpaid instead of failing the completion.
+fn pay_task_reward(task_id: &str, amount: i64, winner_wallet: Option<&str>) -> serde_json::Value {
+    let unpaid = |reason: &str| serde_json::json!({"amount": amount, "paid": false, "reason": reason});
+    let Some(to_wallet) = winner_wallet else {
+        return unpaid("winner has no wallet");
+    };
+
+    let from_wallet = Spi::get_one::<String>(
+        "SELECT w.id::text FROM kerai.wallets w
+         JOIN kerai.instances i ON w.instance_id = i.id
+         WHERE i.is_self = true AND w.wallet_type = 'instance'",
+    )
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Self instance wallet not found"));
+
+    let balance = Spi::get_one::<i64>(&format!(
+        "SELECT ((SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE to_wallet = '{0}'::uuid)
+               - (SELECT COALESCE(SUM(amount), 0) FROM kerai.ledger WHERE from_wallet = '{0}'::uuid))::bigint",
+        sql_escape(&from_wallet),
+    ))
+    .unwrap()
+    .unwrap_or(0);
+    if balance < amount {
+        return unpaid("insufficient instance balance");
+    }
+
+    let transfer = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT kerai.transfer_koi('{}'::uuid, '{}'::uuid, {}, '{}')",
+        sql_escape(&from_wallet),
+        sql_escape(to_wallet),
+        amount,
+        sql_escape(&format!("task_reward:{}", task_id)),
+    ))
+    .unwrap()
+    .unwrap()
+    .0;
+
+    serde_json::json!({
+        "amount": amount,
+        "paid": true,
+        "wallet_id": to_wallet,
+        "ledger_id": transfer["id"],
+    })
+}
+
+/// Task overview with test result counts and, once completed, the winner.
+/// NULL task_id = all tasks.
 #[pg_extern]
 fn swarm_status(task_id: Option<pgrx::Uuid>) -> pgrx::JsonB {
     let where_clause = match task_id {
@@ -251,6 +429,8 @@ fn swarm_status(task_id: Option<pgrx::Uuid>) -> pgrx::JsonB {
                 t.agent_kind,
                 t.agent_count,
                 a.name AS swarm_name,
+                w.name AS winner,
+                t.completed_at,
                 count(tr.id) AS total_results,
                 count(tr.id) FILTER (WHERE tr.passed) AS passed,
                 count(tr.id) FILTER (WHERE NOT tr.passed) AS failed,
@@ -258,10 +438,11 @@ fn swarm_status(task_id: Option<pgrx::Uuid>) -> pgrx::JsonB {
                 t.updated_at
             FROM kerai.tasks t
             LEFT JOIN kerai.agents a ON t.swarm_id = a.id
+            LEFT JOIN kerai.agents w ON t.winner_agent_id = w.id
             LEFT JOIN kerai.test_results tr ON tr.task_id = t.id
             {}
             GROUP BY t.id, t.description, t.status, t.agent_kind, t.agent_count,
-                     a.name, t.created_at, t.updated_at
+                     a.name, w.name, t.completed_at, t.created_at, t.updated_at
         ) sub",
         where_clause,
     ))
@@ -269,3 +450,210 @@ fn swarm_status(task_id: Option<pgrx::Uuid>) -> pgrx::JsonB {
     .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
     json
 }
+
+/// Minimum results an agent needs before it can be judged underperforming.
+const AUTOSCALE_MIN_RESULTS: i64 = 5;
+
+/// Number of most recent results compared against the window before them.
+const AUTOSCALE_WINDOW: i64 = 20;
+
+/// Scale a running swarm toward a target pass rate (0.0–1.0).
+///
+/// Compares the pass rate of the latest results with the window before it.
+/// Below target and not improving: grow by half the current count (at least
+/// one), registering `swarm-<task id>-<n>` agents in `kerai.swarm_agents`, up
+/// to `max_agents`. At or above target: hold. Agents with at least five results
+/// and under half the target rate are marked stopped for this task and dropped
+/// from the count. If `budget_ops` or `budget_seconds` (measured from launch)
+/// is exhausted the swarm is stopped instead, and growth is skipped once less
+/// than a quarter of either budget remains.
+#[pg_extern]
+fn autoscale_swarm(task_id: pgrx::Uuid, target_pass_rate: f64, max_agents: i32) -> pgrx::JsonB {
+    if !(0.0..=1.0).contains(&target_pass_rate) {
+        error!("target_pass_rate must be between 0.0 and 1.0, got {}", target_pass_rate);
+    }
+    if max_agents < 1 {
+        error!("max_agents must be at least 1");
+    }
+
+    let task = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'status', status,
+            'agent_count', COALESCE(agent_count, 1),
+            'agent_kind', COALESCE(agent_kind, 'llm'),
+            'agent_model', agent_model,
+            'budget_ops', budget_ops,
+            'budget_seconds', budget_seconds,
+            'ops_used', (SELECT COALESCE(SUM(ops_count), 0) FROM kerai.test_results WHERE task_id = t.id),
+            'seconds_elapsed', EXTRACT(EPOCH FROM now() - COALESCE(t.launched_at, t.created_at))::bigint
+        ) FROM kerai.tasks t WHERE id = '{}'::uuid",
+        task_id,
+    ))
+    .unwrap_or(None)
+    .unwrap_or_else(|| error!("Task not found: {}", task_id))
+    .0;
+
+    let status = task["status"].as_str().unwrap_or("");
+    if status != "running" {
+        error!("Task must be 'running' to autoscale, currently '{}'", status);
+    }
+
+    let current = task["agent_count"].as_i64().unwrap_or(1);
+    let ops_used = task["ops_used"].as_i64().unwrap_or(0);
+    let seconds_elapsed = task["seconds_elapsed"].as_i64().unwrap_or(0);
+    let budget_ops = task["budget_ops"].as_i64();
+    let budget_seconds = task["budget_seconds"].as_i64();
+
+    // Fraction of the tightest budget still available (1.0 when unbounded)
+    let remaining = [
+        budget_ops.map(|b| 1.0 - ops_used as f64 / b.max(1) as f64),
+        budget_seconds.map(|b| 1.0 - seconds_elapsed as f64 / b.max(1) as f64),
+    ]
+    .into_iter()
+    .flatten()
+    .fold(1.0_f64, f64::min);
+
+    // Pass rate trend: latest window vs the window before it
+    let rates = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'recent', (SELECT avg(passed::int)::float8 FROM (
+                SELECT passed FROM kerai.test_results WHERE task_id = '{id}'::uuid
+                ORDER BY created_at DESC LIMIT {w}) r),
+            'prior', (SELECT avg(passed::int)::float8 FROM (
+                SELECT passed FROM kerai.test_results WHERE task_id = '{id}'::uuid
+                ORDER BY created_at DESC OFFSET {w} LIMIT {w}) p)
+        )",
+        id = task_id,
+        w = AUTOSCALE_WINDOW,
+    ))
+    .unwrap()
+    .unwrap()
+    .0;
+    let recent = rates["recent"].as_f64();
+    let prior = rates["prior"].as_f64();
+
+    let mut decision;
+    let mut new_count = current;
+    let mut retired: Vec<String> = Vec::new();
+    let mut retired_ids: Vec<String> = Vec::new();
+    let mut registered: Vec<String> = Vec::new();
+
+    if remaining <= 0.0 {
+        decision = "stop";
+        Spi::run(&format!(
+            "UPDATE kerai.tasks SET status = 'stopped', updated_at = now() WHERE id = '{}'::uuid",
+            task_id,
+        ))
+        .unwrap();
+    } else {
+        // Retire agents well below target with enough history to judge
+        Spi::connect(|client| {
+            let query = format!(
+                "SELECT a.id::text AS id, a.name
+                 FROM kerai.test_results tr
+                 JOIN kerai.agents a ON tr.agent_id = a.id
+                 LEFT JOIN kerai.swarm_agents sa
+                     ON sa.task_id = tr.task_id AND sa.agent_id = a.id
+                 WHERE tr.task_id = '{}'::uuid
+                   AND COALESCE(sa.status, 'active') = 'active'
+                 GROUP BY a.id, a.name
+                 HAVING count(*) >= {}
+                    AND avg(tr.passed::int) < {}
+                 ORDER BY avg(tr.passed::int) ASC",
+                task_id,
+                AUTOSCALE_MIN_RESULTS,
+                target_pass_rate / 2.0,
+            );
+            let result = client.select(&query, None, &[]).unwrap();
+            for row in result {
+                let id = row.get_by_name::<String, _>("id").unwrap();
+                let name = row.get_by_name::<String, _>("name").unwrap();
+                if let (Some(id), Some(name)) = (id, name) {
+                    retired_ids.push(id);
+                    retired.push(name);
+                }
+            }
+        });
+        for id in &retired_ids {
+            Spi::run(&format!(
+                "INSERT INTO kerai.swarm_agents (task_id, agent_id, status)
+                 VALUES ('{}'::uuid, '{}'::uuid, 'stopped')
+                 ON CONFLICT (task_id, agent_id) DO UPDATE SET status = 'stopped'",
+                task_id,
+                sql_escape(id),
+            ))
+            .unwrap();
+        }
+        new_count = (current - retired.len() as i64).max(1);
+
+        let below_target = recent.map(|r| r < target_pass_rate).unwrap_or(true);
+        let improving = matches!((recent, prior), (Some(r), Some(p)) if r > p);
+
+        decision = if retired.is_empty() { "hold" } else { "scale_down" };
+        if below_target && !improving && remaining >= 0.25 && new_count < max_agents as i64 {
+            let grown = (new_count + (new_count / 2).max(1)).min(max_agents as i64);
+            let model_sql = match task["agent_model"].as_str() {
+                Some(m) => format!("'{}'", sql_escape(m)),
+                None => "NULL".to_string(),
+            };
+            // Number past every agent registered for this task, retired ones included
+            let prefix = format!("swarm-{}-", task_id);
+            let existing = Spi::get_one::<i64>(&format!(
+                "SELECT count(*)::bigint FROM kerai.swarm_agents sa
+                 JOIN kerai.agents a ON a.id = sa.agent_id
+                 WHERE sa.task_id = '{}'::uuid AND starts_with(a.name, '{}')",
+                task_id,
+                sql_escape(&prefix),
+            ))
+            .unwrap()
+            .unwrap_or(0);
+            for n in 1..=(grown - new_count) {
+                let name = format!("{}{}", prefix, existing + n);
+                Spi::run(&format!(
+                    "WITH agent AS (
+                        INSERT INTO kerai.agents (name, kind, model, config)
+                        VALUES ('{}', '{}', {}, '{{\"task_id\": \"{}\"}}'::jsonb)
+                        RETURNING id
+                    )
+                    INSERT INTO kerai.swarm_agents (task_id, agent_id)
+                    SELECT '{}'::uuid, id FROM agent",
+                    sql_escape(&name),
+                    sql_escape(task["agent_kind"].as_str().unwrap_or("llm")),
+                    model_sql,
+                    task_id,
+                    task_id,
+                ))
+                .unwrap();
+                registered.push(name);
+            }
+            new_count = grown;
+            decision = "scale_up";
+        }
+        new_count = new_count.min(max_agents as i64);
+
+        Spi::run(&format!(
+            "UPDATE kerai.tasks SET agent_count = {}, updated_at = now() WHERE id = '{}'::uuid",
+            new_count, task_id,
+        ))
+        .unwrap();
+    }
+
+    pgrx::JsonB(serde_json::json!({
+        "task_id": task_id.to_string(),
+        "decision": decision,
+        "previous_agent_count": current,
+        "agent_count": new_count,
+        "target_pass_rate": target_pass_rate,
+        "recent_pass_rate": recent,
+        "prior_pass_rate": prior,
+        "registered_agents": registered,
+        "retired_agents": retired,
+        "budget": {
+            "ops_used": ops_used,
+            "budget_ops": budget_ops,
+            "seconds_elapsed": seconds_elapsed,
+            "budget_seconds": budget_seconds,
+            "remaining_fraction": remaining,
+        },
+    }))
+}
@@ -1,9 +1,14 @@
 /// Task management — create, get, list, update status for swarm tasks.
 use pgrx::prelude::*;
 
+use crate::pagination;
 use crate::sql::sql_escape;
 
-/// Create a new task with status='pending'.
+/// Statuses that satisfy a dependency on a task.
+const SUCCEEDED: &str = "('succeeded', 'completed')";
+
+/// Create a new task with status='pending'. `reward` (nKoi) is paid to the
+/// winning agent when `evaluate_task` completes the task.
 #[pg_extern]
 fn create_task(
     description: &str,
@@ -11,7 +16,11 @@ fn create_task(
     scope_node_id: Option<pgrx::Uuid>,
     budget_ops: Option<i32>,
     budget_seconds: Option<i32>,
+    reward: default!(Option<i64>, "NULL"),
 ) -> pgrx::JsonB {
+    if reward.is_some_and(|r| r <= 0) {
+        error!("Task reward must be positive");
+    }
     let scope_sql = match scope_node_id {
         Some(id) => format!("'{}'::uuid", id),
         None => "NULL".to_string(),
@@ -24,10 +33,11 @@ fn create_task(
         Some(b) => b.to_string(),
         None => "NULL".to_string(),
     };
+    let reward_sql = reward.map_or_else(|| "NULL".to_string(), |r| r.to_string());
 
     let row = Spi::get_one::<pgrx::JsonB>(&format!(
-        "INSERT INTO kerai.tasks (description, success_command, scope_node_id, budget_ops, budget_seconds)
-         VALUES ('{}', '{}', {}, {}, {})
+        "INSERT INTO kerai.tasks (description, success_command, scope_node_id, budget_ops, budget_seconds, reward)
+         VALUES ('{}', '{}', {}, {}, {}, {})
          RETURNING jsonb_build_object(
              'id', id,
              'description', description,
@@ -35,6 +45,7 @@ fn create_task(
              'scope_node_id', scope_node_id,
              'budget_ops', budget_ops,
              'budget_seconds', budget_seconds,
+             'reward', reward,
              'status', status,
              'created_at', created_at
          )",
@@ -43,6 +54,7 @@ fn create_task(
         scope_sql,
         budget_ops_sql,
         budget_seconds_sql,
+        reward_sql,
     ))
     .unwrap()
     .unwrap();
@@ -60,17 +72,23 @@ fn get_task(task_id: pgrx::Uuid) -> pgrx::JsonB {
             'scope_node_id', t.scope_node_id,
             'budget_ops', t.budget_ops,
             'budget_seconds', t.budget_seconds,
+            'reward', t.reward,
             'status', t.status,
             'agent_kind', t.agent_kind,
             'agent_model', t.agent_model,
             'agent_count', t.agent_count,
             'swarm_id', t.swarm_id,
             'swarm_name', a.name,
+            'winner', w.name,
+            'completed_at', t.completed_at,
+            'depends_on', (SELECT COALESCE(jsonb_agg(d.depends_on), '[]'::jsonb)
+                           FROM kerai.task_dependencies d WHERE d.task_id = t.id),
             'created_at', t.created_at,
             'updated_at', t.updated_at
         )
         FROM kerai.tasks t
         LEFT JOIN kerai.agents a ON t.swarm_id = a.id
+        LEFT JOIN kerai.agents w ON t.winner_agent_id = w.id
         WHERE t.id = '{}'::uuid",
         task_id,
     ))
@@ -82,17 +100,22 @@ fn get_task(task_id: pgrx::Uuid) -> pgrx::JsonB {
     }
 }
 
-/// List tasks, optionally filtered by status.
+/// List tasks, optionally filtered by status, newest first.
+///
+/// With `limit` or `cursor`, returns one page `{items, total_count, next_cursor}`
+/// in creation order instead (see `pagination`); pass `next_cursor` back as
+/// `cursor` for the following page.
 #[pg_extern]
-fn list_tasks(status_filter: Option<&str>) -> pgrx::JsonB {
-    let where_clause = match status_filter {
-        Some(s) => format!("WHERE t.status = '{}'", sql_escape(s)),
-        None => String::new(),
-    };
-
-    let json = Spi::get_one::<pgrx::JsonB>(&format!(
-        "SELECT COALESCE(
-            jsonb_agg(jsonb_build_object(
+fn list_tasks(
+    status_filter: Option<&str>,
+    limit: default!(Option<i32>, "NULL"),
+    cursor: default!(Option<&str>, "NULL"),
+) -> pgrx::JsonB {
+    let conditions: Vec<String> = status_filter
+        .map(|s| format!("t.status = '{}'", sql_escape(s)))
+        .into_iter()
+        .collect();
+    let object = "jsonb_build_object(
                 'id', t.id,
                 'description', t.description,
                 'status', t.status,
@@ -101,26 +124,33 @@ fn list_tasks(status_filter: Option<&str>) -> pgrx::JsonB {
                 'swarm_name', a.name,
                 'created_at', t.created_at,
                 'updated_at', t.updated_at
-            ) ORDER BY t.created_at DESC),
-            '[]'::jsonb
-        )
-        FROM kerai.tasks t
-        LEFT JOIN kerai.agents a ON t.swarm_id = a.id
+            )";
+    let from = "kerai.tasks t LEFT JOIN kerai.agents a ON t.swarm_id = a.id";
+
+    if pagination::requested(limit, cursor) {
+        return pagination::keyset_page(object, from, &conditions, "t", limit, cursor);
+    }
+
+    let json = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg({object} ORDER BY t.created_at DESC), '[]'::jsonb)
+        FROM {from}
         {}",
-        where_clause,
+        pagination::where_clause(&conditions),
     ))
     .unwrap()
     .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
     json
 }
 
-/// Update a task's status. Validates status is one of: pending, running, succeeded, failed, stopped.
+/// Update a task's status. Validates status is one of: pending, running, succeeded,
+/// failed, stopped, completed. A task that has exceeded its budget cannot go
+/// back to pending or running; it is set to 'stopped' instead.
 #[pg_extern]
 fn update_task_status(task_id: pgrx::Uuid, new_status: &str) -> pgrx::JsonB {
-    let valid_statuses = ["pending", "running", "succeeded", "failed", "stopped"];
+    let valid_statuses = ["pending", "running", "succeeded", "failed", "stopped", "completed"];
     if !valid_statuses.contains(&new_status) {
         error!(
-            "Invalid task status '{}'. Must be one of: pending, running, succeeded, failed, stopped",
+            "Invalid task status '{}'. Must be one of: pending, running, succeeded, failed, stopped, completed",
             new_status
         );
     }
@@ -137,6 +167,14 @@ fn update_task_status(task_id: pgrx::Uuid, new_status: &str) -> pgrx::JsonB {
         error!("Task not found: {}", task_id);
     }
 
+    let budget_exceeded = (new_status == "pending" || new_status == "running")
+        && budget_usage(task_id)["exceeded"].as_bool() == Some(true);
+    let new_status = if budget_exceeded {
+        "stopped"
+    } else {
+        new_status
+    };
+
     Spi::run(&format!(
         "UPDATE kerai.tasks SET status = '{}', updated_at = now() WHERE id = '{}'::uuid",
         sql_escape(new_status),
@@ -148,11 +186,166 @@ fn update_task_status(task_id: pgrx::Uuid, new_status: &str) -> pgrx::JsonB {
         "SELECT jsonb_build_object(
             'id', id,
             'status', status,
+            'budget_exceeded', {},
             'updated_at', updated_at
         ) FROM kerai.tasks WHERE id = '{}'::uuid",
-        task_id,
+        budget_exceeded, task_id,
     ))
     .unwrap()
     .unwrap();
     row
 }
+
+/// Report a task's budget usage: ops summed from its test results and wall
+/// time since launch, against `budget_ops` / `budget_seconds` (NULL = unlimited).
+/// Returns `{ops_used, ops_budget, seconds_used, seconds_budget, exceeded}`.
+#[pg_extern]
+fn check_task_budget(task_id: pgrx::Uuid) -> pgrx::JsonB {
+    pgrx::JsonB(budget_usage(task_id))
+}
+
+fn budget_usage(task_id: pgrx::Uuid) -> serde_json::Value {
+    let usage = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'task_id', t.id,
+            'ops_used', u.ops_used,
+            'ops_budget', t.budget_ops,
+            'seconds_used', u.seconds_used,
+            'seconds_budget', t.budget_seconds,
+            'exceeded', COALESCE(u.ops_used > t.budget_ops, false)
+                        OR COALESCE(u.seconds_used > t.budget_seconds, false)
+        )
+        FROM kerai.tasks t
+        CROSS JOIN LATERAL (
+            SELECT
+                (SELECT COALESCE(SUM(r.ops_count), 0)::bigint
+                 FROM kerai.test_results r WHERE r.task_id = t.id) AS ops_used,
+                COALESCE(extract(epoch FROM COALESCE(t.completed_at, now()) - t.launched_at), 0)::bigint
+                    AS seconds_used
+        ) u
+        WHERE t.id = '{}'::uuid",
+        task_id,
+    ))
+    .unwrap_or(None);
+
+    match usage {
+        Some(j) => j.0,
+        None => error!("Task not found: {}", task_id),
+    }
+}
+
+/// Stop a running task that has exceeded its budget. Returns whether it did.
+pub(crate) fn stop_if_over_budget(task_id: pgrx::Uuid) -> bool {
+    if budget_usage(task_id)["exceeded"].as_bool() != Some(true) {
+        return false;
+    }
+    Spi::get_one::<bool>(&format!(
+        "WITH s AS (
+            UPDATE kerai.tasks SET status = 'stopped', updated_at = now()
+            WHERE id = '{}'::uuid AND status = 'running'
+            RETURNING 1
+        ) SELECT EXISTS(SELECT 1 FROM s)",
+        task_id,
+    ))
+    .unwrap()
+    .unwrap_or(false)
+}
+
+/// Record that `task_id` cannot launch until `depends_on` has succeeded.
+/// Rejects self-dependencies and edges that would form a cycle.
+#[pg_extern]
+fn add_task_dependency(task_id: pgrx::Uuid, depends_on: pgrx::Uuid) -> pgrx::JsonB {
+    for id in [task_id, depends_on] {
+        let exists = Spi::get_one::<bool>(&format!(
+            "SELECT EXISTS(SELECT 1 FROM kerai.tasks WHERE id = '{}'::uuid)",
+            id,
+        ))
+        .unwrap()
+        .unwrap_or(false);
+        if !exists {
+            error!("Task not found: {}", id);
+        }
+    }
+    if task_id == depends_on {
+        error!("Task {} cannot depend on itself", task_id);
+    }
+
+    // A cycle exists if depends_on already (transitively) depends on task_id
+    let cyclic = Spi::get_one::<bool>(&format!(
+        "WITH RECURSIVE upstream(id) AS (
+            SELECT depends_on FROM kerai.task_dependencies WHERE task_id = '{0}'::uuid
+            UNION
+            SELECT d.depends_on FROM kerai.task_dependencies d JOIN upstream u ON d.task_id = u.id
+        ) SELECT EXISTS(SELECT 1 FROM upstream WHERE id = '{1}'::uuid)",
+        depends_on, task_id,
+    ))
+    .unwrap()
+    .unwrap_or(false);
+    if cyclic {
+        error!(
+            "Dependency {} -> {} would create a cycle",
+            task_id, depends_on
+        );
+    }
+
+    Spi::run(&format!(
+        "INSERT INTO kerai.task_dependencies (task_id, depends_on)
+         VALUES ('{}'::uuid, '{}'::uuid)
+         ON CONFLICT DO NOTHING",
+        task_id, depends_on,
+    ))
+    .unwrap();
+
+    pgrx::JsonB(serde_json::json!({
+        "task_id": task_id.to_string(),
+        "depends_on": depends_on.to_string(),
+    }))
+}
+
+/// Pending tasks whose dependencies have all succeeded, oldest first.
+#[pg_extern]
+fn ready_tasks() -> pgrx::JsonB {
+    Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'id', t.id,
+            'description', t.description,
+            'status', t.status,
+            'created_at', t.created_at
+        ) ORDER BY t.created_at), '[]'::jsonb)
+        FROM kerai.tasks t
+        WHERE t.status = 'pending'
+          AND NOT EXISTS (
+              SELECT 1 FROM kerai.task_dependencies d
+              JOIN kerai.tasks dep ON dep.id = d.depends_on
+              WHERE d.task_id = t.id AND dep.status NOT IN {}
+          )",
+        SUCCEEDED,
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])))
+}
+
+/// Dependencies of `task_id` that have not succeeded yet, as (id, status).
+pub(crate) fn unmet_dependencies(task_id: pgrx::Uuid) -> Vec<(String, String)> {
+    let rows = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_array(dep.id, dep.status) ORDER BY dep.created_at), '[]'::jsonb)
+         FROM kerai.task_dependencies d
+         JOIN kerai.tasks dep ON dep.id = d.depends_on
+         WHERE d.task_id = '{}'::uuid AND dep.status NOT IN {}",
+        task_id, SUCCEEDED,
+    ))
+    .unwrap()
+    .map(|j| j.0)
+    .unwrap_or_else(|| serde_json::json!([]));
+
+    rows.as_array()
+        .into_iter()
+        .flatten()
+        .map(|r| {
+            (
+                r[0].as_str().unwrap_or_default().to_string(),
+                r[1].as_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect()
+}
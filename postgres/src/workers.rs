@@ -1,6 +1,100 @@
+use std::ffi::CString;
+use std::time::Duration;
+
+use pgrx::bgworkers::{BackgroundWorker, BackgroundWorkerBuilder, SignalWakeFlags};
+use pgrx::guc::{GucContext, GucFlags, GucRegistry, GucSetting};
 use pgrx::prelude::*;
 
-/// Register background workers. Real workers come in Plans 04, 06, 10.
+/// Database the background workers connect to (the one kerai is installed in).
+static DATABASE: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(Some(c"postgres"));
+
+/// How often the mining worker checks whether a scheduled run is due.
+/// The schedule itself lives in kerai.mining_schedule.
+const MINING_POLL: Duration = Duration::from_secs(10);
+
+/// How often the bounty worker closes bounties past their expiry.
+const BOUNTY_EXPIRY_POLL: Duration = Duration::from_secs(60);
+
+/// Register background workers. Workers only start when kerai is listed in
+/// `shared_preload_libraries`. The `kerai.database` setting is postmaster-level,
+/// so it is only defined then too — defining it from a backend that loads kerai
+/// later is FATAL.
 pub fn register_workers() {
-    info!("Kerai: background worker registration (no workers yet)");
+    if unsafe { !pg_sys::process_shared_preload_libraries_in_progress } {
+        return;
+    }
+
+    GucRegistry::define_string_guc(
+        c"kerai.database",
+        c"Database kerai background workers connect to",
+        c"Must be the database where CREATE EXTENSION kerai was run.",
+        &DATABASE,
+        GucContext::Postmaster,
+        GucFlags::default(),
+    );
+
+    BackgroundWorkerBuilder::new("kerai mining")
+        .set_function("kerai_mining_worker_main")
+        .set_library("kerai")
+        .enable_spi_access()
+        .set_restart_time(Some(Duration::from_secs(60)))
+        .load();
+
+    BackgroundWorkerBuilder::new("kerai bounty expiry")
+        .set_function("kerai_bounty_expiry_worker_main")
+        .set_library("kerai")
+        .enable_spi_access()
+        .set_restart_time(Some(Duration::from_secs(60)))
+        .load();
+}
+
+/// Mining worker: calls kerai.mining_tick() every poll. The tick returns
+/// immediately unless an interval is set and has elapsed since the last run.
+#[pg_guard]
+#[no_mangle]
+pub extern "C-unwind" fn kerai_mining_worker_main(_arg: pg_sys::Datum) {
+    connect_worker();
+
+    while BackgroundWorker::wait_latch(Some(MINING_POLL)) {
+        BackgroundWorker::transaction(|| {
+            if extension_installed() {
+                Spi::run("SELECT kerai.mining_tick()").unwrap();
+            }
+        });
+    }
+}
+
+/// Bounty expiry worker: calls kerai.expire_bounties() every poll, closing
+/// open bounties past `expires_at` and refunding their escrowed rewards.
+#[pg_guard]
+#[no_mangle]
+pub extern "C-unwind" fn kerai_bounty_expiry_worker_main(_arg: pg_sys::Datum) {
+    connect_worker();
+
+    while BackgroundWorker::wait_latch(Some(BOUNTY_EXPIRY_POLL)) {
+        BackgroundWorker::transaction(|| {
+            if extension_installed() {
+                Spi::run("SELECT kerai.expire_bounties()").unwrap();
+            }
+        });
+    }
+}
+
+/// Install signal handlers and connect to the `kerai.database` database.
+fn connect_worker() {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+
+    let database = DATABASE
+        .get()
+        .and_then(|db| db.into_string().ok())
+        .unwrap_or_else(|| "postgres".to_string());
+    BackgroundWorker::connect_worker_to_spi(Some(&database), None);
+}
+
+/// The extension may not be created yet (or was dropped).
+fn extension_installed() -> bool {
+    Spi::get_one::<bool>("SELECT EXISTS(SELECT 1 FROM pg_extension WHERE extname = 'kerai')")
+        .unwrap_or(None)
+        .unwrap_or(false)
 }
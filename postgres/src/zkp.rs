@@ -1,15 +1,31 @@
-/// Zero-knowledge proof stubs — attestation-only mode using SHA-256 commitments.
+/// Zero-knowledge proof stubs — attestation-only mode using hash commitments
+/// (SHA-256 by default, or BLAKE3 / SHA3-256).
 /// Real ZK-STARK/SNARK implementation will replace these stubs in a future iteration.
 use pgrx::prelude::*;
 use sha2::{Digest, Sha256};
+use sha3::Sha3_256;
+
+use crate::identity;
+use crate::sql::sql_text;
 
 /// Generate a proof for an attestation.
-/// Currently produces a SHA-256 commitment over the attestation's underlying data
-/// (scope, claim_type, perspective_count, avg_weight). This is an "attestation-only"
+/// Currently produces a hash commitment over the attestation's id, issuing instance
+/// and underlying data (scope, claim_type, perspective_count, avg_weight). This is an "attestation-only"
 /// proof that commits to the claimed values without zero-knowledge properties.
+/// `scheme` is `sha256` (default), `blake3`, or `sha3_256`; it is stored as the
+/// proof type so verification recomputes with the same hash. A signed attestation
+/// keeps the scheme its signature covers.
 /// Future: Replace with ZK-STARK proof generation.
 #[pg_extern]
-fn generate_proof(attestation_id: pgrx::Uuid) -> pgrx::JsonB {
+fn generate_proof(attestation_id: pgrx::Uuid, scheme: default!(&str, "'sha256'")) -> pgrx::JsonB {
+    let scheme = Scheme::parse(scheme).unwrap_or_else(|| {
+        error!(
+            "Unknown commitment scheme '{}'. Must be one of: {}",
+            scheme,
+            Scheme::NAMES.join(", "),
+        )
+    });
+
     // Fetch attestation data
     let att = Spi::get_one::<pgrx::JsonB>(&format!(
         "SELECT jsonb_build_object(
@@ -20,7 +36,9 @@ fn generate_proof(attestation_id: pgrx::Uuid) -> pgrx::JsonB {
             'avg_weight', avg_weight,
             'compute_cost', compute_cost,
             'uniqueness_score', uniqueness_score,
-            'instance_id', instance_id
+            'instance_id', instance_id,
+            'proof_type', proof_type,
+            'signed', signature IS NOT NULL
         ) FROM kerai.attestations WHERE id = '{}'::uuid",
         attestation_id,
     ))
@@ -33,47 +51,307 @@ fn generate_proof(attestation_id: pgrx::Uuid) -> pgrx::JsonB {
 
     let obj = att.0.as_object().unwrap();
 
-    // Build commitment: SHA-256(scope || claim_type || perspective_count || avg_weight)
-    let mut hasher = Sha256::new();
-    hasher.update(obj["scope"].as_str().unwrap_or("").as_bytes());
-    hasher.update(obj["claim_type"].as_str().unwrap_or("").as_bytes());
-    hasher.update(obj["perspective_count"].as_i64().unwrap_or(0).to_le_bytes());
-    let avg_w = obj["avg_weight"].as_f64().unwrap_or(0.0);
-    hasher.update(avg_w.to_le_bytes());
-    let hash = hasher.finalize();
+    if obj["signed"] == true {
+        let signed_scheme = Scheme::from_proof_type(obj["proof_type"].as_str());
+        if signed_scheme != Some(scheme) {
+            error!(
+                "Attestation {} is signed over a {} commitment; cannot re-prove with {}",
+                attestation_id,
+                obj["proof_type"].as_str().unwrap_or("unknown"),
+                scheme.name(),
+            );
+        }
+    }
+
+    // Build commitment: H(id || instance_id || scope || claim_type || perspective_count || avg_weight)
+    let hash = commitment(
+        scheme,
+        obj["id"].as_str().unwrap_or(""),
+        obj["instance_id"].as_str().unwrap_or(""),
+        obj["scope"].as_str().unwrap_or(""),
+        obj["claim_type"].as_str().unwrap_or(""),
+        obj["perspective_count"].as_i64().unwrap_or(0),
+        obj["avg_weight"].as_f64().unwrap_or(0.0),
+    );
 
-    let proof_hex: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+    let proof_hex = hex::encode(&hash);
 
     // Store proof in attestation
     Spi::run(&format!(
         "UPDATE kerai.attestations
-         SET proof_type = 'sha256_commitment', proof_data = '\\x{}'::bytea
+         SET proof_type = '{}', proof_data = '\\x{}'::bytea
          WHERE id = '{}'::uuid",
-        proof_hex, attestation_id,
+        scheme.proof_type(),
+        proof_hex,
+        attestation_id,
     ))
     .unwrap();
 
     pgrx::JsonB(serde_json::json!({
         "attestation_id": attestation_id.to_string(),
-        "proof_type": "sha256_commitment",
+        "proof_type": scheme.proof_type(),
+        "scheme": scheme.name(),
         "proof_hex": proof_hex,
         "note": "Attestation-only mode. ZK-STARK proofs will replace this.",
     }))
 }
 
 /// Verify a proof for an attestation.
-/// Currently re-computes the SHA-256 commitment and compares.
+/// Currently re-computes the commitment with the scheme stored by `generate_proof`
+/// (SHA-256 when none was stored) and compares. If the attestation
+/// carries a signature, the signer is also checked against the issuing instance's key.
 /// Future: Replace with ZK-STARK proof verification.
 #[pg_extern]
 fn verify_proof(attestation_id: pgrx::Uuid, proof_data: Vec<u8>) -> pgrx::JsonB {
-    // Fetch attestation data
+    let claim = load_claim(attestation_id);
+    let expected = claim.commitment();
+
+    let valid = proof_data.as_slice() == expected.as_slice();
+    let signer_valid = claim.signature.as_ref().map(|sig| claim.signer_valid(&expected, sig));
+
+    pgrx::JsonB(serde_json::json!({
+        "attestation_id": attestation_id.to_string(),
+        "valid": valid,
+        "proof_type": claim.scheme.proof_type(),
+        "signed": claim.signature.is_some(),
+        "signer_valid": signer_valid,
+    }))
+}
+
+/// Create a signed attestation for the self instance.
+/// perspective_count and avg_weight are computed from kerai.perspectives on nodes
+/// under `scope`. The SHA-256 commitment is stored as the proof and signed with
+/// the instance key, so the claim can be checked by any peer holding the public key.
+#[pg_extern]
+fn create_attestation(scope: &str, claim_type: &str) -> pgrx::JsonB {
+    let instance_id = self_instance_id();
+    let signing_key = identity::load_signing_key()
+        .unwrap_or_else(|| error!("No signing key found — identity not initialized"));
+
+    pgrx::JsonB(attest(&instance_id, &signing_key, scope, claim_type, None))
+}
+
+/// Create signed `consensus` attestations for nodes under `scope` that enough
+/// agents agree on: at least `min_agents` distinct agents and an average
+/// weight of at least `min_weight`. The attestation scope is the node's path
+/// and its stats are the node's own perspectives. Paths that already have an
+/// unexpired consensus attestation from this instance are skipped, so repeated
+/// runs only attest new consensus; where several qualifying nodes share a path,
+/// the one with the most agents is attested.
+/// Returns the attestations created, each with the `node_id` that triggered it.
+#[pg_extern]
+fn auto_attest(
+    scope: &str,
+    min_agents: default!(i32, 2),
+    min_weight: default!(f64, 0.5),
+) -> pgrx::JsonB {
+    let instance_id = self_instance_id();
+    let signing_key = identity::load_signing_key()
+        .unwrap_or_else(|| error!("No signing key found — identity not initialized"));
+
+    let candidates = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT COALESCE(jsonb_agg(jsonb_build_object(
+            'node_id', c.node_id, 'path', c.path::text
+        ) ORDER BY c.path), '[]'::jsonb)
+        FROM (
+            SELECT DISTINCT ON (q.path) q.node_id, q.path
+            FROM (
+                SELECT n.id AS node_id, n.path,
+                       count(DISTINCT p.agent_id) AS agents, avg(p.weight) AS weight
+                FROM kerai.perspectives p
+                JOIN kerai.nodes n ON n.id = p.node_id
+                WHERE n.path <@ {scope}::ltree
+                GROUP BY n.id, n.path
+                HAVING count(DISTINCT p.agent_id) >= {min_agents}
+                   AND avg(p.weight) >= {min_weight}
+            ) q
+            ORDER BY q.path, q.agents DESC, q.weight DESC, q.node_id
+        ) c
+        WHERE NOT EXISTS (
+            SELECT 1 FROM kerai.attestations a
+            WHERE a.instance_id = {instance}::uuid
+              AND a.scope = c.path
+              AND a.claim_type = 'consensus'
+              AND (a.expires_at IS NULL OR a.expires_at > now())
+        )",
+        scope = sql_text(scope),
+        instance = sql_text(&instance_id),
+    ))
+    .unwrap()
+    .unwrap_or_else(|| pgrx::JsonB(serde_json::json!([])));
+
+    let mut created = Vec::new();
+    for candidate in candidates.0.as_array().into_iter().flatten() {
+        let path = candidate["path"].as_str().unwrap_or_default();
+        let node_id = candidate["node_id"].as_str();
+        let mut att = attest(&instance_id, &signing_key, path, "consensus", node_id);
+        att["node_id"] = candidate["node_id"].clone();
+        created.push(att);
+    }
+
+    pgrx::JsonB(serde_json::Value::Array(created))
+}
+
+fn self_instance_id() -> String {
+    Spi::get_one::<String>("SELECT id::text FROM kerai.instances WHERE is_self = true")
+        .unwrap_or(None)
+        .unwrap_or_else(|| error!("Self instance not found"))
+}
+
+/// Insert an attestation for `scope`, commit to its perspective stats, and sign it.
+/// The stats cover every node under `scope`, or only `node_id` when given.
+fn attest(
+    instance_id: &str,
+    signing_key: &ed25519_dalek::SigningKey,
+    scope: &str,
+    claim_type: &str,
+    node_id: Option<&str>,
+) -> serde_json::Value {
+    let filter = match node_id {
+        Some(id) => format!("p.node_id = {}::uuid", sql_text(id)),
+        None => format!("n.path <@ {}::ltree", sql_text(scope)),
+    };
+    let stats = Spi::get_one::<pgrx::JsonB>(&format!(
+        "SELECT jsonb_build_object(
+            'perspective_count', count(p.id),
+            'avg_weight', COALESCE(avg(p.weight), 0.0)
+        )
+        FROM kerai.perspectives p
+        JOIN kerai.nodes n ON n.id = p.node_id
+        WHERE {}",
+        filter,
+    ))
+    .unwrap()
+    .unwrap();
+    let perspective_count = stats.0["perspective_count"].as_i64().unwrap_or(0);
+    let avg_weight = stats.0["avg_weight"].as_f64().unwrap_or(0.0);
+
+    // Round-trip through the column types so the commitment matches what verify recomputes
+    let attestation_id = Spi::get_one::<pgrx::Uuid>(&format!(
+        "INSERT INTO kerai.attestations (instance_id, scope, claim_type, perspective_count, avg_weight)
+         VALUES ({}::uuid, {}::ltree, {}, {}, {})
+         RETURNING id",
+        sql_text(instance_id),
+        sql_text(scope),
+        sql_text(claim_type),
+        perspective_count,
+        avg_weight,
+    ))
+    .unwrap()
+    .unwrap();
+
+    let claim = load_claim(attestation_id);
+    let hash = claim.commitment();
+    let signature = identity::sign_data(signing_key, &hash);
+
+    Spi::run(&format!(
+        "UPDATE kerai.attestations
+         SET proof_type = 'sha256_commitment',
+             proof_data = '\\x{}'::bytea,
+             signature = '\\x{}'::bytea
+         WHERE id = '{}'::uuid",
+        hex::encode(&hash),
+        hex::encode(&signature),
+        attestation_id,
+    ))
+    .unwrap();
+
+    serde_json::json!({
+        "id": attestation_id.to_string(),
+        "instance_id": instance_id,
+        "scope": claim.scope,
+        "claim_type": claim.claim_type,
+        "perspective_count": claim.perspective_count,
+        "avg_weight": claim.avg_weight,
+        "proof_type": "sha256_commitment",
+        "proof_hex": hex::encode(&hash),
+        "signer": identity::fingerprint(&signing_key.verifying_key()),
+    })
+}
+
+/// Verify a signed attestation against its issuing instance's public key.
+/// Checks both that the stored proof matches the attestation's current values
+/// and that the signature over that commitment was made by the instance.
+#[pg_extern]
+fn verify_attestation(attestation_id: pgrx::Uuid) -> pgrx::JsonB {
+    let claim = load_claim(attestation_id);
+    let expected = claim.commitment();
+
+    let commitment_valid = claim.proof_data.as_deref() == Some(expected.as_slice());
+    let signature_valid = match &claim.signature {
+        Some(sig) => claim.signer_valid(&expected, sig),
+        None => false,
+    };
+
+    pgrx::JsonB(serde_json::json!({
+        "attestation_id": attestation_id.to_string(),
+        "valid": commitment_valid && signature_valid,
+        "commitment_valid": commitment_valid,
+        "signature_valid": signature_valid,
+        "signed": claim.signature.is_some(),
+        "signer": claim.key_fingerprint,
+    }))
+}
+
+/// Attestation values and provenance needed to recompute and check a proof.
+struct Claim {
+    id: String,
+    instance_id: String,
+    scope: String,
+    claim_type: String,
+    perspective_count: i64,
+    avg_weight: f64,
+    scheme: Scheme,
+    proof_data: Option<Vec<u8>>,
+    signature: Option<Vec<u8>>,
+    public_key: Vec<u8>,
+    key_fingerprint: String,
+}
+
+impl Claim {
+    fn commitment(&self) -> Vec<u8> {
+        commitment(
+            self.scheme,
+            &self.id,
+            &self.instance_id,
+            &self.scope,
+            &self.claim_type,
+            self.perspective_count,
+            self.avg_weight,
+        )
+    }
+
+    /// Whether `signature` over `data` was made by the issuing instance.
+    fn signer_valid(&self, data: &[u8], signature: &[u8]) -> bool {
+        let pk: [u8; 32] = match self.public_key.as_slice().try_into() {
+            Ok(b) => b,
+            Err(_) => return false,
+        };
+        match ed25519_dalek::VerifyingKey::from_bytes(&pk) {
+            Ok(key) => identity::verify_signature(&key, data, signature),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Load an attestation with its issuing instance's public key.
+fn load_claim(attestation_id: pgrx::Uuid) -> Claim {
     let att = Spi::get_one::<pgrx::JsonB>(&format!(
         "SELECT jsonb_build_object(
-            'scope', scope::text,
-            'claim_type', claim_type,
-            'perspective_count', perspective_count,
-            'avg_weight', avg_weight
-        ) FROM kerai.attestations WHERE id = '{}'::uuid",
+            'id', a.id,
+            'instance_id', a.instance_id,
+            'scope', a.scope::text,
+            'claim_type', a.claim_type,
+            'perspective_count', a.perspective_count,
+            'avg_weight', a.avg_weight,
+            'proof_type', a.proof_type,
+            'proof_data', encode(a.proof_data, 'hex'),
+            'signature', encode(a.signature, 'hex'),
+            'public_key', encode(i.public_key, 'hex'),
+            'key_fingerprint', i.key_fingerprint
+        ) FROM kerai.attestations a
+        JOIN kerai.instances i ON i.id = a.instance_id
+        WHERE a.id = '{}'::uuid",
         attestation_id,
     ))
     .unwrap_or(None);
@@ -84,21 +362,103 @@ fn verify_proof(attestation_id: pgrx::Uuid, proof_data: Vec<u8>) -> pgrx::JsonB
     };
 
     let obj = att.0.as_object().unwrap();
+    let bytes = |key: &str| obj[key].as_str().and_then(|h| hex::decode(h).ok());
 
-    // Recompute commitment
-    let mut hasher = Sha256::new();
-    hasher.update(obj["scope"].as_str().unwrap_or("").as_bytes());
-    hasher.update(obj["claim_type"].as_str().unwrap_or("").as_bytes());
-    hasher.update(obj["perspective_count"].as_i64().unwrap_or(0).to_le_bytes());
-    let avg_w = obj["avg_weight"].as_f64().unwrap_or(0.0);
-    hasher.update(avg_w.to_le_bytes());
-    let expected = hasher.finalize();
+    Claim {
+        id: obj["id"].as_str().unwrap_or("").to_string(),
+        instance_id: obj["instance_id"].as_str().unwrap_or("").to_string(),
+        scope: obj["scope"].as_str().unwrap_or("").to_string(),
+        claim_type: obj["claim_type"].as_str().unwrap_or("").to_string(),
+        perspective_count: obj["perspective_count"].as_i64().unwrap_or(0),
+        avg_weight: obj["avg_weight"].as_f64().unwrap_or(0.0),
+        scheme: Scheme::from_proof_type(obj["proof_type"].as_str()).unwrap_or(Scheme::Sha256),
+        proof_data: bytes("proof_data"),
+        signature: bytes("signature"),
+        public_key: bytes("public_key").unwrap_or_default(),
+        key_fingerprint: obj["key_fingerprint"].as_str().unwrap_or("").to_string(),
+    }
+}
 
-    let valid = proof_data.as_slice() == expected.as_slice();
+/// Hash function a commitment is computed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scheme {
+    Sha256,
+    Blake3,
+    Sha3_256,
+}
 
-    pgrx::JsonB(serde_json::json!({
-        "attestation_id": attestation_id.to_string(),
-        "valid": valid,
-        "proof_type": "sha256_commitment",
-    }))
+impl Scheme {
+    const NAMES: [&'static str; 3] = ["sha256", "blake3", "sha3_256"];
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sha256" => Some(Scheme::Sha256),
+            "blake3" => Some(Scheme::Blake3),
+            "sha3_256" => Some(Scheme::Sha3_256),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Scheme::Sha256 => "sha256",
+            Scheme::Blake3 => "blake3",
+            Scheme::Sha3_256 => "sha3_256",
+        }
+    }
+
+    /// Value stored in `attestations.proof_type`.
+    fn proof_type(self) -> String {
+        format!("{}_commitment", self.name())
+    }
+
+    /// Scheme of a stored proof type; no proof type means SHA-256.
+    fn from_proof_type(proof_type: Option<&str>) -> Option<Self> {
+        match proof_type {
+            None => Some(Scheme::Sha256),
+            Some(t) => t.strip_suffix("_commitment").and_then(Scheme::parse),
+        }
+    }
+}
+
+/// H(attestation_id || instance_id || scope || claim_type || perspective_count || avg_weight)
+///
+/// Binding the attestation and issuing instance ids means a proof or signature
+/// copied onto another attestation row no longer verifies.
+fn commitment(
+    scheme: Scheme,
+    attestation_id: &str,
+    instance_id: &str,
+    scope: &str,
+    claim_type: &str,
+    perspective_count: i64,
+    avg_weight: f64,
+) -> Vec<u8> {
+    let parts: [&[u8]; 6] = [
+        attestation_id.as_bytes(),
+        instance_id.as_bytes(),
+        scope.as_bytes(),
+        claim_type.as_bytes(),
+        &perspective_count.to_le_bytes(),
+        &avg_weight.to_le_bytes(),
+    ];
+    match scheme {
+        Scheme::Sha256 => digest::<Sha256>(&parts),
+        Scheme::Sha3_256 => digest::<Sha3_256>(&parts),
+        Scheme::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.finalize().as_bytes().to_vec()
+        }
+    }
+}
+
+fn digest<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = D::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().to_vec()
 }